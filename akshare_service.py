@@ -10,41 +10,156 @@ import akshare as ak
 import pandas as pd
 from datetime import datetime, timedelta
 import json
+import logging
 
 app = Flask(__name__)
 CORS(app)
+logger = logging.getLogger(__name__)
+
+def fetch_price_rows(stock_code, days, adjust="", start=None, end=None, history=ak.stock_zh_a_hist):
+    """Fetch daily price rows for one stock; adjust is "" (unadjusted), "qfq" or "hfq".
+    start/end (YYYY-MM-DD, inclusive) select a fixed window instead of the last `days` days.
+    history is the akshare daily history function of the stock's market (A-shares by default)"""
+    # Use stock code directly without prefix for akshare
+    full_code = stock_code
+    
+    # Get historical data
+    if start and end:
+        start_date = start.replace('-', '')
+        end_date = end.replace('-', '')
+    else:
+        end_date = datetime.now().strftime('%Y%m%d')
+        start_date = (datetime.now() - timedelta(days=days)).strftime('%Y%m%d')
+    
+    stock_data = history(
+        symbol=full_code,
+        period="daily",
+        start_date=start_date,
+        end_date=end_date,
+        adjust=adjust
+    )
+    
+    # Convert to list of dicts
+    data = []
+    for _, row in stock_data.iterrows():
+        data.append({
+            'date': str(row['日期']),
+            'open': float(row['开盘']) if pd.notna(row['开盘']) else 0.0,
+            'close': float(row['收盘']) if pd.notna(row['收盘']) else 0.0,
+            'high': float(row['最高']) if pd.notna(row['最高']) else 0.0,
+            'low': float(row['最低']) if pd.notna(row['最低']) else 0.0,
+            'volume': int(row['成交量']) if pd.notna(row['成交量']) else 0,
+            'change_pct': float(row['涨跌幅']) if pd.notna(row.get('涨跌幅')) else None,
+            'turnover': float(row['成交额']) if pd.notna(row.get('成交额')) else None,
+            'turnover_rate': float(row['换手率']) if pd.notna(row.get('换手率')) else None
+        })
+    return data
+
+def market_price_history(stock_code):
+    """Pick the akshare daily history function for the code's market, mirroring the Rust side's
+    market detection: 6-digit A-share codes and 1-5 digit Hong Kong codes. Returns (function, symbol),
+    or None for markets this service has no batch source for"""
+    if stock_code.isdigit() and len(stock_code) == 6:
+        if stock_code[0] in '03468' or stock_code.startswith('92'):
+            return ak.stock_zh_a_hist, stock_code
+        return None
+    if stock_code.isdigit() and 1 <= len(stock_code) <= 5:
+        return ak.stock_hk_hist, stock_code.zfill(5)
+    return None
 
 @app.route('/api/stock/<stock_code>/price')
 def get_stock_price(stock_code):
-    """Get stock price data"""
+    """Get stock price data: ?days=30&adjust=qfq|hfq (omitted or none for unadjusted),
+    or a fixed window with ?start=2024-01-01&end=2024-06-30"""
     try:
         days = int(request.args.get('days', 30))
+        start = request.args.get('start')
+        end = request.args.get('end')
+        adjust = request.args.get('adjust', '')
+        if adjust == 'none':
+            adjust = ''
+        if adjust not in ('', 'qfq', 'hfq'):
+            return jsonify({'error': f'unsupported adjust: {adjust}'}), 400
+        return jsonify(fetch_price_rows(stock_code, days, adjust, start, end))
+    except Exception as e:
+        return jsonify({'error': str(e)}), 500
+
+@app.route('/api/stock/<stock_code>/intraday')
+def get_stock_intraday(stock_code):
+    """Get minute bars for the last few trading days: ?period=1|5|15|30|60"""
+    try:
+        period = request.args.get('period', '5')
+        if period not in ('1', '5', '15', '30', '60'):
+            return jsonify({'error': f'unsupported period: {period}'}), 400
         
-        # Use stock code directly without prefix for akshare
-        full_code = stock_code
-        
-        # Get historical data
-        end_date = datetime.now().strftime('%Y%m%d')
-        start_date = (datetime.now() - timedelta(days=days)).strftime('%Y%m%d')
-        
-        stock_data = ak.stock_zh_a_hist(
-            symbol=full_code,
-            period="daily",
+        start_date = (datetime.now() - timedelta(days=5)).strftime('%Y-%m-%d 09:00:00')
+        end_date = datetime.now().strftime('%Y-%m-%d %H:%M:%S')
+        minute_data = ak.stock_zh_a_hist_min_em(
+            symbol=stock_code,
+            period=period,
             start_date=start_date,
             end_date=end_date,
             adjust=""
         )
         
-        # Convert to list of dicts
         data = []
-        for _, row in stock_data.iterrows():
+        for _, row in minute_data.iterrows():
             data.append({
-                'date': str(row['日期']),
+                'date': str(row['时间']),
                 'open': float(row['开盘']) if pd.notna(row['开盘']) else 0.0,
                 'close': float(row['收盘']) if pd.notna(row['收盘']) else 0.0,
                 'high': float(row['最高']) if pd.notna(row['最高']) else 0.0,
                 'low': float(row['最低']) if pd.notna(row['最低']) else 0.0,
-                'volume': int(row['成交量']) if pd.notna(row['成交量']) else 0
+                'volume': int(row['成交量']) if pd.notna(row['成交量']) else 0,
+                'change_pct': float(row['涨跌幅']) if pd.notna(row.get('涨跌幅')) else None,
+                'turnover': float(row['成交额']) if pd.notna(row.get('成交额')) else None,
+                'turnover_rate': float(row['换手率']) if pd.notna(row.get('换手率')) else None
+            })
+        return jsonify(data)
+    except Exception as e:
+        return jsonify({'error': str(e)}), 500
+
+@app.route('/api/stocks/price')
+def get_stocks_price():
+    """Get price data for several stocks: ?codes=000001,600036&days=60
+    Returns {code: [rows]}; codes that fail or whose market has no batch source are omitted
+    so the caller can retry them individually through the per-market route"""
+    days = int(request.args.get('days', 30))
+    codes = [c.strip() for c in request.args.get('codes', '').split(',') if c.strip()]
+    
+    result = {}
+    for code in codes:
+        source = market_price_history(code)
+        if source is None:
+            logger.info("No batch price source for %s, leaving it to the per-stock route", code)
+            continue
+        history, symbol = source
+        try:
+            result[code] = fetch_price_rows(symbol, days, history=history)
+        except Exception as e:
+            logger.warning("Batch price fetch failed for %s: %s", code, e)
+    return jsonify(result)
+
+@app.route('/api/index/<index_code>/price')
+def get_index_price(index_code):
+    """Get daily index data, e.g. sh000001 for 上证指数"""
+    try:
+        days = int(request.args.get('days', 30))
+        index_data = ak.stock_zh_index_daily(symbol=index_code)
+        start_date = (datetime.now() - timedelta(days=days)).date()
+        
+        data = []
+        for _, row in index_data.iterrows():
+            date = pd.to_datetime(row['date']).date()
+            if date < start_date:
+                continue
+            data.append({
+                'date': str(date),
+                'open': float(row['open']) if pd.notna(row['open']) else 0.0,
+                'close': float(row['close']) if pd.notna(row['close']) else 0.0,
+                'high': float(row['high']) if pd.notna(row['high']) else 0.0,
+                'low': float(row['low']) if pd.notna(row['low']) else 0.0,
+                'volume': int(row['volume']) if pd.notna(row['volume']) else 0
             })
         
         return jsonify(data)
@@ -238,7 +353,9 @@ def index():
     return jsonify({
         'service': 'akshare-proxy',
         'endpoints': [
-            '/api/stock/<code>/price?days=30',
+            '/api/stock/<code>/price?days=30&adjust=qfq',
+            '/api/stock/<code>/price?start=2024-01-01&end=2024-06-30',
+            '/api/stock/<code>/intraday?period=5',
             '/api/stock/<code>/fundamental',
             '/api/stock/<code>/news?days=15',
             '/api/stock/<code>/name',
@@ -74,27 +74,27 @@ fn print_dev_info() {
     if profile == "debug" {
         println!("🔧 Development Build");
         println!("==================");
-        println!("");
+        println!();
         println!("📁 Template watching is enabled!");
-        println!("");
+        println!();
         println!("🚀 Quick start commands:");
         println!("  cargo run                    # Build and run");
         println!("  ./dev.sh                     # Development mode with watching");
         println!("  cargo install cargo-watch    # Install cargo-watch");
         println!("  cargo watch -x run           # Auto-restart on changes");
-        println!("");
+        println!();
         println!("📂 Watched files and directories:");
         println!("  • templates/*.html           # HTML templates");
         println!("  • src/*.rs                   # Rust source files");
         println!("  • Cargo.toml                 # Dependencies");
         println!("  • static/**/*               # Static assets");
-        println!("");
+        println!();
         println!("💡 Development tips:");
         println!("  • Use ./dev.sh for the best development experience");
         println!("  • Template changes will auto-restart the server");
         println!("  • Check logs/ directory for server logs");
         println!("  • Use RUST_LOG=debug for verbose logging");
-        println!("");
+        println!();
     }
 }
 
@@ -164,7 +164,7 @@ done
     if let Err(e) = fs::write("watch_inotify.sh", script_content) {
         eprintln!("Warning: Failed to create inotify watcher: {}", e);
     } else {
-        Command::new("chmod").args(&["+x", "watch_inotify.sh"]).output().ok();
+        Command::new("chmod").args(["+x", "watch_inotify.sh"]).output().ok();
         println!("✅ Created watch_inotify.sh");
     }
 }
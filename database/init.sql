@@ -15,6 +15,8 @@ CREATE TABLE IF NOT EXISTS saved_analyses (
     data_quality JSONB NOT NULL,
     ai_provider VARCHAR(50),
     ai_model VARCHAR(50),
+    ai_tokens BIGINT,
+    ai_latency_ms BIGINT,
     created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
 );
 
@@ -28,11 +30,99 @@ CREATE TABLE IF NOT EXISTS saved_configurations (
     updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
 );
 
+CREATE TABLE IF NOT EXISTS trading_signals (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    stock_code VARCHAR(20) NOT NULL,
+    stock_name VARCHAR(100) NOT NULL,
+    strategy_name VARCHAR(100) NOT NULL,
+    signal_type VARCHAR(20) NOT NULL,
+    strength DOUBLE PRECISION NOT NULL,
+    price DOUBLE PRECISION NOT NULL,
+    signal_time TIMESTAMP WITH TIME ZONE NOT NULL,
+    reason TEXT NOT NULL,
+    confidence DOUBLE PRECISION NOT NULL,
+    risk_level VARCHAR(20) NOT NULL,
+    expected_profit DOUBLE PRECISION NOT NULL,
+    stop_loss DOUBLE PRECISION NOT NULL,
+    take_profit DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS paper_accounts (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    name VARCHAR(100) NOT NULL,
+    initial_cash DOUBLE PRECISION NOT NULL,
+    position_fraction DOUBLE PRECISION NOT NULL,
+    auto_execute_stocks JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS paper_trades (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    account_id UUID NOT NULL REFERENCES paper_accounts(id) ON DELETE CASCADE,
+    stock_code VARCHAR(20) NOT NULL,
+    stock_name VARCHAR(100) NOT NULL,
+    side VARCHAR(10) NOT NULL,
+    quantity BIGINT NOT NULL,
+    price DOUBLE PRECISION NOT NULL,
+    amount DOUBLE PRECISION NOT NULL,
+    signal VARCHAR(20) NOT NULL,
+    realized_pnl DOUBLE PRECISION NOT NULL,
+    executed_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    username VARCHAR(50) NOT NULL UNIQUE,
+    email VARCHAR(255) NOT NULL UNIQUE,
+    password_hash VARCHAR(100) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_login TIMESTAMP WITH TIME ZONE,
+    is_admin BOOLEAN NOT NULL DEFAULT FALSE,
+    api_usage BIGINT NOT NULL DEFAULT 0,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+CREATE TABLE IF NOT EXISTS watchlist (
+    owner VARCHAR(100) NOT NULL,
+    stock_code VARCHAR(20) NOT NULL,
+    note TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (owner, stock_code)
+);
+
+CREATE TABLE IF NOT EXISTS alerts (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    owner VARCHAR(100) NOT NULL,
+    stock_code VARCHAR(20) NOT NULL,
+    alert_condition VARCHAR(100) NOT NULL,
+    webhook_url TEXT,
+    condition_met BOOLEAN NOT NULL DEFAULT FALSE,
+    last_checked TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS triggered_alerts (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    alert_id UUID NOT NULL,
+    owner VARCHAR(100) NOT NULL,
+    stock_code VARCHAR(20) NOT NULL,
+    alert_condition VARCHAR(100) NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    triggered_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
 CREATE INDEX IF NOT EXISTS idx_saved_analyses_stock_code ON saved_analyses(stock_code);
 CREATE INDEX IF NOT EXISTS idx_saved_analyses_analysis_date ON saved_analyses(analysis_date);
 CREATE INDEX IF NOT EXISTS idx_saved_analyses_created_at ON saved_analyses(created_at);
+CREATE INDEX IF NOT EXISTS idx_saved_analyses_search ON saved_analyses
+    USING GIN (to_tsvector('simple', COALESCE(ai_analysis, '') || ' ' || recommendation));
 CREATE INDEX IF NOT EXISTS idx_saved_configurations_type ON saved_configurations(config_type);
 CREATE INDEX IF NOT EXISTS idx_saved_configurations_active ON saved_configurations(is_active);
+CREATE INDEX IF NOT EXISTS idx_trading_signals_stock_time ON trading_signals(stock_code, signal_time);
+CREATE INDEX IF NOT EXISTS idx_paper_trades_account_time ON paper_trades(account_id, executed_at);
+CREATE INDEX IF NOT EXISTS idx_triggered_alerts_owner_time ON triggered_alerts(owner, triggered_at);
 
 CREATE OR REPLACE FUNCTION update_updated_at_column()
 RETURNS TRIGGER AS $$
@@ -1,48 +1,76 @@
 use chrono::Utc;
-use reqwest::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tokio::sync::mpsc;
 
-use crate::models::{AIConfig, AnalysisReport};
+use crate::models::{AIConfig, AnalysisMetadata, AnalysisReport, PeerComparison};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(dead_code)]
 pub struct StreamingAnalysisRequest {
     pub report: AnalysisReport,
     pub enable_streaming: bool,
     pub analysis_depth: AnalysisDepth,
+    /// 覆盖本次请求的采样温度，例如 0 用于生成可复现的报告
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    /// 覆盖本次请求的最大回复 token 数
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[allow(dead_code)]
 pub enum AnalysisDepth {
     Basic,
+    #[default]
     Standard,
     Comprehensive,
     Professional,
 }
 
-impl Default for AnalysisDepth {
-    fn default() -> Self {
-        AnalysisDepth::Standard
-    }
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamingChunk {
     pub content: String,
     pub chunk_type: String,
     pub progress: f64,
     pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// 完成块携带提供商返回的 token 用量，提供商未返回时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_used: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AnalysisMetadata {
-    pub provider: String,
-    pub model: String,
-    pub tokens_used: u32,
-    pub processing_time_ms: u64,
-    pub confidence_score: f64,
-    pub analysis_dimensions: Vec<String>,
+impl StreamingChunk {
+    fn new(content: String, chunk_type: &str, progress: f64) -> Self {
+        Self {
+            content,
+            chunk_type: chunk_type.to_string(),
+            progress,
+            timestamp: Utc::now(),
+            tokens_used: None,
+        }
+    }
+}
+
+/// 粗略估算文本的 token 数：中日韩字符按每字 1 个，其余字符按每 4 个 1 个
+pub fn estimate_tokens(text: &str) -> u32 {
+    let (cjk, other) = text.chars().fold((0u32, 0u32), |(cjk, other), c| {
+        let is_cjk = matches!(
+            c,
+            '\u{4e00}'..='\u{9fff}'
+                | '\u{3400}'..='\u{4dbf}'
+                | '\u{3000}'..='\u{303f}'
+                | '\u{ff00}'..='\u{ffef}'
+        );
+        if is_cjk {
+            (cjk + 1, other)
+        } else {
+            (cjk, other + 1)
+        }
+    });
+    cjk + other.div_ceil(4)
 }
 
 #[derive(Debug, Clone)]
@@ -61,6 +89,7 @@ impl AIService {
         Self { config, client }
     }
 
+    #[allow(dead_code)]
     pub async fn generate_streaming_analysis(
         &self,
         request: StreamingAnalysisRequest,
@@ -76,7 +105,11 @@ impl AIService {
         }
 
         let prompt = self.build_enhanced_analysis_prompt(&request.report, &request.analysis_depth);
-        let config = self.config.clone();
+        let config = AIConfig {
+            temperature: request.temperature.or(self.config.temperature),
+            max_tokens: request.max_tokens.or(self.config.max_tokens),
+            ..self.config.clone()
+        };
 
         // Spawn streaming task
         tokio::spawn(async move {
@@ -86,6 +119,7 @@ impl AIService {
         Ok(rx)
     }
 
+    #[allow(dead_code)]
     async fn send_fallback_in_chunks(tx: mpsc::UnboundedSender<StreamingChunk>, content: String) {
         let chunks: Vec<&str> = content.split("\n\n").collect();
         let total_chunks = chunks.len();
@@ -97,6 +131,7 @@ impl AIService {
                     chunk_type: "analysis".to_string(),
                     progress: (i as f64 / total_chunks as f64) * 100.0,
                     timestamp: Utc::now(),
+                    tokens_used: None,
                 };
 
                 if tx.send(streaming_chunk).is_err() {
@@ -114,33 +149,161 @@ impl AIService {
             chunk_type: "complete".to_string(),
             progress: 100.0,
             timestamp: Utc::now(),
+            tokens_used: None,
         };
         let _ = tx.send(completion_chunk);
     }
 
+    #[allow(dead_code)]
     pub async fn generate_analysis(&self, report: &AnalysisReport) -> Result<String, String> {
-        if !self.config.enabled || self.config.api_key.is_empty() {
-            return Ok(self.generate_fallback_analysis(report));
+        self.generate_analysis_with_metadata(report)
+            .await
+            .map(|(analysis, _)| analysis)
+    }
+
+    /// 生成AI分析并返回实际提供服务的提供商、模型、token 数与耗时；
+    /// 主提供商失败时依次尝试 fallback_providers，全部失败时返回错误，
+    /// AI 未启用或没有可用的提供商时返回备用分析，元数据为 None
+    pub async fn generate_analysis_with_metadata(
+        &self,
+        report: &AnalysisReport,
+    ) -> Result<(String, Option<AnalysisMetadata>), String> {
+        let chain = self.provider_chain();
+        if chain.is_empty() {
+            return Ok((self.generate_fallback_analysis(report), None));
         }
 
         let prompt = self.build_analysis_prompt(report);
+        let mut errors = Vec::new();
+        for config in chain {
+            match Self::generate_with_provider(config, &prompt, report).await {
+                Ok((analysis, metadata)) if std::ptr::eq(config, &self.config) => {
+                    return Ok((analysis, Some(metadata)));
+                }
+                Ok((analysis, metadata)) => {
+                    let header = format!(
+                        "> 主AI提供商 {} 不可用，本次分析由备用提供商 {}（{}）生成\n\n",
+                        self.config.provider, metadata.provider, metadata.model
+                    );
+                    return Ok((header + &analysis, Some(metadata)));
+                }
+                Err(err) => {
+                    log::warn!("AI provider {} failed: {}", config.provider, err);
+                    errors.push(format!("{}: {}", config.provider, err));
+                }
+            }
+        }
+
+        Err(errors.join("；"))
+    }
+
+    /// 按尝试顺序排列的提供商：可用的主提供商在前，随后是可用的备用提供商
+    fn provider_chain(&self) -> Vec<&AIConfig> {
+        if !self.config.enabled {
+            return Vec::new();
+        }
+
+        let primary = self.config.is_usable().then_some(&self.config);
+        primary
+            .into_iter()
+            .chain(
+                self.config
+                    .fallback_providers
+                    .iter()
+                    .filter(|fallback| fallback.is_usable()),
+            )
+            .collect()
+    }
+
+    /// 使用指定提供商生成一次分析
+    async fn generate_with_provider(
+        config: &AIConfig,
+        prompt: &str,
+        report: &AnalysisReport,
+    ) -> Result<(String, AnalysisMetadata), String> {
+        let started = std::time::Instant::now();
+        let prompt_tokens = estimate_tokens(prompt);
 
         // Use streaming for all providers
         let (tx, mut rx) = mpsc::unbounded_channel();
 
-        let config = self.config.clone();
-        let provider = self.config.provider.clone();
+        let stream_config = config.clone();
+        let stream_prompt = prompt.to_string();
         tokio::spawn(async move {
-            Self::stream_provider_analysis(&provider, &prompt, tx, &config).await;
+            Self::stream_provider_analysis(
+                &stream_config.provider,
+                &stream_prompt,
+                tx,
+                &stream_config,
+            )
+            .await;
         });
 
         // Collect all streaming chunks
         let mut complete_response = String::new();
+        let mut tokens_used = None;
         while let Some(chunk) = rx.recv().await {
-            complete_response.push_str(&chunk.content);
+            match chunk.chunk_type.as_str() {
+                "error" => return Err(chunk.content),
+                "completion" => tokens_used = chunk.tokens_used,
+                _ => complete_response.push_str(&chunk.content),
+            }
         }
 
-        Ok(complete_response)
+        let metadata = AnalysisMetadata {
+            provider: config.provider.clone(),
+            model: config
+                .model
+                .clone()
+                .unwrap_or_else(|| "default".to_string()),
+            // Estimated from the text only when the provider reported no usage
+            tokens_used: tokens_used
+                .unwrap_or_else(|| prompt_tokens + estimate_tokens(&complete_response)),
+            processing_time_ms: started.elapsed().as_millis() as u64,
+            confidence_score: report.sentiment.confidence_score,
+            analysis_dimensions: analysis_dimensions(report),
+        };
+
+        Ok((complete_response, metadata))
+    }
+
+    /// 通过简短提示词生成 3-4 句纯文本摘要；AI 未启用时返回错误，由调用方退回模板摘要
+    pub async fn generate_summary(&self, report: &AnalysisReport) -> Result<String, String> {
+        if !self.config.is_usable() {
+            return Err("AI服务未启用".to_string());
+        }
+
+        let prompt = format!(
+            "请根据以下股票分析要点，用3到4句中文写一段纯文本摘要，不要使用Markdown、标题或列表，只输出这一段话：\n\n{}\n技术面评分：{:.1}，基本面评分：{:.1}，情绪面评分：{:.1}，新闻情绪：{}",
+            generate_summary_text(report),
+            report.scores.technical,
+            report.scores.fundamental,
+            report.scores.sentiment,
+            report.sentiment.sentiment_trend
+        );
+
+        let summary = self.complete(&prompt).await?;
+        let summary = summary.split_whitespace().collect::<Vec<_>>().join(" ");
+        if summary.is_empty() {
+            return Err("AI返回的摘要为空".to_string());
+        }
+        Ok(summary)
+    }
+
+    /// 按当前提供商发送单轮非流式请求
+    async fn complete(&self, prompt: &str) -> Result<String, String> {
+        match self.config.provider.as_str() {
+            "openai" => self.call_openai(prompt).await,
+            "claude" => self.call_claude(prompt).await,
+            "baidu" => self.call_baidu(prompt).await,
+            "tencent" => self.call_tencent(prompt).await,
+            "glm" => self.call_glm(prompt).await,
+            "qwen" => self.call_qwen(prompt).await,
+            "kimi" => self.call_kimi(prompt).await,
+            "ollama" => self.call_ollama(prompt).await,
+            "gemini" => self.call_gemini(prompt).await,
+            other => Err(format!("不支持的AI提供商: {}", other)),
+        }
     }
 
     async fn call_openai(&self, prompt: &str) -> Result<String, String> {
@@ -154,15 +317,15 @@ impl AIService {
             "messages": [
                 {
                     "role": "system",
-                    "content": "你是一位资深的股票分析师，具有丰富的市场经验和深厚的金融知识。请提供专业、客观、有深度的股票分析。"
+                    "content": system_prompt(&self.config)
                 },
                 {
                     "role": "user",
                     "content": prompt
                 }
             ],
-            "max_tokens": 4000,
-            "temperature": 0.7
+            "max_tokens": max_tokens(&self.config),
+            "temperature": temperature(&self.config)
         });
 
         self.make_post_request(
@@ -179,16 +342,20 @@ impl AIService {
             _ => "https://api.anthropic.com/v1/messages".to_string(),
         };
 
-        let payload = json!({
+        let mut payload = json!({
             "model": self.config.model.as_ref().unwrap_or(&"claude-3-sonnet-20240229".to_string()),
-            "max_tokens": 4000,
+            "max_tokens": max_tokens(&self.config),
             "messages": [
                 {
                     "role": "user",
-                    "content": format!("你是一位资深的股票分析师，具有丰富的市场经验和深厚的金融知识。请提供专业、客观、有深度的股票分析。\n\n{}", prompt)
+                    "content": format!("{}\n\n{}", system_prompt(&self.config), prompt)
                 }
             ]
         });
+        // Claude's own default temperature applies unless one is configured
+        if let Some(temperature) = self.config.temperature {
+            payload["temperature"] = json!(temperature);
+        }
 
         self.make_post_request(
             &url,
@@ -212,15 +379,15 @@ impl AIService {
             "messages": [
                 {
                     "role": "system",
-                    "content": "你是一位资深的股票分析师，具有丰富的市场经验和深厚的金融知识。请提供专业、客观、有深度的股票分析。"
+                    "content": system_prompt(&self.config)
                 },
                 {
                     "role": "user",
                     "content": prompt
                 }
             ],
-            "temperature": 0.7,
-            "max_tokens": 4000
+            "temperature": temperature(&self.config),
+            "max_tokens": max_tokens(&self.config)
         });
 
         self.make_post_request(
@@ -242,16 +409,16 @@ impl AIService {
             "Messages": [
                 {
                     "Role": "system",
-                    "Content": "你是一位资深的股票分析师，具有丰富的市场经验和深厚的金融知识。请提供专业、客观、有深度的股票分析。"
+                    "Content": system_prompt(&self.config)
                 },
                 {
                     "Role": "user",
                     "Content": prompt
                 }
             ],
-            "Temperature": 0.7,
+            "Temperature": temperature(&self.config),
             "TopP": 0.9,
-            "MaxTokens": 4000
+            "MaxTokens": max_tokens(&self.config)
         });
 
         self.make_post_request(
@@ -273,15 +440,15 @@ impl AIService {
             "messages": [
                 {
                     "role": "system",
-                    "content": "你是一位资深的股票分析师，具有丰富的市场经验和深厚的金融知识。请提供专业、客观、有深度的股票分析。"
+                    "content": system_prompt(&self.config)
                 },
                 {
                     "role": "user",
                     "content": prompt
                 }
             ],
-            "max_tokens": 4000,
-            "temperature": 0.7
+            "max_tokens": max_tokens(&self.config),
+            "temperature": temperature(&self.config)
         });
 
         self.make_post_request(
@@ -303,15 +470,15 @@ impl AIService {
             "messages": [
                 {
                     "role": "system",
-                    "content": "你是一位资深的股票分析师，具有丰富的市场经验和深厚的金融知识。请提供专业、客观、有深度的股票分析。"
+                    "content": system_prompt(&self.config)
                 },
                 {
                     "role": "user",
                     "content": prompt
                 }
             ],
-            "max_tokens": 4000,
-            "temperature": 0.7
+            "max_tokens": max_tokens(&self.config),
+            "temperature": temperature(&self.config)
         });
 
         self.make_post_request(
@@ -333,15 +500,15 @@ impl AIService {
             "messages": [
                 {
                     "role": "system",
-                    "content": "你是一位资深的股票分析师，具有丰富的市场经验和深厚的金融知识。请提供专业、客观、有深度的股票分析。"
+                    "content": system_prompt(&self.config)
                 },
                 {
                     "role": "user",
                     "content": prompt
                 }
             ],
-            "max_tokens": 4000,
-            "temperature": 0.7
+            "max_tokens": max_tokens(&self.config),
+            "temperature": temperature(&self.config)
         });
 
         self.make_post_request(
@@ -363,15 +530,15 @@ impl AIService {
             "messages": [
                 {
                     "role": "system",
-                    "content": "你是一位资深的股票分析师，具有丰富的市场经验和深厚的金融知识。请提供专业、客观、有深度的股票分析。"
+                    "content": system_prompt(&self.config)
                 },
                 {
                     "role": "user",
                     "content": prompt
                 }
             ],
-            "max_tokens": 4000,
-            "temperature": 0.7
+            "max_tokens": max_tokens(&self.config),
+            "temperature": temperature(&self.config)
         });
 
         self.make_post_request(
@@ -382,6 +549,41 @@ impl AIService {
         .await
     }
 
+    async fn call_gemini(&self, prompt: &str) -> Result<String, String> {
+        let url = match &self.config.base_url {
+            Some(url) if !url.is_empty() => url.clone(),
+            _ => format!(
+                "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent",
+                self.config.model.as_deref().unwrap_or("gemini-1.5-flash")
+            ),
+        };
+
+        let payload = json!({
+            "systemInstruction": {
+                "parts": [{ "text": system_prompt(&self.config) }]
+            },
+            "contents": [
+                {
+                    "role": "user",
+                    "parts": [{ "text": prompt }]
+                }
+            ],
+            "generationConfig": {
+                "temperature": temperature(&self.config),
+                "maxOutputTokens": max_tokens(&self.config)
+            }
+        });
+
+        // The key goes in a header rather than the `key` query parameter so it never
+        // shows up in request errors, which include the URL
+        self.make_post_request(
+            &url,
+            &payload,
+            &[("x-goog-api-key", self.config.api_key.clone())],
+        )
+        .await
+    }
+
     async fn make_post_request(
         &self,
         url: &str,
@@ -393,33 +595,45 @@ impl AIService {
         for (key, value) in headers {
             request = request.header(*key, value);
         }
+        request = with_custom_headers(request, &self.config);
 
         let response = request
             .send()
             .await
             .map_err(|e| format!("Request failed: {}", e))?;
 
-        if !response.status().is_success() {
-            return Err(format!("API error: {}", response.status()));
-        }
+        let response_json = Self::read_json_response(response).await?;
+
+        extract_response_content(&self.config.provider, &response_json)
+    }
 
-        let response_json: Value = response
-            .json()
+    /// 读取提供商响应体并解析为 JSON；
+    /// 非 JSON 响应（错误页、流式接口返回的 SSE 帧）附带截断片段报错，便于发现 base_url 配置错误
+    async fn read_json_response(response: reqwest::Response) -> Result<Value, String> {
+        let status = response.status();
+        let bytes = response
+            .bytes()
             .await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+            .map_err(|e| format!("Failed to read response body: {}", e))?;
+        let body = String::from_utf8_lossy(&bytes);
 
-        // Extract content from different response formats
-        let content = response_json
-            .get("choices")
-            .and_then(|v| v.get(0))
-            .and_then(|v| v.get("message"))
-            .and_then(|v| v.get("content"))
-            .or_else(|| response_json.get("content"))
-            .or_else(|| response_json.get("result"))
-            .and_then(|v| v.as_str())
-            .unwrap_or("AI分析功能暂不可用，请稍后再试。");
+        if !status.is_success() {
+            return Err(format!("API error: {} ({})", status, body_snippet(&body)));
+        }
+
+        let trimmed = body.trim();
+        if let Ok(value) = serde_json::from_str::<Value>(trimmed) {
+            return Ok(value);
+        }
 
-        Ok(content.to_string())
+        if let Some(value) = parse_sse_body(trimmed) {
+            return Ok(value);
+        }
+
+        Err(format!(
+            "Failed to parse response as JSON, check the provider base_url (body: {})",
+            body_snippet(trimmed)
+        ))
     }
 
     fn build_analysis_prompt(&self, report: &AnalysisReport) -> String {
@@ -463,6 +677,22 @@ impl AIService {
             news_summary.overall_sentiment
         );
 
+        let peer_text = build_peer_comparison_text(report);
+        let extended_hours_text = build_extended_hours_text(report);
+        let relative_index_text = report
+            .relative_to_index
+            .as_ref()
+            .map(|relative| {
+                format!(
+                    "\n- 相对{}：个股 {:+.2}%，指数 {:+.2}%，超额收益 {:+.2}%",
+                    relative.index_name,
+                    relative.stock_return * 100.0,
+                    relative.index_return * 100.0,
+                    relative.excess_return * 100.0
+                )
+            })
+            .unwrap_or_default();
+
         // Build comprehensive prompt similar to Python version
         format!(
             "请作为一位资深的股票分析师，基于以下详细数据对股票进行深度分析：
@@ -473,7 +703,7 @@ impl AIService {
 - 当前价格：{:.2}元
 - 涨跌幅：{:.2}%
 - 成交量比率：{:.2}
-- 波动率：{:.2}%
+- 年化波动率：{:.2}%{}{}
 
 **技术分析详情：**
 - 均线趋势：{}
@@ -481,6 +711,8 @@ impl AIService {
 - MACD信号：{}
 - 布林带位置：{:.2}
 - 成交量状态：{}
+- OBV能量潮：{:.0}（{}）
+- KDJ指标：K {:.1} / D {:.1} / J {:.1}（{}；J>100 超买，J<0 超卖）
 
 {}
 
@@ -491,7 +723,7 @@ impl AIService {
 **行业信息：**
 - 行业：{}
 - 板块：{}
-
+{}
 {}
 
 **市场情绪分析：**
@@ -505,6 +737,7 @@ impl AIService {
 - 基本面得分：{:.0}/100
 - 情绪面得分：{:.0}/100
 - 综合得分：{:.0}/100
+- 动量得分：{:.0}/100
 
 **分析要求：**
 
@@ -553,16 +786,25 @@ impl AIService {
             report.price_info.price_change,
             report.price_info.volume_ratio,
             report.price_info.volatility,
+            extended_hours_text,
+            relative_index_text,
             report.technical.ma_trend,
             report.technical.rsi,
             report.technical.macd_signal,
             report.technical.bb_position,
             report.technical.volume_status,
+            report.technical.obv,
+            report.technical.obv_trend,
+            report.technical.stochastic_k,
+            report.technical.stochastic_d,
+            report.technical.kdj_j,
+            kdj_signal(report.technical.kdj_j),
             financial_text,
             report.fundamental.valuation.get("pe_ratio").unwrap_or(&0.0),
             report.fundamental.valuation.get("pb_ratio").unwrap_or(&0.0),
             report.fundamental.industry,
             report.fundamental.sector,
+            peer_text,
             news_text,
             report.sentiment.overall_sentiment,
             report.sentiment.sentiment_trend,
@@ -572,6 +814,7 @@ impl AIService {
             report.scores.fundamental,
             report.scores.sentiment,
             report.scores.comprehensive,
+            report.scores.momentum,
         )
     }
 
@@ -580,6 +823,7 @@ impl AIService {
             crate::models::Market::ASHARES => "A股",
             crate::models::Market::HONGKONG => "港股",
             crate::models::Market::US => "美股",
+            crate::models::Market::CRYPTO => "加密货币",
             crate::models::Market::UNKNOWN => "股市",
         };
 
@@ -607,6 +851,8 @@ impl AIService {
             }
         }
 
+        let industry = industry_label(report);
+
         // Generate detailed markdown analysis
         let mut analysis = String::new();
 
@@ -689,16 +935,16 @@ impl AIService {
             analysis.push_str(&format!(
                 "{}({})当前展示出{}的财务状况，主要体现在以下三个关键指标：
 
-1. **流动比率与速动比率分别为{:.2}和{:.2}**：这一数值{}银行业2:1的合理标准，表明公司拥有{}的短期偿债能力。{}，反映出公司持有{}的高流动性资产，{}流动性风险。{}，过高的比率也可能暗示资产利用效率不高，大量资金沉淀在低收益资产中。
+1. **流动比率与速动比率分别为{:.2}和{:.2}**：这一数值{}2:1的常见标准，表明公司拥有{}的短期偿债能力。{}，反映出公司持有{}的高流动性资产，{}流动性风险。{}，过高的比率也可能暗示资产利用效率不高，大量资金沉淀在低收益资产中。
 
-2. **产权比率为{:.2}**：这一{}水平表明公司财务杠杆{}，长期偿债能力{}，财务结构{}。作为对比，银行业平均产权比率通常在0.8-1.2之间，{}的保守策略使其在经济下行周期中更具抗风险能力。
+2. **产权比率为{:.2}**：这一{}水平表明公司财务杠杆{}，长期偿债能力{}，财务结构{}。产权比率的合理区间因行业而异，需结合{industry}的资本结构特点判断。
 
 ### 财务优势与风险点
 
 **财务优势**：
 - 短期偿债能力{}，流动性风险{}
 - 财务结构{}，资本充足率{}
-- 速动比率与流动比率{}，表明存货管理高效或存货极少（符合银行业特点）
+- 速动比率与流动比率{}，表明存货管理高效或存货极少
 
 **潜在风险点**：
 - 资产利用效率可能{}，影响资本回报率
@@ -716,7 +962,6 @@ impl AIService {
                 report.stock_name,
                 report.stock_code,
                 if cr > 3.0 { "极为稳健" } else if cr > 2.0 { "相对稳健" } else { "一般" },
-                if cr > 3.0 { "远超" } else if cr > 2.0 { "超过" } else { "接近" },
                 cr,
                 qr,
                 if cr > 3.0 { "远超" } else if cr > 2.0 { "超过" } else { "接近" },
@@ -727,9 +972,9 @@ impl AIService {
                 if cr > 3.0 { "然而" } else if cr > 2.0 { "同时" } else { "不过" },
                 dte,
                 if dte < 0.5 { "低" } else if dte < 1.0 { "适中" } else { "高" },
-                if dte < 0.5 { "适中" } else if dte < 1.0 { "适中" } else { "偏高" },
+                if dte < 1.0 { "适中" } else { "偏高" },
                 if dte < 0.5 { "强" } else if dte < 1.0 { "较好" } else { "一般" },
-                if dte < 0.5 { "稳健" } else if dte < 1.0 { "稳健" } else { "相对激进" },
+                if dte < 1.0 { "稳健" } else { "相对激进" },
                 if cr > 3.0 { "极强" } else if cr > 2.0 { "较强" } else { "一般" },
                 if cr > 3.0 { "几乎为零" } else if cr > 2.0 { "较低" } else { "存在一定" },
                 if cr > 3.0 { "非常稳健" } else if cr > 2.0 { "较为稳健" } else { "一般" },
@@ -740,11 +985,39 @@ impl AIService {
                 if dte < 0.5 { "限制" } else if dte < 1.0 { "适度限制" } else { "允许" },
                 report.stock_name,
                 if cr > 3.0 { "更为保守" } else if cr > 2.0 { "相对保守" } else { "中性" },
-                if cr > 3.0 { "稳健" } else if cr > 2.0 { "稳健" } else { "中性" },
+                if cr > 2.0 { "稳健" } else { "中性" },
                 if dte < 0.5 { "适度提高" } else if dte < 1.0 { "保持" } else { "适度降低" }
             ));
         }
 
+        // Profitability
+        if let Some(r) = roe {
+            analysis.push_str(&format!(
+                "### 盈利能力
+
+**净资产收益率(ROE)为{:.2}%**：股东权益的回报水平{}，{}。
+
+",
+                r,
+                if r > 15.0 {
+                    "优秀"
+                } else if r > 8.0 {
+                    "良好"
+                } else if r > 0.0 {
+                    "偏弱"
+                } else {
+                    "为负"
+                },
+                if r > 15.0 {
+                    "体现出较强的盈利能力和竞争优势"
+                } else if r > 8.0 {
+                    "盈利能力处于合理区间"
+                } else {
+                    "需关注盈利能力能否改善"
+                }
+            ));
+        }
+
         // Technical analysis
         analysis.push_str("\n## 二、技术面精准分析\n\n### 多维度技术指标解读\n\n");
 
@@ -777,7 +1050,7 @@ impl AIService {
         };
 
         analysis.push_str(&format!(
-            "1. **均线趋势：{}** - {}\n\n2. **RSI指标：{:.1}** - {}\n\n3. **MACD信号：{}** - {}\n\n4. **布林带位置：{:.2}** - {}\n\n5. **成交量状态：{}** - {}\n\n",
+            "1. **均线趋势：{}** - {}\n\n2. **RSI指标：{:.1}** - {}\n\n3. **MACD信号：{}** - {}\n\n4. **布林带位置：{:.2}** - {}\n\n5. **成交量状态：{}** - {}\n\n6. **KDJ指标：J值{:.1}** - {}\n\n",
             ma_trend_desc,
             if ma_trend_desc == "多头排列" {
                 "表明股票处于上升趋势中，短期、中期和长期均线从下到上依次排列，对股价形成支撑"
@@ -795,6 +1068,12 @@ impl AIService {
                 "放量" => "交易活跃度有所提升，显示有资金开始关注或介入，但力度尚不够强劲",
                 "缩量" => "交易活跃度下降，市场关注度降低",
                 _ => "交易活跃度稳定，市场表现相对平静"
+            },
+            report.technical.kdj_j,
+            match kdj_signal(report.technical.kdj_j) {
+                "超买" => "J值高于100，处于超买区域，短期存在回调压力",
+                "超卖" => "J值低于0，处于超卖区域，短期可能出现技术性反弹",
+                _ => "J值处于0-100之间，未出现极端信号",
             }
         ));
 
@@ -827,9 +1106,6 @@ impl AIService {
 
         // Risk-reward analysis
         analysis.push_str("### 风险收益比评估\n\n");
-        analysis.push_str("当前位置风险收益比较为均衡：\n");
-        analysis.push_str("- 优势：股价处于相对{}，RSI{}，技术反弹可能性{}；估值{}，安全边际{}；股息率{}，提供稳定收益。\n");
-        analysis.push_str("- 劣势：{}仍对上行构成{}；MACD{}显示方向不明；成交量配合度{}。\n\n");
 
         let current_price_status = if report.technical.bb_position < 0.3 {
             "低位"
@@ -982,7 +1258,7 @@ impl AIService {
 
         if let (Some(pe), Some(pb), Some(dy)) = (pe_ratio, pb_ratio, dividend_yield) {
             analysis.push_str(&format!(
-                "- **PE（市盈率）**：{:.2}，处于历史{}，银行业平均估值水平。\n- **PE TTM（滚动市盈率）**：{:.2}，同样处于{}，反映市场对公司盈利能力的{}态度。\n- **PB（市净率）**：{:.2}，{}1，表明股价{}每股净资产。\n- **股息率**：{:.2}%，{}银行存款利率和多数理财产品收益率。\n\n",
+                "- **PE（市盈率）**：{:.2}，处于历史{}，可结合{industry}平均估值水平对比。\n- **PE TTM（滚动市盈率）**：{:.2}，同样处于{}，反映市场对公司盈利能力的{}态度。\n- **PB（市净率）**：{:.2}，{}1，表明股价{}每股净资产。\n- **股息率**：{:.2}%，{}银行存款利率和多数理财产品收益率。\n\n",
                 pe,
                 if pe < 10.0 { "低位" } else if pe < 20.0 { "中位" } else { "高位" },
                 pe,
@@ -997,7 +1273,7 @@ impl AIService {
 
             analysis.push_str("### 内在价值评估\n\n");
             analysis.push_str(&format!(
-                "基于低PE、低PB和高股息率，{}可能存在价值被低估的情况：\n- 优势：估值安全边际{}，股息回报{}，财务结构{}。\n- 劣势：缺乏详细业绩数据评估成长潜力，银行业整体面临增长压力。\n\n",
+                "基于低PE、低PB和高股息率，{}可能存在价值被低估的情况：\n- 优势：估值安全边际{}，股息回报{}，财务结构{}。\n- 劣势：缺乏详细业绩数据评估成长潜力，需关注{industry}景气度变化。\n\n",
                 report.stock_name,
                 if pe < 15.0 && pb < 1.0 { "较高" } else if pe < 25.0 && pb < 2.0 { "适中" } else { "较低" },
                 if dy > 3.0 { "丰厚" } else if dy > 1.5 { "良好" } else { "一般" },
@@ -1012,84 +1288,80 @@ impl AIService {
         // Industry position
         analysis.push_str("### 行业地位与竞争优势\n\n");
         analysis.push_str(&format!(
-            "{}作为{}旗下的重要金融板块，依托集团生态优势，在零售银行和综合金融服务方面具有较强竞争力。作为股份制银行，其在产品创新、数字化转型等方面具有优势，但在网点规模和客户基础方面与大型国有银行相比仍有差距。\n\n",
-            report.stock_name,
-            if report.stock_code.starts_with("000001") || report.stock_code.starts_with("600036") {
-                "平安集团"
-            } else if report.stock_code.starts_with("600519") {
-                "茅台集团"
-            } else {
-                "相关集团"
-            }
+            "{}所属行业为{}。备用分析未包含同业对比数据，公司的行业地位与竞争优势需结合市场份额、盈利能力及同业估值水平综合判断。\n\n",
+            report.stock_name, industry
         ));
 
         // Investment strategy
         analysis.push_str("## 五、综合投资策略\n\n### 买卖建议\n\n");
         analysis.push_str("**建议策略**：");
 
+        let rsi_text = if report.technical.rsi < 30.0 {
+            "超卖"
+        } else {
+            "当前"
+        };
+        let bb_text = if report.technical.bb_position < 0.3 {
+            "接近下轨"
+        } else {
+            "相对稳定"
+        };
+        let valuation_text = if let (Some(pe), Some(pb)) = (pe_ratio, pb_ratio) {
+            if pe < 15.0 && pb < 1.0 {
+                "低位"
+            } else if pe < 25.0 && pb < 2.0 {
+                "合理水平"
+            } else {
+                "高位"
+            }
+        } else {
+            "需要评估"
+        };
+        let safety_text = if let (Some(pe), Some(pb)) = (pe_ratio, pb_ratio) {
+            if pe < 15.0 && pb < 1.0 {
+                "较高"
+            } else if pe < 25.0 && pb < 2.0 {
+                "适中"
+            } else {
+                "较低"
+            }
+        } else {
+            "需要评估"
+        };
+        let dividend_text = dividend_yield.map_or("需要数据评估".to_string(), |dy| {
+            if dy > 3.0 {
+                "高达".to_string()
+            } else if dy > 1.5 {
+                "适中".to_string()
+            } else {
+                "偏低".to_string()
+            }
+        });
+        let sentiment_text = if report.sentiment.overall_sentiment > 0.1 {
+            "偏向积极"
+        } else {
+            "相对稳定"
+        };
+
         if report.scores.comprehensive >= 70.0 {
             analysis.push_str("逢低布局，分批建仓\n\n**理由**：\n1. 技术面表现强势，趋势向好\n2. 估值相对合理，具备成长空间\n3. 基本面稳健，财务状况良好\n4. 市场情绪积极，有改善迹象");
         } else if report.scores.comprehensive >= 50.0 {
-            analysis.push_str("持有观望\n\n**理由**：\n1. 技术面RSI{}且布林带{}，存在反弹空间\n2. 估值处于{}，安全边际{}\n3. 股息率{}，提供稳定收益\n4. 市场情绪趋势{}，有改善迹象");
+            analysis.push_str(&format!(
+                "持有观望\n\n**理由**：\n1. 技术面RSI{}且布林带{}，存在反弹空间\n2. 估值处于{}，安全边际{}\n3. 股息率{}，提供稳定收益\n4. 市场情绪{}",
+                rsi_text, bb_text, valuation_text, safety_text, dividend_text, sentiment_text
+            ));
         } else {
             analysis.push_str("谨慎操作，等待时机\n\n**理由**：\n1. 技术面偏弱，存在回调风险\n2. 估值可能偏高，安全边际不足\n3. 基本面有待改善\n4. 市场情绪偏消极");
         }
 
         if report.scores.comprehensive >= 50.0 {
-            let rsi_text = if report.technical.rsi < 30.0 {
-                "超卖"
-            } else {
-                "当前"
-            };
-            let bb_text = if report.technical.bb_position < 0.3 {
-                "接近下轨"
-            } else {
-                "相对稳定"
-            };
-            let _valuation_text = if let (Some(pe), Some(pb)) = (pe_ratio, pb_ratio) {
-                if pe < 15.0 && pb < 1.0 {
-                    "低位"
-                } else if pe < 25.0 && pb < 2.0 {
-                    "合理水平"
-                } else {
-                    "高位"
-                }
-            } else {
-                "需要评估"
-            };
-            let safety_text = if let (Some(pe), Some(pb)) = (pe_ratio, pb_ratio) {
-                if pe < 15.0 && pb < 1.0 {
-                    "较高"
-                } else if pe < 25.0 && pb < 2.0 {
-                    "适中"
-                } else {
-                    "较低"
-                }
-            } else {
-                "需要评估"
-            };
-            let dividend_text = dividend_yield.map_or("需要数据评估".to_string(), |dy| {
-                if dy > 3.0 {
-                    "高达".to_string()
-                } else if dy > 1.5 {
-                    "适中".to_string()
-                } else {
-                    "偏低".to_string()
-                }
-            });
-            let sentiment_text = if report.sentiment.overall_sentiment > 0.1 {
-                "偏向积极"
-            } else {
-                "相对稳定"
-            };
-
             analysis.push_str(&format!(
-                "基于低{}、低{}和高{}，平安银行可能存在价值被低估的情况：优势：估值安全边际{}，股息回报{}，财务结构稳健。劣势：缺乏详细业绩数据评估成长潜力，银行业整体面临增长压力。市场情绪{}。",
-                rsi_text,
-                bb_text,
-                dividend_text,
+                "\n\n综合来看，{}估值处于{}，安全边际{}，股息率{}。劣势：缺乏详细业绩数据评估成长潜力，需关注{}景气度变化。市场情绪{}。",
+                report.stock_name,
+                valuation_text,
                 safety_text,
                 dividend_text,
+                industry,
                 sentiment_text
             ));
         }
@@ -1134,25 +1406,37 @@ impl AIService {
         analysis.push_str("### 投资时间周期\n\n");
         analysis.push_str("- **短期**：1-3个月，关注技术面反弹机会\n");
         analysis.push_str("- **中期**：3-12个月，关注基本面改善和估值修复\n");
-        analysis.push_str("- **长期**：1年以上，关注银行业整体发展趋势和公司战略转型成效\n\n");
+        analysis.push_str(&format!(
+            "- **长期**：1年以上，关注{}整体发展趋势和公司战略转型成效\n\n",
+            industry
+        ));
 
         // Risk assessment
         analysis.push_str("## 六、风险机会识别\n\n### 主要投资风险及应对措施\n\n");
-        analysis.push_str("1. **行业风险**：银行业面临经济下行、资产质量恶化的风险\n   - **应对**：密切关注不良贷款率、拨备覆盖率等资产质量指标\n\n");
-        analysis.push_str("2. **政策风险**：金融监管政策变化可能影响业务发展\n   - **应对**：跟踪政策动向，评估对公司业务的潜在影响\n\n");
+        analysis.push_str(&format!(
+            "1. **行业风险**：{}面临经济下行、景气度回落的风险\n   - **应对**：密切关注行业供需变化及公司营收、盈利质量指标\n\n",
+            industry
+        ));
+        analysis.push_str("2. **政策风险**：行业监管政策变化可能影响业务发展\n   - **应对**：跟踪政策动向，评估对公司业务的潜在影响\n\n");
         analysis.push_str("3. **市场风险**：股市整体波动可能影响股价表现\n   - **应对**：分散投资，控制仓位，设置止损\n\n");
         analysis.push_str("4. **流动性风险**：虽然公司流动性指标良好，但市场流动性变化仍需关注\n   - **应对**：保持一定现金储备，避免在市场极度恐慌时被迫卖出\n\n");
 
         // Potential catalysts
         analysis.push_str("### 潜在催化剂和成长机会\n\n");
-        analysis.push_str("1. **经济复苏**：宏观经济企稳回升将利好银行业整体表现\n   - **影响**：可能带动信贷需求增长，改善资产质量\n\n");
-        analysis.push_str("2. **利率市场化**：利率市场化进程可能带来新的业务机会\n   - **影响**：可能提升净息差，增加中间业务收入\n\n");
-        analysis.push_str("3. **数字化转型**：金融科技应用深化可能提升运营效率\n   - **影响**：降低成本，提高客户体验，增强竞争力\n\n");
-        analysis.push_str("4. **综合金融协同**：依托平安集团生态，强化综合金融服务\n   - **影响**：可能带来交叉销售机会，提升客户价值\n\n");
+        analysis.push_str(&format!(
+            "1. **经济复苏**：宏观经济企稳回升将利好{}整体表现\n   - **影响**：可能带动需求增长，改善盈利能力\n\n",
+            industry
+        ));
+        analysis.push_str("2. **政策支持**：产业政策与市场化改革可能带来新的业务机会\n   - **影响**：可能拓展业务空间，增加收入来源\n\n");
+        analysis.push_str("3. **数字化转型**：数字化技术应用深化可能提升运营效率\n   - **影响**：降低成本，提高客户体验，增强竞争力\n\n");
+        analysis.push_str("4. **业务协同**：深化主营业务与产业链上下游协同\n   - **影响**：可能带来交叉销售机会，提升客户价值\n\n");
 
         // Macro environment
         analysis.push_str("### 宏观环境与政策影响\n\n");
-        analysis.push_str("当前宏观经济面临一定下行压力，货币政策可能保持宽松，这对银行业整体利好（降低资金成本）。但需关注地方政府债务风险、房地产调控等政策变化对银行业资产质量的潜在影响。\n\n");
+        analysis.push_str(&format!(
+            "当前宏观经济面临一定下行压力，货币政策可能保持宽松，有助于降低企业融资成本。但需关注相关产业政策变化对{}的潜在影响。\n\n",
+            industry
+        ));
 
         // Dynamic adjustment suggestions
         analysis.push_str("### 动态调整建议\n\n");
@@ -1173,13 +1457,14 @@ impl AIService {
         };
 
         analysis.push_str(&format!(
-            "{}当前呈现\"{}\"的特征，投资价值{}。建议投资者采取{}的策略，分批建仓，设置合理止损，重点关注{}机会和{}信号。同时，需密切关注银行业整体环境变化和公司资产质量状况，适时调整投资策略。\n\n",
+            "{}当前呈现\"{}\"的特征，投资价值{}。建议投资者采取{}的策略，分批建仓，设置合理止损，重点关注{}机会和{}信号。同时，需密切关注{}整体环境变化和公司经营状况，适时调整投资策略。\n\n",
             report.stock_name,
             characteristics,
             if characteristics.contains("低估值") || characteristics.contains("技术强势") { "凸显" } else { "适中" },
             if report.scores.comprehensive >= 50.0 { "逢低布局" } else { "谨慎对待" },
             if report.technical.rsi < 30.0 { "技术面反弹" } else { "基本面改善" },
-            if report.technical.rsi < 30.0 { "基本面改善" } else { "技术面突破" }
+            if report.technical.rsi < 30.0 { "基本面改善" } else { "技术面突破" },
+            industry
         ));
 
         analysis.push_str(&format!(
@@ -1214,6 +1499,7 @@ impl AIService {
         }
     }
 
+    #[allow(dead_code)]
     fn build_enhanced_analysis_prompt(
         &self,
         report: &AnalysisReport,
@@ -1371,6 +1657,7 @@ impl AIService {
         base_prompt + &enhanced_context
     }
 
+    #[allow(dead_code)]
     fn generate_enhanced_fallback_analysis(
         &self,
         report: &AnalysisReport,
@@ -1384,6 +1671,7 @@ impl AIService {
         }
     }
 
+    #[allow(dead_code)]
     fn generate_enhanced_fallback_analysis_static(
         _report: &AnalysisReport,
         depth: &AnalysisDepth,
@@ -1423,32 +1711,38 @@ impl AIService {
         }
     }
 
+    #[allow(dead_code)]
     fn basic_analysis_template() -> String {
         "**基础分析：**
 基于当前技术指标和基本面数据，该股票显示出{}的趋势。RSI为{:.1}，处于{}状态。MACD信号为{}，表明短期动能{}。".to_string()
     }
 
+    #[allow(dead_code)]
     fn standard_analysis_template() -> String {
         "**标准分析：**
 从估值角度看，当前市盈率{:.2}倍，市净率{:.2}倍，{}行业平均水平。成交量比率{:.2}，显示市场参与度{}。"
             .to_string()
     }
 
+    #[allow(dead_code)]
     fn technical_analysis_template() -> String {
         "**技术分析深度：**
 布林带位置{:.2}，表明价格处于{}区域。ADX趋势强度{:.2}，显示趋势力度{}。随机指标显示短期超买超卖状态。".to_string()
     }
 
+    #[allow(dead_code)]
     fn fundamental_analysis_template() -> String {
         "**基本面深度分析：**
 公司财务健康度评分为{:.1}/100，其中盈利能力{:.1}，流动性{:.1}。分析师预测未来{}增长潜力{}。风险等级为{}，需要关注{}。".to_string()
     }
 
+    #[allow(dead_code)]
     fn quantitative_analysis_template() -> String {
         "**量化分析：**
 基于多因子模型评分，该股票在{}维度表现{}。贝塔系数{:.2}，显示{}系统性风险。最大回撤分析显示下行保护{}。".to_string()
     }
 
+    #[allow(dead_code)]
     fn risk_analysis_template() -> String {
         "**风险评估：**
 主要风险包括：{}。建议止损价位设置在当前价位的{}以下。目标价位区间为{}，风险收益比约为{}。"
@@ -1456,22 +1750,27 @@ impl AIService {
     }
 
     // These methods would be implemented with actual report data
+    #[allow(dead_code)]
     fn generate_basic_fallback_analysis(&self, report: &AnalysisReport) -> String {
         self.generate_detailed_fallback_analysis(report, false)
     }
 
+    #[allow(dead_code)]
     fn generate_standard_fallback_analysis(&self, report: &AnalysisReport) -> String {
         self.generate_detailed_fallback_analysis(report, true)
     }
 
+    #[allow(dead_code)]
     fn generate_comprehensive_fallback_analysis(&self, report: &AnalysisReport) -> String {
         self.generate_detailed_fallback_analysis(report, true)
     }
 
+    #[allow(dead_code)]
     fn generate_professional_fallback_analysis(&self, report: &AnalysisReport) -> String {
         self.generate_detailed_fallback_analysis(report, true)
     }
 
+    #[allow(dead_code)]
     fn generate_detailed_fallback_analysis(
         &self,
         report: &AnalysisReport,
@@ -1600,7 +1899,7 @@ impl AIService {
         if report.sentiment.overall_sentiment < -0.2 {
             analysis.push_str("- 市场情绪偏消极，注意情绪面风险\n");
         }
-        if report.price_info.volatility > 3.0 {
+        if report.price_info.volatility > HIGH_VOLATILITY_PCT {
             analysis.push_str("- 股价波动较大，注意控制仓位\n");
         }
 
@@ -1672,6 +1971,17 @@ impl AIService {
                 analysis.push_str("- 布林带: 在中轨附近运行，趋势相对稳定\n");
             }
 
+            // KDJ分析
+            let kdj_desc = match kdj_signal(report.technical.kdj_j) {
+                "超买" => "超买区域，警惕短期回调",
+                "超卖" => "超卖区域，关注反弹机会",
+                _ => "中性区域，无极端信号",
+            };
+            analysis.push_str(&format!(
+                "- KDJ (J: {:.1}): {}\n",
+                report.technical.kdj_j, kdj_desc
+            ));
+
             // 成交量分析
             let volume_desc = match report.technical.volume_status.as_str() {
                 "放量" => "成交量放大，市场活跃度提升",
@@ -1695,14 +2005,12 @@ impl AIService {
             analysis.push_str("\n## 📈 市场微观结构分析\n\n");
             analysis.push_str(&format!(
                 "- 价格波动率: {:.2}%\n- 成交量比率: {:.2}\n- ATR (平均真实范围): {:.4}\n\n",
-                report.price_info.volatility * 100.0,
-                report.price_info.volume_ratio,
-                report.technical.atr
+                report.price_info.volatility, report.price_info.volume_ratio, report.technical.atr
             ));
 
-            let volatility_desc = if report.price_info.volatility < 1.0 {
+            let volatility_desc = if report.price_info.volatility < LOW_VOLATILITY_PCT {
                 "低波动，适合稳健型投资者"
-            } else if report.price_info.volatility < 2.5 {
+            } else if report.price_info.volatility < HIGH_VOLATILITY_PCT {
                 "中等波动，风险收益平衡"
             } else {
                 "高波动，适合风险承受能力较强的投资者"
@@ -1734,7 +2042,12 @@ impl AIService {
     }
 
     pub fn is_enabled(&self) -> bool {
-        self.config.enabled && !self.config.api_key.is_empty()
+        self.config.is_usable()
+    }
+
+    /// 向当前服务商发送一个极短的请求，确认 API 可达且密钥有效
+    pub async fn check_connection(&self) -> Result<(), String> {
+        self.complete("ping").await.map(|_| ())
     }
 
     pub fn get_provider(&self) -> &str {
@@ -1742,11 +2055,7 @@ impl AIService {
     }
 
     pub fn get_model(&self) -> &str {
-        self.config
-            .model
-            .as_ref()
-            .map(|s| s.as_str())
-            .unwrap_or("default")
+        self.config.model.as_deref().unwrap_or("default")
     }
 
     pub fn update_config(&mut self, config: AIConfig) {
@@ -1761,6 +2070,15 @@ impl AIService {
     async fn simulate_streaming_analysis(
         result: Result<String, String>,
         tx: mpsc::UnboundedSender<StreamingChunk>,
+    ) {
+        Self::simulate_streaming_with_usage(result, None, tx).await;
+    }
+
+    /// 模拟流式输出，完成块附带非流式响应中的 token 用量
+    async fn simulate_streaming_with_usage(
+        result: Result<String, String>,
+        tokens_used: Option<u32>,
+        tx: mpsc::UnboundedSender<StreamingChunk>,
     ) {
         match result {
             Ok(content) => {
@@ -1775,6 +2093,7 @@ impl AIService {
                         chunk_type: "content".to_string(),
                         progress,
                         timestamp: Utc::now(),
+                        tokens_used: None,
                     };
                     let _ = tx.send(streaming_chunk);
 
@@ -1788,6 +2107,7 @@ impl AIService {
                     chunk_type: "completion".to_string(),
                     progress: 1.0,
                     timestamp: Utc::now(),
+                    tokens_used,
                 };
                 let _ = tx.send(completion_chunk);
             }
@@ -1797,13 +2117,105 @@ impl AIService {
                     chunk_type: "error".to_string(),
                     progress: 0.0,
                     timestamp: Utc::now(),
+                    tokens_used: None,
                 };
                 let _ = tx.send(error_chunk);
             }
         }
     }
 
+    /// 逐块读取提供商的 SSE 响应，由 `parse_line` 解析每行并即时转发增量内容；
+    /// 错误响应或未按 SSE 返回的兼容服务按完整 JSON 解析后模拟流式输出
+    async fn forward_sse_stream(
+        provider: &str,
+        response: reqwest::Response,
+        tx: mpsc::UnboundedSender<StreamingChunk>,
+        parse_line: fn(&str) -> StreamEvent,
+    ) {
+        use futures::StreamExt;
+
+        let is_event_stream = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("text/event-stream"));
+        if !response.status().is_success() || !is_event_stream {
+            let response_json = Self::read_json_response(response).await;
+            let tokens_used = response_json.as_ref().ok().and_then(response_token_usage);
+            let result = response_json
+                .and_then(|response_json| extract_response_content(provider, &response_json));
+            Self::simulate_streaming_with_usage(result, tokens_used, tx).await;
+            return;
+        }
+
+        let mut lines = SseLineBuffer::default();
+        let mut received = String::new();
+        let (mut input_tokens, mut output_tokens) = (None, None);
+        let mut body = response.bytes_stream();
+        let mut finished = false;
+
+        while !finished {
+            let pending = match body.next().await {
+                Some(Ok(bytes)) => lines.push(&bytes),
+                Some(Err(e)) => {
+                    let _ = tx.send(StreamingChunk::new(
+                        format!("Failed to read stream: {}", e),
+                        "error",
+                        0.0,
+                    ));
+                    return;
+                }
+                // A body closed without `[DONE]` still ends the stream, flush what is left
+                None => {
+                    finished = true;
+                    lines.finish().into_iter().collect()
+                }
+            };
+
+            for line in pending {
+                match parse_line(&line) {
+                    StreamEvent::Delta(text) => {
+                        received.push_str(&text);
+                        let progress = (estimate_tokens(&received) as f64 / 4000.0).min(0.99);
+                        if tx
+                            .send(StreamingChunk::new(text, "content", progress))
+                            .is_err()
+                        {
+                            // Receiver dropped, stop reading the body
+                            return;
+                        }
+                    }
+                    StreamEvent::Done => {
+                        finished = true;
+                        break;
+                    }
+                    StreamEvent::Error(e) => {
+                        let _ = tx.send(StreamingChunk::new(
+                            format!("{} API error: {}", provider, e),
+                            "error",
+                            0.0,
+                        ));
+                        return;
+                    }
+                    StreamEvent::Usage { input, output } => {
+                        input_tokens = input.or(input_tokens);
+                        output_tokens = output.or(output_tokens);
+                    }
+                    StreamEvent::Ignored => {}
+                }
+            }
+        }
+
+        let mut completion = StreamingChunk::new(String::new(), "completion", 1.0);
+        completion.tokens_used = match (input_tokens, output_tokens) {
+            (None, None) => None,
+            (input, output) => Some(input.unwrap_or(0) + output.unwrap_or(0)),
+        };
+        let _ = tx.send(completion);
+    }
+
     // Streaming analysis methods for different providers
+    #[allow(dead_code)]
     async fn stream_openai_analysis(
         prompt: &str,
         tx: mpsc::UnboundedSender<StreamingChunk>,
@@ -1819,37 +2231,26 @@ impl AIService {
             "messages": [
                 {
                     "role": "system",
-                    "content": "你是一位资深的股票分析师，具有丰富的市场经验和深厚的金融知识。请提供专业、客观、有深度的股票分析。"
+                    "content": system_prompt(config)
                 },
                 {
                     "role": "user",
                     "content": prompt
                 }
             ],
-            "max_tokens": 4000,
-            "temperature": 0.7
+            "max_tokens": max_tokens(config),
+            "temperature": temperature(config)
         });
 
         let client = Client::new();
         let mut request = client.post(&url).json(&payload);
         request = request.header("Authorization", format!("Bearer {}", config.api_key));
+        request = with_custom_headers(request, config);
 
         let result = match request.send().await {
-            Ok(response) => {
-                if response.status().is_success() {
-                    let response_json: Value = response.json().await.unwrap_or_default();
-                    let content = response_json
-                        .get("choices")
-                        .and_then(|v| v.get(0))
-                        .and_then(|v| v.get("message"))
-                        .and_then(|v| v.get("content"))
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("AI分析功能暂不可用，请稍后再试。");
-                    Ok(content.to_string())
-                } else {
-                    Err(format!("API error: {}", response.status()))
-                }
-            }
+            Ok(response) => Self::read_json_response(response)
+                .await
+                .and_then(|response_json| extract_response_content("openai", &response_json)),
             Err(e) => Err(format!("Request failed: {}", e)),
         };
 
@@ -1875,36 +2276,75 @@ impl AIService {
                     "messages": [
                         {
                             "role": "system",
-                            "content": "你是一位资深的股票分析师，具有丰富的市场经验和深厚的金融知识。请提供专业、客观、有深度的股票分析。"
+                            "content": system_prompt(config)
                         },
                         {
                             "role": "user",
                             "content": prompt
                         }
                     ],
-                    "max_tokens": 4000,
-                    "temperature": 0.7
+                    "max_tokens": max_tokens(config),
+                    "temperature": temperature(config),
+                    "stream": true,
+                    "stream_options": { "include_usage": true }
                 });
 
                 let client = Client::new();
                 let mut request = client.post(&url).json(&payload);
                 request = request.header("Authorization", format!("Bearer {}", config.api_key));
+                request = with_custom_headers(request, config);
 
                 match request.send().await {
                     Ok(response) => {
-                        if response.status().is_success() {
-                            let response_json: Value = response.json().await.unwrap_or_default();
-                            let content = response_json
-                                .get("choices")
-                                .and_then(|v| v.get(0))
-                                .and_then(|v| v.get("message"))
-                                .and_then(|v| v.get("content"))
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("AI分析功能暂不可用，请稍后再试。");
-                            Ok(content.to_string())
-                        } else {
-                            Err(format!("API error: {}", response.status()))
+                        return Self::forward_sse_stream(
+                            "openai",
+                            response,
+                            tx,
+                            parse_openai_stream_line,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(format!("Request failed: {}", e)),
+                }
+            }
+            "claude" => {
+                let url = match &config.base_url {
+                    Some(url) if !url.is_empty() => url.clone(),
+                    _ => "https://api.anthropic.com/v1/messages".to_string(),
+                };
+
+                let mut payload = json!({
+                    "model": config.model.as_ref().unwrap_or(&"claude-3-sonnet-20240229".to_string()),
+                    "max_tokens": max_tokens(config),
+                    "stream": true,
+                    "messages": [
+                        {
+                            "role": "user",
+                            "content": format!("{}\n\n{}", system_prompt(config), prompt)
                         }
+                    ]
+                });
+                if let Some(temperature) = config.temperature {
+                    payload["temperature"] = json!(temperature);
+                }
+
+                let client = Client::new();
+                let mut request = client
+                    .post(&url)
+                    .json(&payload)
+                    .header("x-api-key", &config.api_key)
+                    .header("anthropic-version", "2023-06-01");
+                request = with_custom_headers(request, config);
+
+                match request.send().await {
+                    Ok(response) => {
+                        return Self::forward_sse_stream(
+                            "claude",
+                            response,
+                            tx,
+                            parse_claude_stream_line,
+                        )
+                        .await
                     }
                     Err(e) => Err(format!("Request failed: {}", e)),
                 }
@@ -1920,47 +2360,536 @@ impl AIService {
                     "messages": [
                         {
                             "role": "system",
-                            "content": "你是一位资深的股票分析师，具有丰富的市场经验和深厚的金融知识。请提供专业、客观、有深度的股票分析。"
+                            "content": system_prompt(config)
                         },
                         {
                             "role": "user",
                             "content": prompt
                         }
                     ],
-                    "max_tokens": 4000,
-                    "temperature": 0.7
+                    "max_tokens": max_tokens(config),
+                    "temperature": temperature(config)
                 });
 
                 let client = Client::new();
                 let mut request = client.post(&url).json(&payload);
                 request = request.header("Authorization", format!("Bearer {}", config.api_key));
+                request = with_custom_headers(request, config);
 
                 match request.send().await {
                     Ok(response) => {
-                        if response.status().is_success() {
-                            let response_json: Value = response.json().await.unwrap_or_default();
-                            let content = response_json
-                                .get("choices")
-                                .and_then(|v| v.get(0))
-                                .and_then(|v| v.get("message"))
-                                .and_then(|v| v.get("content"))
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("AI分析功能暂不可用，请稍后再试。");
-                            Ok(content.to_string())
-                        } else {
-                            Err(format!("API error: {}", response.status()))
-                        }
+                        let response_json = Self::read_json_response(response).await;
+                        let tokens_used =
+                            response_json.as_ref().ok().and_then(response_token_usage);
+                        let result = response_json.and_then(|response_json| {
+                            extract_response_content("glm", &response_json)
+                        });
+                        return Self::simulate_streaming_with_usage(result, tokens_used, tx).await;
                     }
                     Err(e) => Err(format!("Request failed: {}", e)),
                 }
             }
-            _ => Ok(format!("{} 流式分析暂未实现，使用模拟数据。", provider)),
+            // Providers without a streaming implementation answer in one request
+            _ => AIService::new(config.clone()).complete(prompt).await,
         };
 
         Self::simulate_streaming_analysis(result, tx).await;
     }
 }
 
+/// 备用分析中的行业名称：依次取行业、板块，均未知时使用通用表述
+fn industry_label(report: &AnalysisReport) -> &str {
+    [&report.fundamental.industry, &report.fundamental.sector]
+        .into_iter()
+        .map(|name| name.trim())
+        .find(|name| !name.is_empty() && *name != "未知")
+        .unwrap_or("所属行业")
+}
+
+/// KDJ 的 J 值解读：高于 100 为超买，低于 0 为超卖
+fn kdj_signal(j: f64) -> &'static str {
+    if j > 100.0 {
+        "超买"
+    } else if j < 0.0 {
+        "超卖"
+    } else {
+        "中性"
+    }
+}
+
+/// 年化波动率（%）低于该值视为低波动
+#[allow(dead_code)]
+const LOW_VOLATILITY_PCT: f64 = 20.0;
+/// 年化波动率（%）高于该值视为高波动
+#[allow(dead_code)]
+const HIGH_VOLATILITY_PCT: f64 = 40.0;
+
+/// 未配置 temperature 时使用的采样温度
+pub const DEFAULT_TEMPERATURE: f64 = 0.7;
+/// 未配置 max_tokens 时单次回复的最大 token 数
+pub const DEFAULT_MAX_TOKENS: u32 = 4000;
+
+fn temperature(config: &AIConfig) -> f64 {
+    config.temperature.unwrap_or(DEFAULT_TEMPERATURE)
+}
+
+fn max_tokens(config: &AIConfig) -> u32 {
+    config.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS)
+}
+
+/// 未配置 system_prompt 时使用的系统提示词
+pub const DEFAULT_SYSTEM_PROMPT: &str =
+    "你是一位资深的股票分析师，具有丰富的市场经验和深厚的金融知识。请提供专业、客观、有深度的股票分析。";
+
+/// 配置的系统提示词（未配置时为默认中文提示词），设置 output_language 时追加输出语言要求
+fn system_prompt(config: &AIConfig) -> String {
+    let base = config
+        .system_prompt
+        .as_deref()
+        .map(str::trim)
+        .filter(|prompt| !prompt.is_empty())
+        .unwrap_or(DEFAULT_SYSTEM_PROMPT);
+
+    match config
+        .output_language
+        .as_deref()
+        .map(str::trim)
+        .filter(|language| !language.is_empty())
+    {
+        Some(language) => format!("{}\n\nRespond in {}.", base, language),
+        None => base.to_string(),
+    }
+}
+
+/// 合并配置的自定义请求头，同名请求头（包括认证头）以配置为准；无效的名称或值会被跳过
+fn with_custom_headers(request: RequestBuilder, config: &AIConfig) -> RequestBuilder {
+    let Some(custom_headers) = &config.custom_headers else {
+        return request;
+    };
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in custom_headers {
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            }
+            _ => log::warn!("Skipping invalid custom AI request header: {}", name),
+        }
+    }
+    request.headers(headers)
+}
+
+/// 分析提示词中实际包含的报告部分
+fn analysis_dimensions(report: &AnalysisReport) -> Vec<String> {
+    let mut dimensions = vec!["技术面".to_string(), "估值".to_string()];
+    if !report.fundamental.financial_indicators.is_empty() {
+        dimensions.push("财务指标".to_string());
+    }
+    if report.sentiment.total_analyzed > 0 {
+        dimensions.push("新闻情绪".to_string());
+    }
+    if !report.peers.is_empty() {
+        dimensions.push("同业对比".to_string());
+    }
+    if report.relative_to_index.is_some() {
+        dimensions.push("相对基准指数".to_string());
+    }
+    if report.price_info.extended_hours.is_some() {
+        dimensions.push("盘前盘后".to_string());
+    }
+    dimensions
+}
+
+/// 由价格、评分、建议与关键技术信号按模板生成 3-4 句纯文本摘要
+pub fn generate_summary_text(report: &AnalysisReport) -> String {
+    let rsi_note = if report.technical.rsi >= 70.0 {
+        "，处于超买区间"
+    } else if report.technical.rsi <= 30.0 {
+        "，处于超卖区间"
+    } else {
+        ""
+    };
+
+    let mut summary = format!(
+        "{}（{}）最新价{:.2}{}，近期涨跌幅{:+.2}%。综合评分{:.1}分，投资建议为“{}”。技术面上股价{}，MACD信号{}，RSI为{:.1}{}。",
+        report.stock_name,
+        report.stock_code,
+        report.price_info.current_price,
+        report.market.get_currency(),
+        report.price_info.price_change,
+        report.scores.comprehensive,
+        report.recommendation,
+        report.technical.ma_trend,
+        report.technical.macd_signal,
+        report.technical.rsi,
+        rsi_note
+    );
+
+    if let Some(relative) = &report.relative_to_index {
+        summary.push_str(&format!(
+            "分析窗口内相对{}的超额收益为{:+.2}%。",
+            relative.index_name,
+            relative.excess_return * 100.0
+        ));
+    } else if report.sentiment.total_analyzed > 0 {
+        summary.push_str(&format!(
+            "近期新闻情绪{}。",
+            report.sentiment.sentiment_trend
+        ));
+    }
+    summary
+}
+
+/// 美股盘前/盘后报价行（含换行前缀），无数据时返回空字符串
+fn build_extended_hours_text(report: &AnalysisReport) -> String {
+    let Some(quote) = &report.price_info.extended_hours else {
+        return String::new();
+    };
+
+    let mut text = String::new();
+    for (label, price, change_pct) in [
+        ("盘前", quote.pre_market_price, quote.pre_market_change_pct),
+        (
+            "盘后",
+            quote.post_market_price,
+            quote.post_market_change_pct,
+        ),
+    ] {
+        if let Some(price) = price {
+            text.push_str(&format!(
+                "\n- {}价格：{:.2}（{:+.2}%）",
+                label,
+                price,
+                change_pct.unwrap_or(0.0)
+            ));
+        }
+    }
+    text
+}
+
+/// 同业对比表，无同业数据时返回空字符串
+fn build_peer_comparison_text(report: &AnalysisReport) -> String {
+    if report.peers.is_empty() {
+        return String::new();
+    }
+
+    let fmt = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.2}", v));
+    let current = PeerComparison::from_fundamental(
+        &report.stock_code,
+        &report.stock_name,
+        &report.fundamental,
+    );
+    let mut text = String::from(
+        "\n**同业对比：**\n| 股票 | 行业 | 市盈率 | 市净率 | ROE |\n|---|---|---|---|---|\n",
+    );
+    for peer in std::iter::once(&current).chain(&report.peers) {
+        text.push_str(&format!(
+            "| {}({}) | {} | {} | {} | {} |\n",
+            peer.stock_name,
+            peer.stock_code,
+            peer.industry,
+            fmt(peer.pe_ratio),
+            fmt(peer.pb_ratio),
+            fmt(peer.roe),
+        ));
+    }
+    text.push_str("请结合同业对比数据，给出相对估值与竞争地位的具体判断。\n");
+    text
+}
+
+/// 从提供商的非流式响应中提取生成文本；未识别的格式按 OpenAI 兼容的
+/// `choices[0].message.content` 解析（自定义端点、代理及重组后的 SSE 响应），
+/// 提供商错误与无法识别的格式返回错误而非占位文本，以便调用方回退
+fn extract_response_content(provider: &str, response: &Value) -> Result<String, String> {
+    if let Some(error) = provider_error_message(response) {
+        return Err(format!("{} API error: {}", provider, error));
+    }
+
+    let content = match provider {
+        "claude" => claude_content(response),
+        "gemini" => gemini_content(response),
+        "baidu" => response
+            .get("result")
+            .and_then(|v| v.as_str())
+            .map(str::to_string),
+        "tencent" => response
+            .get("Response")
+            .and_then(|v| v.get("Choices"))
+            .and_then(|v| v.get(0))
+            .and_then(|v| v.get("Message"))
+            .and_then(|v| v.get("Content"))
+            .and_then(|v| v.as_str())
+            .map(str::to_string),
+        _ => None,
+    }
+    .or_else(|| openai_content(response));
+
+    content.ok_or_else(|| {
+        format!(
+            "Unrecognized {} response format: {}",
+            provider,
+            body_snippet(&response.to_string())
+        )
+    })
+}
+
+fn openai_content(response: &Value) -> Option<String> {
+    response
+        .get("choices")
+        .and_then(|v| v.get(0))
+        .and_then(|v| v.get("message"))
+        .and_then(|v| v.get("content"))
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+}
+
+/// Claude 的 `content` 为内容块列表，拼接其中的文本块
+fn claude_content(response: &Value) -> Option<String> {
+    let blocks = response.get("content")?.as_array()?;
+    let texts: Vec<&str> = blocks
+        .iter()
+        .filter(|block| block.get("type").and_then(|v| v.as_str()) == Some("text"))
+        .filter_map(|block| block.get("text").and_then(|v| v.as_str()))
+        .collect();
+
+    (!texts.is_empty()).then(|| texts.concat())
+}
+
+/// 拼接 Gemini `candidates[0].content.parts` 中的文本片段
+fn gemini_content(response: &Value) -> Option<String> {
+    let parts = response
+        .get("candidates")?
+        .get(0)?
+        .get("content")?
+        .get("parts")?
+        .as_array()?;
+    let texts: Vec<&str> = parts
+        .iter()
+        .filter_map(|part| part.get("text").and_then(|v| v.as_str()))
+        .collect();
+
+    (!texts.is_empty()).then(|| texts.concat())
+}
+
+/// 提取已知格式的错误信息：OpenAI/Claude 的 `error.message`、百度的 `error_msg`、
+/// 腾讯的 `Response.Error.Message`
+fn provider_error_message(response: &Value) -> Option<String> {
+    let error = response.get("error");
+    error
+        .and_then(|v| v.get("message"))
+        .or_else(|| error.filter(|v| v.is_string()))
+        .or_else(|| response.get("error_msg"))
+        .or_else(|| {
+            response
+                .get("Response")
+                .and_then(|v| v.get("Error"))
+                .and_then(|v| v.get("Message"))
+        })
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+}
+
+/// 错误信息中附带的原始响应体最大字符数
+const ERROR_BODY_SNIPPET_CHARS: usize = 300;
+
+fn body_snippet(body: &str) -> String {
+    let body = body.trim();
+    if body.is_empty() {
+        return "<empty body>".to_string();
+    }
+
+    let mut snippet: String = body.chars().take(ERROR_BODY_SNIPPET_CHARS).collect();
+    if body.chars().count() > ERROR_BODY_SNIPPET_CHARS {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+/// 将 SSE 帧格式（`data: {...}` 行）的对话补全重组为 `choices[0].message.content` 形式的 JSON，
+/// 没有任何帧携带内容时返回 None
+fn parse_sse_body(body: &str) -> Option<Value> {
+    let mut content = String::new();
+    let mut found = false;
+
+    for line in body.lines() {
+        let Some(data) = line.trim().strip_prefix("data:") else {
+            continue;
+        };
+        let data = data.trim();
+        if data.is_empty() || data == "[DONE]" {
+            continue;
+        }
+
+        let Ok(frame) = serde_json::from_str::<Value>(data) else {
+            continue;
+        };
+        let choice = frame.get("choices").and_then(|v| v.get(0));
+        let piece = choice
+            .and_then(|c| c.get("delta").or_else(|| c.get("message")))
+            .and_then(|v| v.get("content"))
+            .or_else(|| frame.get("content"))
+            .or_else(|| frame.get("result"))
+            .and_then(|v| v.as_str());
+
+        if let Some(piece) = piece {
+            content.push_str(piece);
+            found = true;
+        }
+    }
+
+    found.then(|| json!({ "choices": [{ "message": { "content": content } }] }))
+}
+
+/// 把分块到达的 SSE 字节流切分为完整的行；跨分块的半行（包括被截断的多字节字符）
+/// 留在缓冲区，等下一块到达后再拼接
+#[derive(Debug, Default)]
+struct SseLineBuffer {
+    pending: Vec<u8>,
+}
+
+impl SseLineBuffer {
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(bytes);
+        let mut lines = Vec::new();
+        while let Some(end) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            lines.push(Self::decode(&line));
+        }
+        lines
+    }
+
+    /// 流结束时剩余的最后一行（没有以换行结尾）
+    fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.pending);
+        (!rest.is_empty()).then(|| Self::decode(&rest))
+    }
+
+    fn decode(line: &[u8]) -> String {
+        String::from_utf8_lossy(line)
+            .trim_end_matches(['\r', '\n'])
+            .to_string()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum StreamEvent {
+    Delta(String),
+    /// token 用量，未给出的一项保持之前收到的值
+    Usage {
+        input: Option<u32>,
+        output: Option<u32>,
+    },
+    Done,
+    Error(String),
+    Ignored,
+}
+
+/// 解析 OpenAI 流式响应的一行：`data: {...}` 中 `choices[0].delta.content` 为增量内容，
+/// `data: [DONE]` 表示结束；空行、注释、角色帧等其他行忽略
+fn parse_openai_stream_line(line: &str) -> StreamEvent {
+    let Some(data) = line.trim().strip_prefix("data:") else {
+        return StreamEvent::Ignored;
+    };
+    let data = data.trim();
+    if data == "[DONE]" {
+        return StreamEvent::Done;
+    }
+
+    let Ok(frame) = serde_json::from_str::<Value>(data) else {
+        return StreamEvent::Ignored;
+    };
+    if let Some(error) = provider_error_message(&frame) {
+        return StreamEvent::Error(error);
+    }
+
+    let delta = frame
+        .get("choices")
+        .and_then(|v| v.get(0))
+        .and_then(|v| v.get("delta"))
+        .and_then(|v| v.get("content"))
+        .and_then(|v| v.as_str())
+        .filter(|text| !text.is_empty());
+    if let Some(text) = delta {
+        return StreamEvent::Delta(text.to_string());
+    }
+
+    // With `include_usage` the last frame before `[DONE]` has empty choices and the usage
+    let usage = frame.get("usage").filter(|v| v.is_object());
+    match usage {
+        Some(usage) => StreamEvent::Usage {
+            input: token_count(usage, "prompt_tokens"),
+            output: token_count(usage, "completion_tokens"),
+        },
+        None => StreamEvent::Ignored,
+    }
+}
+
+fn token_count(usage: &Value, field: &str) -> Option<u32> {
+    usage.get(field).and_then(|v| v.as_u64()).map(|v| v as u32)
+}
+
+/// 非流式响应中的 token 总数：OpenAI 兼容格式的 `usage.total_tokens`，
+/// Claude 的 `usage.input_tokens + usage.output_tokens`
+fn response_token_usage(response: &Value) -> Option<u32> {
+    let usage = response.get("usage")?;
+    token_count(usage, "total_tokens").or_else(|| {
+        match (
+            token_count(usage, "input_tokens"),
+            token_count(usage, "output_tokens"),
+        ) {
+            (None, None) => None,
+            (input, output) => Some(input.unwrap_or(0) + output.unwrap_or(0)),
+        }
+    })
+}
+
+/// 解析 Claude 流式响应的一行：`content_block_delta` 事件的 `delta.text` 为增量内容，
+/// `message_stop` 表示结束，`error` 事件返回错误；`event:` 行与其他事件忽略
+fn parse_claude_stream_line(line: &str) -> StreamEvent {
+    let Some(data) = line.trim().strip_prefix("data:") else {
+        return StreamEvent::Ignored;
+    };
+    let Ok(frame) = serde_json::from_str::<Value>(data.trim()) else {
+        return StreamEvent::Ignored;
+    };
+    if let Some(error) = provider_error_message(&frame) {
+        return StreamEvent::Error(error);
+    }
+
+    match frame.get("type").and_then(|v| v.as_str()) {
+        Some("content_block_delta") => frame
+            .get("delta")
+            .and_then(|v| v.get("text"))
+            .and_then(|v| v.as_str())
+            .filter(|text| !text.is_empty())
+            .map_or(StreamEvent::Ignored, |text| {
+                StreamEvent::Delta(text.to_string())
+            }),
+        // message_start carries the input tokens, message_delta the cumulative output tokens
+        Some("message_start") => frame.get("message").and_then(|v| v.get("usage")).map_or(
+            StreamEvent::Ignored,
+            |usage| StreamEvent::Usage {
+                input: token_count(usage, "input_tokens"),
+                output: token_count(usage, "output_tokens"),
+            },
+        ),
+        Some("message_delta") => {
+            frame
+                .get("usage")
+                .map_or(StreamEvent::Ignored, |usage| StreamEvent::Usage {
+                    input: None,
+                    output: token_count(usage, "output_tokens"),
+                })
+        }
+        Some("message_stop") => StreamEvent::Done,
+        _ => StreamEvent::Ignored,
+    }
+}
+
+#[allow(dead_code)]
 pub fn get_supported_providers() -> Vec<String> {
     vec![
         "openai".to_string(),
@@ -1971,6 +2900,7 @@ pub fn get_supported_providers() -> Vec<String> {
         "qwen".to_string(),
         "kimi".to_string(),
         "ollama".to_string(),
+        "gemini".to_string(),
         "custom".to_string(),
     ]
 }
@@ -2033,6 +2963,13 @@ pub fn get_ai_providers_info() -> Vec<serde_json::Value> {
             "models": vec!["llama2", "mistral", "codellama", "llama3", "qwen", "glm4"],
             "base_url": "http://localhost:11434/v1"
         }),
+        json!({
+            "name": "Google Gemini",
+            "provider": "gemini",
+            "description": "Google Gemini系列模型",
+            "models": vec!["gemini-1.5-flash", "gemini-1.5-pro", "gemini-2.0-flash"],
+            "base_url": "https://generativelanguage.googleapis.com/v1beta"
+        }),
         json!({
             "name": "自定义API",
             "provider": "custom",
@@ -2042,3 +2979,405 @@ pub fn get_ai_providers_info() -> Vec<serde_json::Value> {
         }),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_openai_compatible_content() {
+        let body = json!({
+            "id": "chatcmpl-1",
+            "choices": [{ "index": 0, "message": { "role": "assistant", "content": "看涨" } }]
+        });
+        for provider in ["openai", "glm", "qwen", "kimi", "ollama", "custom"] {
+            assert_eq!(extract_response_content(provider, &body).unwrap(), "看涨");
+        }
+    }
+
+    #[test]
+    fn test_extract_claude_content_blocks() {
+        let body = json!({
+            "type": "message",
+            "role": "assistant",
+            "content": [
+                { "type": "text", "text": "第一段" },
+                { "type": "tool_use", "id": "t1", "name": "x", "input": {} },
+                { "type": "text", "text": "第二段" }
+            ],
+            "stop_reason": "end_turn"
+        });
+        assert_eq!(
+            extract_response_content("claude", &body).unwrap(),
+            "第一段第二段"
+        );
+
+        let error = json!({
+            "type": "error",
+            "error": { "type": "overloaded_error", "message": "Overloaded" }
+        });
+        let err = extract_response_content("claude", &error).unwrap_err();
+        assert!(err.contains("Overloaded"));
+    }
+
+    #[test]
+    fn test_extract_gemini_candidate_parts() {
+        let body = json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": "估值" }, { "text": "偏低" }] },
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": { "totalTokenCount": 42 }
+        });
+        assert_eq!(
+            extract_response_content("gemini", &body).unwrap(),
+            "估值偏低"
+        );
+
+        let error = json!({ "error": { "code": 400, "message": "API key not valid" } });
+        let err = extract_response_content("gemini", &error).unwrap_err();
+        assert!(err.contains("API key not valid"));
+    }
+
+    #[test]
+    fn test_extract_baidu_result() {
+        let body = json!({
+            "id": "as-1",
+            "object": "chat.completion",
+            "result": "震荡",
+            "is_truncated": false
+        });
+        assert_eq!(extract_response_content("baidu", &body).unwrap(), "震荡");
+
+        let error =
+            json!({ "error_code": 110, "error_msg": "Access token invalid or no longer valid" });
+        let err = extract_response_content("baidu", &error).unwrap_err();
+        assert!(err.contains("Access token invalid"));
+    }
+
+    #[test]
+    fn test_extract_tencent_response() {
+        let body = json!({
+            "Response": {
+                "RequestId": "r-1",
+                "Choices": [{ "FinishReason": "stop", "Message": { "Role": "assistant", "Content": "看跌" } }]
+            }
+        });
+        assert_eq!(extract_response_content("tencent", &body).unwrap(), "看跌");
+
+        let error = json!({
+            "Response": {
+                "RequestId": "r-2",
+                "Error": { "Code": "AuthFailure.SignatureFailure", "Message": "signature mismatch" }
+            }
+        });
+        let err = extract_response_content("tencent", &error).unwrap_err();
+        assert!(err.contains("signature mismatch"));
+    }
+
+    #[test]
+    fn test_extract_unknown_shape_is_error() {
+        let body = json!({ "output": { "text": "?" } });
+        let err = extract_response_content("openai", &body).unwrap_err();
+        assert!(err.contains("Unrecognized openai response format"));
+        assert!(!err.contains("暂不可用"));
+    }
+
+    #[test]
+    fn test_kdj_signal_thresholds() {
+        assert_eq!(kdj_signal(3.0 * 95.0 - 2.0 * 85.0), "超买");
+        assert_eq!(kdj_signal(3.0 * 10.0 - 2.0 * 20.0), "超卖");
+        assert_eq!(kdj_signal(100.0), "中性");
+        assert_eq!(kdj_signal(0.0), "中性");
+    }
+
+    #[test]
+    fn test_system_prompt_defaults_and_language() {
+        let config = AIConfig::default();
+        assert_eq!(system_prompt(&config), DEFAULT_SYSTEM_PROMPT);
+
+        let config = AIConfig {
+            system_prompt: Some("You are a seasoned equity analyst.".to_string()),
+            output_language: Some("English".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            system_prompt(&config),
+            "You are a seasoned equity analyst.\n\nRespond in English."
+        );
+
+        let config = AIConfig {
+            system_prompt: Some("  ".to_string()),
+            output_language: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(system_prompt(&config), DEFAULT_SYSTEM_PROMPT);
+    }
+
+    #[test]
+    fn test_keyless_ollama_primary_is_used() {
+        let service = AIService::new(AIConfig {
+            provider: "ollama".to_string(),
+            fallback_providers: vec![AIConfig {
+                api_key: "sk-test".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        assert!(service.is_enabled());
+        let providers: Vec<_> = service
+            .provider_chain()
+            .iter()
+            .map(|config| config.provider.as_str())
+            .collect();
+        assert_eq!(providers, vec!["ollama", "openai"]);
+
+        let keyless = AIService::new(AIConfig::default());
+        assert!(!keyless.is_enabled());
+        assert!(keyless.provider_chain().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_gemini_request_error_omits_api_key() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let service = AIService::new(AIConfig {
+            provider: "gemini".to_string(),
+            api_key: "gemini-secret-key".to_string(),
+            base_url: Some(format!(
+                "http://{}/v1beta/models/test:generateContent",
+                addr
+            )),
+            ..Default::default()
+        });
+        let err = service.call_gemini("prompt").await.unwrap_err();
+        assert!(err.starts_with("Request failed"), "{}", err);
+        assert!(!err.contains("gemini-secret-key"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_configured_sampling_reaches_payload() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (body_tx, body_rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            // Headers and body may arrive in separate reads
+            let mut request = Vec::new();
+            let mut buf = [0u8; 8192];
+            let body = loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break String::new();
+                }
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= length {
+                        break body.to_string();
+                    }
+                }
+            };
+            let _ = body_tx.send(body);
+            let reply = r#"{"choices":[{"message":{"content":"ok"}}]}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                reply.len(),
+                reply
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let config = AIConfig {
+            api_key: "sk-test".to_string(),
+            base_url: Some(format!("http://{}/v1/chat/completions", addr)),
+            temperature: Some(0.0),
+            max_tokens: Some(256),
+            ..Default::default()
+        };
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        AIService::stream_provider_analysis("openai", "prompt", tx, &config).await;
+        while rx.recv().await.is_some() {}
+
+        let payload: Value = serde_json::from_str(&body_rx.await.unwrap()).unwrap();
+        assert_eq!(payload["temperature"], 0.0);
+        assert_eq!(payload["max_tokens"], 256);
+
+        let defaults = AIConfig::default();
+        assert_eq!(temperature(&defaults), DEFAULT_TEMPERATURE);
+        assert_eq!(max_tokens(&defaults), DEFAULT_MAX_TOKENS);
+    }
+
+    #[test]
+    fn test_custom_headers_merged_into_request() {
+        let config = AIConfig {
+            api_key: "sk-test".to_string(),
+            custom_headers: AIConfig::parse_custom_headers(
+                "X-Org-Id=acme, Authorization=Bearer gateway-key,Bad Header=x",
+            ),
+            ..Default::default()
+        };
+
+        let request = Client::new()
+            .post("http://localhost/v1/chat/completions")
+            .header("Authorization", format!("Bearer {}", config.api_key));
+        let request = with_custom_headers(request, &config).build().unwrap();
+        let headers = request.headers();
+
+        assert_eq!(headers["x-org-id"], "acme");
+        assert_eq!(headers.get_all("authorization").iter().count(), 1);
+        assert_eq!(headers["authorization"], "Bearer gateway-key");
+        assert!(!headers.contains_key("bad header"));
+    }
+
+    #[test]
+    fn test_sse_lines_split_across_chunks() {
+        let mut buffer = SseLineBuffer::default();
+        let frame = "data: {\"choices\":[{\"delta\":{\"content\":\"看涨\"}}]}\r\n";
+        // Split inside the multi-byte character so neither half is valid UTF-8 on its own
+        let split = frame.find("看").unwrap() + 1;
+
+        assert!(buffer.push(&frame.as_bytes()[..split]).is_empty());
+        let lines = buffer.push(&frame.as_bytes()[split..]);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(
+            parse_openai_stream_line(&lines[0]),
+            StreamEvent::Delta("看涨".to_string())
+        );
+
+        let lines = buffer.push(b"\ndata: [DONE]");
+        assert_eq!(lines, vec![String::new()]);
+        assert_eq!(
+            parse_openai_stream_line(&buffer.finish().unwrap()),
+            StreamEvent::Done
+        );
+        assert!(buffer.finish().is_none());
+    }
+
+    #[test]
+    fn test_parse_openai_stream_line_variants() {
+        let role = r#"data: {"choices":[{"delta":{"role":"assistant"}}]}"#;
+        assert_eq!(parse_openai_stream_line(role), StreamEvent::Ignored);
+        assert_eq!(
+            parse_openai_stream_line(": keep-alive"),
+            StreamEvent::Ignored
+        );
+        let error = r#"data: {"error":{"message":"rate limited"}}"#;
+        assert_eq!(
+            parse_openai_stream_line(error),
+            StreamEvent::Error("rate limited".to_string())
+        );
+    }
+
+    #[test]
+    fn test_response_token_usage_shapes() {
+        let openai = json!({ "usage": { "prompt_tokens": 10, "total_tokens": 25 } });
+        assert_eq!(response_token_usage(&openai), Some(25));
+        let claude = json!({ "usage": { "input_tokens": 10, "output_tokens": 7 } });
+        assert_eq!(response_token_usage(&claude), Some(17));
+        assert_eq!(response_token_usage(&json!({ "result": "x" })), None);
+    }
+
+    #[test]
+    fn test_parse_claude_stream_events() {
+        let lines = [
+            "event: message_start",
+            r#"data: {"type":"message_start","message":{"id":"msg_1","content":[],"usage":{"input_tokens":300,"output_tokens":1}}}"#,
+            r#"data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"估值"}}"#,
+            r#"data: {"type":"ping"}"#,
+            r#"data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"合理"}}"#,
+            r#"data: {"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":42}}"#,
+            r#"data: {"type":"message_stop"}"#,
+        ];
+        let events: Vec<StreamEvent> = lines
+            .iter()
+            .map(|line| parse_claude_stream_line(line))
+            .filter(|event| *event != StreamEvent::Ignored)
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                StreamEvent::Usage {
+                    input: Some(300),
+                    output: Some(1),
+                },
+                StreamEvent::Delta("估值".to_string()),
+                StreamEvent::Delta("合理".to_string()),
+                StreamEvent::Usage {
+                    input: None,
+                    output: Some(42),
+                },
+                StreamEvent::Done,
+            ]
+        );
+
+        let error =
+            r#"data: {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        assert_eq!(
+            parse_claude_stream_line(error),
+            StreamEvent::Error("Overloaded".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_forwards_deltas_as_they_arrive() {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0u8; 8192];
+            let _ = tokio::io::AsyncReadExt::read(&mut socket, &mut request).await;
+
+            let pieces = [
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n",
+                "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+                "data: {\"choices\":[{\"delta\":{\"content\":\"技术面\"}}]}\n\ndata: {\"choi",
+                "ces\":[{\"delta\":{\"content\":\"偏强\"}}]}\n\n",
+                "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":120,\"completion_tokens\":8,\"total_tokens\":128}}\n\n",
+                "data: [DONE]\n\n",
+            ];
+            for piece in pieces {
+                socket.write_all(piece.as_bytes()).await.unwrap();
+                socket.flush().await.unwrap();
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        });
+
+        let config = AIConfig {
+            api_key: "sk-test".to_string(),
+            base_url: Some(format!("http://{}/v1/chat/completions", addr)),
+            ..Default::default()
+        };
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        AIService::stream_provider_analysis("openai", "prompt", tx, &config).await;
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            chunks.push(chunk);
+        }
+        let contents: Vec<&str> = chunks
+            .iter()
+            .filter(|c| c.chunk_type == "content")
+            .map(|c| c.content.as_str())
+            .collect();
+        assert_eq!(contents, vec!["技术面", "偏强"]);
+        let completion = chunks.last().unwrap();
+        assert_eq!(completion.chunk_type, "completion");
+        assert_eq!(completion.tokens_used, Some(128));
+    }
+}
@@ -1,23 +1,44 @@
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
-use crate::ai_service::AIService;
-use crate::data_fetcher::DataFetcher;
+use crate::ai_service::{generate_summary_text, AIService};
+use crate::chip_monitor::ChipMonitor;
+use crate::data_fetcher::{get_all_data_concurrent, DataFetcher};
 use crate::database::Database;
+use crate::indicators::{self, Indicator, IndicatorRegistry};
 use crate::models::Market;
 use crate::models::*;
+use crate::signal_alerts::SignalAlertSystem;
+use crate::trading_strategies::{TradingStrategiesAnalyzer, DEFAULT_ACCOUNT_RISK_PCT};
+
+/// 单次分析允许请求的最大价格数据窗口（天）
+pub const MAX_ANALYSIS_DAYS: i32 = 500;
+
+const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+
+/// 判断 OBV 与价格方向的回看交易日数
+const OBV_TREND_PERIOD: usize = 10;
+
+/// 计算 `PriceInfo::volatility` 使用的日收益率个数
+const VOLATILITY_WINDOW: usize = 20;
+
+/// 单次分析最多纳入AI提示词的同业股票数量
+pub const MAX_PEERS: usize = 3;
 
 pub struct StockAnalyzer {
-    data_fetcher: Box<dyn DataFetcher>,
+    data_fetcher: Arc<dyn DataFetcher>,
     config: AnalysisConfig,
     ai_service: Arc<RwLock<AIService>>,
     database: Option<Arc<Database>>,
+    indicators: IndicatorRegistry,
 }
 
 impl StockAnalyzer {
     pub fn new(
-        data_fetcher: Box<dyn DataFetcher>,
+        data_fetcher: Arc<dyn DataFetcher>,
         config: AnalysisConfig,
         ai_service: Arc<RwLock<AIService>>,
     ) -> Self {
@@ -26,11 +47,12 @@ impl StockAnalyzer {
             config,
             ai_service,
             database: None,
+            indicators: IndicatorRegistry::with_builtins(),
         }
     }
 
     pub fn with_database(
-        data_fetcher: Box<dyn DataFetcher>,
+        data_fetcher: Arc<dyn DataFetcher>,
         config: AnalysisConfig,
         ai_service: Arc<RwLock<AIService>>,
         database: Arc<Database>,
@@ -40,47 +62,183 @@ impl StockAnalyzer {
             config,
             ai_service,
             database: Some(database),
+            indicators: IndicatorRegistry::with_builtins(),
         }
     }
 
-    pub fn data_fetcher(&self) -> &dyn DataFetcher {
-        self.data_fetcher.as_ref()
+    /// 注册额外的指标，其结果写入 `custom_indicators`
+    #[allow(dead_code)]
+    pub fn register_indicator(&mut self, indicator: Box<dyn Indicator>) {
+        self.indicators.register(indicator);
+    }
+
+    pub fn config(&self) -> &AnalysisConfig {
+        &self.config
+    }
+
+    pub fn data_fetcher(&self) -> &Arc<dyn DataFetcher> {
+        &self.data_fetcher
     }
 
     pub async fn analyze_single_stock(
         &self,
         stock_code: &str,
-        enable_ai: bool,
+        options: &AnalysisOptions,
+    ) -> Result<AnalysisReport, String> {
+        self.analyze_with_price_data(stock_code, options, None)
+            .await
+    }
+
+    /// 批量预取价格数据，供批量分析复用以减少上游请求；获取失败的股票不在结果中
+    pub async fn prefetch_price_data(
+        &self,
+        stock_codes: &[String],
+        options: &AnalysisOptions,
+    ) -> HashMap<String, Vec<PriceData>> {
+        // Default windows differ per market, so batch codes sharing the same window together
+        let mut codes_by_days: HashMap<i32, Vec<&str>> = HashMap::new();
+        for code in stock_codes {
+            let market = Market::from_stock_code(&Market::normalize_code(code));
+            if let Ok(days) = self.resolve_period_days(options.days, &market) {
+                codes_by_days.entry(days).or_default().push(code);
+            }
+        }
+
+        let mut prefetched = HashMap::new();
+        for (days, codes) in codes_by_days {
+            prefetched.extend(
+                self.data_fetcher
+                    .get_stock_data_batch(&codes, days)
+                    .await
+                    .into_iter()
+                    .filter_map(|(code, result)| result.ok().map(|data| (code, data))),
+            );
+        }
+        prefetched
+    }
+
+    /// 分析单只股票，可传入预取的价格数据；未提供时与其他数据一并获取
+    pub async fn analyze_with_price_data(
+        &self,
+        stock_code: &str,
+        options: &AnalysisOptions,
+        price_data: Option<Vec<PriceData>>,
     ) -> Result<AnalysisReport, String> {
+        let stock_code = &Market::normalize_code(stock_code);
         let market = Market::from_stock_code(stock_code);
+        let days = self.resolve_period_days(options.days, &market)?;
+        let news_days = self.config.parameters_for(&market).sentiment_period_days;
+
+        // Use concurrent data fetching for better performance, bounded by the fetch-phase budget
+        let fetch = async {
+            match price_data {
+                Some(price_data) => {
+                    let (fundamental_data, news_data, stock_name) = tokio::join!(
+                        self.data_fetcher.get_fundamental_data(stock_code),
+                        self.data_fetcher.get_news_data(stock_code, news_days),
+                        self.data_fetcher.get_stock_name(stock_code),
+                    );
+                    Ok((price_data, fundamental_data?, news_data?, stock_name))
+                }
+                None => {
+                    get_all_data_concurrent(&self.data_fetcher, stock_code, days, news_days).await
+                }
+            }
+        };
+        let fetch_timeout = self.config.data_fetch_timeout_seconds;
+        let (price_data, mut fundamental_data, (news_data, sentiment_data), stock_name) =
+            tokio::time::timeout(Duration::from_secs(fetch_timeout), fetch)
+                .await
+                .map_err(|_| format!("数据获取超时（{}秒）", fetch_timeout))??;
+
+        let total_news_count = news_data.len() as i32;
+        let (news_data, sentiment_data) =
+            select_news(news_data, sentiment_data, self.config.max_news);
+        let data_quality = DataQuality {
+            financial_indicators_count: fundamental_data.financial_indicators.len() as i32,
+            total_news_count,
+            analyzed_news_count: news_data.len() as i32,
+            analysis_completeness: "完整".to_string(),
+        };
 
-        // Use concurrent data fetching for better performance
-        let (price_data, fundamental_data, (news_data, sentiment_data), stock_name) = self
-            .data_fetcher
-            .get_all_data_concurrent(stock_code, self.config.parameters.technical_period_days)
-            .await?;
+        let relative_to_index = self.relative_to_index(&market, &price_data, days).await;
+        if fundamental_data.risk_assessment.beta.is_none() && self.config.local_beta {
+            let local_beta = match &relative_to_index {
+                Some(relative) => relative
+                    .beta
+                    .map(|beta| (beta, relative.index_code.clone())),
+                None => self
+                    .fetch_relative_performance(&market, &price_data, days)
+                    .await
+                    .and_then(|relative| relative.beta.map(|beta| (beta, relative.index_code))),
+            };
+            if let Some((beta, index_code)) = local_beta {
+                let risk = &mut fundamental_data.risk_assessment;
+                risk.beta = Some(beta);
+                risk.beta_source = Some(BetaSource::Local);
+                risk.beta_index = Some(index_code);
+            }
+        }
 
-        let technical = self.calculate_technical_analysis(&price_data);
-        let price_info = self.calculate_price_info(&price_data);
+        let mut technical = self.calculate_technical_analysis(&price_data);
+        let mut price_info = self.calculate_price_info(&price_data);
+        if options.normalize_indicators {
+            technical.normalized =
+                indicators::normalize_indicators(&technical, price_info.current_price);
+        }
+        if options.include_extended_hours && market == Market::US {
+            match self.data_fetcher.get_extended_hours_quote(stock_code).await {
+                Ok(quote) => price_info.extended_hours = quote,
+                Err(e) => log::warn!(
+                    "Failed to fetch extended-hours quote for {}: {}",
+                    stock_code,
+                    e
+                ),
+            }
+        }
 
         let technical_score = self.calculate_technical_score(&technical, &price_data);
         let fundamental_score = self.calculate_fundamental_score(&fundamental_data, &market);
         let sentiment_score = self.calculate_sentiment_score(&sentiment_data);
+        let momentum_score = self.calculate_momentum_score(&technical, &price_data);
 
-        let comprehensive_score = technical_score * self.config.weights.technical
-            + fundamental_score * self.config.weights.fundamental
-            + sentiment_score * self.config.weights.sentiment;
+        let comprehensive_score =
+            self.weighted_comprehensive_score(technical_score, fundamental_score, sentiment_score);
 
         let scores = AnalysisScores {
             technical: technical_score,
             fundamental: fundamental_score,
             sentiment: sentiment_score,
             comprehensive: comprehensive_score,
+            momentum: momentum_score,
+        };
+
+        // Signals on thinly traded names are unreliable, so cap the advice regardless of score
+        let liquidity_warning = self.check_liquidity(&price_data);
+        let recommendation =
+            self.gated_recommendation(&scores, &technical, liquidity_warning.as_deref());
+        let grade = self.config.grade_for(scores.comprehensive);
+
+        let strategy_analysis = if options.enable_strategies {
+            self.analyze_strategies(stock_code, &stock_name, &price_data)
+                .await
+        } else {
+            None
         };
 
-        let recommendation = self.generate_recommendation(&scores, &technical);
+        // Peer metrics only feed the AI prompt, so skip fetching them otherwise
+        let peers = if options.enable_ai && !options.structured_only {
+            self.fetch_peer_comparisons(stock_code, &options.peers)
+                .await
+        } else {
+            Vec::new()
+        };
 
-        let (ai_analysis, fallback_used, fallback_reason) = if enable_ai {
+        let (ai_analysis, fallback_used, fallback_reason, ai_metadata) = if options.structured_only
+        {
+            // Structured-only callers discard the narrative, so skip AI and template generation
+            (String::new(), false, None, None)
+        } else if options.enable_ai {
             let ai_service = self.ai_service.read().await;
             let report_for_ai = AnalysisReport {
                 stock_code: stock_code.to_string(),
@@ -94,18 +252,30 @@ impl StockAnalyzer {
                 scores: scores.clone(),
                 recommendation: recommendation.clone(),
                 ai_analysis: String::new(),
-                data_quality: DataQuality {
-                    financial_indicators_count: fundamental_data.financial_indicators.len() as i32,
-                    total_news_count: news_data.len() as i32,
-                    analysis_completeness: "完整".to_string(),
-                },
+                data_quality: data_quality.clone(),
                 strategy_analysis: None,
                 fallback_used: false,
                 fallback_reason: None,
+                anomaly: None,
+                peers: peers.clone(),
+                relative_to_index: relative_to_index.clone(),
+                summary: String::new(),
+                grade: grade.clone(),
+                liquidity_warning: liquidity_warning.clone(),
+                ai_metadata: None,
             };
 
-            match ai_service.generate_analysis(&report_for_ai).await {
-                Ok(analysis) => (analysis, false, None),
+            // A slow model must not cost the already computed structured results
+            let ai_timeout = self.config.ai_phase_timeout_seconds;
+            let generated = tokio::time::timeout(
+                Duration::from_secs(ai_timeout),
+                ai_service.generate_analysis_with_metadata(&report_for_ai),
+            )
+            .await
+            .unwrap_or_else(|_| Err(format!("{}秒内未完成，已返回结构化分析结果", ai_timeout)));
+
+            match generated {
+                Ok((analysis, metadata)) => (analysis, false, None, metadata),
                 Err(err) => {
                     log::error!("Failed to generate AI analysis: {}", err);
                     let reason = format!("AI分析失败: {}", err);
@@ -113,7 +283,7 @@ impl StockAnalyzer {
                     fallback_report.fallback_used = true;
                     fallback_report.fallback_reason = Some(reason.clone());
                     let fallback_analysis = ai_service.generate_fallback_analysis(&fallback_report);
-                    (fallback_analysis, true, Some(reason))
+                    (fallback_analysis, true, Some(reason), None)
                 }
             }
         } else {
@@ -132,20 +302,28 @@ impl StockAnalyzer {
                 scores: scores.clone(),
                 recommendation: recommendation.clone(),
                 ai_analysis: String::new(),
-                data_quality: DataQuality {
-                    financial_indicators_count: fundamental_data.financial_indicators.len() as i32,
-                    total_news_count: news_data.len() as i32,
-                    analysis_completeness: "完整".to_string(),
-                },
+                data_quality: data_quality.clone(),
                 strategy_analysis: None,
                 fallback_used: true,
                 fallback_reason: Some(reason.clone()),
+                anomaly: None,
+                peers: peers.clone(),
+                relative_to_index: relative_to_index.clone(),
+                summary: String::new(),
+                grade: grade.clone(),
+                liquidity_warning: liquidity_warning.clone(),
+                ai_metadata: None,
             };
             let fallback_analysis = ai_service.generate_fallback_analysis(&report_for_ai);
-            (fallback_analysis, true, Some(reason))
+            (fallback_analysis, true, Some(reason), None)
         };
 
-        let report = AnalysisReport {
+        // Compare against the previous stored analysis before this one is saved
+        let anomaly = self
+            .detect_score_anomaly(stock_code, scores.comprehensive)
+            .await;
+
+        let mut report = AnalysisReport {
             stock_code: stock_code.to_string(),
             stock_name,
             market,
@@ -157,31 +335,848 @@ impl StockAnalyzer {
             scores,
             recommendation,
             ai_analysis,
-            data_quality: DataQuality {
-                financial_indicators_count: fundamental_data.financial_indicators.len() as i32,
-                total_news_count: news_data.len() as i32,
-                analysis_completeness: "完整".to_string(),
-            },
-            strategy_analysis: None,
+            data_quality,
+            strategy_analysis,
             fallback_used,
             fallback_reason,
+            anomaly,
+            peers,
+            relative_to_index,
+            summary: String::new(),
+            grade,
+            liquidity_warning,
+            ai_metadata,
         };
+        report.summary = self.generate_summary(&report, options).await;
 
         // Save analysis to database if available
         if let Some(database) = &self.database {
-            let ai_service_guard = self.ai_service.read().await;
-            let ai_provider = Some(ai_service_guard.get_provider().to_string());
-            let ai_model = Some(ai_service_guard.get_model().to_string());
-            drop(ai_service_guard);
+            // Record the provider that actually served the analysis, which may be a fallback
+            let (ai_provider, ai_model) = match &report.ai_metadata {
+                Some(metadata) => (metadata.provider.clone(), metadata.model.clone()),
+                None => {
+                    let ai_service = self.ai_service.read().await;
+                    (
+                        ai_service.get_provider().to_string(),
+                        ai_service.get_model().to_string(),
+                    )
+                }
+            };
 
-            if let Err(e) = database.save_analysis(&report, ai_provider, ai_model).await {
+            if let Err(e) = database
+                .save_analysis(
+                    &report,
+                    Some(ai_provider),
+                    Some(ai_model),
+                    report.ai_metadata.as_ref(),
+                )
+                .await
+            {
                 log::warn!("Failed to save analysis to database: {}", e);
             }
         }
 
+        if let Some(anomaly) = &report.anomaly {
+            log::warn!("{}: {}", report.stock_code, anomaly.message);
+            self.notify_anomaly_webhook(&report, anomaly);
+        }
+
         Ok(report)
     }
 
+    /// 按配置的摘要模式生成报告摘要，AI 摘要失败或超时时退回模板摘要
+    async fn generate_summary(&self, report: &AnalysisReport, options: &AnalysisOptions) -> String {
+        match self.config.summary_mode {
+            SummaryMode::Off => String::new(),
+            SummaryMode::Llm if options.enable_ai && !options.structured_only => {
+                let ai_service = self.ai_service.read().await;
+                let ai_timeout = Duration::from_secs(self.config.ai_phase_timeout_seconds);
+                match tokio::time::timeout(ai_timeout, ai_service.generate_summary(report)).await {
+                    Ok(Ok(summary)) => summary,
+                    Ok(Err(e)) => {
+                        log::warn!("AI summary failed for {}: {}", report.stock_code, e);
+                        generate_summary_text(report)
+                    }
+                    Err(_) => {
+                        log::warn!("AI summary timed out for {}", report.stock_code);
+                        generate_summary_text(report)
+                    }
+                }
+            }
+            SummaryMode::Llm | SummaryMode::Deterministic => generate_summary_text(report),
+        }
+    }
+
+    /// 与数据库中最近一次分析对比综合评分，变化超过阈值时返回异动信息
+    async fn detect_score_anomaly(
+        &self,
+        stock_code: &str,
+        current_score: f64,
+    ) -> Option<ScoreAnomaly> {
+        let alert_config = &self.config.anomaly_alert;
+        if !alert_config.enabled {
+            return None;
+        }
+        let database = self.database.as_ref()?;
+
+        let query = HistoryQuery {
+            stock_code: Some(stock_code.to_string()),
+            start_date: None,
+            end_date: None,
+            limit: Some(1),
+            offset: Some(0),
+            q: None,
+        };
+        let previous = match database.get_analysis_history(&query).await {
+            Ok(history) => history.analyses.into_iter().next()?,
+            Err(e) => {
+                log::warn!("Failed to load previous analysis for {}: {}", stock_code, e);
+                return None;
+            }
+        };
+        let previous_score = previous.scores.get("comprehensive")?.as_f64()?;
+
+        evaluate_score_anomaly(
+            previous_score,
+            current_score,
+            previous.analysis_date,
+            alert_config.score_delta_threshold,
+        )
+    }
+
+    /// 异步推送评分异动到配置的 webhook，失败仅记录日志
+    fn notify_anomaly_webhook(&self, report: &AnalysisReport, anomaly: &ScoreAnomaly) {
+        let Some(url) = self.config.anomaly_alert.webhook_url.clone() else {
+            return;
+        };
+        let payload = serde_json::json!({
+            "event": "score_anomaly",
+            "stock_code": report.stock_code,
+            "stock_name": report.stock_name,
+            "analysis_date": report.analysis_date,
+            "anomaly": anomaly,
+        });
+
+        tokio::spawn(async move {
+            let result = reqwest::Client::new()
+                .post(&url)
+                .timeout(std::time::Duration::from_secs(10))
+                .json(&payload)
+                .send()
+                .await;
+            match result {
+                Ok(response) if !response.status().is_success() => {
+                    log::warn!("Anomaly webhook returned status {}", response.status());
+                }
+                Err(e) => log::warn!("Failed to send anomaly webhook: {}", e),
+                _ => {}
+            }
+        });
+    }
+
+    /// 按当前配置权重合成综合评分
+    fn weighted_comprehensive_score(
+        &self,
+        technical: f64,
+        fundamental: f64,
+        sentiment: f64,
+    ) -> f64 {
+        self.config
+            .weights
+            .combine(technical, fundamental, sentiment)
+    }
+
+    /// 分别按现行权重与候选权重计算样本股票的评分与建议；只读取（缓存的）数据，不调用AI也不保存记录
+    pub async fn simulate_weights(
+        &self,
+        stock_codes: &[String],
+        proposed: &AnalysisWeights,
+    ) -> WeightSimulationResponse {
+        let mut prefetched = self
+            .prefetch_price_data(stock_codes, &AnalysisOptions::default())
+            .await;
+
+        let simulations = stock_codes.iter().map(|code| {
+            let price_data = prefetched.remove(code);
+            async move {
+                let result = self
+                    .simulate_stock_weights(code, price_data, proposed)
+                    .await;
+                (code, result)
+            }
+        });
+
+        let mut results = Vec::new();
+        let mut failed = HashMap::new();
+        for (code, result) in futures::future::join_all(simulations).await {
+            match result {
+                Ok(result) => results.push(result),
+                Err(e) => {
+                    failed.insert(code.clone(), e);
+                }
+            }
+        }
+
+        WeightSimulationResponse {
+            current_weights: self.config.weights.clone(),
+            proposed_weights: proposed.clone(),
+            results,
+            failed,
+        }
+    }
+
+    /// 以有界并发计算多只股票的精简评分（不调用AI、不保存、不触发异动检测），结果按输入顺序排列
+    pub async fn score_stocks(&self, stock_codes: &[String]) -> ScoresResponse {
+        use futures::stream::{self, StreamExt};
+
+        let results: Vec<(&String, Result<StockScoreSummary, String>)> = stream::iter(stock_codes)
+            .map(|code| async move { (code, self.score_stock(code).await) })
+            .buffered(self.config.max_workers.max(1))
+            .collect()
+            .await;
+
+        let mut scores = Vec::new();
+        let mut failed = std::collections::BTreeMap::new();
+        for (code, result) in results {
+            match result {
+                Ok(score) => scores.push(score),
+                Err(e) => {
+                    failed.insert(code.clone(), e);
+                }
+            }
+        }
+        ScoresResponse { scores, failed }
+    }
+
+    /// 以有界并发分析多只股票并生成横向对比报告，相关系数矩阵按排名顺序排列
+    pub async fn compare_stocks(
+        &self,
+        stock_codes: &[String],
+        options: &AnalysisOptions,
+    ) -> ComparisonReport {
+        use futures::stream::{self, StreamExt};
+
+        let results: Vec<_> = stream::iter(stock_codes)
+            .map(|code| async move {
+                let result = async {
+                    let report = self.analyze_single_stock(code, options).await?;
+                    // Same window as the analysis, so this is normally served from the cache
+                    let days = self.resolve_period_days(options.days, &report.market)?;
+                    let price_data = self
+                        .data_fetcher
+                        .get_stock_data(&report.stock_code, days)
+                        .await?;
+                    Ok::<_, String>((report, price_data))
+                };
+                (code, result.await)
+            })
+            .buffered(self.config.max_workers.max(1))
+            .collect()
+            .await;
+
+        let mut analyzed = Vec::new();
+        let mut failed = std::collections::BTreeMap::new();
+        for (code, result) in results {
+            match result {
+                Ok(analysis) => analyzed.push(analysis),
+                Err(e) => {
+                    failed.insert(code.clone(), e);
+                }
+            }
+        }
+        analyzed
+            .sort_by(|(a, _), (b, _)| b.scores.comprehensive.total_cmp(&a.scores.comprehensive));
+
+        let matrix = analyzed
+            .iter()
+            .enumerate()
+            .map(|(i, (_, a))| {
+                analyzed
+                    .iter()
+                    .enumerate()
+                    .map(|(j, (_, b))| {
+                        if i == j {
+                            Some(1.0)
+                        } else {
+                            Self::returns_correlation(&Self::align_closes(a, b))
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let stocks: Vec<ComparisonEntry> = analyzed
+            .into_iter()
+            .enumerate()
+            .map(|(i, (report, _))| ComparisonEntry {
+                rank: i + 1,
+                stock_code: report.stock_code,
+                stock_name: report.stock_name,
+                market: report.market,
+                scores: report.scores,
+                recommendation: report.recommendation,
+                current_price: report.price_info.current_price,
+                price_change: report.price_info.price_change,
+                volatility: report.price_info.volatility,
+                rsi: report.technical.rsi,
+                macd_signal: report.technical.macd_signal,
+                ma20: report.technical.ma20,
+                bb_position: report.technical.bb_position,
+                valuation: report.fundamental.valuation,
+                requested_positions: Vec::new(),
+            })
+            .collect();
+
+        ComparisonReport {
+            correlation: CorrelationMatrix {
+                codes: stocks.iter().map(|s| s.stock_code.clone()).collect(),
+                matrix,
+            },
+            stocks,
+            failed,
+            generated_at: Utc::now(),
+        }
+    }
+
+    /// 按交易日对齐两组价格，返回共同交易日上的 (日期, a 收盘价, b 收盘价)，按日期升序
+    pub fn align_closes(a: &[PriceData], b: &[PriceData]) -> Vec<(NaiveDate, f64, f64)> {
+        let b_closes: HashMap<NaiveDate, f64> =
+            b.iter().map(|p| (p.date.date_naive(), p.close)).collect();
+        let mut aligned: Vec<(NaiveDate, f64, f64)> = a
+            .iter()
+            .filter_map(|p| {
+                let date = p.date.date_naive();
+                b_closes.get(&date).map(|b_close| (date, p.close, *b_close))
+            })
+            .filter(|(_, a_close, b_close)| *a_close > 0.0 && *b_close > 0.0)
+            .collect();
+        aligned.sort_by_key(|(date, _, _)| *date);
+        aligned.dedup_by_key(|(date, _, _)| *date);
+        aligned
+    }
+
+    /// 对齐收盘价的日收益率 Pearson 相关系数；少于 3 个收益率样本或任一方波动为零时为 None
+    pub fn returns_correlation(aligned: &[(NaiveDate, f64, f64)]) -> Option<f64> {
+        ReturnMoments::from_returns(&Self::aligned_returns(aligned))?.correlation()
+    }
+
+    /// 对齐收盘价的日简单收益率 (a, b)，第 i 项对应 aligned[i + 1] 的交易日
+    fn aligned_returns(aligned: &[(NaiveDate, f64, f64)]) -> Vec<(f64, f64)> {
+        aligned
+            .windows(2)
+            .map(|w| (w[1].1 / w[0].1 - 1.0, w[1].2 / w[0].2 - 1.0))
+            .collect()
+    }
+
+    /// 获取两只股票的价格窗口并计算相关性；两只股票的窗口分别按各自市场解析
+    pub async fn correlation_analysis(
+        &self,
+        stock_a: &str,
+        stock_b: &str,
+        days: Option<i32>,
+        window: usize,
+    ) -> Result<CorrelationAnalysis, String> {
+        let days_a = self.resolve_period_days(days, &Market::from_stock_code(stock_a))?;
+        let days_b = self.resolve_period_days(days, &Market::from_stock_code(stock_b))?;
+        let (data_a, data_b) = tokio::join!(
+            self.data_fetcher.get_stock_data(stock_a, days_a),
+            self.data_fetcher.get_stock_data(stock_b, days_b),
+        );
+        Self::compute_correlation(stock_a, stock_b, &data_a?, &data_b?, window)
+    }
+
+    /// 按交易日取交集后计算整体相关系数、stock_a 相对 stock_b 的 beta 与滚动相关系数
+    pub fn compute_correlation(
+        stock_a: &str,
+        stock_b: &str,
+        data_a: &[PriceData],
+        data_b: &[PriceData],
+        window: usize,
+    ) -> Result<CorrelationAnalysis, String> {
+        if window < 3 {
+            return Err(format!("滚动窗口无效: {}，最小为 3", window));
+        }
+        let aligned = Self::align_closes(data_a, data_b);
+        let returns = Self::aligned_returns(&aligned);
+        let moments = ReturnMoments::from_returns(&returns).ok_or_else(|| {
+            format!(
+                "{} 与 {} 的共同交易日不足，无法计算相关性: {} 天",
+                stock_a,
+                stock_b,
+                aligned.len()
+            )
+        })?;
+
+        let rolling = if returns.len() < window {
+            Vec::new()
+        } else {
+            returns
+                .windows(window)
+                .enumerate()
+                .map(|(i, slice)| RollingCorrelationPoint {
+                    date: aligned[i + window].0,
+                    correlation: ReturnMoments::from_returns(slice)
+                        .and_then(|moments| moments.correlation()),
+                })
+                .collect()
+        };
+
+        Ok(CorrelationAnalysis {
+            stock_a: stock_a.to_string(),
+            stock_b: stock_b.to_string(),
+            start_date: aligned[0].0,
+            end_date: aligned[aligned.len() - 1].0,
+            observations: returns.len(),
+            correlation: moments.correlation(),
+            beta: moments.beta(),
+            window,
+            rolling,
+        })
+    }
+
+    async fn score_stock(&self, stock_code: &str) -> Result<StockScoreSummary, String> {
+        let stock_code = Market::normalize_code(stock_code);
+        let (price_data, technical, scores) = self.compute_scores(&stock_code).await?;
+
+        let liquidity_warning = self.check_liquidity(&price_data);
+        Ok(StockScoreSummary {
+            recommendation: self.gated_recommendation(
+                &scores,
+                &technical,
+                liquidity_warning.as_deref(),
+            ),
+            comprehensive: scores.comprehensive,
+            price: self.calculate_price_info(&price_data).current_price,
+            code: stock_code,
+        })
+    }
+
+    /// 计算价格提醒所需的行情快照（价格、RSI 与各项评分），不调用AI、不保存
+    pub async fn alert_snapshot(&self, stock_code: &str) -> Result<AlertSnapshot, String> {
+        let stock_code = Market::normalize_code(stock_code);
+        let (price_data, technical, scores) = self.compute_scores(&stock_code).await?;
+
+        Ok(AlertSnapshot {
+            price: self.calculate_price_info(&price_data),
+            rsi: technical.rsi,
+            scores,
+            stock_code,
+        })
+    }
+
+    /// 获取分钟K线并在同一序列上计算技术指标；指标按K线根数计算，与日线共用同一套算法
+    pub async fn intraday_analysis(
+        &self,
+        stock_code: &str,
+        interval: IntradayInterval,
+    ) -> Result<IntradayAnalysis, String> {
+        let stock_code = Market::normalize_code(stock_code);
+        let prices = self
+            .data_fetcher
+            .get_intraday_data(&stock_code, interval.as_str())
+            .await?;
+        if prices.is_empty() {
+            return Err(format!("{} 没有{}分钟K线数据", stock_code, interval));
+        }
+
+        Ok(IntradayAnalysis {
+            technical: self.calculate_technical_analysis(&prices),
+            stock_code,
+            interval,
+            prices,
+        })
+    }
+
+    /// 获取数据并计算技术指标与各项评分，stock_code 需已规范化
+    async fn compute_scores(
+        &self,
+        stock_code: &str,
+    ) -> Result<(Vec<PriceData>, TechnicalAnalysis, AnalysisScores), String> {
+        let market = Market::from_stock_code(stock_code);
+        let days = self.resolve_period_days(None, &market)?;
+        let news_days = self.config.parameters_for(&market).sentiment_period_days;
+
+        let (price_data, fundamental, news) = tokio::join!(
+            self.data_fetcher.get_stock_data(stock_code, days),
+            self.data_fetcher.get_fundamental_data(stock_code),
+            self.data_fetcher.get_news_data(stock_code, news_days),
+        );
+        let (price_data, fundamental, (_, sentiment)) = (price_data?, fundamental?, news?);
+
+        let technical = self.calculate_technical_analysis(&price_data);
+        let technical_score = self.calculate_technical_score(&technical, &price_data);
+        let fundamental_score = self.calculate_fundamental_score(&fundamental, &market);
+        let sentiment_score = self.calculate_sentiment_score(&sentiment);
+        let scores = AnalysisScores {
+            technical: technical_score,
+            fundamental: fundamental_score,
+            sentiment: sentiment_score,
+            comprehensive: self.weighted_comprehensive_score(
+                technical_score,
+                fundamental_score,
+                sentiment_score,
+            ),
+            momentum: self.calculate_momentum_score(&technical, &price_data),
+        };
+
+        Ok((price_data, technical, scores))
+    }
+
+    async fn simulate_stock_weights(
+        &self,
+        stock_code: &str,
+        price_data: Option<Vec<PriceData>>,
+        proposed: &AnalysisWeights,
+    ) -> Result<WeightSimulationResult, String> {
+        let stock_code = &Market::normalize_code(stock_code);
+        let market = Market::from_stock_code(stock_code);
+        let days = self.resolve_period_days(None, &market)?;
+        let news_days = self.config.parameters_for(&market).sentiment_period_days;
+
+        let price_data = match price_data {
+            Some(price_data) => price_data,
+            None => self.data_fetcher.get_stock_data(stock_code, days).await?,
+        };
+        let (fundamental, news, stock_name) = tokio::join!(
+            self.data_fetcher.get_fundamental_data(stock_code),
+            self.data_fetcher.get_news_data(stock_code, news_days),
+            self.data_fetcher.get_stock_name(stock_code),
+        );
+        let (fundamental, (_, sentiment)) = (fundamental?, news?);
+
+        let technical = self.calculate_technical_analysis(&price_data);
+        let technical_score = self.calculate_technical_score(&technical, &price_data);
+        let fundamental_score = self.calculate_fundamental_score(&fundamental, &market);
+        let sentiment_score = self.calculate_sentiment_score(&sentiment);
+
+        let current = AnalysisScores {
+            technical: technical_score,
+            fundamental: fundamental_score,
+            sentiment: sentiment_score,
+            comprehensive: self.weighted_comprehensive_score(
+                technical_score,
+                fundamental_score,
+                sentiment_score,
+            ),
+            momentum: self.calculate_momentum_score(&technical, &price_data),
+        };
+        let proposed = AnalysisScores {
+            comprehensive: proposed.combine(technical_score, fundamental_score, sentiment_score),
+            ..current.clone()
+        };
+
+        let current_recommendation = self.generate_recommendation(&current, &technical);
+        let proposed_recommendation = self.generate_recommendation(&proposed, &technical);
+        Ok(WeightSimulationResult {
+            stock_code: stock_code.clone(),
+            stock_name,
+            comprehensive_delta: proposed.comprehensive - current.comprehensive,
+            recommendation_changed: current_recommendation != proposed_recommendation,
+            current,
+            proposed,
+            current_recommendation,
+            proposed_recommendation,
+        })
+    }
+
+    /// 按当前权重重新计算已保存分析的评分；重算分项得分时重新获取分析日之前
+    /// 技术分析窗口内的价格数据，使依赖价格序列的评分（量价确认、风险调整、动量）与当时一致
+    pub async fn rescore_saved_analysis(
+        &self,
+        saved: &SavedAnalysis,
+        recompute_subscores: bool,
+    ) -> Result<RescoreResult, String> {
+        let report = saved.to_report()?;
+        let mut after = report.scores.clone();
+
+        if recompute_subscores {
+            let days = self.resolve_period_days(None, &report.market)?;
+            let end = report.analysis_date.date_naive();
+            let start = end - chrono::Duration::days(days as i64 - 1);
+            let price_data = self
+                .data_fetcher
+                .get_stock_data_range(&report.stock_code, start, end)
+                .await?;
+
+            after.technical = self.calculate_technical_score(&report.technical, &price_data);
+            after.momentum = self.calculate_momentum_score(&report.technical, &price_data);
+            after.fundamental =
+                self.calculate_fundamental_score(&report.fundamental, &report.market);
+            after.sentiment = self.calculate_sentiment_score(&report.sentiment);
+        }
+        after.comprehensive =
+            self.weighted_comprehensive_score(after.technical, after.fundamental, after.sentiment);
+
+        Ok(RescoreResult {
+            id: saved.id.clone(),
+            stock_code: report.stock_code,
+            stock_name: report.stock_name,
+            analysis_date: report.analysis_date,
+            before: report.scores,
+            after,
+        })
+    }
+
+    /// 并发获取至多 `MAX_PEERS` 只同业股票的关键指标，获取失败的同业跳过
+    async fn fetch_peer_comparisons(
+        &self,
+        stock_code: &str,
+        peers: &[String],
+    ) -> Vec<PeerComparison> {
+        let mut codes: Vec<String> = Vec::new();
+        for peer in peers {
+            let code = Market::normalize_code(peer);
+            if !code.is_empty() && code != stock_code && !codes.contains(&code) {
+                codes.push(code);
+            }
+        }
+        codes.truncate(MAX_PEERS);
+
+        let fetches = codes.iter().map(|code| async move {
+            let (fundamental, name) = futures::join!(
+                self.data_fetcher.get_fundamental_data(code),
+                self.data_fetcher.get_stock_name(code)
+            );
+            match fundamental {
+                Ok(fundamental) => {
+                    Some(PeerComparison::from_fundamental(code, &name, &fundamental))
+                }
+                Err(e) => {
+                    log::warn!("Failed to fetch peer {} fundamentals: {}", code, e);
+                    None
+                }
+            }
+        });
+
+        futures::future::join_all(fetches)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// 获取价格窗口并计算年化波动率、夏普/索提诺比率与最大回撤
+    pub async fn get_risk_metrics(
+        &self,
+        stock_code: &str,
+        days: Option<i32>,
+    ) -> Result<RiskMetrics, String> {
+        let market = Market::from_stock_code(&Market::normalize_code(stock_code));
+        let days = self.resolve_period_days(days, &market)?;
+        let price_data = self.data_fetcher.get_stock_data(stock_code, days).await?;
+        Self::compute_risk_metrics(stock_code, &price_data, self.config.risk_free_rate)
+    }
+
+    /// 获取价格窗口并逐根K线回测指定交易策略
+    pub async fn backtest_strategy(
+        &self,
+        stock_code: &str,
+        days: Option<i32>,
+        strategy: StrategyKind,
+    ) -> Result<BacktestResult, String> {
+        let market = Market::from_stock_code(&Market::normalize_code(stock_code));
+        let days = self.resolve_period_days(days, &market)?;
+        let price_data = self.data_fetcher.get_stock_data(stock_code, days).await?;
+        if price_data.len() < 2 {
+            return Err(format!("价格数据不足，无法回测: {} 条", price_data.len()));
+        }
+
+        Ok(TradingStrategiesAnalyzer::new().backtest(&price_data, strategy))
+    }
+
+    /// 只运行交易策略并生成信号，跳过 AI 分析与持久化
+    pub async fn strategy_signals(
+        &self,
+        stock_code: &str,
+        days: Option<i32>,
+        account_risk_pct: f64,
+        params: &StrategyParams,
+    ) -> Result<StrategySignals, String> {
+        if !(account_risk_pct > 0.0 && account_risk_pct <= 100.0) {
+            return Err(format!(
+                "account_risk_pct 无效: {}，允许范围为 (0, 100]",
+                account_risk_pct
+            ));
+        }
+        let strategies_analyzer = TradingStrategiesAnalyzer::with_params(params)?;
+
+        let market = Market::from_stock_code(&Market::normalize_code(stock_code));
+        let days = self.resolve_period_days(days, &market)?;
+        let price_data = self.data_fetcher.get_stock_data(stock_code, days).await?;
+        let current_price = price_data
+            .last()
+            .map(|p| p.close)
+            .ok_or_else(|| "No price data available".to_string())?;
+
+        let trading_strategies = strategies_analyzer
+            .analyze_all_strategies(stock_code, &price_data)
+            .await
+            .map_err(|e| format!("Failed to analyze strategies: {}", e))?;
+        let signals = strategies_analyzer.generate_trading_signals(
+            &trading_strategies,
+            current_price,
+            account_risk_pct,
+        );
+
+        Ok(StrategySignals {
+            stock_code: stock_code.to_string(),
+            current_price,
+            trading_strategies,
+            signals,
+        })
+    }
+
+    /// 基于日简单收益率计算风险指标，年化按每年 252 个交易日
+    pub fn compute_risk_metrics(
+        stock_code: &str,
+        price_data: &[PriceData],
+        risk_free_rate: f64,
+    ) -> Result<RiskMetrics, String> {
+        if price_data.len() < 3 {
+            return Err(format!(
+                "价格数据不足，无法计算风险指标: {} 条",
+                price_data.len()
+            ));
+        }
+
+        let prices: Vec<f64> = price_data.iter().map(|p| p.close).collect();
+        if prices.iter().any(|p| *p <= 0.0) {
+            return Err("价格数据包含无效收盘价".to_string());
+        }
+
+        let returns: Vec<f64> = prices.windows(2).map(|w| w[1] / w[0] - 1.0).collect();
+        let n = returns.len() as f64;
+        let mean = returns.iter().sum::<f64>() / n;
+        let std_dev = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+
+        let daily_risk_free = risk_free_rate / TRADING_DAYS_PER_YEAR;
+        let excess = mean - daily_risk_free;
+        let downside_dev = (returns
+            .iter()
+            .map(|r| (r - daily_risk_free).min(0.0).powi(2))
+            .sum::<f64>()
+            / n)
+            .sqrt();
+        let annualize = TRADING_DAYS_PER_YEAR.sqrt();
+
+        Ok(RiskMetrics {
+            stock_code: stock_code.to_string(),
+            start_date: price_data[0].date,
+            end_date: price_data[price_data.len() - 1].date,
+            observations: returns.len(),
+            risk_free_rate,
+            annualized_return: mean * TRADING_DAYS_PER_YEAR,
+            annualized_volatility: std_dev * annualize,
+            sharpe_ratio: (std_dev > 0.0).then(|| excess / std_dev * annualize),
+            sortino_ratio: (downside_dev > 0.0).then(|| excess / downside_dev * annualize),
+            max_drawdown: indicators::max_drawdown(&prices).unwrap_or(0.0),
+        })
+    }
+
+    /// 拉取市场基准指数并计算相对表现，未启用或指数数据不可用时返回 None
+    async fn relative_to_index(
+        &self,
+        market: &Market,
+        price_data: &[PriceData],
+        days: i32,
+    ) -> Option<RelativePerformance> {
+        if !self.config.compare_to_index {
+            return None;
+        }
+        self.fetch_relative_performance(market, price_data, days)
+            .await
+    }
+
+    async fn fetch_relative_performance(
+        &self,
+        market: &Market,
+        price_data: &[PriceData],
+        days: i32,
+    ) -> Option<RelativePerformance> {
+        let (index_name, index_code) = market.primary_index()?;
+
+        match self.data_fetcher.get_index_data(index_code, days).await {
+            Ok(index_data) => {
+                Self::compute_relative_performance(index_name, index_code, price_data, &index_data)
+            }
+            Err(e) => {
+                log::warn!("Failed to fetch benchmark index {}: {}", index_code, e);
+                None
+            }
+        }
+    }
+
+    /// 按交易日对齐个股与指数收盘价，计算区间收益、超额收益与 beta
+    pub fn compute_relative_performance(
+        index_name: &str,
+        index_code: &str,
+        price_data: &[PriceData],
+        index_data: &[PriceData],
+    ) -> Option<RelativePerformance> {
+        let index_closes: HashMap<NaiveDate, f64> = index_data
+            .iter()
+            .map(|p| (p.date.date_naive(), p.close))
+            .collect();
+        let aligned: Vec<(f64, f64)> = price_data
+            .iter()
+            .filter_map(|p| {
+                index_closes
+                    .get(&p.date.date_naive())
+                    .map(|index_close| (p.close, *index_close))
+            })
+            .filter(|(stock, index)| *stock > 0.0 && *index > 0.0)
+            .collect();
+        if aligned.len() < 3 {
+            return None;
+        }
+
+        let (first, last) = (aligned[0], aligned[aligned.len() - 1]);
+        let stock_return = last.0 / first.0 - 1.0;
+        let index_return = last.1 / first.1 - 1.0;
+
+        let returns: Vec<(f64, f64)> = aligned
+            .windows(2)
+            .map(|w| (w[1].0 / w[0].0 - 1.0, w[1].1 / w[0].1 - 1.0))
+            .collect();
+        let n = returns.len() as f64;
+        let mean_stock = returns.iter().map(|r| r.0).sum::<f64>() / n;
+        let mean_index = returns.iter().map(|r| r.1).sum::<f64>() / n;
+        let covariance = returns
+            .iter()
+            .map(|(s, i)| (s - mean_stock) * (i - mean_index))
+            .sum::<f64>()
+            / (n - 1.0);
+        let index_variance = returns
+            .iter()
+            .map(|r| (r.1 - mean_index).powi(2))
+            .sum::<f64>()
+            / (n - 1.0);
+        let beta = (index_variance > 0.0).then(|| covariance / index_variance);
+
+        Some(RelativePerformance {
+            index_name: index_name.to_string(),
+            index_code: index_code.to_string(),
+            observations: returns.len(),
+            stock_return,
+            index_return,
+            excess_return: stock_return - index_return,
+            beta,
+            alpha: beta.map(|beta| stock_return - beta * index_return),
+        })
+    }
+
+    /// 解析请求的数据窗口，未指定时使用该市场配置的默认值
+    fn resolve_period_days(&self, days: Option<i32>, market: &Market) -> Result<i32, String> {
+        match days {
+            None => Ok(self.config.parameters_for(market).technical_period_days),
+            Some(d) if !(1..=MAX_ANALYSIS_DAYS).contains(&d) => Err(format!(
+                "数据窗口天数无效: {}，允许范围为 1-{}",
+                d, MAX_ANALYSIS_DAYS
+            )),
+            Some(d) => Ok(d),
+        }
+    }
+
     fn calculate_technical_analysis(&self, price_data: &[PriceData]) -> TechnicalAnalysis {
         if price_data.is_empty() {
             return TechnicalAnalysis::default();
@@ -211,6 +1206,7 @@ impl StockAnalyzer {
         let williams_r = self.calculate_williams_r(&highs, &lows, &prices, 14);
         let cci = self.calculate_cci(&highs, &lows, &prices, 20);
         let (stochastic_k, stochastic_d) = self.calculate_stochastic(&highs, &lows, &prices, 14, 3);
+        let kdj_j = 3.0 * stochastic_k - 2.0 * stochastic_d;
 
         // Volume and Trend
         let current_volume = *volumes.last().unwrap_or(&0.0);
@@ -240,6 +1236,10 @@ impl StockAnalyzer {
             "弱趋势".to_string()
         };
 
+        let obv_series = self.calculate_obv(&prices, &volumes);
+        let obv = *obv_series.last().unwrap_or(&0.0);
+        let obv_trend = Self::obv_trend(&obv_series, &volumes, OBV_TREND_PERIOD).to_string();
+
         TechnicalAnalysis {
             // Moving Averages
             ma5,
@@ -266,21 +1266,64 @@ impl StockAnalyzer {
             cci,
             stochastic_k,
             stochastic_d,
+            kdj_j,
 
             // Volume and Trend
             volume_status: volume_status.to_string(),
             ma_trend,
             adx,
             trend_strength,
-        }
-    }
+            obv,
+            obv_trend,
 
-    fn calculate_ma(&self, data: &[f64], period: usize) -> f64 {
-        if data.len() < period {
-            return data.iter().sum::<f64>() / data.len() as f64;
+            // Registered custom indicators
+            custom_indicators: self.indicators.compute_all(price_data),
+
+            normalized: HashMap::new(),
         }
-        let slice = &data[data.len() - period..];
-        slice.iter().sum::<f64>() / period as f64
+    }
+
+    /// 能量潮序列：收盘上涨累加当日成交量，下跌扣减，平盘不变
+    fn calculate_obv(&self, prices: &[f64], volumes: &[f64]) -> Vec<f64> {
+        let mut obv = 0.0;
+        let mut series = Vec::with_capacity(prices.len());
+        series.push(obv);
+
+        for i in 1..prices.len().min(volumes.len()) {
+            if prices[i] > prices[i - 1] {
+                obv += volumes[i];
+            } else if prices[i] < prices[i - 1] {
+                obv -= volumes[i];
+            }
+            series.push(obv);
+        }
+
+        series
+    }
+
+    /// OBV 在回看期内的净变化不足期间成交量的 5% 时视为持平
+    fn obv_trend(obv: &[f64], volumes: &[f64], period: usize) -> &'static str {
+        if obv.len() <= period {
+            return "持平";
+        }
+
+        let change = obv[obv.len() - 1] - obv[obv.len() - 1 - period];
+        let window_volume: f64 = volumes[volumes.len() - period..].iter().sum();
+        if window_volume <= 0.0 || change.abs() < window_volume * 0.05 {
+            "持平"
+        } else if change > 0.0 {
+            "上升"
+        } else {
+            "下降"
+        }
+    }
+
+    fn calculate_ma(&self, data: &[f64], period: usize) -> f64 {
+        if data.len() < period {
+            return data.iter().sum::<f64>() / data.len() as f64;
+        }
+        let slice = &data[data.len() - period..];
+        slice.iter().sum::<f64>() / period as f64
     }
 
     fn calculate_rsi(&self, data: &[f64], period: usize) -> f64 {
@@ -317,12 +1360,18 @@ impl StockAnalyzer {
 
     // Enhanced MACD calculation with histogram
     fn calculate_macd(&self, data: &[f64]) -> (String, f64, f64) {
-        let short_ma = self.calculate_ma(data, 12.min(data.len()));
-        let long_ma = self.calculate_ma(data, 26.min(data.len()));
-        let macd_line = short_ma - long_ma;
+        let fast_ema = self.calculate_ema_series(data, 12);
+        let slow_ema = self.calculate_ema_series(data, 26);
+        let macd_series: Vec<f64> = fast_ema
+            .iter()
+            .zip(&slow_ema)
+            .map(|(fast, slow)| fast - slow)
+            .collect();
 
-        // Calculate signal line (9-period EMA of MACD line)
-        let signal_line = self.calculate_ema(&[macd_line], 9);
+        // Signal line is the 9-period EMA of the whole MACD line series
+        let signal_series = self.calculate_ema_series(&macd_series, 9);
+        let macd_line = macd_series.last().copied().unwrap_or(0.0);
+        let signal_line = signal_series.last().copied().unwrap_or(0.0);
         let macd_histogram = macd_line - signal_line;
 
         let macd_signal = if macd_line > signal_line {
@@ -505,8 +1554,8 @@ impl StockAnalyzer {
         if smooth_tr > 0.0 {
             let plus_di = 100.0 * smooth_plus_dm / smooth_tr;
             let minus_di = 100.0 * smooth_minus_dm / smooth_tr;
-            let dx = (plus_di - minus_di).abs() / (plus_di + minus_di) * 100.0;
-            dx
+
+            (plus_di - minus_di).abs() / (plus_di + minus_di) * 100.0
         } else {
             25.0
         }
@@ -521,27 +1570,36 @@ impl StockAnalyzer {
         let sum = data.iter().take(period.min(data.len())).sum::<f64>();
         let mut smoothed = sum / period as f64;
 
-        for i in period..data.len() {
-            smoothed = (smoothed * (period - 1) as f64 + data[i]) / period as f64;
+        for value in &data[period.min(data.len())..] {
+            smoothed = (smoothed * (period - 1) as f64 + value) / period as f64;
         }
 
         smoothed
     }
 
     // Exponential Moving Average (EMA) calculation
+    #[allow(dead_code)]
     fn calculate_ema(&self, data: &[f64], period: usize) -> f64 {
-        if data.is_empty() {
-            return 0.0;
-        }
+        self.calculate_ema_series(data, period)
+            .last()
+            .copied()
+            .unwrap_or(0.0)
+    }
 
+    // EMA value at every point, seeded with the first value
+    fn calculate_ema_series(&self, data: &[f64], period: usize) -> Vec<f64> {
         let multiplier = 2.0 / (period as f64 + 1.0);
-        let mut ema = data[0];
+        let mut series = Vec::with_capacity(data.len());
 
-        for i in 1..data.len() {
-            ema = data[i] * multiplier + ema * (1.0 - multiplier);
+        for &value in data {
+            let ema = match series.last() {
+                Some(previous) => value * multiplier + previous * (1.0 - multiplier),
+                None => value,
+            };
+            series.push(ema);
         }
 
-        ema
+        series
     }
 
     fn calculate_price_info(&self, price_data: &[PriceData]) -> PriceInfo {
@@ -571,20 +1629,23 @@ impl StockAnalyzer {
         };
 
         let prices: Vec<f64> = price_data.iter().map(|p| p.close).collect();
-        let volatility = self.calculate_std_dev(&prices, 20.min(prices.len()));
+        let volatility = indicators::log_return_volatility(&prices, VOLATILITY_WINDOW)
+            .map(|daily| daily * TRADING_DAYS_PER_YEAR.sqrt() * 100.0)
+            .unwrap_or(0.0);
 
         PriceInfo {
             current_price,
             price_change,
             volume_ratio,
             volatility,
+            extended_hours: None,
         }
     }
 
     fn calculate_technical_score(
         &self,
         technical: &TechnicalAnalysis,
-        _price_data: &[PriceData],
+        price_data: &[PriceData],
     ) -> f64 {
         let mut score: f64 = 50.0;
 
@@ -660,7 +1721,62 @@ impl StockAnalyzer {
             _ => {}
         }
 
-        score.min(100.0).max(0.0)
+        // OBV confirmation: volume flowing in the same direction as a rising price
+        if price_data.len() > OBV_TREND_PERIOD {
+            let last = price_data[price_data.len() - 1].close;
+            let earlier = price_data[price_data.len() - 1 - OBV_TREND_PERIOD].close;
+            if last > earlier {
+                match technical.obv_trend.as_str() {
+                    "上升" => score += 5.0, // 量价齐升
+                    "下降" => score -= 3.0, // 价升量背离
+                    _ => {}
+                }
+            }
+        }
+
+        if self.config.scoring_mode == ScoringMode::RiskAdjusted {
+            score += Self::risk_adjusted_adjustment(price_data);
+        }
+
+        score.clamp(0.0, 100.0)
+    }
+
+    /// 按日对数收益的年化类夏普比率调整评分，稳步上涨优于剧烈震荡
+    fn risk_adjusted_adjustment(price_data: &[PriceData]) -> f64 {
+        let prices: Vec<f64> = price_data.iter().map(|p| p.close).collect();
+        match indicators::log_return_sharpe(&prices) {
+            Some(sharpe) => (sharpe * TRADING_DAYS_PER_YEAR.sqrt() * 5.0).clamp(-12.0, 12.0),
+            None => 0.0,
+        }
+    }
+
+    /// 综合多周期涨跌幅、RSI 与 MACD 柱方向计算动量评分
+    fn calculate_momentum_score(
+        &self,
+        technical: &TechnicalAnalysis,
+        price_data: &[PriceData],
+    ) -> f64 {
+        let prices: Vec<f64> = price_data.iter().map(|p| p.close).collect();
+        let mut score: f64 = 50.0;
+
+        // Rate of change over short, medium and long lookbacks; recent moves weigh more
+        for (period, weight) in [(5, 1.5), (20, 1.0), (60, 0.5)] {
+            if let Some(roc) = indicators::rate_of_change(&prices, period) {
+                score += (roc * weight).clamp(-10.0, 10.0);
+            }
+        }
+
+        // RSI above/below the midline
+        score += ((technical.rsi - 50.0) * 0.3).clamp(-8.0, 8.0);
+
+        // MACD histogram direction
+        if technical.macd_histogram > 0.0 {
+            score += 6.0;
+        } else if technical.macd_histogram < 0.0 {
+            score -= 6.0;
+        }
+
+        score.clamp(0.0, 100.0)
     }
 
     fn calculate_fundamental_score(&self, fundamental: &FundamentalData, market: &Market) -> f64 {
@@ -712,7 +1828,7 @@ impl StockAnalyzer {
                             score -= 10.0;
                         }
                     }
-                    Market::UNKNOWN => {
+                    Market::CRYPTO | Market::UNKNOWN => {
                         if indicator.value > 0.0 && indicator.value < 20.0 {
                             score += 6.0;
                         } else if indicator.value > 50.0 {
@@ -742,7 +1858,7 @@ impl StockAnalyzer {
                             score -= 10.0;
                         }
                     }
-                    Market::UNKNOWN => {
+                    Market::CRYPTO | Market::UNKNOWN => {
                         if indicator.value > 0.0 && indicator.value < 3.0 {
                             score += 6.0;
                         } else if indicator.value > 6.0 {
@@ -839,7 +1955,7 @@ impl StockAnalyzer {
             }
         }
 
-        score.min(100.0).max(0.0)
+        score.clamp(0.0, 100.0)
     }
 
     fn calculate_sentiment_score(&self, sentiment: &SentimentAnalysis) -> f64 {
@@ -859,7 +1975,103 @@ impl StockAnalyzer {
         // Confidence score impact
         score += (sentiment.confidence_score - 0.5) * 20.0;
 
-        score.min(100.0).max(0.0)
+        score.clamp(0.0, 100.0)
+    }
+
+    /// 计算筹码分布、各交易策略与交易信号并汇总为策略分析；任一步骤失败时记录日志并返回 None，
+    /// 不影响其余分析结果
+    async fn analyze_strategies(
+        &self,
+        stock_code: &str,
+        stock_name: &str,
+        price_data: &[PriceData],
+    ) -> Option<StrategyAnalysis> {
+        let current_price = price_data.last()?.close;
+
+        let chip_analysis = match ChipMonitor::new()
+            .analyze_chips(stock_code, price_data)
+            .await
+        {
+            Ok(analysis) => analysis,
+            Err(e) => {
+                log::warn!("Failed to analyze chips for {}: {}", stock_code, e);
+                return None;
+            }
+        };
+
+        let strategies_analyzer = TradingStrategiesAnalyzer::new();
+        let trading_strategies = match strategies_analyzer
+            .analyze_all_strategies(stock_code, price_data)
+            .await
+        {
+            Ok(strategies) => strategies,
+            Err(e) => {
+                log::warn!("Failed to analyze strategies for {}: {}", stock_code, e);
+                return None;
+            }
+        };
+        let signals = strategies_analyzer.generate_trading_signals(
+            &trading_strategies,
+            current_price,
+            DEFAULT_ACCOUNT_RISK_PCT,
+        );
+
+        // The report is a snapshot, so alert state from the signal endpoints is not touched
+        Some(SignalAlertSystem::new().generate_strategy_analysis_report(
+            stock_code,
+            stock_name,
+            &chip_analysis,
+            &trading_strategies,
+            &signals,
+        ))
+    }
+
+    /// 近 `lookback_days` 个交易日日均成交量或成交额低于下限时返回低流动性提示
+    fn check_liquidity(&self, price_data: &[PriceData]) -> Option<String> {
+        let gate = &self.config.liquidity_gate;
+        if !gate.enabled || gate.lookback_days == 0 || price_data.is_empty() {
+            return None;
+        }
+
+        let window = &price_data[price_data.len().saturating_sub(gate.lookback_days)..];
+        let days = window.len() as f64;
+        let avg_volume = window.iter().fold(0.0, |sum, p| sum + p.volume as f64) / days;
+        let avg_turnover = window.iter().fold(0.0, |sum, p| sum + p.turnover) / days;
+
+        let mut reasons = Vec::new();
+        if gate.min_avg_volume > 0.0 && avg_volume < gate.min_avg_volume {
+            reasons.push(format!(
+                "日均成交量 {:.0} 低于下限 {:.0}",
+                avg_volume, gate.min_avg_volume
+            ));
+        }
+        if gate.min_avg_turnover > 0.0 && avg_turnover < gate.min_avg_turnover {
+            reasons.push(format!(
+                "日均成交额 {:.0} 低于下限 {:.0}",
+                avg_turnover, gate.min_avg_turnover
+            ));
+        }
+
+        (!reasons.is_empty()).then(|| {
+            format!(
+                "流动性不足（近{}个交易日{}），技术信号可靠性低且难以按当前价格成交，投资建议限制为观望",
+                window.len(),
+                reasons.join("，")
+            )
+        })
+    }
+
+    /// 存在低流动性提示时建议限制为"观望"，否则按评分给出建议
+    fn gated_recommendation(
+        &self,
+        scores: &AnalysisScores,
+        technical: &TechnicalAnalysis,
+        liquidity_warning: Option<&str>,
+    ) -> String {
+        match liquidity_warning {
+            Some(_) => "观望".to_string(),
+            None => self.generate_recommendation(scores, technical),
+        }
     }
 
     fn generate_recommendation(
@@ -878,6 +2090,7 @@ impl StockAnalyzer {
         .to_string()
     }
 
+    #[allow(dead_code)]
     fn generate_fallback_analysis(
         &self,
         stock_code: &str,
@@ -944,7 +2157,7 @@ impl StockAnalyzer {
 
         // 添加技术面简述
         analysis.push_str(&format!(
-            "\n技术面：当前价格波动率 {:.2}%，建议关注技术指标变化\n",
+            "\n技术面：当前年化波动率 {:.2}%，建议关注技术指标变化\n",
             price_info.volatility
         ));
 
@@ -980,13 +2193,88 @@ impl Default for TechnicalAnalysis {
             cci: 0.0,
             stochastic_k: 50.0,
             stochastic_d: 50.0,
+            kdj_j: 50.0,
 
             // Volume and Trend
             volume_status: "正常".to_string(),
             ma_trend: "中性".to_string(),
             adx: 25.0,
             trend_strength: "弱趋势".to_string(),
+            obv: 0.0,
+            obv_trend: "持平".to_string(),
+
+            // Registered custom indicators
+            custom_indicators: HashMap::new(),
+            normalized: HashMap::new(),
+        }
+    }
+}
+
+/// 判断两次综合评分的变化是否超过阈值
+fn evaluate_score_anomaly(
+    previous_score: f64,
+    current_score: f64,
+    previous_analysis_date: DateTime<Utc>,
+    threshold: f64,
+) -> Option<ScoreAnomaly> {
+    let delta = current_score - previous_score;
+    if delta.abs() < threshold {
+        return None;
+    }
+
+    let direction = if delta > 0.0 { "上升" } else { "下降" };
+    Some(ScoreAnomaly {
+        previous_score,
+        current_score,
+        delta,
+        threshold,
+        previous_analysis_date,
+        message: format!(
+            "综合评分由 {:.1} {}至 {:.1}，变化 {:.1} 分，超过阈值 {:.1}",
+            previous_score,
+            direction,
+            current_score,
+            delta.abs(),
+            threshold
+        ),
+    })
+}
+
+/// 成对收益率样本的离差平方和与协方差和
+struct ReturnMoments {
+    covariance: f64,
+    variance_a: f64,
+    variance_b: f64,
+}
+
+impl ReturnMoments {
+    /// 少于 3 个样本时为 None
+    fn from_returns(returns: &[(f64, f64)]) -> Option<Self> {
+        if returns.len() < 3 {
+            return None;
         }
+        let n = returns.len() as f64;
+        let mean_a = returns.iter().map(|r| r.0).sum::<f64>() / n;
+        let mean_b = returns.iter().map(|r| r.1).sum::<f64>() / n;
+        Some(Self {
+            covariance: returns
+                .iter()
+                .map(|(a, b)| (a - mean_a) * (b - mean_b))
+                .sum(),
+            variance_a: returns.iter().map(|r| (r.0 - mean_a).powi(2)).sum(),
+            variance_b: returns.iter().map(|r| (r.1 - mean_b).powi(2)).sum(),
+        })
+    }
+
+    fn correlation(&self) -> Option<f64> {
+        (self.variance_a > 0.0 && self.variance_b > 0.0).then(|| {
+            (self.covariance / (self.variance_a * self.variance_b).sqrt()).clamp(-1.0, 1.0)
+        })
+    }
+
+    /// a 相对 b 的 beta
+    fn beta(&self) -> Option<f64> {
+        (self.variance_b > 0.0).then(|| self.covariance / self.variance_b)
     }
 }
 
@@ -997,25 +2285,1537 @@ impl Default for PriceInfo {
             price_change: 0.0,
             volume_ratio: 1.0,
             volatility: 0.0,
+            extended_hours: None,
         }
     }
 }
 
+/// 按标题去重后保留最新、相关度最高的 `max_news` 条新闻（0 表示不限）；
+/// 有新闻被剔除时按保留的新闻重新汇总情绪，沿用上游的置信度
+fn select_news(
+    news: Vec<News>,
+    sentiment: SentimentAnalysis,
+    max_news: usize,
+) -> (Vec<News>, SentimentAnalysis) {
+    let total = news.len();
+    let mut seen_titles = std::collections::HashSet::new();
+    let mut selected: Vec<News> = news
+        .into_iter()
+        .filter(|n| n.title.trim().is_empty() || seen_titles.insert(n.title.trim().to_string()))
+        .collect();
+
+    if max_news > 0 && selected.len() > max_news {
+        selected.sort_by(|a, b| {
+            b.date
+                .date_naive()
+                .cmp(&a.date.date_naive())
+                .then(b.relevance.total_cmp(&a.relevance))
+        });
+        selected.truncate(max_news);
+    }
+
+    if selected.len() == total {
+        return (selected, sentiment);
+    }
+    let sentiment = SentimentAnalysis::from_news(&selected, sentiment.confidence_score);
+    (selected, sentiment)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::ai_service::AIService;
-    use crate::data_fetcher::MockDataFetcher;
+    use crate::data_fetcher::{AkshareProxy, MockDataFetcher};
 
     #[tokio::test]
     async fn test_analyze_single_stock() {
-        let data_fetcher = Box::new(MockDataFetcher);
+        let data_fetcher = Arc::new(MockDataFetcher);
         let config = AnalysisConfig::default();
         let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
 
         let analyzer = StockAnalyzer::new(data_fetcher, config, ai_service);
 
-        let result = analyzer.analyze_single_stock("000001", false).await;
+        let options = AnalysisOptions {
+            enable_ai: false,
+            ..Default::default()
+        };
+        let result = analyzer.analyze_single_stock("000001", &options).await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_analyze_single_stock_days_override() {
+        let data_fetcher = Arc::new(MockDataFetcher);
+        let config = AnalysisConfig::default();
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+
+        let analyzer = StockAnalyzer::new(data_fetcher, config, ai_service);
+
+        for (days, ok) in [
+            (Some(250), true),
+            (Some(0), false),
+            (Some(MAX_ANALYSIS_DAYS + 1), false),
+        ] {
+            let options = AnalysisOptions {
+                enable_ai: false,
+                days,
+                ..Default::default()
+            };
+            let result = analyzer.analyze_single_stock("000001", &options).await;
+            assert_eq!(result.is_ok(), ok);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extended_hours_only_for_us_stocks() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+
+        let options = AnalysisOptions {
+            structured_only: true,
+            include_extended_hours: true,
+            ..Default::default()
+        };
+        let us = analyzer
+            .analyze_single_stock("AAPL", &options)
+            .await
+            .unwrap();
+        let quote = us.price_info.extended_hours.unwrap();
+        assert!(quote.pre_market_price.is_none());
+        assert!((quote.post_market_change_pct.unwrap() - 1.2).abs() < 1e-9);
+
+        let ashare = analyzer
+            .analyze_single_stock("000001", &options)
+            .await
+            .unwrap();
+        assert!(ashare.price_info.extended_hours.is_none());
+
+        let default_options = AnalysisOptions {
+            structured_only: true,
+            ..Default::default()
+        };
+        let us = analyzer
+            .analyze_single_stock("AAPL", &default_options)
+            .await
+            .unwrap();
+        assert!(us.price_info.extended_hours.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_price_data_falls_back_per_stock() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        // Unreachable proxy: the batch request fails and each stock falls back individually
+        let data_fetcher = Arc::new(
+            AkshareProxy::new("http://127.0.0.1:1".to_string(), 1)
+                .with_price_batch_size(2)
+                .with_mock_fallback(true),
+        );
+        let analyzer = StockAnalyzer::new(data_fetcher, AnalysisConfig::default(), ai_service);
+
+        let stock_codes: Vec<String> = ["000001", "600036", "00700"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        let options = AnalysisOptions {
+            structured_only: true,
+            ..Default::default()
+        };
+        let mut prefetched = analyzer.prefetch_price_data(&stock_codes, &options).await;
+        assert_eq!(prefetched.len(), 3);
+
+        let report = analyzer
+            .analyze_with_price_data("600036", &options, prefetched.remove("600036"))
+            .await
+            .unwrap();
+        assert_eq!(report.stock_code, "600036");
+    }
+
+    #[tokio::test]
+    async fn test_strategy_analysis_is_opt_in() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+
+        let options = AnalysisOptions {
+            structured_only: true,
+            ..Default::default()
+        };
+        let report = analyzer
+            .analyze_single_stock("000001", &options)
+            .await
+            .unwrap();
+        assert!(report.strategy_analysis.is_none());
+
+        let options = AnalysisOptions {
+            structured_only: true,
+            enable_strategies: true,
+            ..Default::default()
+        };
+        let report = analyzer
+            .analyze_single_stock("000001", &options)
+            .await
+            .unwrap();
+        let strategy = report.strategy_analysis.unwrap();
+        assert!(strategy.trading_strategies.macd.is_some());
+        assert!(!strategy.overall_signal.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_analyze_strategies_without_ai() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+
+        let result = analyzer
+            .strategy_signals(
+                "000001",
+                Some(120),
+                DEFAULT_ACCOUNT_RISK_PCT,
+                &StrategyParams::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.stock_code, "000001");
+        assert!(result.current_price > 0.0);
+        assert!(result.trading_strategies.macd.is_some());
+        assert!(result
+            .signals
+            .iter()
+            .all(|s| s.signal_type != "持有" && s.price == result.current_price));
+
+        assert!(analyzer
+            .strategy_signals(
+                "000001",
+                Some(0),
+                DEFAULT_ACCOUNT_RISK_PCT,
+                &StrategyParams::default(),
+            )
+            .await
+            .is_err());
+        assert!(analyzer
+            .strategy_signals("000001", None, 0.0, &StrategyParams::default())
+            .await
+            .is_err());
+
+        // 自定义 RSI 阈值体现在结果中，非法参数直接拒绝
+        let params = StrategyParams {
+            rsi_overbought: Some(80.0),
+            rsi_oversold: Some(20.0),
+            ..Default::default()
+        };
+        let result = analyzer
+            .strategy_signals("000001", Some(120), DEFAULT_ACCOUNT_RISK_PCT, &params)
+            .await
+            .unwrap();
+        assert_eq!(result.trading_strategies.rsi.unwrap().overbought, 80.0);
+        let params = StrategyParams {
+            ma_short_period: Some(0),
+            ..Default::default()
+        };
+        assert!(analyzer
+            .strategy_signals("000001", None, DEFAULT_ACCOUNT_RISK_PCT, &params)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_analyze_single_stock_structured_only() {
+        let data_fetcher = Arc::new(MockDataFetcher);
+        let config = AnalysisConfig::default();
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+
+        let analyzer = StockAnalyzer::new(data_fetcher, config, ai_service);
+
+        let options = AnalysisOptions {
+            structured_only: true,
+            ..Default::default()
+        };
+        let report = analyzer
+            .analyze_single_stock("000001", &options)
+            .await
+            .unwrap();
+        assert!(report.ai_analysis.is_empty());
+        assert!(!report.fallback_used);
+    }
+
+    #[tokio::test]
+    async fn test_saved_analysis_restores_report() {
+        let path = std::env::temp_dir().join(format!("analyses_{}.db", uuid::Uuid::new_v4()));
+        let database = Arc::new(
+            Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+                .await
+                .unwrap(),
+        );
+        database.create_tables().await.unwrap();
+
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::with_database(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+            database.clone(),
+        );
+
+        let options = AnalysisOptions {
+            structured_only: true,
+            ..Default::default()
+        };
+        let report = analyzer
+            .analyze_single_stock("000001", &options)
+            .await
+            .unwrap();
+
+        let query = HistoryQuery {
+            stock_code: Some("000001".to_string()),
+            start_date: None,
+            end_date: None,
+            limit: Some(1),
+            offset: Some(0),
+            q: None,
+        };
+        let history = database.get_analysis_history(&query).await.unwrap();
+        let restored = history.analyses[0].to_report().unwrap();
+        assert_eq!(restored.scores.comprehensive, report.scores.comprehensive);
+        assert_eq!(restored.market, Market::ASHARES);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_ai_usage_stats_aggregate_by_provider() {
+        let path = std::env::temp_dir().join(format!("usage_{}.db", uuid::Uuid::new_v4()));
+        let database = Arc::new(
+            Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+                .await
+                .unwrap(),
+        );
+        database.create_tables().await.unwrap();
+
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+        let options = AnalysisOptions {
+            structured_only: true,
+            ..Default::default()
+        };
+        let report = analyzer
+            .analyze_single_stock("000001", &options)
+            .await
+            .unwrap();
+
+        let metadata = |tokens: u32, latency: u64| AnalysisMetadata {
+            provider: "openai".to_string(),
+            model: "gpt-4o".to_string(),
+            tokens_used: tokens,
+            processing_time_ms: latency,
+            confidence_score: 0.5,
+            analysis_dimensions: Vec::new(),
+        };
+        for (tokens, latency) in [(1000, 2000), (3000, 4000)] {
+            database
+                .save_analysis(
+                    &report,
+                    Some("openai".to_string()),
+                    Some("gpt-4o".to_string()),
+                    Some(&metadata(tokens, latency)),
+                )
+                .await
+                .unwrap();
+        }
+        // A fallback analysis is counted but contributes no usage
+        database
+            .save_analysis(&report, Some("claude".to_string()), None, None)
+            .await
+            .unwrap();
+
+        let usage = database.get_ai_usage(None, None).await.unwrap();
+        let prices = AIConfig::parse_token_prices("openai=0.01, bad, claude=x");
+        let stats = HistoryStats::from_usage(None, None, usage, &prices);
+        assert_eq!(stats.total_analyses, 3);
+        assert_eq!(stats.ai_analyses, 2);
+        assert_eq!(stats.total_tokens, 4000);
+        assert_eq!(stats.avg_latency_ms, Some(3000.0));
+        assert!((stats.estimated_cost - 0.04).abs() < 1e-9);
+
+        let openai = &stats.providers[0];
+        assert_eq!(openai.provider, "openai");
+        assert_eq!(openai.model.as_deref(), Some("gpt-4o"));
+        let claude = &stats.providers[1];
+        assert_eq!((claude.analyses, claude.ai_analyses), (1, 0));
+        assert_eq!(claude.estimated_cost, None);
+
+        let future = Utc::now() + chrono::Duration::days(1);
+        let usage = database.get_ai_usage(Some(future), None).await.unwrap();
+        assert!(usage.is_empty());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_history_search_matches_ai_analysis_text() {
+        let path = std::env::temp_dir().join(format!("search_{}.db", uuid::Uuid::new_v4()));
+        let database = Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        database.create_tables().await.unwrap();
+
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+        let options = AnalysisOptions {
+            structured_only: true,
+            ..Default::default()
+        };
+        let mut report = analyzer
+            .analyze_single_stock("000001", &options)
+            .await
+            .unwrap();
+        report.recommendation = "中性".to_string();
+        for text in [
+            "维持持有，目标价12元",
+            "建议减持，目标价10元，跌破支撑继续减持",
+            "适度减持",
+            "观望为主",
+        ] {
+            report.ai_analysis = text.to_string();
+            database
+                .save_analysis(&report, None, None, None)
+                .await
+                .unwrap();
+        }
+
+        let search = |q: &str, stock_code: Option<&str>| HistoryQuery {
+            stock_code: stock_code.map(str::to_string),
+            start_date: None,
+            end_date: None,
+            limit: Some(10),
+            offset: Some(0),
+            q: Some(q.to_string()),
+        };
+
+        // Two-character terms use LIKE, ranked by how often the term appears
+        let history = database
+            .get_analysis_history(&search("减持", None))
+            .await
+            .unwrap();
+        assert_eq!(history.total, 2);
+        assert!(history.analyses[0].ai_analysis.starts_with("建议减持"));
+        assert_eq!(history.analyses[1].ai_analysis, "适度减持");
+
+        let history = database
+            .get_analysis_history(&search("目标价", None))
+            .await
+            .unwrap();
+        assert_eq!(history.total, 2);
+        assert!(history
+            .analyses
+            .iter()
+            .all(|analysis| analysis.ai_analysis.contains("目标价")));
+
+        let filtered = database
+            .get_analysis_history(&search("目标价", Some("600036")))
+            .await
+            .unwrap();
+        assert_eq!(filtered.total, 0);
+        let wildcard = database
+            .get_analysis_history(&search("%", None))
+            .await
+            .unwrap();
+        assert_eq!(wildcard.total, 0);
+
+        // Deleted analyses drop out of the full-text index
+        database
+            .delete_analyses_before(Utc::now() + chrono::Duration::days(1))
+            .await
+            .unwrap();
+        let history = database
+            .get_analysis_history(&search("目标价", None))
+            .await
+            .unwrap();
+        assert!(history.analyses.is_empty());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_intraday_analysis_reuses_technical_indicators() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+
+        let intraday = analyzer
+            .intraday_analysis("000001", IntradayInterval::FiveMinutes)
+            .await
+            .unwrap();
+        assert_eq!(intraday.interval, IntradayInterval::FiveMinutes);
+        assert!(intraday
+            .prices
+            .windows(2)
+            .all(|pair| (pair[1].date - pair[0].date).num_minutes() == 5));
+
+        // Indicators are computed per bar, exactly as for a daily series
+        let expected = analyzer.calculate_technical_analysis(&intraday.prices);
+        assert_eq!(intraday.technical.ma20, expected.ma20);
+        assert_eq!(intraday.technical.rsi, expected.rsi);
+        assert_eq!(intraday.technical.macd_signal, expected.macd_signal);
+        let closes: Vec<f64> = intraday.prices.iter().map(|p| p.close).collect();
+        let ma5 = closes[closes.len() - 5..].iter().sum::<f64>() / 5.0;
+        assert!((intraday.technical.ma5 - ma5).abs() < 1e-9);
+        assert!((0.0..=100.0).contains(&intraday.technical.rsi));
+    }
+
+    #[tokio::test]
+    async fn test_stats_summary_groups_history() {
+        let path = std::env::temp_dir().join(format!("summary_{}.db", uuid::Uuid::new_v4()));
+        let database = Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        database.create_tables().await.unwrap();
+
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+        let options = AnalysisOptions {
+            structured_only: true,
+            ..Default::default()
+        };
+        let today = Utc::now();
+        let yesterday = today - chrono::Duration::days(1);
+        for (code, comprehensive, recommendation, date) in [
+            ("000001", 80.0, "强烈推荐买入", yesterday),
+            ("000001", 60.0, "可以考虑买入", today),
+            ("000001", 50.0, "观望", today),
+            ("600036", 20.0, "强烈建议卖出", today),
+        ] {
+            let mut report = analyzer.analyze_single_stock(code, &options).await.unwrap();
+            report.scores.comprehensive = comprehensive;
+            report.recommendation = recommendation.to_string();
+            report.analysis_date = date;
+            database
+                .save_analysis(&report, None, None, None)
+                .await
+                .unwrap();
+        }
+
+        let summary = StatsSummary::new(
+            database
+                .get_recommendation_counts(None, None)
+                .await
+                .unwrap(),
+            database.get_daily_scores(None, None).await.unwrap(),
+            database
+                .get_most_analyzed_stocks(None, None, 1)
+                .await
+                .unwrap(),
+            HistoryStats::from_usage(
+                None,
+                None,
+                database.get_ai_usage(None, None).await.unwrap(),
+                &HashMap::new(),
+            ),
+        );
+        assert_eq!(summary.total_analyses, 4);
+        assert_eq!(
+            (summary.bullish, summary.neutral, summary.bearish),
+            (2, 1, 1)
+        );
+        assert_eq!(summary.recommendations.len(), 4);
+
+        assert_eq!(summary.daily_scores.len(), 2);
+        assert_eq!(summary.daily_scores[0].date, yesterday.date_naive());
+        assert_eq!(summary.daily_scores[0].avg_comprehensive, Some(80.0));
+        assert_eq!(summary.daily_scores[1].analyses, 3);
+        assert!((summary.daily_scores[1].avg_comprehensive.unwrap() - 130.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(summary.top_stocks.len(), 1);
+        assert_eq!(summary.top_stocks[0].stock_code, "000001");
+        assert_eq!(summary.top_stocks[0].analyses, 3);
+        assert_eq!(summary.top_stocks[0].last_analyzed, today);
+        assert_eq!(summary.usage.total_analyses, 4);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_rescore_saved_analysis_with_new_weights() {
+        let path = std::env::temp_dir().join(format!("rescore_{}.db", uuid::Uuid::new_v4()));
+        let database = Arc::new(
+            Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+                .await
+                .unwrap(),
+        );
+        database.create_tables().await.unwrap();
+
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let options = AnalysisOptions {
+            structured_only: true,
+            ..Default::default()
+        };
+        StockAnalyzer::with_database(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service.clone(),
+            database.clone(),
+        )
+        .analyze_single_stock("000001", &options)
+        .await
+        .unwrap();
+
+        let config = AnalysisConfig {
+            weights: AnalysisWeights {
+                technical: 1.0,
+                fundamental: 0.0,
+                sentiment: 0.0,
+            },
+            ..Default::default()
+        };
+        let analyzer = StockAnalyzer::new(Arc::new(MockDataFetcher), config, ai_service);
+
+        let since = Utc::now() - chrono::Duration::hours(1);
+        let saved = database
+            .get_analyses_in_range(Some("000001"), Some(since), None, 10)
+            .await
+            .unwrap();
+        assert_eq!(saved.len(), 1);
+        let future = database
+            .get_analyses_in_range(
+                None,
+                Some(Utc::now() + chrono::Duration::hours(1)),
+                None,
+                10,
+            )
+            .await
+            .unwrap();
+        assert!(future.is_empty());
+
+        let result = analyzer
+            .rescore_saved_analysis(&saved[0], false)
+            .await
+            .unwrap();
+        assert_eq!(result.after.comprehensive, result.before.technical);
+        assert_eq!(result.after.fundamental, result.before.fundamental);
+
+        assert!(database
+            .update_analysis_scores(&result.id, &result.after)
+            .await
+            .unwrap());
+        let reloaded = database
+            .get_analyses_in_range(Some("000001"), None, None, 10)
+            .await
+            .unwrap();
+        let scores: AnalysisScores = serde_json::from_value(reloaded[0].scores.clone()).unwrap();
+        assert_eq!(scores.comprehensive, result.after.comprehensive);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    /// 收盘价只取决于日期的数据源，不同时刻获取同一窗口得到相同序列
+    struct DatedPriceFetcher;
+
+    #[async_trait::async_trait]
+    impl DataFetcher for DatedPriceFetcher {
+        async fn get_stock_data(
+            &self,
+            _stock_code: &str,
+            days: i32,
+        ) -> Result<Vec<PriceData>, String> {
+            Ok((0..days as i64)
+                .rev()
+                .map(|i| {
+                    let date = Utc::now() - chrono::Duration::days(i);
+                    let day = (date.timestamp() / 86_400) as f64;
+                    let close = 20.0 + (day % 400.0) * 0.02 + (day * 0.7).sin();
+                    PriceData {
+                        date,
+                        open: close,
+                        close,
+                        high: close * 1.01,
+                        low: close * 0.99,
+                        volume: 100_000 + (day as i64 % 7) * 10_000,
+                        change_pct: 0.0,
+                        turnover: 0.0,
+                        turnover_rt: 0.0,
+                    }
+                })
+                .collect())
+        }
+
+        async fn get_fundamental_data(&self, stock_code: &str) -> Result<FundamentalData, String> {
+            MockDataFetcher.get_fundamental_data(stock_code).await
+        }
+
+        async fn get_news_data(
+            &self,
+            stock_code: &str,
+            days: i32,
+        ) -> Result<(Vec<News>, SentimentAnalysis), String> {
+            MockDataFetcher.get_news_data(stock_code, days).await
+        }
+
+        async fn get_stock_name(&self, stock_code: &str) -> String {
+            MockDataFetcher.get_stock_name(stock_code).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rescore_matches_fresh_risk_adjusted_score() {
+        let path = std::env::temp_dir().join(format!("rescore_{}.db", uuid::Uuid::new_v4()));
+        let database = Arc::new(
+            Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+                .await
+                .unwrap(),
+        );
+        database.create_tables().await.unwrap();
+
+        let config = AnalysisConfig {
+            scoring_mode: ScoringMode::RiskAdjusted,
+            ..Default::default()
+        };
+        let analyzer = StockAnalyzer::with_database(
+            Arc::new(DatedPriceFetcher),
+            config,
+            Arc::new(RwLock::new(AIService::new(AIConfig::default()))),
+            database.clone(),
+        );
+        let options = AnalysisOptions {
+            structured_only: true,
+            ..Default::default()
+        };
+        let fresh = analyzer
+            .analyze_single_stock("000001", &options)
+            .await
+            .unwrap();
+
+        let saved = database
+            .get_analyses_in_range(Some("000001"), None, None, 10)
+            .await
+            .unwrap();
+        let result = analyzer
+            .rescore_saved_analysis(&saved[0], true)
+            .await
+            .unwrap();
+
+        assert_eq!(result.after.technical, fresh.scores.technical);
+        assert_eq!(result.after.momentum, fresh.scores.momentum);
+        // Without the price series the risk adjustment and OBV confirmation would drop out
+        assert_ne!(
+            result.after.technical,
+            analyzer.calculate_technical_score(&fresh.technical, &[])
+        );
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_ai_phase_timeout_returns_structured_report() {
+        // Accepts connections at the socket level but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let ai_config = AIConfig {
+            api_key: "test-key".to_string(),
+            base_url: Some(format!(
+                "http://{}/v1/chat/completions",
+                listener.local_addr().unwrap()
+            )),
+            ..Default::default()
+        };
+        let config = AnalysisConfig {
+            ai_phase_timeout_seconds: 1,
+            ..Default::default()
+        };
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            config,
+            Arc::new(RwLock::new(AIService::new(ai_config))),
+        );
+
+        let report = analyzer
+            .analyze_single_stock("000001", &AnalysisOptions::default())
+            .await
+            .unwrap();
+        assert!(report.fallback_used);
+        assert!(report.fallback_reason.unwrap().contains("1秒内未完成"));
+        assert!(!report.ai_analysis.is_empty());
+        assert!(report.scores.comprehensive > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_provider_serves_when_primary_fails() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Nothing listens on the primary's port once the probe listener is dropped
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_addr = closed.local_addr().unwrap();
+        drop(closed);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0u8; 65536];
+            let _ = socket.read(&mut request).await;
+            let body = r#"{"choices":[{"message":{"content":"备用模型的分析"}}]}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let ai_config = AIConfig {
+            api_key: "test-key".to_string(),
+            base_url: Some(format!("http://{}/v1/chat/completions", closed_addr)),
+            fallback_providers: vec![AIConfig {
+                provider: "ollama".to_string(),
+                base_url: Some(format!("http://{}/v1/chat/completions", addr)),
+                model: Some("llama3".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            Arc::new(RwLock::new(AIService::new(ai_config))),
+        );
+
+        let report = analyzer
+            .analyze_single_stock("000001", &AnalysisOptions::default())
+            .await
+            .unwrap();
+        assert!(!report.fallback_used);
+        assert!(report.ai_analysis.contains("备用提供商 ollama（llama3）"));
+        assert!(report.ai_analysis.contains("备用模型的分析"));
+        let metadata = report.ai_metadata.unwrap();
+        assert_eq!(metadata.provider, "ollama");
+        assert_eq!(metadata.model, "llama3");
+    }
+
+    #[tokio::test]
+    async fn test_summary_modes() {
+        let options = AnalysisOptions {
+            enable_ai: true,
+            ..Default::default()
+        };
+
+        for (mode, expect_summary) in [
+            (SummaryMode::Deterministic, true),
+            (SummaryMode::Llm, true),
+            (SummaryMode::Off, false),
+        ] {
+            let config = AnalysisConfig {
+                summary_mode: mode,
+                ..Default::default()
+            };
+            let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+            let analyzer = StockAnalyzer::new(Arc::new(MockDataFetcher), config, ai_service);
+
+            let report = analyzer
+                .analyze_single_stock("000001", &options)
+                .await
+                .unwrap();
+            assert_eq!(!report.summary.is_empty(), expect_summary);
+            if expect_summary {
+                // AI 未配置时 Llm 模式退回模板摘要
+                assert!(report.summary.contains(&report.stock_name));
+                assert!(report.summary.contains(&report.recommendation));
+                assert!(!report.summary.contains('#'));
+            }
+        }
+    }
+
+    /// 上游基本面不提供 beta 的数据源
+    struct NoBetaFetcher;
+
+    #[async_trait::async_trait]
+    impl DataFetcher for NoBetaFetcher {
+        async fn get_stock_data(
+            &self,
+            stock_code: &str,
+            days: i32,
+        ) -> Result<Vec<PriceData>, String> {
+            MockDataFetcher.get_stock_data(stock_code, days).await
+        }
+
+        async fn get_fundamental_data(&self, stock_code: &str) -> Result<FundamentalData, String> {
+            let mut fundamental = MockDataFetcher.get_fundamental_data(stock_code).await?;
+            fundamental.risk_assessment.beta = None;
+            fundamental.risk_assessment.beta_source = None;
+            fundamental.risk_assessment.beta_index = None;
+            Ok(fundamental)
+        }
+
+        async fn get_news_data(
+            &self,
+            stock_code: &str,
+            days: i32,
+        ) -> Result<(Vec<News>, SentimentAnalysis), String> {
+            MockDataFetcher.get_news_data(stock_code, days).await
+        }
+
+        async fn get_stock_name(&self, stock_code: &str) -> String {
+            MockDataFetcher.get_stock_name(stock_code).await
+        }
+
+        async fn get_index_data(
+            &self,
+            index_code: &str,
+            days: i32,
+        ) -> Result<Vec<PriceData>, String> {
+            MockDataFetcher.get_index_data(index_code, days).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_beta_fills_missing_upstream_beta() {
+        let options = AnalysisOptions {
+            enable_ai: false,
+            ..Default::default()
+        };
+
+        // 即使未开启指数对比，也单独拉取指数计算 beta
+        for compare_to_index in [true, false] {
+            let config = AnalysisConfig {
+                compare_to_index,
+                ..Default::default()
+            };
+            let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+            let analyzer = StockAnalyzer::new(Arc::new(NoBetaFetcher), config, ai_service);
+            let report = analyzer
+                .analyze_single_stock("000001", &options)
+                .await
+                .unwrap();
+            let risk = &report.fundamental.risk_assessment;
+            assert!(risk.beta.is_some());
+            assert_eq!(risk.beta_source, Some(BetaSource::Local));
+            assert_eq!(risk.beta_index.as_deref(), Some("sh000001"));
+        }
+
+        let config = AnalysisConfig {
+            local_beta: false,
+            ..Default::default()
+        };
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(Arc::new(NoBetaFetcher), config, ai_service);
+        let report = analyzer
+            .analyze_single_stock("000001", &options)
+            .await
+            .unwrap();
+        assert!(report.fundamental.risk_assessment.beta.is_none());
+        assert!(report.fundamental.risk_assessment.beta_index.is_none());
+
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+        let report = analyzer
+            .analyze_single_stock("000001", &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            report.fundamental.risk_assessment.beta_source,
+            Some(BetaSource::Upstream)
+        );
+    }
+
+    #[test]
+    fn test_select_news_dedupes_and_caps() {
+        let article = |title: &str, days_ago: i64, relevance: f64, sentiment: f64| News {
+            title: title.to_string(),
+            content: String::new(),
+            date: Utc::now() - chrono::Duration::days(days_ago),
+            source: "test".to_string(),
+            news_type: "company_news".to_string(),
+            relevance,
+            sentiment,
+        };
+        let news = vec![
+            article("旧闻", 5, 0.9, -1.0),
+            article("重复", 1, 0.5, 0.5),
+            article("重复 ", 1, 0.5, 0.5),
+            article("最新", 0, 0.6, 1.0),
+            article("同日高相关", 1, 0.9, 0.0),
+        ];
+        let upstream = SentimentAnalysis::from_news(&news, 0.6);
+
+        let (kept, sentiment) = select_news(news.clone(), upstream.clone(), 3);
+        let titles: Vec<&str> = kept.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, vec!["最新", "同日高相关", "重复"]);
+        assert_eq!(sentiment.total_analyzed, 3);
+        assert!((sentiment.overall_sentiment - 0.5).abs() < 1e-9);
+        assert_eq!(sentiment.confidence_score, 0.6);
+
+        let (kept, _) = select_news(news, upstream, 0);
+        assert_eq!(kept.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_simulate_weights_compares_current_and_proposed() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+        let proposed = AnalysisWeights {
+            technical: 1.0,
+            fundamental: 0.0,
+            sentiment: 0.0,
+        };
+        assert!(proposed.validate().is_ok());
+
+        let codes = vec!["000001".to_string(), "600036".to_string()];
+        let response = analyzer.simulate_weights(&codes, &proposed).await;
+        assert!(response.failed.is_empty());
+        assert_eq!(response.results.len(), 2);
+        for result in &response.results {
+            assert_eq!(result.proposed.comprehensive, result.current.technical);
+            assert_eq!(result.current.fundamental, result.proposed.fundamental);
+            assert!(
+                (result.comprehensive_delta
+                    - (result.proposed.comprehensive - result.current.comprehensive))
+                    .abs()
+                    < 1e-9
+            );
+        }
+
+        let invalid = AnalysisWeights {
+            technical: -0.5,
+            ..proposed
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_score_stocks_keeps_request_order() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let config = AnalysisConfig {
+            max_workers: 2,
+            ..Default::default()
+        };
+        let analyzer = StockAnalyzer::new(Arc::new(MockDataFetcher), config, ai_service);
+
+        let codes: Vec<String> = ["600036", "000001", "600519"]
+            .iter()
+            .map(|code| code.to_string())
+            .collect();
+        let response = analyzer.score_stocks(&codes).await;
+        assert!(response.failed.is_empty());
+
+        let scored: Vec<&str> = response.scores.iter().map(|s| s.code.as_str()).collect();
+        assert_eq!(scored, vec!["600036", "000001", "600519"]);
+        for score in &response.scores {
+            assert!((0.0..=100.0).contains(&score.comprehensive));
+            assert!(score.price > 0.0);
+            assert!(!score.recommendation.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_peer_comparisons_dedupes_and_caps() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+
+        let peers: Vec<String> = [
+            "600519",
+            "000001",
+            "600519.SH",
+            "000002",
+            "300750",
+            "600036",
+        ]
+        .iter()
+        .map(|code| code.to_string())
+        .collect();
+        let comparisons = analyzer.fetch_peer_comparisons("000001", &peers).await;
+
+        let codes: Vec<&str> = comparisons.iter().map(|p| p.stock_code.as_str()).collect();
+        assert_eq!(codes, vec!["600519", "000002", "300750"]);
+    }
+
+    #[test]
+    fn test_evaluate_score_anomaly() {
+        let previous_date = Utc::now();
+
+        let anomaly = evaluate_score_anomaly(75.0, 40.0, previous_date, 20.0).unwrap();
+        assert_eq!(anomaly.delta, -35.0);
+        assert!(evaluate_score_anomaly(75.0, 70.0, previous_date, 20.0).is_none());
+    }
+
+    #[test]
+    fn test_compute_risk_metrics() {
+        let closes = [10.0, 10.5, 10.2, 10.8, 9.9, 10.4, 11.0];
+        let start = Utc::now() - chrono::Duration::days(closes.len() as i64);
+        let price_data: Vec<PriceData> = closes
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| PriceData {
+                date: start + chrono::Duration::days(i as i64),
+                open: close,
+                close,
+                high: close,
+                low: close,
+                volume: 1000,
+                change_pct: 0.0,
+                turnover: 0.0,
+                turnover_rt: 0.0,
+            })
+            .collect();
+
+        let metrics = StockAnalyzer::compute_risk_metrics("000001", &price_data, 0.02).unwrap();
+        assert_eq!(metrics.observations, 6);
+        assert!((metrics.max_drawdown - (10.8 - 9.9) / 10.8).abs() < 1e-9);
+        assert!(metrics.annualized_volatility > 0.0);
+        let sharpe = metrics.sharpe_ratio.unwrap();
+        let sortino = metrics.sortino_ratio.unwrap();
+        assert!(sharpe > 0.0 && sortino > sharpe);
+
+        assert!(StockAnalyzer::compute_risk_metrics("000001", &price_data[..2], 0.02).is_err());
+    }
+
+    #[test]
+    fn test_macd_histogram_follows_momentum() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+
+        // Flat, then an accelerating rally: MACD rises above its signal line
+        let rally: Vec<f64> = (0..60)
+            .map(|i| {
+                if i < 30 {
+                    10.0
+                } else {
+                    10.0 + ((i - 30) as f64).powi(2) * 0.01
+                }
+            })
+            .collect();
+        let (signal, macd_line, histogram) = analyzer.calculate_macd(&rally);
+        assert_eq!(signal, "看涨");
+        assert!(macd_line > 0.0);
+        assert!(histogram > 0.0);
+
+        let selloff: Vec<f64> = rally.iter().map(|p| 20.0 - p).collect();
+        let (signal, macd_line, histogram) = analyzer.calculate_macd(&selloff);
+        assert_eq!(signal, "看跌");
+        assert!(macd_line < 0.0);
+        assert!(histogram < 0.0);
+
+        let ema = analyzer.calculate_ema_series(&[10.0, 12.0, 12.0], 3);
+        assert_eq!(ema, vec![10.0, 11.0, 11.5]);
+    }
+
+    #[test]
+    fn test_obv_accumulates_by_close_direction() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+
+        let obv = analyzer.calculate_obv(
+            &[10.0, 11.0, 10.5, 10.5, 12.0],
+            &[100.0, 200.0, 50.0, 80.0, 30.0],
+        );
+        assert_eq!(obv, vec![0.0, 200.0, 150.0, 150.0, 180.0]);
+
+        // Up days on heavy volume, down days on light volume: OBV rises with price
+        let prices: Vec<f64> = (0..12)
+            .map(|i| 10.0 + (i % 2) as f64 * 0.5 + i as f64 * 0.1)
+            .collect();
+        let volumes: Vec<f64> = (0..12)
+            .map(|i| if i % 2 == 1 { 1000.0 } else { 200.0 })
+            .collect();
+        let obv = analyzer.calculate_obv(&prices, &volumes);
+        assert_eq!(StockAnalyzer::obv_trend(&obv, &volumes, 10), "上升");
+        let reversed: Vec<f64> = volumes.iter().map(|v| 1200.0 - v).collect();
+        let obv = analyzer.calculate_obv(&prices, &reversed);
+        assert_eq!(StockAnalyzer::obv_trend(&obv, &reversed, 10), "下降");
+        assert_eq!(
+            StockAnalyzer::obv_trend(&obv[..5], &reversed[..5], 10),
+            "持平"
+        );
+    }
+
+    #[test]
+    fn test_check_liquidity_flags_thin_trading() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let mut config = AnalysisConfig::default();
+        config.liquidity_gate.lookback_days = 5;
+        let analyzer = StockAnalyzer::new(Arc::new(MockDataFetcher), config, ai_service);
+
+        let candles = |volume: i64| -> Vec<PriceData> {
+            (0..10)
+                .map(|i| PriceData {
+                    date: Utc::now() - chrono::Duration::days(10 - i),
+                    open: 5.0,
+                    close: 5.0,
+                    high: 5.0,
+                    low: 5.0,
+                    volume,
+                    change_pct: 0.0,
+                    turnover: volume as f64 * 5.0,
+                    turnover_rt: 0.0,
+                })
+                .collect()
+        };
+
+        assert!(analyzer.check_liquidity(&candles(500_000)).is_none());
+
+        // Volume clears the floor but turnover does not
+        let warning = analyzer.check_liquidity(&candles(150_000)).unwrap();
+        assert!(warning.contains("日均成交额") && !warning.contains("日均成交量"));
+
+        let mut thin = candles(500_000);
+        for candle in thin.iter_mut().skip(5) {
+            candle.volume = 100;
+            candle.turnover = 1000.0;
+        }
+        let warning = analyzer.check_liquidity(&thin).unwrap();
+        assert!(warning.contains("近5个交易日") && warning.contains("日均成交量"));
+    }
+
+    #[test]
+    fn test_compute_relative_performance_aligns_dates() {
+        let start = Utc::now() - chrono::Duration::days(10);
+        let series = |closes: &[f64], skip: usize| -> Vec<PriceData> {
+            closes
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != skip)
+                .map(|(i, &close)| PriceData {
+                    date: start + chrono::Duration::days(i as i64),
+                    open: close,
+                    close,
+                    high: close,
+                    low: close,
+                    volume: 1000,
+                    change_pct: 0.0,
+                    turnover: 0.0,
+                    turnover_rt: 0.0,
+                })
+                .collect()
+        };
+
+        // The stock moves exactly twice as much as the index each day
+        let index = [100.0, 101.0, 99.0, 102.0, 103.0];
+        let mut stock = vec![10.0];
+        for w in index.windows(2) {
+            let last = *stock.last().unwrap();
+            stock.push(last * (1.0 + 2.0 * (w[1] / w[0] - 1.0)));
+        }
+
+        // A day missing from the index series is dropped from the comparison
+        let relative = StockAnalyzer::compute_relative_performance(
+            "上证指数",
+            "sh000001",
+            &series(&stock, usize::MAX),
+            &series(&index, usize::MAX),
+        )
+        .unwrap();
+        assert_eq!(relative.observations, 4);
+        assert!((relative.index_return - 0.03).abs() < 1e-9);
+        assert!((relative.beta.unwrap() - 2.0).abs() < 1e-9);
+        assert!(
+            (relative.excess_return - (relative.stock_return - relative.index_return)).abs()
+                < 1e-12
+        );
+
+        let gapped = StockAnalyzer::compute_relative_performance(
+            "上证指数",
+            "sh000001",
+            &series(&stock, usize::MAX),
+            &series(&index, 2),
+        )
+        .unwrap();
+        assert_eq!(gapped.observations, 3);
+
+        assert!(StockAnalyzer::compute_relative_performance(
+            "上证指数",
+            "sh000001",
+            &series(&stock, usize::MAX),
+            &[],
+        )
+        .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_compare_stocks_ranks_and_correlates() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+        let options = AnalysisOptions {
+            enable_ai: false,
+            ..Default::default()
+        };
+        let codes = vec![
+            "000001".to_string(),
+            "600036".to_string(),
+            "AAPL".to_string(),
+        ];
+
+        let report = analyzer.compare_stocks(&codes, &options).await;
+        assert!(report.failed.is_empty());
+        assert_eq!(report.stocks.len(), 3);
+        for (i, pair) in report.stocks.windows(2).enumerate() {
+            assert_eq!(pair[0].rank, i + 1);
+            assert!(pair[0].scores.comprehensive >= pair[1].scores.comprehensive);
+        }
+
+        let correlation = &report.correlation;
+        assert_eq!(
+            correlation.codes,
+            report
+                .stocks
+                .iter()
+                .map(|s| s.stock_code.clone())
+                .collect::<Vec<_>>()
+        );
+        for i in 0..3 {
+            assert_eq!(correlation.matrix[i][i], Some(1.0));
+            for j in 0..3 {
+                assert_eq!(correlation.matrix[i][j], correlation.matrix[j][i]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_returns_correlation_intersects_dates() {
+        let start = Utc::now() - chrono::Duration::days(10);
+        let series = |closes: &[f64], skip: Option<usize>| -> Vec<PriceData> {
+            closes
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| Some(*i) != skip)
+                .map(|(i, &close)| PriceData {
+                    date: start + chrono::Duration::days(i as i64),
+                    open: close,
+                    close,
+                    high: close,
+                    low: close,
+                    volume: 1000,
+                    change_pct: 0.0,
+                    turnover: 0.0,
+                    turnover_rt: 0.0,
+                })
+                .collect()
+        };
+        let a = [10.0, 10.5, 10.2, 10.8, 9.9, 10.4];
+        let doubled: Vec<f64> = a.iter().map(|close| close * 2.0).collect();
+        let inverse: Vec<f64> = a.iter().map(|close| 100.0 / close).collect();
+
+        let aligned = StockAnalyzer::align_closes(&series(&a, None), &series(&doubled, Some(2)));
+        assert_eq!(aligned.len(), 5);
+        let correlation = StockAnalyzer::returns_correlation(&aligned).unwrap();
+        assert!((correlation - 1.0).abs() < 1e-9);
+
+        let aligned = StockAnalyzer::align_closes(&series(&a, None), &series(&inverse, None));
+        assert!(StockAnalyzer::returns_correlation(&aligned).unwrap() < -0.9);
+
+        let flat = [5.0; 6];
+        let aligned = StockAnalyzer::align_closes(&series(&a, None), &series(&flat, None));
+        assert!(StockAnalyzer::returns_correlation(&aligned).is_none());
+        assert!(StockAnalyzer::returns_correlation(&aligned[..3]).is_none());
+    }
+
+    #[test]
+    fn test_compute_correlation_with_rolling_window() {
+        let start = Utc::now() - chrono::Duration::days(20);
+        let series = |closes: &[f64], skip: usize| -> Vec<PriceData> {
+            closes
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != skip)
+                .map(|(i, &close)| PriceData {
+                    date: start + chrono::Duration::days(i as i64),
+                    open: close,
+                    close,
+                    high: close,
+                    low: close,
+                    volume: 1000,
+                    change_pct: 0.0,
+                    turnover: 0.0,
+                    turnover_rt: 0.0,
+                })
+                .collect()
+        };
+        let b = [
+            10.0, 10.2, 10.1, 10.4, 10.3, 10.6, 10.5, 10.9, 10.7, 11.0, 10.8, 11.2,
+        ];
+        // Each daily return of a is twice that of b
+        let mut a = vec![20.0];
+        for w in b.windows(2) {
+            let last = a[a.len() - 1];
+            a.push(last * (1.0 + 2.0 * (w[1] / w[0] - 1.0)));
+        }
+
+        let analysis = StockAnalyzer::compute_correlation(
+            "000001",
+            "600036",
+            &series(&a, usize::MAX),
+            &series(&b, 0),
+            5,
+        )
+        .unwrap();
+        assert_eq!(analysis.observations, 10);
+        assert_eq!(
+            analysis.start_date,
+            (start + chrono::Duration::days(1)).date_naive()
+        );
+        assert_eq!(
+            analysis.end_date,
+            (start + chrono::Duration::days(11)).date_naive()
+        );
+        assert!((analysis.correlation.unwrap() - 1.0).abs() < 1e-9);
+        assert!((analysis.beta.unwrap() - 2.0).abs() < 1e-9);
+        assert_eq!(analysis.rolling.len(), 6);
+        assert_eq!(analysis.rolling[5].date, analysis.end_date);
+        assert!(analysis
+            .rolling
+            .iter()
+            .all(|point| (point.correlation.unwrap() - 1.0).abs() < 1e-9));
+
+        let short = StockAnalyzer::compute_correlation(
+            "000001",
+            "600036",
+            &series(&a, usize::MAX),
+            &series(&b, 0),
+            30,
+        )
+        .unwrap();
+        assert!(short.rolling.is_empty());
+
+        assert!(StockAnalyzer::compute_correlation(
+            "000001",
+            "600036",
+            &series(&a[..3], usize::MAX),
+            &series(&b, 0),
+            5
+        )
+        .is_err());
+        assert!(StockAnalyzer::compute_correlation("000001", "600036", &[], &[], 2).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fallback_analysis_has_no_unfilled_placeholders() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service.clone(),
+        );
+        let options = AnalysisOptions {
+            enable_ai: false,
+            ..Default::default()
+        };
+        let mut report = analyzer
+            .analyze_single_stock("000001", &options)
+            .await
+            .unwrap();
+
+        let ai_service = ai_service.read().await;
+        for comprehensive in [30.0, 60.0, 80.0] {
+            report.scores.comprehensive = comprehensive;
+            let analysis = ai_service.generate_fallback_analysis(&report);
+            assert!(!analysis.contains("{}"), "{}", analysis);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_analysis_uses_report_industry() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service.clone(),
+        );
+        let options = AnalysisOptions {
+            enable_ai: false,
+            ..Default::default()
+        };
+        let mut report = analyzer
+            .analyze_single_stock("600519", &options)
+            .await
+            .unwrap();
+        report.scores.comprehensive = 60.0;
+        report.fundamental.industry = "未知".to_string();
+        report.fundamental.sector = "白酒".to_string();
+
+        let analysis = ai_service.read().await.generate_fallback_analysis(&report);
+        assert!(analysis.contains("白酒"));
+        for hardcoded in ["平安", "茅台集团", "银行业"] {
+            assert!(!analysis.contains(hardcoded), "{}", hardcoded);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_analysis_reports_roe() {
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service.clone(),
+        );
+        let options = AnalysisOptions {
+            enable_ai: false,
+            ..Default::default()
+        };
+        let mut report = analyzer
+            .analyze_single_stock("00700", &options)
+            .await
+            .unwrap();
+        report
+            .fundamental
+            .financial_indicators
+            .retain(|i| i.name != "ROE");
+        report
+            .fundamental
+            .financial_indicators
+            .push(FinancialIndicator {
+                name: "ROE".to_string(),
+                value: 18.0,
+                unit: "%".to_string(),
+            });
+
+        let analysis = ai_service.read().await.generate_fallback_analysis(&report);
+        assert!(
+            analysis.contains("净资产收益率(ROE)为18.00%"),
+            "{}",
+            analysis
+        );
+    }
 }
@@ -7,9 +7,10 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+use crate::database::Database;
 use crate::models::{AuthConfig, AuthResponse, LoginRequest, RegisterRequest, User, UserResponse};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     sub: String,
     username: String,
@@ -17,55 +18,131 @@ pub struct Claims {
     is_admin: bool,
 }
 
+/// 注册失败原因，用户名或邮箱冲突需要单独区分以返回 409，未开放注册时返回 403
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegisterError {
+    Disabled(String),
+    Conflict(String),
+    Invalid(String),
+    Internal(String),
+}
+
+impl std::fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegisterError::Disabled(e)
+            | RegisterError::Conflict(e)
+            | RegisterError::Invalid(e)
+            | RegisterError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// 用户存储在数据库中；无状态模式（数据库不可用）下保存在内存，重启后丢失
 pub struct AuthService {
     users: Arc<Mutex<HashMap<String, User>>>,
+    database: Option<Arc<Database>>,
     config: AuthConfig,
 }
 
+impl Claims {
+    pub fn is_admin(&self) -> bool {
+        self.is_admin
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+}
+
 impl AuthService {
     pub fn new(config: AuthConfig) -> Self {
         let mut users = HashMap::new();
 
-        // Add default admin user if no users exist
+        // Seed the admin user from the configured password
         if config.enabled {
-            let admin = User {
-                id: Uuid::new_v4().to_string(),
-                username: "admin".to_string(),
-                email: "admin@example.com".to_string(),
-                password_hash: hash("admin123", config.bcrypt_cost).unwrap_or_default(),
-                created_at: Utc::now(),
-                last_login: None,
-                is_admin: true,
-                api_usage: 0,
-                is_active: true,
-            };
-            users.insert(admin.username.clone(), admin);
+            if let Some(admin) = Self::default_admin(&config) {
+                users.insert(admin.username.clone(), admin);
+            }
         }
 
         Self {
             users: Arc::new(Mutex::new(users)),
+            database: None,
             config,
         }
     }
 
-    pub async fn register(&self, req: RegisterRequest) -> Result<AuthResponse, String> {
-        if !self.config.enabled {
-            return Err("Authentication is disabled".to_string());
+    /// 使用数据库保存用户；认证启用且数据库中没有 admin 用户时按配置的密码创建管理员
+    pub async fn with_database(config: AuthConfig, database: Arc<Database>) -> Self {
+        if config.enabled {
+            match database.get_user("admin").await {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    if let Some(admin) = Self::default_admin(&config) {
+                        if let Err(e) = database.create_user(&admin).await {
+                            log::error!("Failed to create default admin user: {}", e);
+                        }
+                    }
+                }
+                Err(e) => log::error!("Failed to look up default admin user: {}", e),
+            }
         }
 
-        let mut users = self.users.lock().await;
+        Self {
+            users: Arc::new(Mutex::new(HashMap::new())),
+            database: Some(database),
+            config,
+        }
+    }
+
+    /// 以 admin_password 创建管理员，未配置密码时不创建
+    fn default_admin(config: &AuthConfig) -> Option<User> {
+        let password = config.admin_password.as_deref()?;
+        Some(User {
+            id: Uuid::new_v4().to_string(),
+            username: "admin".to_string(),
+            email: "admin@example.com".to_string(),
+            password_hash: hash(password, config.bcrypt_cost).unwrap_or_default(),
+            created_at: Utc::now(),
+            last_login: None,
+            is_admin: true,
+            api_usage: 0,
+            is_active: true,
+        })
+    }
+
+    pub async fn register(&self, req: RegisterRequest) -> Result<AuthResponse, RegisterError> {
+        if !self.config.enabled {
+            return Err(RegisterError::Invalid(
+                "Authentication is disabled".to_string(),
+            ));
+        }
+        if !self.config.allow_registration {
+            return Err(RegisterError::Disabled(
+                "未开放注册，请联系管理员".to_string(),
+            ));
+        }
 
-        if users.contains_key(&req.username) {
-            return Err("Username already exists".to_string());
+        let username = req.username.trim().to_string();
+        let email = req.email.trim().to_lowercase();
+        if username.is_empty() {
+            return Err(RegisterError::Invalid("用户名不能为空".to_string()));
+        }
+        if !email.contains('@') {
+            return Err(RegisterError::Invalid("邮箱格式不正确".to_string()));
+        }
+        if req.password.chars().count() < 6 {
+            return Err(RegisterError::Invalid("密码长度至少为 6 位".to_string()));
         }
 
         let password_hash = hash(&req.password, self.config.bcrypt_cost)
-            .map_err(|e| format!("Failed to hash password: {}", e))?;
+            .map_err(|e| RegisterError::Internal(format!("Failed to hash password: {}", e)))?;
 
         let user = User {
             id: Uuid::new_v4().to_string(),
-            username: req.username.clone(),
-            email: req.email,
+            username,
+            email,
             password_hash,
             created_at: Utc::now(),
             last_login: None,
@@ -74,43 +151,86 @@ impl AuthService {
             is_active: true,
         };
 
-        let token = self.generate_token(&user)?;
-        let user_response = self.user_to_response(&user);
+        match &self.database {
+            Some(database) => {
+                let conflicts = database
+                    .find_user_conflicts(&user.username, &user.email)
+                    .await
+                    .map_err(|e| {
+                        RegisterError::Internal(format!("Failed to check users: {}", e))
+                    })?;
+                Self::check_conflicts(conflicts)?;
+                database.create_user(&user).await.map_err(|e| match e {
+                    // Lost a race with a concurrent registration of the same name or email
+                    sqlx::Error::Database(db) if db.is_unique_violation() => {
+                        RegisterError::Conflict("用户名或邮箱已被注册".to_string())
+                    }
+                    e => RegisterError::Internal(format!("Failed to save user: {}", e)),
+                })?;
+            }
+            None => {
+                let mut users = self.users.lock().await;
+                Self::check_conflicts((
+                    users.contains_key(&user.username),
+                    users.values().any(|existing| existing.email == user.email),
+                ))?;
+                users.insert(user.username.clone(), user.clone());
+            }
+        }
 
-        users.insert(req.username, user);
+        let token = self
+            .generate_token(&user)
+            .map_err(RegisterError::Internal)?;
 
         Ok(AuthResponse {
             token,
-            user: user_response,
+            user: self.user_to_response(&user),
         })
     }
 
+    fn check_conflicts((username_taken, email_taken): (bool, bool)) -> Result<(), RegisterError> {
+        match (username_taken, email_taken) {
+            (true, _) => Err(RegisterError::Conflict("用户名已被注册".to_string())),
+            (false, true) => Err(RegisterError::Conflict("邮箱已被注册".to_string())),
+            (false, false) => Ok(()),
+        }
+    }
+
     pub async fn login(&self, req: LoginRequest) -> Result<AuthResponse, String> {
         if !self.config.enabled {
             return Err("Authentication is disabled".to_string());
         }
 
-        let mut users = self.users.lock().await;
-
-        let user = users
-            .get_mut(&req.username)
-            .ok_or_else(|| "User not found".to_string())?;
+        // Unknown users, wrong passwords and disabled accounts share one message so
+        // the response does not reveal which usernames exist
+        const INVALID_CREDENTIALS: &str = "Invalid credentials";
+        let mut user = self
+            .get_user(req.username.trim())
+            .await
+            .ok_or_else(|| INVALID_CREDENTIALS.to_string())?;
 
-        if !user.is_active {
-            return Err("Account is disabled".to_string());
-        }
-
-        if !verify(&req.password, &user.password_hash)
-            .map_err(|_| "Invalid password".to_string())?
-        {
-            return Err("Invalid credentials".to_string());
+        if !verify(&req.password, &user.password_hash).unwrap_or(false) || !user.is_active {
+            return Err(INVALID_CREDENTIALS.to_string());
         }
 
         user.last_login = Some(Utc::now());
         user.api_usage += 1;
+        match &self.database {
+            Some(database) => {
+                if let Err(e) = database.record_login(&user.username, Utc::now()).await {
+                    log::warn!("Failed to record login for {}: {}", user.username, e);
+                }
+            }
+            None => {
+                self.users
+                    .lock()
+                    .await
+                    .insert(user.username.clone(), user.clone());
+            }
+        }
 
-        let token = self.generate_token(user)?;
-        let user_response = self.user_to_response(user);
+        let token = self.generate_token(&user)?;
+        let user_response = self.user_to_response(&user);
 
         Ok(AuthResponse {
             token,
@@ -150,10 +270,16 @@ impl AuthService {
     }
 
     pub async fn get_user(&self, username: &str) -> Option<User> {
-        let users = self.users.lock().await;
-        users.get(username).cloned()
+        match &self.database {
+            Some(database) => database.get_user(username).await.unwrap_or_else(|e| {
+                log::error!("Failed to load user {}: {}", username, e);
+                None
+            }),
+            None => self.users.lock().await.get(username).cloned(),
+        }
     }
 
+    #[allow(dead_code)]
     pub async fn increment_api_usage(&self, username: &str) -> Result<(), String> {
         let mut users = self.users.lock().await;
         if let Some(user) = users.get_mut(username) {
@@ -162,7 +288,7 @@ impl AuthService {
         Ok(())
     }
 
-    fn user_to_response(&self, user: &User) -> UserResponse {
+    pub fn user_to_response(&self, user: &User) -> UserResponse {
         UserResponse {
             id: user.id.clone(),
             username: user.username.clone(),
@@ -180,3 +306,121 @@ impl AuthService {
         &self.config
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AuthConfig {
+        AuthConfig {
+            enabled: true,
+            secret_key: "test-secret".to_string(),
+            session_timeout: 3600,
+            bcrypt_cost: 4,
+            admin_password: Some("admin-secret".to_string()),
+            allow_registration: true,
+        }
+    }
+
+    fn request(username: &str, email: &str) -> RegisterRequest {
+        RegisterRequest {
+            username: username.to_string(),
+            email: email.to_string(),
+            password: "secret123".to_string(),
+        }
+    }
+
+    fn login(username: &str, password: &str) -> LoginRequest {
+        LoginRequest {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_is_seeded_only_from_configured_password() {
+        let service = AuthService::new(config());
+        assert!(service.login(login("admin", "admin123")).await.is_err());
+        let response = service.login(login("admin", "admin-secret")).await.unwrap();
+        assert!(service.verify_token(&response.token).unwrap().is_admin());
+
+        let unseeded = AuthService::new(AuthConfig {
+            admin_password: None,
+            ..config()
+        });
+        assert!(unseeded.get_user("admin").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_login_errors_do_not_reveal_usernames() {
+        let service = AuthService::new(config());
+        let unknown = service.login(login("nobody", "secret123")).await.err();
+        let wrong_password = service.login(login("admin", "secret123")).await.err();
+        assert_eq!(unknown, Some("Invalid credentials".to_string()));
+        assert_eq!(unknown, wrong_password);
+    }
+
+    #[tokio::test]
+    async fn test_registration_is_closed_by_default() {
+        let service = AuthService::new(AuthConfig {
+            allow_registration: false,
+            ..config()
+        });
+        assert!(matches!(
+            service
+                .register(request("alice", "alice@example.com"))
+                .await,
+            Err(RegisterError::Disabled(_))
+        ));
+        assert!(service.get_user("alice").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_register_persists_users_and_rejects_conflicts() {
+        let path = std::env::temp_dir().join(format!("auth_{}.db", Uuid::new_v4()));
+        let database = Arc::new(
+            Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+                .await
+                .unwrap(),
+        );
+        database.create_tables().await.unwrap();
+        let service = AuthService::with_database(config(), database.clone()).await;
+
+        let registered = service
+            .register(request("alice", "Alice@Example.com"))
+            .await
+            .unwrap();
+        let claims = service.verify_token(&registered.token).unwrap();
+        assert_eq!(claims.username(), "alice");
+        assert!(!claims.is_admin());
+
+        assert_eq!(
+            service
+                .register(request("alice", "other@example.com"))
+                .await
+                .err(),
+            Some(RegisterError::Conflict("用户名已被注册".to_string()))
+        );
+        assert_eq!(
+            service
+                .register(request("bob", "alice@example.com"))
+                .await
+                .err(),
+            Some(RegisterError::Conflict("邮箱已被注册".to_string()))
+        );
+
+        // A fresh service over the same database sees the stored user and the seeded admin
+        let reloaded = AuthService::with_database(config(), database).await;
+        let login = reloaded
+            .login(LoginRequest {
+                username: "alice".to_string(),
+                password: "secret123".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(login.user.email, "alice@example.com");
+        assert!(reloaded.get_user("admin").await.unwrap().is_admin);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
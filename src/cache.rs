@@ -1,7 +1,12 @@
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures::future::{BoxFuture, FutureExt, Shared};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::any::Any;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::sync::RwLock;
 
 use crate::data_fetcher::DataFetcher;
@@ -32,6 +37,7 @@ impl<T> CacheEntry<T> {
         Utc::now() > self.expires_at
     }
 
+    #[allow(dead_code)]
     pub fn is_stale(&self, stale_seconds: i64) -> bool {
         Utc::now() > self.created_at + Duration::seconds(stale_seconds)
     }
@@ -51,6 +57,10 @@ pub struct CacheConfig {
     pub max_entries: usize,        // Maximum entries per cache type
     pub cleanup_interval: i64,     // Cleanup interval in seconds
     pub enable_stats: bool,        // Enable cache statistics
+    pub coalesce_requests: bool,   // Share one upstream fetch between concurrent identical misses
+    pub backend: CacheBackendKind, // Where cached values are stored
+    #[cfg_attr(not(feature = "redis"), allow(dead_code))]
+    pub redis_url: Option<String>, // Connection URL for the Redis backend
 }
 
 impl Default for CacheConfig {
@@ -63,11 +73,14 @@ impl Default for CacheConfig {
             max_entries: 1000,          // Max 1000 entries per cache type
             cleanup_interval: 60,       // Cleanup every minute
             enable_stats: true,
+            coalesce_requests: true,
+            backend: CacheBackendKind::Memory,
+            redis_url: None,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CacheStats {
     pub price_hits: u64,
     pub price_misses: u64,
@@ -81,41 +94,402 @@ pub struct CacheStats {
     pub total_entries: usize,
 }
 
-impl Default for CacheStats {
-    fn default() -> Self {
+/// Kind of cached data; each kind has its own TTL, hit/miss counters and entry limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheKind {
+    Price,
+    Fundamental,
+    News,
+    Name,
+}
+
+impl CacheKind {
+    pub const ALL: [CacheKind; 4] = [
+        CacheKind::Price,
+        CacheKind::Fundamental,
+        CacheKind::News,
+        CacheKind::Name,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheKind::Price => "price",
+            CacheKind::Fundamental => "fundamental",
+            CacheKind::News => "news",
+            CacheKind::Name => "name",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /// Keys are namespaced as `{type}:{stock_code}`, with a `:{days}` suffix
+    /// for windowed data, so one stock's entries can be found by prefix
+    fn key(&self, stock_code: &str, days: Option<i32>) -> String {
+        match days {
+            Some(days) => format!("{}:{}:{}", self.as_str(), stock_code, days),
+            None => format!("{}:{}", self.as_str(), stock_code),
+        }
+    }
+
+    fn is_stock_key(&self, key: &str, stock_code: &str) -> bool {
+        let stock_key = self.key(stock_code, None);
+        key.strip_prefix(stock_key.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(':'))
+    }
+}
+
+/// A cached value with its type erased, so one backend can hold every cached type
+pub type AnyValue = Arc<dyn Any + Send + Sync>;
+
+/// Value handed to a backend. The in-process store keeps the typed value as is;
+/// backends that store bytes serialize it with `to_json`.
+pub struct CacheValue {
+    value: AnyValue,
+    serialize: fn(&(dyn Any + Send + Sync)) -> serde_json::Result<String>,
+}
+
+impl CacheValue {
+    pub fn new<T: Serialize + Send + Sync + 'static>(value: T) -> Self {
+        Self {
+            value: Arc::new(value),
+            serialize: serialize_as::<T>,
+        }
+    }
+
+    #[cfg_attr(not(feature = "redis"), allow(dead_code))]
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        (self.serialize)(self.value.as_ref())
+    }
+}
+
+fn serialize_as<T: Serialize + 'static>(
+    value: &(dyn Any + Send + Sync),
+) -> serde_json::Result<String> {
+    let value = value
+        .downcast_ref::<T>()
+        .expect("cache value is serialized as the type it was created with");
+    serde_json::to_string(value)
+}
+
+/// Value read back from a backend: the stored value itself, or its JSON form
+/// for backends that keep serialized bytes
+pub enum CacheHit {
+    Value(AnyValue),
+    #[cfg_attr(not(feature = "redis"), allow(dead_code))]
+    Json(String),
+}
+
+/// Storage behind `DataCache`. Values are type-erased, so a backend never needs
+/// to know the cached types; only backends that store bytes serialize them.
+#[async_trait::async_trait]
+pub trait CacheBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns the stored value, or None when missing or expired
+    async fn get(&self, kind: CacheKind, key: &str) -> Option<CacheHit>;
+
+    async fn set(&self, kind: CacheKind, key: &str, value: CacheValue, ttl_seconds: i64);
+
+    async fn clear(&self);
+
+    /// Removes every `kind` entry cached for one stock and returns how many were removed
+    async fn remove_stock(&self, kind: CacheKind, stock_code: &str) -> usize;
+
+    /// Drops expired entries and returns how many were removed. Backends that
+    /// expire keys on their own leave this as a no-op.
+    async fn purge_expired(&self) -> usize {
+        0
+    }
+
+    /// Number of live entries across all kinds
+    async fn len(&self) -> usize;
+
+    /// Entries removed so far through expiry, LRU eviction or clearing
+    fn evictions(&self) -> u64;
+}
+
+/// In-process store: one map per kind, LRU eviction at `max_entries`
+pub struct MemoryBackend {
+    max_entries: usize,
+    stores: [RwLock<HashMap<String, CacheEntry<AnyValue>>>; 4],
+    evictions: AtomicU64,
+}
+
+impl MemoryBackend {
+    pub fn new(max_entries: usize) -> Self {
         Self {
-            price_hits: 0,
-            price_misses: 0,
-            fundamental_hits: 0,
-            fundamental_misses: 0,
-            news_hits: 0,
-            news_misses: 0,
-            name_hits: 0,
-            name_misses: 0,
-            evictions: 0,
-            total_entries: 0,
+            max_entries,
+            stores: Default::default(),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    fn store(&self, kind: CacheKind) -> &RwLock<HashMap<String, CacheEntry<AnyValue>>> {
+        &self.stores[kind.index()]
+    }
+}
+
+#[async_trait::async_trait]
+impl CacheBackend for MemoryBackend {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn get(&self, kind: CacheKind, key: &str) -> Option<CacheHit> {
+        let mut cache = self.store(kind).write().await;
+        let entry = cache.get_mut(key)?;
+
+        if entry.is_expired() {
+            cache.remove(key);
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        entry.record_access();
+        Some(CacheHit::Value(entry.data.clone()))
+    }
+
+    async fn set(&self, kind: CacheKind, key: &str, value: CacheValue, ttl_seconds: i64) {
+        let mut cache = self.store(kind).write().await;
+
+        // Enforce max entries limit by dropping the least recently used entry
+        if cache.len() >= self.max_entries && !cache.contains_key(key) {
+            if let Some(lru_key) = cache
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_accessed)
+                .map(|(key, _)| key.clone())
+            {
+                cache.remove(&lru_key);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        cache.insert(key.to_string(), CacheEntry::new(value.value, ttl_seconds));
+    }
+
+    async fn clear(&self) {
+        for store in &self.stores {
+            let mut cache = store.write().await;
+            self.evictions
+                .fetch_add(cache.len() as u64, Ordering::Relaxed);
+            cache.clear();
+        }
+    }
+
+    async fn remove_stock(&self, kind: CacheKind, stock_code: &str) -> usize {
+        let mut cache = self.store(kind).write().await;
+        let before = cache.len();
+        cache.retain(|key, _| !kind.is_stock_key(key, stock_code));
+        let removed = before - cache.len();
+        self.evictions.fetch_add(removed as u64, Ordering::Relaxed);
+        removed
+    }
+
+    async fn purge_expired(&self) -> usize {
+        let mut removed = 0;
+        for store in &self.stores {
+            let mut cache = store.write().await;
+            let before = cache.len();
+            cache.retain(|_, entry| !entry.is_expired());
+            removed += before - cache.len();
+        }
+        self.evictions.fetch_add(removed as u64, Ordering::Relaxed);
+        removed
+    }
+
+    async fn len(&self) -> usize {
+        let mut total = 0;
+        for store in &self.stores {
+            total += store.read().await.len();
+        }
+        total
+    }
+
+    fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+}
+
+/// Redis store shared by every server instance pointing at the same database.
+/// Keys expire through Redis TTLs; `max_entries` is not enforced here, size the
+/// Redis `maxmemory` policy instead. Redis errors are logged and read as misses.
+#[cfg(feature = "redis")]
+pub struct RedisBackend {
+    conn: redis::aio::ConnectionManager,
+    evictions: AtomicU64,
+}
+
+#[cfg(feature = "redis")]
+impl RedisBackend {
+    const KEY_PREFIX: &'static str = "stock_cache";
+
+    pub async fn connect(url: &str) -> Result<Self, String> {
+        // The URL may carry the Redis password, so only the redacted form is reported
+        let display_url = redact_url_credentials(url);
+        let client = redis::Client::open(url)
+            .map_err(|e| format!("Invalid Redis URL {}: {}", display_url, e))?;
+        let conn = client
+            .get_connection_manager()
+            .await
+            .map_err(|e| format!("Failed to connect to Redis at {}: {}", display_url, e))?;
+        Ok(Self {
+            conn,
+            evictions: AtomicU64::new(0),
+        })
+    }
+
+    fn redis_key(key: &str) -> String {
+        format!("{}:{}", Self::KEY_PREFIX, key)
+    }
+
+    /// Escapes glob metacharacters so a stock code only matches itself in SCAN patterns
+    fn escape_pattern(value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+
+    async fn delete_keys(&self, keys: &[String]) -> usize {
+        use redis::AsyncCommands;
+
+        let mut conn = self.conn.clone();
+        let mut removed = 0;
+        for chunk in keys.chunks(500) {
+            match conn.del::<_, u64>(chunk).await {
+                Ok(count) => removed += count,
+                Err(e) => log::warn!("Redis cache delete failed: {}", e),
+            }
+        }
+        self.evictions.fetch_add(removed, Ordering::Relaxed);
+        removed as usize
+    }
+
+    async fn scan_keys(&self, pattern: &str) -> redis::RedisResult<Vec<String>> {
+        let mut conn = self.conn.clone();
+        let mut keys = Vec::new();
+        let mut cursor: u64 = 0;
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(pattern)
+                .arg("COUNT")
+                .arg(500)
+                .query_async(&mut conn)
+                .await?;
+            keys.extend(batch);
+            if next == 0 {
+                return Ok(keys);
+            }
+            cursor = next;
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait::async_trait]
+impl CacheBackend for RedisBackend {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn get(&self, _kind: CacheKind, key: &str) -> Option<CacheHit> {
+        use redis::AsyncCommands;
+
+        let mut conn = self.conn.clone();
+        match conn.get::<_, Option<String>>(Self::redis_key(key)).await {
+            Ok(value) => value.map(CacheHit::Json),
+            Err(e) => {
+                log::warn!("Redis cache read failed for {}: {}", key, e);
+                None
+            }
+        }
+    }
+
+    async fn set(&self, _kind: CacheKind, key: &str, value: CacheValue, ttl_seconds: i64) {
+        use redis::AsyncCommands;
+
+        let value = match value.to_json() {
+            Ok(value) => value,
+            Err(e) => {
+                log::warn!("Failed to serialize cache entry {}: {}", key, e);
+                return;
+            }
+        };
+        let mut conn = self.conn.clone();
+        if let Err(e) = conn
+            .set_ex::<_, _, ()>(Self::redis_key(key), value, ttl_seconds.max(1) as u64)
+            .await
+        {
+            log::warn!("Redis cache write failed for {}: {}", key, e);
+        }
+    }
+
+    async fn clear(&self) {
+        match self.scan_keys(&format!("{}:*", Self::KEY_PREFIX)).await {
+            Ok(keys) => {
+                self.delete_keys(&keys).await;
+            }
+            Err(e) => log::warn!("Redis cache clear failed: {}", e),
+        }
+    }
+
+    async fn remove_stock(&self, kind: CacheKind, stock_code: &str) -> usize {
+        let stock_key = Self::redis_key(&kind.key(stock_code, None));
+        let pattern = format!("{}:*", Self::escape_pattern(&stock_key));
+        match self.scan_keys(&pattern).await {
+            Ok(mut keys) => {
+                keys.push(stock_key);
+                self.delete_keys(&keys).await
+            }
+            Err(e) => {
+                log::warn!("Redis cache clear failed for {}: {}", stock_code, e);
+                0
+            }
+        }
+    }
+
+    async fn len(&self) -> usize {
+        match self.scan_keys(&format!("{}:*", Self::KEY_PREFIX)).await {
+            Ok(keys) => keys.len(),
+            Err(e) => {
+                log::warn!("Redis cache size lookup failed: {}", e);
+                0
+            }
         }
     }
+
+    fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
 }
 
 pub struct DataCache {
     config: CacheConfig,
-    price_cache: Arc<RwLock<HashMap<String, CacheEntry<Vec<PriceData>>>>>,
-    fundamental_cache: Arc<RwLock<HashMap<String, CacheEntry<FundamentalData>>>>,
-    news_cache: Arc<RwLock<HashMap<String, CacheEntry<(Vec<News>, SentimentAnalysis)>>>>,
-    name_cache: Arc<RwLock<HashMap<String, CacheEntry<String>>>>,
+    backend: Arc<dyn CacheBackend>,
     stats: Arc<RwLock<CacheStats>>,
+    #[allow(dead_code)]
     cleanup_task: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl DataCache {
+    /// In-memory cache
     pub fn new(config: CacheConfig) -> Self {
+        let backend = Arc::new(MemoryBackend::new(config.max_entries));
+        Self::with_backend(config, backend)
+    }
+
+    pub fn with_backend(config: CacheConfig, backend: Arc<dyn CacheBackend>) -> Self {
         let cache = Self {
             config: config.clone(),
-            price_cache: Arc::new(RwLock::new(HashMap::new())),
-            fundamental_cache: Arc::new(RwLock::new(HashMap::new())),
-            news_cache: Arc::new(RwLock::new(HashMap::new())),
-            name_cache: Arc::new(RwLock::new(HashMap::new())),
+            backend,
             stats: Arc::new(RwLock::new(CacheStats::default())),
             cleanup_task: None,
         };
@@ -128,12 +502,32 @@ impl DataCache {
         cache
     }
 
+    /// Builds the cache on the backend selected in the configuration
+    pub async fn from_config(config: CacheConfig) -> Result<Self, String> {
+        match config.backend {
+            CacheBackendKind::Memory => Ok(Self::new(config)),
+            #[cfg(feature = "redis")]
+            CacheBackendKind::Redis => {
+                let url = config
+                    .redis_url
+                    .clone()
+                    .ok_or_else(|| "Redis cache backend requires redis_url".to_string())?;
+                let backend = RedisBackend::connect(&url).await?;
+                log::info!(
+                    "Using Redis cache backend at {}",
+                    redact_url_credentials(&url)
+                );
+                Ok(Self::with_backend(config, Arc::new(backend)))
+            }
+            #[cfg(not(feature = "redis"))]
+            CacheBackendKind::Redis => {
+                Err("Redis cache backend requires building with the `redis` feature".to_string())
+            }
+        }
+    }
+
     fn start_cleanup_task(&self) {
-        let price_cache = self.price_cache.clone();
-        let fundamental_cache = self.fundamental_cache.clone();
-        let news_cache = self.news_cache.clone();
-        let name_cache = self.name_cache.clone();
-        let stats = self.stats.clone();
+        let backend = self.backend.clone();
         let interval = self.config.cleanup_interval;
 
         let _cleanup_task = tokio::spawn(async move {
@@ -143,49 +537,7 @@ impl DataCache {
             loop {
                 interval_timer.tick().await;
 
-                let mut evictions = 0;
-
-                // Clean price cache
-                {
-                    let mut cache = price_cache.write().await;
-                    let before = cache.len();
-                    cache.retain(|_, entry| !entry.is_expired());
-                    evictions += before - cache.len();
-                }
-
-                // Clean fundamental cache
-                {
-                    let mut cache = fundamental_cache.write().await;
-                    let before = cache.len();
-                    cache.retain(|_, entry| !entry.is_expired());
-                    evictions += before - cache.len();
-                }
-
-                // Clean news cache
-                {
-                    let mut cache = news_cache.write().await;
-                    let before = cache.len();
-                    cache.retain(|_, entry| !entry.is_expired());
-                    evictions += before - cache.len();
-                }
-
-                // Clean name cache
-                {
-                    let mut cache = name_cache.write().await;
-                    let before = cache.len();
-                    cache.retain(|_, entry| !entry.is_expired());
-                    evictions += before - cache.len();
-                }
-
-                // Update stats
-                if evictions > 0 {
-                    let mut stats_guard = stats.write().await;
-                    stats_guard.evictions += evictions as u64;
-                    stats_guard.total_entries = price_cache.read().await.len()
-                        + fundamental_cache.read().await.len()
-                        + news_cache.read().await.len()
-                        + name_cache.read().await.len();
-                }
+                let evictions = backend.purge_expired().await;
 
                 log::debug!("Cache cleanup completed, evicted {} entries", evictions);
             }
@@ -195,105 +547,126 @@ impl DataCache {
         // For now, we'll let it run in the background
     }
 
-    pub async fn get_price_data(&self, stock_code: &str, days: i32) -> Option<Vec<PriceData>> {
-        let key = self.generate_price_key(stock_code, days);
-        let mut cache = self.price_cache.write().await;
-
-        if let Some(entry) = cache.get_mut(&key) {
-            if !entry.is_expired() {
-                entry.record_access();
-                if self.config.enable_stats {
-                    let mut stats = self.stats.write().await;
-                    stats.price_hits += 1;
-                }
-                return Some(entry.data.clone());
-            } else {
-                // Remove expired entry
-                cache.remove(&key);
-                if self.config.enable_stats {
-                    let mut stats = self.stats.write().await;
-                    stats.evictions += 1;
-                    stats.total_entries = cache.len();
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    fn ttl(&self, kind: CacheKind) -> i64 {
+        match kind {
+            CacheKind::Price => self.config.price_data_ttl,
+            CacheKind::Fundamental => self.config.fundamental_data_ttl,
+            CacheKind::News => self.config.news_data_ttl,
+            CacheKind::Name => self.config.stock_name_ttl,
+        }
+    }
+
+    async fn lookup<T>(&self, kind: CacheKind, key: &str) -> Option<T>
+    where
+        T: DeserializeOwned + Clone + 'static,
+    {
+        let value = self.backend.get(kind, key).await.and_then(|hit| match hit {
+            CacheHit::Value(value) => {
+                let value = value.downcast_ref::<T>().cloned();
+                if value.is_none() {
+                    log::warn!("Discarding cache entry {} of an unexpected type", key);
                 }
+                value
             }
-        }
+            CacheHit::Json(raw) => match serde_json::from_str(&raw) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    log::warn!("Discarding unreadable cache entry {}: {}", key, e);
+                    None
+                }
+            },
+        });
 
         if self.config.enable_stats {
             let mut stats = self.stats.write().await;
-            stats.price_misses += 1;
+            let counter = match (kind, value.is_some()) {
+                (CacheKind::Price, true) => &mut stats.price_hits,
+                (CacheKind::Price, false) => &mut stats.price_misses,
+                (CacheKind::Fundamental, true) => &mut stats.fundamental_hits,
+                (CacheKind::Fundamental, false) => &mut stats.fundamental_misses,
+                (CacheKind::News, true) => &mut stats.news_hits,
+                (CacheKind::News, false) => &mut stats.news_misses,
+                (CacheKind::Name, true) => &mut stats.name_hits,
+                (CacheKind::Name, false) => &mut stats.name_misses,
+            };
+            *counter += 1;
         }
-        None
+
+        value
+    }
+
+    async fn store<T>(&self, kind: CacheKind, key: &str, value: T)
+    where
+        T: Serialize + Send + Sync + 'static,
+    {
+        let value = CacheValue::new(value);
+        self.backend.set(kind, key, value, self.ttl(kind)).await;
+    }
+
+    pub async fn get_price_data(&self, stock_code: &str, days: i32) -> Option<Vec<PriceData>> {
+        self.get_adjusted_price_data(stock_code, days, PriceAdjustment::None)
+            .await
     }
 
     pub async fn set_price_data(&self, stock_code: &str, days: i32, data: Vec<PriceData>) {
-        let key = self.generate_price_key(stock_code, days);
-        let mut cache = self.price_cache.write().await;
+        self.set_adjusted_price_data(stock_code, days, PriceAdjustment::None, data)
+            .await;
+    }
 
-        // Enforce max entries limit
-        if cache.len() >= self.config.max_entries {
-            self.evict_lru_price_cache(&mut cache).await;
-        }
+    pub async fn get_adjusted_price_data(
+        &self,
+        stock_code: &str,
+        days: i32,
+        adjust: PriceAdjustment,
+    ) -> Option<Vec<PriceData>> {
+        let key = self.generate_price_key(stock_code, days, adjust);
+        self.lookup(CacheKind::Price, &key).await
+    }
 
-        cache.insert(key, CacheEntry::new(data, self.config.price_data_ttl));
+    pub async fn set_adjusted_price_data(
+        &self,
+        stock_code: &str,
+        days: i32,
+        adjust: PriceAdjustment,
+        data: Vec<PriceData>,
+    ) {
+        let key = self.generate_price_key(stock_code, days, adjust);
+        self.store(CacheKind::Price, &key, data).await;
+    }
 
-        if self.config.enable_stats {
-            let mut stats = self.stats.write().await;
-            stats.total_entries = cache.len()
-                + self.fundamental_cache.read().await.len()
-                + self.news_cache.read().await.len()
-                + self.name_cache.read().await.len();
-        }
+    pub async fn get_price_range(
+        &self,
+        stock_code: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Option<Vec<PriceData>> {
+        let key = self.generate_price_range_key(stock_code, start, end);
+        self.lookup(CacheKind::Price, &key).await
+    }
+
+    pub async fn set_price_range(
+        &self,
+        stock_code: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+        data: Vec<PriceData>,
+    ) {
+        let key = self.generate_price_range_key(stock_code, start, end);
+        self.store(CacheKind::Price, &key, data).await;
     }
 
     pub async fn get_fundamental_data(&self, stock_code: &str) -> Option<FundamentalData> {
         let key = self.generate_fundamental_key(stock_code);
-        let mut cache = self.fundamental_cache.write().await;
-
-        if let Some(entry) = cache.get_mut(&key) {
-            if !entry.is_expired() {
-                entry.record_access();
-                if self.config.enable_stats {
-                    let mut stats = self.stats.write().await;
-                    stats.fundamental_hits += 1;
-                }
-                return Some(entry.data.clone());
-            } else {
-                cache.remove(&key);
-                if self.config.enable_stats {
-                    let mut stats = self.stats.write().await;
-                    stats.evictions += 1;
-                    stats.total_entries = self.price_cache.read().await.len()
-                        + cache.len()
-                        + self.news_cache.read().await.len()
-                        + self.name_cache.read().await.len();
-                }
-            }
-        }
-
-        if self.config.enable_stats {
-            let mut stats = self.stats.write().await;
-            stats.fundamental_misses += 1;
-        }
-        None
+        self.lookup(CacheKind::Fundamental, &key).await
     }
 
     pub async fn set_fundamental_data(&self, stock_code: &str, data: FundamentalData) {
         let key = self.generate_fundamental_key(stock_code);
-        let mut cache = self.fundamental_cache.write().await;
-
-        if cache.len() >= self.config.max_entries {
-            self.evict_lru_fundamental_cache(&mut cache).await;
-        }
-
-        cache.insert(key, CacheEntry::new(data, self.config.fundamental_data_ttl));
-
-        if self.config.enable_stats {
-            let mut stats = self.stats.write().await;
-            stats.total_entries = self.price_cache.read().await.len()
-                + cache.len()
-                + self.news_cache.read().await.len()
-                + self.name_cache.read().await.len();
-        }
+        self.store(CacheKind::Fundamental, &key, data).await;
     }
 
     pub async fn get_news_data(
@@ -302,34 +675,7 @@ impl DataCache {
         days: i32,
     ) -> Option<(Vec<News>, SentimentAnalysis)> {
         let key = self.generate_news_key(stock_code, days);
-        let mut cache = self.news_cache.write().await;
-
-        if let Some(entry) = cache.get_mut(&key) {
-            if !entry.is_expired() {
-                entry.record_access();
-                if self.config.enable_stats {
-                    let mut stats = self.stats.write().await;
-                    stats.news_hits += 1;
-                }
-                return Some(entry.data.clone());
-            } else {
-                cache.remove(&key);
-                if self.config.enable_stats {
-                    let mut stats = self.stats.write().await;
-                    stats.evictions += 1;
-                    stats.total_entries = self.price_cache.read().await.len()
-                        + self.fundamental_cache.read().await.len()
-                        + cache.len()
-                        + self.name_cache.read().await.len();
-                }
-            }
-        }
-
-        if self.config.enable_stats {
-            let mut stats = self.stats.write().await;
-            stats.news_misses += 1;
-        }
-        None
+        self.lookup(CacheKind::News, &key).await
     }
 
     pub async fn set_news_data(
@@ -339,139 +685,77 @@ impl DataCache {
         data: (Vec<News>, SentimentAnalysis),
     ) {
         let key = self.generate_news_key(stock_code, days);
-        let mut cache = self.news_cache.write().await;
-
-        if cache.len() >= self.config.max_entries {
-            self.evict_lru_news_cache(&mut cache).await;
-        }
-
-        cache.insert(key, CacheEntry::new(data, self.config.news_data_ttl));
-
-        if self.config.enable_stats {
-            let mut stats = self.stats.write().await;
-            stats.total_entries = self.price_cache.read().await.len()
-                + self.fundamental_cache.read().await.len()
-                + cache.len()
-                + self.name_cache.read().await.len();
-        }
+        self.store(CacheKind::News, &key, data).await;
     }
 
     pub async fn get_stock_name(&self, stock_code: &str) -> Option<String> {
         let key = self.generate_name_key(stock_code);
-        let mut cache = self.name_cache.write().await;
-
-        if let Some(entry) = cache.get_mut(&key) {
-            if !entry.is_expired() {
-                entry.record_access();
-                if self.config.enable_stats {
-                    let mut stats = self.stats.write().await;
-                    stats.name_hits += 1;
-                }
-                return Some(entry.data.clone());
-            } else {
-                cache.remove(&key);
-                if self.config.enable_stats {
-                    let mut stats = self.stats.write().await;
-                    stats.evictions += 1;
-                    stats.total_entries = self.price_cache.read().await.len()
-                        + self.fundamental_cache.read().await.len()
-                        + self.news_cache.read().await.len()
-                        + cache.len();
-                }
-            }
-        }
-
-        if self.config.enable_stats {
-            let mut stats = self.stats.write().await;
-            stats.name_misses += 1;
-        }
-        None
+        self.lookup(CacheKind::Name, &key).await
     }
 
     pub async fn set_stock_name(&self, stock_code: &str, name: String) {
         let key = self.generate_name_key(stock_code);
-        let mut cache = self.name_cache.write().await;
-
-        if cache.len() >= self.config.max_entries {
-            self.evict_lru_name_cache(&mut cache).await;
-        }
-
-        cache.insert(key, CacheEntry::new(name, self.config.stock_name_ttl));
-
-        if self.config.enable_stats {
-            let mut stats = self.stats.write().await;
-            stats.total_entries = self.price_cache.read().await.len()
-                + self.fundamental_cache.read().await.len()
-                + self.news_cache.read().await.len()
-                + cache.len();
-        }
+        self.store(CacheKind::Name, &key, name).await;
     }
 
     pub async fn get_stats(&self) -> CacheStats {
-        self.stats.read().await.clone()
+        let mut stats = self.stats.read().await.clone();
+        stats.evictions = self.backend.evictions();
+        stats.total_entries = self.backend.len().await;
+        stats
     }
 
     pub async fn clear(&self) {
-        self.price_cache.write().await.clear();
-        self.fundamental_cache.write().await.clear();
-        self.news_cache.write().await.clear();
-        self.name_cache.write().await.clear();
-
-        if self.config.enable_stats {
-            let mut stats = self.stats.write().await;
-            stats.evictions += stats.total_entries as u64;
-            stats.total_entries = 0;
-        }
+        self.backend.clear().await;
     }
 
-    async fn evict_lru_price_cache(&self, cache: &mut HashMap<String, CacheEntry<Vec<PriceData>>>) {
-        if let Some((lru_key, _)) = cache.iter().min_by_key(|(_, entry)| entry.last_accessed) {
-            let lru_key = lru_key.clone();
-            cache.remove(&lru_key);
+    /// Drops one stock's price, fundamental, news and name entries; returns the number removed
+    pub async fn clear_stock(&self, stock_code: &str) -> usize {
+        let mut removed = 0;
+        for kind in CacheKind::ALL {
+            removed += self.backend.remove_stock(kind, stock_code).await;
         }
+        removed
     }
 
-    async fn evict_lru_fundamental_cache(
-        &self,
-        cache: &mut HashMap<String, CacheEntry<FundamentalData>>,
-    ) {
-        if let Some((lru_key, _)) = cache.iter().min_by_key(|(_, entry)| entry.last_accessed) {
-            let lru_key = lru_key.clone();
-            cache.remove(&lru_key);
+    /// Adjusted series get an extra `:{adjust}` suffix; unadjusted keys are unchanged
+    fn generate_price_key(&self, stock_code: &str, days: i32, adjust: PriceAdjustment) -> String {
+        let key = CacheKind::Price.key(stock_code, Some(days));
+        match adjust {
+            PriceAdjustment::None => key,
+            _ => format!("{}:{}", key, adjust),
         }
     }
 
-    async fn evict_lru_news_cache(
+    /// `price:{stock_code}:{start}:{end}`, next to the trailing-window price keys
+    fn generate_price_range_key(
         &self,
-        cache: &mut HashMap<String, CacheEntry<(Vec<News>, SentimentAnalysis)>>,
-    ) {
-        if let Some((lru_key, _)) = cache.iter().min_by_key(|(_, entry)| entry.last_accessed) {
-            let lru_key = lru_key.clone();
-            cache.remove(&lru_key);
-        }
-    }
-
-    async fn evict_lru_name_cache(&self, cache: &mut HashMap<String, CacheEntry<String>>) {
-        if let Some((lru_key, _)) = cache.iter().min_by_key(|(_, entry)| entry.last_accessed) {
-            let lru_key = lru_key.clone();
-            cache.remove(&lru_key);
-        }
-    }
-
-    fn generate_price_key(&self, stock_code: &str, days: i32) -> String {
-        format!("price_{}_{}", stock_code, days)
+        stock_code: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> String {
+        format!(
+            "{}:{}:{}",
+            CacheKind::Price.key(stock_code, None),
+            start,
+            end
+        )
     }
 
     fn generate_fundamental_key(&self, stock_code: &str) -> String {
-        format!("fundamental_{}", stock_code)
+        CacheKind::Fundamental.key(stock_code, None)
     }
 
     fn generate_news_key(&self, stock_code: &str, days: i32) -> String {
-        format!("news_{}_{}", stock_code, days)
+        CacheKind::News.key(stock_code, Some(days))
     }
 
     fn generate_name_key(&self, stock_code: &str) -> String {
-        format!("name_{}", stock_code)
+        CacheKind::Name.key(stock_code, None)
+    }
+
+    pub fn coalesce_requests(&self) -> bool {
+        self.config.coalesce_requests
     }
 }
 
@@ -482,12 +766,76 @@ impl Drop for DataCache {
     }
 }
 
+type SharedFetch<T> = Shared<BoxFuture<'static, Result<T, String>>>;
+
+/// Coalesces concurrent identical upstream requests: the first caller for a key
+/// starts the fetch, later callers await the same in-flight result.
+pub struct SingleFlight<T: Clone> {
+    enabled: bool,
+    in_flight: Mutex<HashMap<String, SharedFetch<T>>>,
+}
+
+impl<T: Clone + Send + Sync + 'static> SingleFlight<T> {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn run<F>(&self, key: String, fetch: F) -> Result<T, String>
+    where
+        F: Future<Output = Result<T, String>> + Send + 'static,
+    {
+        if !self.enabled {
+            return fetch.await;
+        }
+
+        let shared = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(&key) {
+                Some(existing) => {
+                    log::debug!("Joining in-flight upstream request: {}", key);
+                    existing.clone()
+                }
+                None => {
+                    let shared = fetch.boxed().shared();
+                    in_flight.insert(key.clone(), shared.clone());
+                    shared
+                }
+            }
+        };
+
+        let result = shared.clone().await;
+
+        // Whichever caller finishes first clears the entry, so the next miss
+        // starts a new fetch instead of replaying a finished one
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight
+            .get(&key)
+            .is_some_and(|current| current.ptr_eq(&shared))
+        {
+            in_flight.remove(&key);
+        }
+
+        result
+    }
+
+    #[allow(dead_code)]
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight.lock().unwrap().len()
+    }
+}
+
 #[async_trait::async_trait]
 pub trait CachedDataFetcher: DataFetcher + Send + Sync {}
 
 pub struct CachedDataFetcherWrapper<T: CachedDataFetcher> {
     inner: Arc<T>,
     cache: Arc<DataCache>,
+    price_flights: Arc<SingleFlight<Vec<PriceData>>>,
+    fundamental_flights: Arc<SingleFlight<FundamentalData>>,
+    news_flights: Arc<SingleFlight<(Vec<News>, SentimentAnalysis)>>,
 }
 
 impl<T: CachedDataFetcher> Clone for CachedDataFetcherWrapper<T> {
@@ -495,15 +843,22 @@ impl<T: CachedDataFetcher> Clone for CachedDataFetcherWrapper<T> {
         Self {
             inner: self.inner.clone(),
             cache: self.cache.clone(),
+            price_flights: self.price_flights.clone(),
+            fundamental_flights: self.fundamental_flights.clone(),
+            news_flights: self.news_flights.clone(),
         }
     }
 }
 
 impl<T: CachedDataFetcher> CachedDataFetcherWrapper<T> {
     pub fn new(inner: T, cache: Arc<DataCache>) -> Self {
+        let coalesce = cache.coalesce_requests();
         Self {
             inner: Arc::new(inner),
             cache,
+            price_flights: Arc::new(SingleFlight::new(coalesce)),
+            fundamental_flights: Arc::new(SingleFlight::new(coalesce)),
+            news_flights: Arc::new(SingleFlight::new(coalesce)),
         }
     }
 }
@@ -522,15 +877,119 @@ impl<T: CachedDataFetcher + 'static> DataFetcher for CachedDataFetcherWrapper<T>
             stock_code
         );
 
-        // Fetch from source
-        let data = self.inner.get_stock_data(stock_code, days).await?;
+        // Fetch from source, sharing the request with concurrent identical misses
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        let code = stock_code.to_string();
+        self.price_flights
+            .run(format!("price_{}_{}", stock_code, days), async move {
+                let data = inner.get_stock_data(&code, days).await?;
+                cache.set_price_data(&code, days, data.clone()).await;
+                Ok(data)
+            })
+            .await
+    }
 
-        // Cache the result
-        self.cache
-            .set_price_data(stock_code, days, data.clone())
-            .await;
+    async fn get_stock_data_range(
+        &self,
+        stock_code: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<PriceData>, String> {
+        if let Some(cached_data) = self.cache.get_price_range(stock_code, start, end).await {
+            log::debug!(
+                "Cache hit for price range {}..{}: {}",
+                start,
+                end,
+                stock_code
+            );
+            return Ok(cached_data);
+        }
 
-        Ok(data)
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        let code = stock_code.to_string();
+        self.price_flights
+            .run(
+                format!("price_range_{}_{}_{}", stock_code, start, end),
+                async move {
+                    let data = inner.get_stock_data_range(&code, start, end).await?;
+                    cache.set_price_range(&code, start, end, data.clone()).await;
+                    Ok(data)
+                },
+            )
+            .await
+    }
+
+    async fn get_stock_data_adjusted(
+        &self,
+        stock_code: &str,
+        days: i32,
+        adjust: PriceAdjustment,
+    ) -> Result<Vec<PriceData>, String> {
+        if adjust == PriceAdjustment::None {
+            return self.get_stock_data(stock_code, days).await;
+        }
+
+        if let Some(cached_data) = self
+            .cache
+            .get_adjusted_price_data(stock_code, days, adjust)
+            .await
+        {
+            log::debug!("Cache hit for {} price data: {}", adjust, stock_code);
+            return Ok(cached_data);
+        }
+
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        let code = stock_code.to_string();
+        self.price_flights
+            .run(
+                format!("price_{}_{}_{}", stock_code, days, adjust),
+                async move {
+                    let data = inner.get_stock_data_adjusted(&code, days, adjust).await?;
+                    cache
+                        .set_adjusted_price_data(&code, days, adjust, data.clone())
+                        .await;
+                    Ok(data)
+                },
+            )
+            .await
+    }
+
+    async fn get_stock_data_batch(
+        &self,
+        stock_codes: &[&str],
+        days: i32,
+    ) -> HashMap<String, Result<Vec<PriceData>, String>> {
+        let mut results = HashMap::new();
+        let mut misses = Vec::new();
+
+        for code in stock_codes {
+            match self.cache.get_price_data(code, days).await {
+                Some(cached_data) => {
+                    results.insert(code.to_string(), Ok(cached_data));
+                }
+                None => misses.push(*code),
+            }
+        }
+
+        log::debug!(
+            "Batch price lookup: {} cache hits, {} misses",
+            results.len(),
+            misses.len()
+        );
+
+        if !misses.is_empty() {
+            for (code, result) in self.inner.get_stock_data_batch(&misses, days).await {
+                if let Ok(data) = &result {
+                    self.cache.set_price_data(&code, days, data.clone()).await;
+                }
+                results.insert(code, result);
+            }
+        }
+
+        results
     }
 
     async fn get_fundamental_data(&self, stock_code: &str) -> Result<FundamentalData, String> {
@@ -545,15 +1004,17 @@ impl<T: CachedDataFetcher + 'static> DataFetcher for CachedDataFetcherWrapper<T>
             stock_code
         );
 
-        // Fetch from source
-        let data = self.inner.get_fundamental_data(stock_code).await?;
-
-        // Cache the result
-        self.cache
-            .set_fundamental_data(stock_code, data.clone())
-            .await;
-
-        Ok(data)
+        // Fetch from source, sharing the request with concurrent identical misses
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        let code = stock_code.to_string();
+        self.fundamental_flights
+            .run(format!("fundamental_{}", stock_code), async move {
+                let data = inner.get_fundamental_data(&code).await?;
+                cache.set_fundamental_data(&code, data.clone()).await;
+                Ok(data)
+            })
+            .await
     }
 
     async fn get_news_data(
@@ -572,15 +1033,17 @@ impl<T: CachedDataFetcher + 'static> DataFetcher for CachedDataFetcherWrapper<T>
             stock_code
         );
 
-        // Fetch from source
-        let data = self.inner.get_news_data(stock_code, days).await?;
-
-        // Cache the result
-        self.cache
-            .set_news_data(stock_code, days, data.clone())
-            .await;
-
-        Ok(data)
+        // Fetch from source, sharing the request with concurrent identical misses
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        let code = stock_code.to_string();
+        self.news_flights
+            .run(format!("news_{}_{}", stock_code, days), async move {
+                let data = inner.get_news_data(&code, days).await?;
+                cache.set_news_data(&code, days, data.clone()).await;
+                Ok(data)
+            })
+            .await
     }
 
     async fn get_stock_name(&self, stock_code: &str) -> String {
@@ -604,10 +1067,224 @@ impl<T: CachedDataFetcher + 'static> DataFetcher for CachedDataFetcherWrapper<T>
         name
     }
 
-    fn clone(&self) -> Box<dyn DataFetcher> {
-        Box::new(CachedDataFetcherWrapper {
-            inner: self.inner.clone(),
-            cache: self.cache.clone(),
-        })
+    async fn get_index_data(&self, index_code: &str, days: i32) -> Result<Vec<PriceData>, String> {
+        // Index codes never collide with stock codes, so they share the price cache
+        if let Some(cached_data) = self.cache.get_price_data(index_code, days).await {
+            log::debug!("Cache hit for index data: {}", index_code);
+            return Ok(cached_data);
+        }
+
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        let code = index_code.to_string();
+        self.price_flights
+            .run(format!("index_{}_{}", index_code, days), async move {
+                let data = inner.get_index_data(&code, days).await?;
+                cache.set_price_data(&code, days, data.clone()).await;
+                Ok(data)
+            })
+            .await
+    }
+
+    async fn get_stock_data_fresh(
+        &self,
+        stock_code: &str,
+        days: i32,
+    ) -> Result<Vec<PriceData>, String> {
+        // Bypass the cached copy but keep the cache warm for regular lookups;
+        // concurrent live streams for the same stock still share one request
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        let code = stock_code.to_string();
+        self.price_flights
+            .run(format!("fresh_{}_{}", stock_code, days), async move {
+                let data = inner.get_stock_data_fresh(&code, days).await?;
+                cache.set_price_data(&code, days, data.clone()).await;
+                Ok(data)
+            })
+            .await
+    }
+
+    async fn get_intraday_data(
+        &self,
+        stock_code: &str,
+        interval: &str,
+    ) -> Result<Vec<PriceData>, String> {
+        // The latest bar changes every minute, so intraday data bypasses the cache;
+        // concurrent requests for the same series still share one upstream call
+        let inner = self.inner.clone();
+        let code = stock_code.to_string();
+        let interval = interval.to_string();
+        self.price_flights
+            .run(
+                format!("intraday_{}_{}", stock_code, interval),
+                async move { inner.get_intraday_data(&code, &interval).await },
+            )
+            .await
+    }
+
+    async fn get_extended_hours_quote(
+        &self,
+        stock_code: &str,
+    ) -> Result<Option<ExtendedHoursQuote>, String> {
+        // Extended-hours quotes move continuously, so they bypass the cache
+        self.inner.get_extended_hours_quote(stock_code).await
+    }
+
+    async fn health_check(&self) -> Result<(), String> {
+        self.inner.health_check().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_single_flight_coalesces_concurrent_requests() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let flights = SingleFlight::<u32>::new(true);
+
+        let fetch = |calls: Arc<AtomicUsize>| async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+            Ok(42)
+        };
+
+        let (a, b, c) = tokio::join!(
+            flights.run("price_000001_60".to_string(), fetch(calls.clone())),
+            flights.run("price_000001_60".to_string(), fetch(calls.clone())),
+            flights.run("price_600000_60".to_string(), fetch(calls.clone())),
+        );
+        assert_eq!((a, b, c), (Ok(42), Ok(42), Ok(42)));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(flights.in_flight_count(), 0);
+
+        // A finished request is not replayed to later callers
+        flights
+            .run("price_000001_60".to_string(), fetch(calls.clone()))
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let disabled = SingleFlight::<u32>::new(false);
+        let _ = tokio::join!(
+            disabled.run("k".to_string(), fetch(calls.clone())),
+            disabled.run("k".to_string(), fetch(calls.clone())),
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_memory_backend_keeps_typed_values() {
+        let backend = MemoryBackend::new(10);
+        let value = CacheValue::new(vec![1.5_f64, 2.5]);
+        assert_eq!(value.to_json().unwrap(), "[1.5,2.5]");
+        backend
+            .set(CacheKind::Price, "price:000001:60", value, 60)
+            .await;
+
+        match backend.get(CacheKind::Price, "price:000001:60").await {
+            Some(CacheHit::Value(value)) => {
+                assert_eq!(value.downcast_ref::<Vec<f64>>(), Some(&vec![1.5, 2.5]));
+            }
+            _ => panic!("memory backend should return the stored value"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_backend_round_trips_values_and_tracks_stats() {
+        let cache = DataCache::new(CacheConfig {
+            max_entries: 2,
+            cleanup_interval: 0,
+            ..CacheConfig::default()
+        });
+
+        assert_eq!(cache.get_stock_name("000001").await, None);
+        cache.set_stock_name("000001", "平安银行".to_string()).await;
+        cache.set_stock_name("600000", "浦发银行".to_string()).await;
+        assert_eq!(
+            cache.get_stock_name("000001").await.as_deref(),
+            Some("平安银行")
+        );
+
+        // A third name evicts the least recently used one (600000)
+        cache.set_stock_name("000002", "万科A".to_string()).await;
+        assert_eq!(cache.get_stock_name("600000").await, None);
+
+        let stats = cache.get_stats().await;
+        assert_eq!((stats.name_hits, stats.name_misses), (1, 2));
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.total_entries, 2);
+
+        cache.clear().await;
+        let stats = cache.get_stats().await;
+        assert_eq!(stats.total_entries, 0);
+        assert_eq!(stats.evictions, 3);
+    }
+
+    #[tokio::test]
+    async fn test_clear_stock_only_removes_that_stock() {
+        let cache = DataCache::new(CacheConfig {
+            cleanup_interval: 0,
+            ..CacheConfig::default()
+        });
+
+        for code in ["000001", "0000011", "600000"] {
+            cache.set_price_data(code, 60, Vec::new()).await;
+            cache.set_price_data(code, 120, Vec::new()).await;
+            cache.set_stock_name(code, format!("name {}", code)).await;
+        }
+
+        assert_eq!(cache.clear_stock("000001").await, 3);
+        assert!(cache.get_price_data("000001", 60).await.is_none());
+        assert!(cache.get_stock_name("000001").await.is_none());
+        // A code sharing the prefix keeps its entries
+        assert!(cache.get_price_data("0000011", 120).await.is_some());
+        assert!(cache.get_stock_name("600000").await.is_some());
+        assert_eq!(cache.get_stats().await.total_entries, 6);
+        assert_eq!(cache.clear_stock("000001").await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_adjusted_prices_are_cached_separately() {
+        let cache = DataCache::new(CacheConfig {
+            cleanup_interval: 0,
+            ..CacheConfig::default()
+        });
+        let bar = |close: f64| PriceData {
+            date: Utc::now(),
+            open: close,
+            close,
+            high: close,
+            low: close,
+            volume: 0,
+            change_pct: 0.0,
+            turnover: 0.0,
+            turnover_rt: 0.0,
+        };
+
+        cache.set_price_data("000001", 60, vec![bar(10.0)]).await;
+        assert!(cache
+            .get_adjusted_price_data("000001", 60, PriceAdjustment::Qfq)
+            .await
+            .is_none());
+
+        cache
+            .set_adjusted_price_data("000001", 60, PriceAdjustment::Qfq, vec![bar(8.5)])
+            .await;
+        let qfq = cache
+            .get_adjusted_price_data("000001", 60, PriceAdjustment::Qfq)
+            .await
+            .unwrap();
+        assert_eq!(qfq[0].close, 8.5);
+        assert_eq!(
+            cache.get_price_data("000001", 60).await.unwrap()[0].close,
+            10.0
+        );
+
+        // Clearing a stock drops its adjusted series too
+        assert_eq!(cache.clear_stock("000001").await, 2);
     }
 }
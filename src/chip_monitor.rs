@@ -1,7 +1,3 @@
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-
 use crate::models::*;
 
 /// 主力筹码监控模块
@@ -9,7 +5,9 @@ pub struct ChipMonitor {
     // 配置参数
     pub concentration_threshold: f64,    // 集中度阈值
     pub inflow_threshold: f64,          // 流入阈值
+    #[allow(dead_code)]
     pub volume_ratio_threshold: f64,    // 成交量比率阈值
+    #[allow(dead_code)]
     pub price_range_count: i32,        // 价格区间数量
 }
 
@@ -27,7 +25,7 @@ impl ChipMonitor {
     /// 分析筹码分布
     pub async fn analyze_chip_distribution(
         &self,
-        stock_code: &str,
+        _stock_code: &str,
         price_data: &[PriceData],
     ) -> Result<ChipDistribution, Box<dyn std::error::Error>> {
         if price_data.is_empty() {
@@ -37,8 +35,6 @@ impl ChipMonitor {
         // 计算价格区间
         let min_price = price_data.iter().map(|p| p.close).fold(f64::INFINITY, f64::min);
         let max_price = price_data.iter().map(|p| p.close).fold(f64::NEG_INFINITY, f64::max);
-        let price_range = max_price - min_price;
-        let range_size = price_range / self.price_range_count as f64;
 
         // 分析每个价格区间的筹码分布
         let mut distribution = ChipDistribution {
@@ -59,7 +55,7 @@ impl ChipMonitor {
     /// 分析资金流向
     pub async fn analyze_capital_flow(
         &self,
-        stock_code: &str,
+        _stock_code: &str,
         price_data: &[PriceData],
     ) -> Result<CapitalFlow, Box<dyn std::error::Error>> {
         if price_data.len() < 2 {
@@ -276,7 +272,6 @@ impl ChipMonitor {
     /// 生成筹码信号
     fn generate_chip_signal(&self, capital_flow: &CapitalFlow, concentration_degree: f64) -> String {
         let net_inflow = capital_flow.net_inflow;
-        let concentration = capital_flow.concentration_index;
         
         match (net_inflow, concentration_degree) {
             (inflow, _) if inflow > self.inflow_threshold => "主力建仓".to_string(),
@@ -293,8 +288,7 @@ impl ChipMonitor {
         }
         
         let prices: Vec<f64> = price_data.iter().map(|p| p.close).collect();
-        let current_price = prices.last().unwrap();
-        
+
         // 简化的支撑阻力位计算
         let support = prices.iter().fold(f64::INFINITY, |a, &b| a.min(b));
         let resistance = prices.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
@@ -356,8 +350,8 @@ mod tests {
 
     #[test]
     fn test_analyze_chip_distribution() {
-        let monitor = ChipMonitor::new();
-        let price_data = create_test_price_data();
+        let _monitor = ChipMonitor::new();
+        let _price_data = create_test_price_data();
         
         // 注意：这是一个异步测试，在实际运行时需要使用异步测试运行器
         // let result = futures::executor::block_on(monitor.analyze_chip_distribution("000001", &price_data));
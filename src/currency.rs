@@ -1,4 +1,4 @@
-use chrono::{DateTime, Timelike, Utc};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -15,12 +15,23 @@ pub struct ExchangeRate {
     pub source: String,
 }
 
+/// 默认的实时汇率来源，`{base}` 会被替换为报价基准货币
+pub const DEFAULT_RATE_SOURCE: &str = "https://open.er-api.com/v6/latest/{base}";
+
+/// 汇率请求超时，超时后回退到缓存或交叉汇率
+const RATE_FETCH_TIMEOUT_SECS: u64 = 5;
+
 #[derive(Debug, Clone)]
 pub struct CurrencyConverter {
+    /// 以基准货币计价的汇率表：1 单位基准货币可兑换的各货币数量
     rates: Arc<RwLock<HashMap<String, f64>>>,
+    /// 按货币对缓存的汇率，过期后仍保留作为获取失败时的回退值
+    pair_rates: Arc<RwLock<HashMap<(String, String), ExchangeRate>>>,
     last_updated: Arc<RwLock<DateTime<Utc>>>,
     base_currency: String,
     cache_ttl_seconds: i64,
+    client: reqwest::Client,
+    rate_source: Option<String>,
 }
 
 impl CurrencyConverter {
@@ -28,50 +39,179 @@ impl CurrencyConverter {
         let mut rates = HashMap::new();
         rates.insert(base_currency.clone(), 1.0); // Base currency to itself is 1.0
 
-        // Initialize with some common exchange rates (in a real app, these would come from an API)
-        rates.insert("CNY".to_string(), 0.14); // USD to CNY
-        rates.insert("HKD".to_string(), 0.13); // USD to HKD
-        rates.insert("EUR".to_string(), 1.08); // USD to EUR
-        rates.insert("GBP".to_string(), 1.27); // USD to GBP
-        rates.insert("JPY".to_string(), 0.0064); // USD to JPY
+        // Approximate USD quotes used until live rates are fetched
+        rates.insert("CNY".to_string(), 7.2); // 1 USD = 7.2 CNY
+        rates.insert("HKD".to_string(), 7.8); // 1 USD = 7.8 HKD
+        rates.insert("EUR".to_string(), 0.92); // 1 USD = 0.92 EUR
+        rates.insert("GBP".to_string(), 0.79); // 1 USD = 0.79 GBP
+        rates.insert("JPY".to_string(), 156.0); // 1 USD = 156 JPY
 
         Self {
             rates: Arc::new(RwLock::new(rates)),
+            pair_rates: Arc::new(RwLock::new(HashMap::new())),
             last_updated: Arc::new(RwLock::new(Utc::now())),
             base_currency,
             cache_ttl_seconds,
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(RATE_FETCH_TIMEOUT_SECS))
+                .build()
+                .unwrap_or_default(),
+            rate_source: None,
         }
     }
 
+    /// 设置实时汇率接口地址（含 `{base}` 占位符）；为 None 时只使用内置汇率
+    pub fn with_rate_source(mut self, rate_source: Option<String>) -> Self {
+        self.rate_source = rate_source;
+        self
+    }
+
+    /// 依次尝试：未过期的货币对缓存、实时接口的直接汇率、过期的货币对缓存、经基准货币的交叉汇率
     pub async fn get_exchange_rate(
         &self,
         from_currency: &str,
         to_currency: &str,
     ) -> Result<f64, String> {
+        let from_currency = from_currency.trim().to_uppercase();
+        let to_currency = to_currency.trim().to_uppercase();
         if from_currency == to_currency {
             return Ok(1.0);
         }
 
-        let rates = self.rates.read().await;
+        let pair = (from_currency.clone(), to_currency.clone());
+        if let Some(cached) = self.pair_rates.read().await.get(&pair) {
+            if !self.is_expired(cached.timestamp) {
+                return Ok(cached.rate);
+            }
+        }
+
+        if self.rate_source.is_some() {
+            match self.refresh_quotes(&from_currency).await {
+                Ok(quotes) => {
+                    if let Some(&rate) = quotes.get(&to_currency) {
+                        return Ok(rate);
+                    }
+                }
+                Err(e) => log::warn!("Failed to fetch {} exchange rates: {}", from_currency, e),
+            }
+
+            if let Some(cached) = self.pair_rates.read().await.get(&pair) {
+                log::warn!(
+                    "Using cached {}/{} rate from {}",
+                    from_currency,
+                    to_currency,
+                    cached.timestamp
+                );
+                return Ok(cached.rate);
+            }
+        }
 
-        // If both currencies are in our cache
-        if let (Some(&from_rate), Some(&to_rate)) =
-            (rates.get(from_currency), rates.get(to_currency))
+        self.cross_rate(&from_currency, &to_currency).await
+    }
+
+    /// 通过基准货币计算交叉汇率：from → base → to
+    async fn cross_rate(&self, from_currency: &str, to_currency: &str) -> Result<f64, String> {
+        if self.rate_source.is_some()
+            && from_currency != self.base_currency
+            && self.is_cache_expired().await
         {
-            return Ok(to_rate / from_rate);
+            if let Err(e) = self.refresh_quotes(&self.base_currency).await {
+                log::warn!("Failed to refresh base exchange rates: {}", e);
+            }
+        }
+
+        let rates = self.rates.read().await;
+        match (rates.get(from_currency), rates.get(to_currency)) {
+            (Some(&from_rate), Some(&to_rate)) if from_rate > 0.0 => Ok(to_rate / from_rate),
+            _ => Err(format!(
+                "Exchange rate not found for {} to {}",
+                from_currency, to_currency
+            )),
         }
+    }
+
+    /// 拉取以 `quote_base` 计价的实时汇率并写入货币对缓存；基准货币的报价同时刷新汇率表
+    async fn refresh_quotes(&self, quote_base: &str) -> Result<HashMap<String, f64>, String> {
+        let quotes = self.fetch_quotes(quote_base).await?;
+        let now = Utc::now();
 
-        // If we have the inverse rate
-        if let (Some(&to_rate), Some(&from_rate)) =
-            (rates.get(to_currency), rates.get(from_currency))
         {
-            return Ok(from_rate / to_rate);
+            let mut pair_rates = self.pair_rates.write().await;
+            for (currency, &rate) in &quotes {
+                pair_rates.insert(
+                    (quote_base.to_string(), currency.clone()),
+                    ExchangeRate {
+                        from_currency: quote_base.to_string(),
+                        to_currency: currency.clone(),
+                        rate,
+                        timestamp: now,
+                        source: "live".to_string(),
+                    },
+                );
+            }
+        }
+
+        if quote_base == self.base_currency {
+            let mut rates = quotes.clone();
+            rates.insert(self.base_currency.clone(), 1.0);
+            self.update_rates(rates).await?;
+        }
+
+        Ok(quotes)
+    }
+
+    async fn fetch_quotes(&self, quote_base: &str) -> Result<HashMap<String, f64>, String> {
+        let source = self
+            .rate_source
+            .as_ref()
+            .ok_or_else(|| "Exchange rate source is not configured".to_string())?;
+        let url = source.replace("{base}", quote_base);
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid response: {}", e))?;
+        if body.get("result").and_then(|v| v.as_str()) == Some("error") {
+            let reason = body
+                .get("error-type")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown error");
+            return Err(format!("Rate source error: {}", reason));
+        }
+
+        let quotes: HashMap<String, f64> = body
+            .get("rates")
+            .and_then(|v| v.as_object())
+            .map(|rates| {
+                rates
+                    .iter()
+                    .filter_map(|(currency, rate)| {
+                        rate.as_f64()
+                            .filter(|rate| *rate > 0.0)
+                            .map(|rate| (currency.to_uppercase(), rate))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        if quotes.is_empty() {
+            return Err("Response contains no rates".to_string());
         }
 
-        Err(format!(
-            "Exchange rate not found for {} to {}",
-            from_currency, to_currency
-        ))
+        Ok(quotes)
+    }
+
+    fn is_expired(&self, timestamp: DateTime<Utc>) -> bool {
+        Utc::now().signed_duration_since(timestamp).num_seconds() > self.cache_ttl_seconds
     }
 
     pub async fn convert_amount(
@@ -84,16 +224,19 @@ impl CurrencyConverter {
         Ok(amount * rate)
     }
 
+    #[allow(dead_code)]
     pub async fn convert_to_base(&self, amount: f64, currency: &str) -> Result<f64, String> {
         self.convert_amount(amount, currency, &self.base_currency)
             .await
     }
 
+    #[allow(dead_code)]
     pub async fn convert_from_base(&self, amount: f64, currency: &str) -> Result<f64, String> {
         self.convert_amount(amount, &self.base_currency, currency)
             .await
     }
 
+    #[allow(dead_code)]
     pub async fn convert_between_markets(
         &self,
         amount: f64,
@@ -116,20 +259,18 @@ impl CurrencyConverter {
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub async fn get_all_rates(&self) -> HashMap<String, f64> {
         self.rates.read().await.clone()
     }
 
+    #[allow(dead_code)]
     pub async fn get_last_updated(&self) -> DateTime<Utc> {
         *self.last_updated.read().await
     }
 
     pub async fn is_cache_expired(&self) -> bool {
-        let last_updated = self.last_updated.read().await;
-        Utc::now()
-            .signed_duration_since(*last_updated)
-            .num_seconds()
-            > self.cache_ttl_seconds
+        self.is_expired(*self.last_updated.read().await)
     }
 
     pub async fn get_supported_currencies(&self) -> Vec<String> {
@@ -137,6 +278,7 @@ impl CurrencyConverter {
         rates.keys().cloned().collect()
     }
 
+    #[allow(dead_code)]
     pub async fn format_currency(&self, amount: f64, currency: &str) -> String {
         let symbol = match currency {
             "USD" => "$",
@@ -151,6 +293,7 @@ impl CurrencyConverter {
         format!("{}{:.2}", symbol, amount)
     }
 
+    #[allow(dead_code)]
     pub async fn get_currency_info(&self, currency: &str) -> CurrencyInfo {
         match currency {
             "USD" => CurrencyInfo {
@@ -207,6 +350,7 @@ impl CurrencyConverter {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(dead_code)]
 pub struct CurrencyInfo {
     pub name: String,
     pub symbol: String,
@@ -228,95 +372,61 @@ pub struct MarketTimeInfo {
 }
 
 impl MarketTimeInfo {
+    /// 以交易所当地时间计算开闭市状态与时段，午间休市视为闭市
     pub fn new(market: Market, current_time: DateTime<Utc>) -> Self {
-        let is_trading_day = market.is_trading_day(current_time.date_naive());
-        let is_open = if is_trading_day {
-            market.is_market_open(current_time)
-        } else {
-            false
+        let timezone = market.timezone();
+        let market_time = current_time.with_timezone(&timezone);
+        let today = market_time.date_naive();
+        let now = market_time.time();
+
+        let is_trading_day = market.is_trading_day(today);
+        let is_open = market.is_market_open(current_time);
+
+        // "24:00" 表示交易至当日结束（7x24 市场）
+        let parse = |hhmm: &str| match hhmm {
+            "24:00" => NaiveTime::from_hms_nano_opt(23, 59, 59, 999_999_999),
+            _ => NaiveTime::parse_from_str(hhmm, "%H:%M").ok(),
         };
-
-        let sessions = market.get_trading_sessions();
-        let current_session = if is_open {
-            sessions
-                .iter()
-                .find(|(open, close)| {
-                    let current_hour = current_time.hour();
-                    let current_min = current_time.minute();
-                    let current_time_minutes = current_hour * 60 + current_min;
-
-                    let open_hour = open[..2].parse::<u32>().unwrap_or(9);
-                    let open_min = open[3..].parse::<u32>().unwrap_or(30);
-                    let close_hour = close[..2].parse::<u32>().unwrap_or(16);
-                    let close_min = close[3..].parse::<u32>().unwrap_or(0);
-
-                    let open_minutes = open_hour * 60 + open_min;
-                    let close_minutes = close_hour * 60 + close_min;
-
-                    current_time_minutes >= open_minutes && current_time_minutes <= close_minutes
-                })
-                .map(|(open, close)| format!("{}-{}", open, close))
-        } else {
-            None
+        let sessions: Vec<(NaiveTime, NaiveTime)> = market
+            .get_trading_sessions()
+            .iter()
+            .filter_map(|(open, close)| Some((parse(open)?, parse(close)?)))
+            .collect();
+        let to_utc = |date: NaiveDate, time: NaiveTime| {
+            timezone
+                .from_local_datetime(&date.and_time(time))
+                .earliest()
+                .map(|dt| dt.with_timezone(&Utc))
         };
 
-        // Calculate next session times
-        let (next_open, next_close) = if is_open {
-            // Current session times - calculate when it closes
-            if let Some((_, close)) = sessions.iter().find(|(open, close)| {
-                let current_hour = current_time.hour();
-                let current_min = current_time.minute();
-                let current_time_minutes = current_hour * 60 + current_min;
-
-                let open_hour = open[..2].parse::<u32>().unwrap_or(9);
-                let open_min = open[3..].parse::<u32>().unwrap_or(30);
-                let close_hour = close[..2].parse::<u32>().unwrap_or(16);
-                let close_min = close[3..].parse::<u32>().unwrap_or(0);
-
-                let open_minutes = open_hour * 60 + open_min;
-                let close_minutes = close_hour * 60 + close_min;
-
-                current_time_minutes >= open_minutes && current_time_minutes <= close_minutes
-            }) {
-                // Current session close time
-                let close_hour = close[..2].parse::<u32>().unwrap_or(16);
-                let close_min = close[3..].parse::<u32>().unwrap_or(0);
-                let close_time = current_time
-                    .date_naive()
-                    .and_hms_opt(close_hour, close_min, 0)
-                    .map(|dt| dt.and_utc());
-                (None, close_time)
-            } else {
-                (None, None)
-            }
-        } else {
-            // Next trading day session times
-            let next_trading_day = market.get_next_trading_day(current_time.date_naive());
-
-            if let Some((open, close)) = sessions.first() {
-                let open_hour = open[..2].parse::<u32>().unwrap_or(9);
-                let open_min = open[3..].parse::<u32>().unwrap_or(30);
-                let close_hour = close[..2].parse::<u32>().unwrap_or(16);
-                let close_min = close[3..].parse::<u32>().unwrap_or(0);
-
-                let next_open = next_trading_day
-                    .and_hms_opt(open_hour, open_min, 0)
-                    .map(|dt| dt.and_utc());
-                let next_close = next_trading_day
-                    .and_hms_opt(close_hour, close_min, 0)
-                    .map(|dt| dt.and_utc());
-
-                (next_open, next_close)
-            } else {
-                (None, None)
+        let current = sessions
+            .iter()
+            .find(|(open, close)| is_open && now >= *open && now < *close);
+        let current_session = current
+            .map(|(open, close)| format!("{}-{}", open.format("%H:%M"), close.format("%H:%M")));
+
+        let (next_open, next_close) = match current {
+            // 交易中：给出本时段的收盘时间
+            Some((_, close)) => (None, to_utc(today, *close)),
+            // 闭市：今日尚未开始的时段（含午休后的下午盘），否则下一交易日的首个时段
+            None => {
+                let later_today = sessions
+                    .iter()
+                    .find(|(open, _)| is_trading_day && now < *open)
+                    .map(|session| (today, *session));
+                let next = later_today.or_else(|| {
+                    sessions
+                        .first()
+                        .map(|session| (market.get_next_trading_day(today), *session))
+                });
+                match next {
+                    Some((date, (open, close))) => (to_utc(date, open), to_utc(date, close)),
+                    None => (None, None),
+                }
             }
         };
 
-        // Format local time
-        let local_time = current_time
-            .with_timezone(&chrono::Local)
-            .format("%Y-%m-%d %H:%M:%S %Z")
-            .to_string();
+        let local_time = market_time.format("%Y-%m-%d %H:%M:%S %Z").to_string();
 
         Self {
             market,
@@ -330,6 +440,7 @@ impl MarketTimeInfo {
         }
     }
 
+    #[allow(dead_code)]
     pub fn get_status_emoji(&self) -> &'static str {
         if self.is_open {
             "🟢"
@@ -340,6 +451,7 @@ impl MarketTimeInfo {
         }
     }
 
+    #[allow(dead_code)]
     pub fn get_status_text(&self) -> &'static str {
         if self.is_open {
             "交易中"
@@ -352,6 +464,126 @@ impl MarketTimeInfo {
 }
 
 // Default converter instance
+#[allow(dead_code)]
 pub fn get_default_converter() -> CurrencyConverter {
     CurrencyConverter::new("USD".to_string(), 3600) // 1 hour TTL
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serves USD and EUR quotes until `failing` is set, then answers every request with 500
+    async fn spawn_rate_source(failing: Arc<AtomicBool>, requests: Arc<AtomicUsize>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                requests.fetch_add(1, Ordering::SeqCst);
+
+                let (status, body) = if failing.load(Ordering::SeqCst) {
+                    ("500 Internal Server Error", "{}")
+                } else if request.starts_with("GET /latest/USD ") {
+                    (
+                        "200 OK",
+                        r#"{"result":"success","rates":{"USD":1,"CNY":7.0,"EUR":0.5}}"#,
+                    )
+                } else if request.starts_with("GET /latest/EUR ") {
+                    (
+                        "200 OK",
+                        r#"{"result":"success","rates":{"EUR":1,"USD":2.0}}"#,
+                    )
+                } else {
+                    (
+                        "200 OK",
+                        r#"{"result":"error","error-type":"unsupported-code"}"#,
+                    )
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{}/latest/{{base}}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_live_rates_cache_and_fallbacks() {
+        let failing = Arc::new(AtomicBool::new(false));
+        let requests = Arc::new(AtomicUsize::new(0));
+        let source = spawn_rate_source(failing.clone(), requests.clone()).await;
+
+        let converter =
+            CurrencyConverter::new("USD".to_string(), 3600).with_rate_source(Some(source.clone()));
+        assert_eq!(
+            converter.get_exchange_rate("usd", "CNY").await.unwrap(),
+            7.0
+        );
+        // Served from the pair cache within the TTL
+        assert_eq!(
+            converter.get_exchange_rate("USD", "CNY").await.unwrap(),
+            7.0
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // EUR quotes lack CNY, so the rate is crossed through USD: 7.0 / 0.5
+        assert_eq!(
+            converter.get_exchange_rate("EUR", "CNY").await.unwrap(),
+            14.0
+        );
+
+        // With a zero TTL every lookup refetches; failures fall back to the last cached pair
+        let converter = CurrencyConverter::new("USD".to_string(), 0).with_rate_source(Some(source));
+        assert_eq!(
+            converter.get_exchange_rate("EUR", "USD").await.unwrap(),
+            2.0
+        );
+        assert_eq!(
+            converter.get_exchange_rate("USD", "EUR").await.unwrap(),
+            0.5
+        );
+        failing.store(true, Ordering::SeqCst);
+        assert_eq!(
+            converter.get_exchange_rate("EUR", "USD").await.unwrap(),
+            2.0
+        );
+        // No pair was cached for CNY/EUR, so the last base table is used: 0.5 / 7.0
+        let rate = converter.get_exchange_rate("CNY", "EUR").await.unwrap();
+        assert!((rate - 0.5 / 7.0).abs() < 1e-12);
+        assert!(converter.get_exchange_rate("CNY", "XYZ").await.is_err());
+    }
+
+    #[test]
+    fn test_market_time_info_uses_exchange_sessions() {
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+
+        // 10:00 in Shanghai: morning session, closes at 11:30 local
+        let info = MarketTimeInfo::new(Market::ASHARES, at("2025-01-06T02:00:00Z"));
+        assert!(info.is_open);
+        assert_eq!(info.current_session.as_deref(), Some("09:30-11:30"));
+        assert_eq!(info.next_session_close, Some(at("2025-01-06T03:30:00Z")));
+        assert!(info.local_time.starts_with("2025-01-06 10:00:00"));
+
+        // 12:00 in Shanghai: lunch break, the afternoon session opens next
+        let info = MarketTimeInfo::new(Market::ASHARES, at("2025-01-06T04:00:00Z"));
+        assert!(!info.is_open && info.is_trading_day);
+        assert_eq!(info.current_session, None);
+        assert_eq!(info.next_session_open, Some(at("2025-01-06T05:00:00Z")));
+        assert_eq!(info.next_session_close, Some(at("2025-01-06T07:00:00Z")));
+
+        // Friday 16:30 in New York rolls over to Monday's open
+        let info = MarketTimeInfo::new(Market::US, at("2025-01-10T21:30:00Z"));
+        assert!(!info.is_open);
+        assert_eq!(info.next_session_open, Some(at("2025-01-13T14:30:00Z")));
+    }
+}
@@ -1,7 +1,9 @@
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use futures::StreamExt;
 use reqwest::Client;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration as StdDuration, Instant};
 
@@ -9,42 +11,44 @@ use crate::cache::CachedDataFetcher;
 use crate::models::Market;
 use crate::models::*;
 
-// Rate limiter for API calls (max 10 requests per second)
+// Rate limiter for API calls (max `max_requests` requests per second, 0 disables the limit)
 pub struct RateLimiter {
+    max_requests: usize,
     request_times: Arc<tokio::sync::Mutex<Vec<Instant>>>,
 }
 
 impl RateLimiter {
-    pub fn new(_max_requests: usize) -> Self {
+    pub fn new(max_requests: usize) -> Self {
         Self {
+            max_requests,
             request_times: Arc::new(tokio::sync::Mutex::new(Vec::new())),
         }
     }
 
     pub async fn acquire(&self) -> RateLimiterPermit {
-        let mut times = self.request_times.lock().await;
-        let now = Instant::now();
-
-        // Clean up old requests (older than 1 second)
-        times.retain(|&time| now.duration_since(time) < StdDuration::from_secs(1));
-
-        // If we have too many recent requests, wait
-        if times.len() >= 10 {
-            if let Some(&oldest_time) = times.first() {
-                let wait_time =
-                    StdDuration::from_secs(1).saturating_sub(now.duration_since(oldest_time));
-                if wait_time > StdDuration::from_millis(0) {
-                    drop(times);
-                    tokio::time::sleep(wait_time).await;
-                    times = self.request_times.lock().await;
-                }
-            }
+        if self.max_requests == 0 {
+            return RateLimiterPermit;
         }
 
-        // Record this request time
-        times.push(now);
+        loop {
+            let mut times = self.request_times.lock().await;
+            let now = Instant::now();
+
+            // Clean up old requests (older than 1 second)
+            times.retain(|&time| now.duration_since(time) < StdDuration::from_secs(1));
+
+            if times.len() < self.max_requests {
+                // Record this request time
+                times.push(now);
+                return RateLimiterPermit;
+            }
 
-        RateLimiterPermit
+            // Wait until the oldest request leaves the window, then check again since
+            // other callers may have taken the freed slot meanwhile
+            let wait_time = StdDuration::from_secs(1).saturating_sub(now.duration_since(times[0]));
+            drop(times);
+            tokio::time::sleep(wait_time).await;
+        }
     }
 }
 
@@ -61,61 +65,189 @@ pub trait DataFetcher: Send + Sync {
     ) -> Result<(Vec<News>, SentimentAnalysis), String>;
     async fn get_stock_name(&self, stock_code: &str) -> String;
 
-    // New method for concurrent data fetching
-    async fn get_all_data_concurrent(
+    /// 指数日线数据，数据源不支持时返回错误
+    async fn get_index_data(&self, index_code: &str, _days: i32) -> Result<Vec<PriceData>, String> {
+        Err(format!("数据源不支持指数数据: {}", index_code))
+    }
+
+    /// 分钟K线（interval 为 1m/5m/15m/30m/60m），按时间升序，数据源不支持时返回错误
+    async fn get_intraday_data(
         &self,
         stock_code: &str,
+        _interval: &str,
+    ) -> Result<Vec<PriceData>, String> {
+        Err(format!("数据源不支持分钟数据: {}", stock_code))
+    }
+
+    /// 美股盘前/盘后报价，非美股或数据源不支持时返回 None
+    async fn get_extended_hours_quote(
+        &self,
+        _stock_code: &str,
+    ) -> Result<Option<ExtendedHoursQuote>, String> {
+        Ok(None)
+    }
+
+    /// 获取 [start, end] 日期范围（含）内的价格数据；默认取覆盖起始日至今的近 N 日数据后按日期过滤
+    async fn get_stock_data_range(
+        &self,
+        stock_code: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<PriceData>, String> {
+        validate_date_range(start, end)?;
+        let prices = self.get_stock_data(stock_code, days_since(start)).await?;
+        Ok(filter_date_range(prices, start, end))
+    }
+
+    /// 按复权方式获取价格数据；不复权时等同于 `get_stock_data`，数据源不支持复权时返回错误
+    async fn get_stock_data_adjusted(
+        &self,
+        stock_code: &str,
+        days: i32,
+        adjust: PriceAdjustment,
+    ) -> Result<Vec<PriceData>, String> {
+        match adjust {
+            PriceAdjustment::None => self.get_stock_data(stock_code, days).await,
+            _ => Err(format!("数据源不支持复权价格: {}", adjust)),
+        }
+    }
+
+    /// 跳过缓存直接从数据源获取价格数据，供实时行情推送使用
+    async fn get_stock_data_fresh(
+        &self,
+        stock_code: &str,
+        days: i32,
+    ) -> Result<Vec<PriceData>, String> {
+        self.get_stock_data(stock_code, days).await
+    }
+
+    /// 检查数据源是否可达，供健康检查使用；本地数据源默认视为可用
+    async fn health_check(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// 批量获取多只股票的价格数据，结果以传入的代码为键；默认逐只并发获取
+    async fn get_stock_data_batch(
+        &self,
+        stock_codes: &[&str],
         days: i32,
-    ) -> Result<
-        (
-            Vec<PriceData>,
-            FundamentalData,
-            (Vec<News>, SentimentAnalysis),
-            String,
-        ),
+    ) -> HashMap<String, Result<Vec<PriceData>, String>> {
+        fetch_stock_data_individually(self, stock_codes, days).await
+    }
+}
+
+/// 并发获取价格、基本面、新闻与名称；各任务共享同一个数据源，限流器与 HTTP 客户端在任务间共用
+pub async fn get_all_data_concurrent(
+    fetcher: &Arc<dyn DataFetcher>,
+    stock_code: &str,
+    days: i32,
+    news_days: i32,
+) -> Result<
+    (
+        Vec<PriceData>,
+        FundamentalData,
+        (Vec<News>, SentimentAnalysis),
         String,
-    > {
+    ),
+    String,
+> {
+    let stock_code_clone = stock_code.to_string();
+
+    // Spawn all requests concurrently
+    let price_future = tokio::spawn({
+        let fetcher = fetcher.clone();
+        async move { fetcher.get_stock_data(&stock_code_clone, days).await }
+    });
+
+    let fundamental_future = tokio::spawn({
         let stock_code_clone = stock_code.to_string();
+        let fetcher = fetcher.clone();
+        async move { fetcher.get_fundamental_data(&stock_code_clone).await }
+    });
 
-        // Spawn all three requests concurrently
-        let price_future = tokio::spawn({
-            let fetcher = self.clone();
-            async move { fetcher.get_stock_data(&stock_code_clone, days).await }
-        });
+    let news_future = tokio::spawn({
+        let stock_code_clone = stock_code.to_string();
+        let fetcher = fetcher.clone();
+        async move { fetcher.get_news_data(&stock_code_clone, news_days).await }
+    });
 
-        let fundamental_future = tokio::spawn({
-            let stock_code_clone = stock_code.to_string();
-            let fetcher = self.clone();
-            async move { fetcher.get_fundamental_data(&stock_code_clone).await }
-        });
+    let name_future = tokio::spawn({
+        let stock_code_clone = stock_code.to_string();
+        let fetcher = fetcher.clone();
+        async move { fetcher.get_stock_name(&stock_code_clone).await }
+    });
 
-        let news_future = tokio::spawn({
-            let stock_code_clone = stock_code.to_string();
-            let fetcher = self.clone();
-            async move { fetcher.get_news_data(&stock_code_clone, days).await }
-        });
+    // Wait for all results
+    let (price_result, fundamental_result, news_result, name_result) =
+        tokio::join!(price_future, fundamental_future, news_future, name_future);
 
-        let name_future = tokio::spawn({
-            let stock_code_clone = stock_code.to_string();
-            let fetcher = self.clone();
-            async move { fetcher.get_stock_name(&stock_code_clone).await }
-        });
+    let price_data = price_result.map_err(|e| format!("Price task failed: {}", e))??;
+    let fundamental_data =
+        fundamental_result.map_err(|e| format!("Fundamental task failed: {}", e))??;
+    let news_data = news_result.map_err(|e| format!("News task failed: {}", e))??;
+    let stock_name = name_result.map_err(|e| format!("Name task failed: {}", e))?;
 
-        // Wait for all results
-        let (price_result, fundamental_result, news_result, name_result) =
-            tokio::join!(price_future, fundamental_future, news_future, name_future);
+    Ok((price_data, fundamental_data, news_data, stock_name))
+}
 
-        let price_data = price_result.map_err(|e| format!("Price task failed: {}", e))??;
-        let fundamental_data =
-            fundamental_result.map_err(|e| format!("Fundamental task failed: {}", e))??;
-        let news_data = news_result.map_err(|e| format!("News task failed: {}", e))??;
-        let stock_name = name_result.map_err(|e| format!("Name task failed: {}", e))?;
+/// 按日期范围获取价格时允许的最大跨度（天）
+pub const MAX_PRICE_RANGE_DAYS: i64 = 3650;
 
-        Ok((price_data, fundamental_data, news_data, stock_name))
+/// 校验日期范围：起始日期早于结束日期、不晚于今天，且跨度不超过 `MAX_PRICE_RANGE_DAYS`
+pub fn validate_date_range(start: NaiveDate, end: NaiveDate) -> Result<(), String> {
+    if start >= end {
+        return Err(format!("起始日期 {} 必须早于结束日期 {}", start, end));
+    }
+    if start > Utc::now().date_naive() {
+        return Err(format!("起始日期 {} 晚于今天", start));
     }
+    let span = (end - start).num_days();
+    if span > MAX_PRICE_RANGE_DAYS {
+        return Err(format!(
+            "日期范围 {} 天超过上限 {} 天",
+            span, MAX_PRICE_RANGE_DAYS
+        ));
+    }
+    Ok(())
+}
+
+/// 从 start 到今天（含两端）的自然日数，用于以近 N 日接口覆盖一个日期范围
+fn days_since(start: NaiveDate) -> i32 {
+    let days = (Utc::now().date_naive() - start).num_days() + 1;
+    days.clamp(1, i32::MAX as i64) as i32
+}
+
+fn filter_date_range(
+    mut prices: Vec<PriceData>,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Vec<PriceData> {
+    prices.retain(|price| (start..=end).contains(&price.date.date_naive()));
+    prices
+}
+
+/// 逐只获取价格数据时同时进行的最大请求数
+pub const MAX_CONCURRENT_PRICE_FETCHES: usize = 10;
+
+/// 逐只并发获取价格数据（最多 `MAX_CONCURRENT_PRICE_FETCHES` 个同时进行），批量接口不可用时的回退路径
+pub async fn fetch_stock_data_individually<F: DataFetcher + ?Sized>(
+    fetcher: &F,
+    stock_codes: &[&str],
+    days: i32,
+) -> HashMap<String, Result<Vec<PriceData>, String>> {
+    let fetches: Vec<_> = stock_codes
+        .iter()
+        .map(|code| code.to_string())
+        .map(|code| async move {
+            let result = fetcher.get_stock_data(&code, days).await;
+            (code, result)
+        })
+        .collect();
 
-    // Helper method for cloning
-    fn clone(&self) -> Box<dyn DataFetcher>;
+    futures::stream::iter(fetches)
+        .buffer_unordered(MAX_CONCURRENT_PRICE_FETCHES)
+        .collect()
+        .await
 }
 
 pub struct AkshareProxy {
@@ -123,6 +255,12 @@ pub struct AkshareProxy {
     base_url: String,
     timeout: std::time::Duration,
     rate_limiter: Arc<RateLimiter>,
+    price_batch_size: usize,
+    endpoints: AkshareEndpoints,
+    max_retries: u32,
+    allow_mock_fallback: bool,
+    // Cleared once the proxy answers 404 so later batches skip straight to per-stock fetching
+    batch_endpoint_available: Arc<AtomicBool>,
 }
 
 impl AkshareProxy {
@@ -136,11 +274,67 @@ impl AkshareProxy {
             client,
             base_url,
             timeout: std::time::Duration::from_secs(timeout_secs),
-            rate_limiter: Arc::new(RateLimiter::new(10)), // Max 10 requests per second
+            rate_limiter: Arc::new(RateLimiter::new(10)),
+            price_batch_size: 0,
+            endpoints: AkshareEndpoints::default(),
+            max_retries: 0,
+            allow_mock_fallback: false,
+            batch_endpoint_available: Arc::new(AtomicBool::new(true)),
         }
     }
 
+    /// 启用多股票价格批量接口，每次请求最多 `batch_size` 只股票，0 表示禁用
+    pub fn with_price_batch_size(mut self, batch_size: usize) -> Self {
+        self.price_batch_size = batch_size;
+        self
+    }
+
+    /// 每秒最多向代理发出 `requests_per_second` 个请求，0 表示不限制
+    pub fn with_rate_limit(mut self, requests_per_second: usize) -> Self {
+        self.rate_limiter = Arc::new(RateLimiter::new(requests_per_second));
+        self
+    }
+
+    /// 网络错误或 5xx 响应时最多重试 `max_retries` 次
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// 请求最终失败时是否退回随机生成的模拟数据
+    pub fn with_mock_fallback(mut self, allow_mock_fallback: bool) -> Self {
+        self.allow_mock_fallback = allow_mock_fallback;
+        self
+    }
+
+    /// 使用自定义的接口路径模板
+    pub fn with_endpoints(mut self, endpoints: AkshareEndpoints) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
     async fn make_request(&self, endpoint: &str) -> Result<Value, String> {
+        let mut attempt = 0;
+        loop {
+            match self.try_request(endpoint).await {
+                Err((error, true)) if attempt < self.max_retries => {
+                    attempt += 1;
+                    log::warn!(
+                        "Request to {} failed, retrying ({}/{}): {}",
+                        endpoint,
+                        attempt,
+                        self.max_retries,
+                        error
+                    );
+                    tokio::time::sleep(StdDuration::from_millis(200 * attempt as u64)).await;
+                }
+                result => return result.map_err(|(error, _)| error),
+            }
+        }
+    }
+
+    /// 发送一次请求，错误附带是否值得重试（网络错误与 5xx 可重试，4xx 与解析错误不重试）
+    async fn try_request(&self, endpoint: &str) -> Result<Value, (String, bool)> {
         // Acquire rate limit permit
         let _permit = self.rate_limiter.acquire().await;
 
@@ -150,20 +344,45 @@ impl AkshareProxy {
             .get(&url)
             .send()
             .await
-            .map_err(|e| format!("Request failed: {}", e))?;
-
-        if !response.status().is_success() {
-            return Err(format!(
-                "HTTP {}: {}",
-                response.status(),
-                response.text().await.unwrap_or_default()
+            .map_err(|e| (format!("Request failed: {}", e), true))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err((
+                format!(
+                    "HTTP {}: {}",
+                    status,
+                    response.text().await.unwrap_or_default()
+                ),
+                status.is_server_error(),
             ));
         }
 
         response
             .json::<Value>()
             .await
-            .map_err(|e| format!("JSON parse failed: {}", e))
+            .map_err(|e| (format!("JSON parse failed: {}", e), false))
+    }
+
+    /// 请求失败时按配置退回模拟数据或返回错误
+    fn mock_or_error<T>(
+        &self,
+        what: &str,
+        stock_code: &str,
+        error: String,
+        mock: impl FnOnce() -> Result<T, String>,
+    ) -> Result<T, String> {
+        if self.allow_mock_fallback {
+            log::warn!(
+                "Failed to fetch {} for {}, using mock data: {}",
+                what,
+                stock_code,
+                error
+            );
+            mock()
+        } else {
+            Err(format!("获取 {} 的{}失败: {}", stock_code, what, error))
+        }
     }
 }
 
@@ -174,6 +393,11 @@ impl Clone for AkshareProxy {
             base_url: self.base_url.clone(),
             timeout: self.timeout,
             rate_limiter: self.rate_limiter.clone(),
+            price_batch_size: self.price_batch_size,
+            endpoints: self.endpoints.clone(),
+            max_retries: self.max_retries,
+            allow_mock_fallback: self.allow_mock_fallback,
+            batch_endpoint_available: self.batch_endpoint_available.clone(),
         }
     }
 }
@@ -181,87 +405,126 @@ impl Clone for AkshareProxy {
 #[async_trait::async_trait]
 impl DataFetcher for AkshareProxy {
     async fn get_stock_data(&self, stock_code: &str, days: i32) -> Result<Vec<PriceData>, String> {
+        self.get_stock_data_adjusted(stock_code, days, PriceAdjustment::None)
+            .await
+    }
+
+    async fn get_stock_data_adjusted(
+        &self,
+        stock_code: &str,
+        days: i32,
+        adjust: PriceAdjustment,
+    ) -> Result<Vec<PriceData>, String> {
+        let stock_code = &Market::normalize_code(stock_code);
         let market = Market::from_stock_code(stock_code);
-        let endpoint = match market {
-            Market::ASHARES => format!("api/stock/{}/price?days={}", stock_code, days),
-            Market::HONGKONG => format!("api/stock/hk/{}/price?days={}", stock_code, days),
-            Market::US => format!("api/stock/us/{}/price?days={}", stock_code, days),
-            Market::UNKNOWN => format!("api/stock/{}/price?days={}", stock_code, days),
+        // Crypto has no dividends or splits, so its series is the same either way
+        let adjust = match market {
+            Market::CRYPTO => PriceAdjustment::None,
+            _ => adjust,
         };
+        let endpoint = with_adjust_param(
+            render_endpoint(self.endpoints.price.for_market(&market), stock_code, days),
+            adjust,
+        );
 
         match self.make_request(&endpoint).await {
-            Ok(data) => {
-                let mut prices = Vec::new();
+            Ok(data) => Ok(data
+                .as_array()
+                .map(|items| parse_price_items(items, &market))
+                .unwrap_or_default()),
+            Err(e) => self.mock_or_error("价格数据", stock_code, e, || {
+                self.get_mock_stock_data(stock_code, days, &market)
+            }),
+        }
+    }
 
-                if let Some(items) = data.as_array() {
-                    for item in items {
-                        let date_str = item["date"].as_str().unwrap_or("");
-                        let date = DateTime::parse_from_str(date_str, "%Y-%m-%d")
-                            .map(|dt| dt.with_timezone(&Utc))
-                            .unwrap_or_else(|_| Utc::now());
+    async fn get_stock_data_range(
+        &self,
+        stock_code: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<PriceData>, String> {
+        validate_date_range(start, end)?;
+        let stock_code = &Market::normalize_code(stock_code);
+        let market = Market::from_stock_code(stock_code);
+        let template = self.endpoints.price_range.for_market(&market);
+        let endpoint = render_endpoint(template, stock_code, 0)
+            .replace("{start}", &start.to_string())
+            .replace("{end}", &end.to_string());
 
-                        prices.push(PriceData {
-                            date,
-                            open: item["open"].as_f64().unwrap_or(0.0),
-                            close: item["close"].as_f64().unwrap_or(0.0),
-                            high: item["high"].as_f64().unwrap_or(0.0),
-                            low: item["low"].as_f64().unwrap_or(0.0),
-                            volume: item["volume"].as_i64().unwrap_or(0),
-                            change_pct: 0.0,  // Will be calculated
-                            turnover: 0.0,    // Will be calculated
-                            turnover_rt: 0.0, // Will be calculated
-                        });
-                    }
+        match self.make_request(&endpoint).await {
+            Ok(data) => Ok(filter_date_range(
+                data.as_array()
+                    .map(|items| parse_price_items(items, &market))
+                    .unwrap_or_default(),
+                start,
+                end,
+            )),
+            Err(e) => self.mock_or_error("价格数据", stock_code, e, || {
+                self.get_mock_stock_data(stock_code, days_since(start), &market)
+                    .map(|prices| filter_date_range(prices, start, end))
+            }),
+        }
+    }
 
-                    // Sort by date ascending
-                    prices.sort_by(|a, b| a.date.cmp(&b.date));
-
-                    // Calculate additional fields
-                    let mut change_pcts = Vec::with_capacity(prices.len());
-                    let mut turnovers = Vec::with_capacity(prices.len());
-                    let mut turnover_rts = Vec::with_capacity(prices.len());
-
-                    for i in 0..prices.len() {
-                        if i > 0 {
-                            let prev_close = prices[i - 1].close;
-                            if prev_close > 0.0 {
-                                change_pcts
-                                    .push(((prices[i].close - prev_close) / prev_close) * 100.0);
-                            } else {
-                                change_pcts.push(0.0);
+    async fn get_stock_data_batch(
+        &self,
+        stock_codes: &[&str],
+        days: i32,
+    ) -> HashMap<String, Result<Vec<PriceData>, String>> {
+        if self.price_batch_size == 0 || !self.batch_endpoint_available.load(Ordering::Relaxed) {
+            return fetch_stock_data_individually(self, stock_codes, days).await;
+        }
+
+        let mut results = HashMap::new();
+        let mut missing: Vec<&str> = Vec::new();
+
+        for (index, chunk) in stock_codes.chunks(self.price_batch_size).enumerate() {
+            let normalized: Vec<String> = chunk.iter().map(|c| Market::normalize_code(c)).collect();
+            let endpoint = render_endpoint(&self.endpoints.price_batch, "", days)
+                .replace("{codes}", &normalized.join(","));
+
+            match self.make_request(&endpoint).await {
+                Ok(data) => {
+                    for (code, normalized_code) in chunk.iter().zip(&normalized) {
+                        match data.get(normalized_code).and_then(|v| v.as_array()) {
+                            Some(items) => {
+                                let market = Market::from_stock_code(normalized_code);
+                                let prices = parse_price_items(items, &market);
+                                results.insert(code.to_string(), Ok(prices));
                             }
-                        } else {
-                            change_pcts.push(0.0);
+                            None => missing.push(code),
                         }
-                        turnovers.push(prices[i].volume as f64 * prices[i].close);
-                        turnover_rts.push(prices[i].volume as f64 / 100_000_000.0);
-                    }
-
-                    // Apply calculated values
-                    for (i, price) in prices.iter_mut().enumerate() {
-                        price.change_pct = change_pcts[i];
-                        price.turnover = turnovers[i];
-                        price.turnover_rt = turnover_rts[i];
                     }
                 }
-
-                Ok(prices)
-            }
-            Err(_) => {
-                // Fallback to mock data
-                self.get_mock_stock_data(stock_code, days, &market)
+                Err(e) if e.starts_with("HTTP 404") => {
+                    log::warn!(
+                        "Batch price endpoint not available, falling back to per-stock fetching"
+                    );
+                    self.batch_endpoint_available
+                        .store(false, Ordering::Relaxed);
+                    missing.extend(&stock_codes[index * self.price_batch_size..]);
+                    break;
+                }
+                Err(e) => {
+                    log::warn!("Batch price request failed, retrying per stock: {}", e);
+                    missing.extend(chunk);
+                }
             }
         }
+
+        results.extend(fetch_stock_data_individually(self, &missing, days).await);
+        results
     }
 
     async fn get_fundamental_data(&self, stock_code: &str) -> Result<FundamentalData, String> {
+        let stock_code = &Market::normalize_code(stock_code);
         let market = Market::from_stock_code(stock_code);
-        let endpoint = match market {
-            Market::ASHARES => format!("api/stock/{}/fundamental", stock_code),
-            Market::HONGKONG => format!("api/stock/hk/{}/fundamental", stock_code),
-            Market::US => format!("api/stock/us/{}/fundamental", stock_code),
-            Market::UNKNOWN => format!("api/stock/{}/fundamental", stock_code),
-        };
+        let endpoint = render_endpoint(
+            self.endpoints.fundamental.for_market(&market),
+            stock_code,
+            0,
+        );
 
         match self.make_request(&endpoint).await {
             Ok(data) => {
@@ -305,8 +568,11 @@ impl DataFetcher for AkshareProxy {
                         .to_string(),
                 };
 
+                let beta = data["risk_assessment"]["beta"].as_f64();
                 let risk_assessment = RiskAssessment {
-                    beta: data["risk_assessment"]["beta"].as_f64(),
+                    beta,
+                    beta_source: beta.map(|_| BetaSource::Upstream),
+                    beta_index: None,
                     debt_to_equity: data["risk_assessment"]["debt_to_equity"].as_f64(),
                     current_ratio: data["risk_assessment"]["current_ratio"].as_f64(),
                     quick_ratio: data["risk_assessment"]["quick_ratio"].as_f64(),
@@ -345,10 +611,9 @@ impl DataFetcher for AkshareProxy {
                     financial_health,
                 })
             }
-            Err(_) => {
-                // Fallback to mock data
+            Err(e) => self.mock_or_error("基本面数据", stock_code, e, || {
                 self.get_mock_fundamental_data(stock_code, &market)
-            }
+            }),
         }
     }
 
@@ -357,13 +622,9 @@ impl DataFetcher for AkshareProxy {
         stock_code: &str,
         days: i32,
     ) -> Result<(Vec<News>, SentimentAnalysis), String> {
+        let stock_code = &Market::normalize_code(stock_code);
         let market = Market::from_stock_code(stock_code);
-        let endpoint = match market {
-            Market::ASHARES => format!("api/stock/{}/news?days={}", stock_code, days),
-            Market::HONGKONG => format!("api/stock/hk/{}/news?days={}", stock_code, days),
-            Market::US => format!("api/stock/us/{}/news?days={}", stock_code, days),
-            Market::UNKNOWN => format!("api/stock/{}/news?days={}", stock_code, days),
-        };
+        let endpoint = render_endpoint(self.endpoints.news.for_market(&market), stock_code, days);
 
         match self.make_request(&endpoint).await {
             Ok(data) => {
@@ -371,10 +632,8 @@ impl DataFetcher for AkshareProxy {
 
                 if let Some(news_array) = data["news"].as_array() {
                     for item in news_array {
-                        let date_str = item["date"].as_str().unwrap_or("");
-                        let date = DateTime::parse_from_str(date_str, "%Y-%m-%d")
-                            .map(|dt| dt.with_timezone(&Utc))
-                            .unwrap_or_else(|_| Utc::now());
+                        let date =
+                            parse_timestamp(item["date"].as_str().unwrap_or(""), market.timezone());
 
                         news.push(News {
                             title: item["title"].as_str().unwrap_or("").to_string(),
@@ -422,15 +681,15 @@ impl DataFetcher for AkshareProxy {
 
                 Ok((news, sentiment_analysis))
             }
-            Err(_) => {
-                // Fallback to mock data
+            Err(e) => self.mock_or_error("新闻数据", stock_code, e, || {
                 self.get_mock_news_data(stock_code, days, &market)
-            }
+            }),
         }
     }
 
     async fn get_stock_name(&self, stock_code: &str) -> String {
-        let endpoint = format!("api/stock/{}/name", stock_code);
+        let stock_code = &Market::normalize_code(stock_code);
+        let endpoint = render_endpoint(&self.endpoints.name, stock_code, 0);
 
         match self.make_request(&endpoint).await {
             Ok(data) => data["name"].as_str().unwrap_or("").to_string(),
@@ -438,8 +697,59 @@ impl DataFetcher for AkshareProxy {
         }
     }
 
-    fn clone(&self) -> Box<dyn DataFetcher> {
-        Box::new(Clone::clone(self))
+    async fn get_index_data(&self, index_code: &str, days: i32) -> Result<Vec<PriceData>, String> {
+        // No mock fallback: a synthetic benchmark would make the comparison meaningless
+        let endpoint = render_endpoint(&self.endpoints.index_price, index_code, days);
+        let market = Market::from_stock_code(index_code);
+        let data = self.make_request(&endpoint).await?;
+        data.as_array()
+            .map(|items| parse_price_items(items, &market))
+            .filter(|prices| !prices.is_empty())
+            .ok_or_else(|| format!("指数 {} 没有返回价格数据", index_code))
+    }
+
+    async fn get_intraday_data(
+        &self,
+        stock_code: &str,
+        interval: &str,
+    ) -> Result<Vec<PriceData>, String> {
+        let interval: IntradayInterval = interval.parse()?;
+        let stock_code = &Market::normalize_code(stock_code);
+        let market = Market::from_stock_code(stock_code);
+        let endpoint = render_endpoint(self.endpoints.intraday.for_market(&market), stock_code, 0)
+            .replace("{period}", &interval.minutes().to_string());
+
+        match self.make_request(&endpoint).await {
+            Ok(data) => Ok(data
+                .as_array()
+                .map(|items| parse_price_items(items, &market))
+                .unwrap_or_default()),
+            Err(e) => self.mock_or_error("分钟数据", stock_code, e, || {
+                self.get_mock_intraday_data(stock_code, interval, &market)
+            }),
+        }
+    }
+
+    async fn get_extended_hours_quote(
+        &self,
+        stock_code: &str,
+    ) -> Result<Option<ExtendedHoursQuote>, String> {
+        let stock_code = &Market::normalize_code(stock_code);
+        if Market::from_stock_code(stock_code) != Market::US {
+            return Ok(None);
+        }
+
+        let endpoint = render_endpoint(&self.endpoints.extended_hours, stock_code, 0);
+        let data = self.make_request(&endpoint).await?;
+        Ok(parse_extended_hours_quote(&data))
+    }
+
+    async fn health_check(&self) -> Result<(), String> {
+        // A single attempt without mock fallback, so an unreachable proxy is reported as such
+        self.try_request("health")
+            .await
+            .map(|_| ())
+            .map_err(|(error, _)| error)
     }
 }
 
@@ -461,6 +771,9 @@ impl AkshareProxy {
                 50.0 + (stock_code.chars().map(|c| c as u32).sum::<u32>() % 200) as f64
             }
             Market::US => 100.0 + (stock_code.chars().map(|c| c as u32).sum::<u32>() % 400) as f64,
+            Market::CRYPTO => {
+                1000.0 + (stock_code.chars().map(|c| c as u32).sum::<u32>() % 50_000) as f64
+            }
             Market::UNKNOWN => {
                 50.0 + (stock_code.chars().map(|c| c as u32).sum::<u32>() % 100) as f64
             }
@@ -477,6 +790,7 @@ impl AkshareProxy {
                 Market::ASHARES => 0.1,
                 Market::HONGKONG => 0.15,
                 Market::US => 0.08,
+                Market::CRYPTO => 0.2,
                 Market::UNKNOWN => 0.12,
             };
 
@@ -491,6 +805,7 @@ impl AkshareProxy {
                 Market::ASHARES => 1_000_000 + rand::random::<i64>().rem_euclid(5_000_000),
                 Market::HONGKONG => 500_000 + rand::random::<i64>().rem_euclid(2_000_000),
                 Market::US => 100_000 + rand::random::<i64>().rem_euclid(1_000_000),
+                Market::CRYPTO => 10_000 + rand::random::<i64>().rem_euclid(100_000),
                 Market::UNKNOWN => 500_000 + rand::random::<i64>().rem_euclid(2_000_000),
             };
 
@@ -503,7 +818,7 @@ impl AkshareProxy {
                 volume,
                 change_pct: ((close - open) / open) * 100.0,
                 turnover: volume as f64 * close,
-                turnover_rt: volume as f64 / 100_000_000.0,
+                turnover_rt: estimated_turnover_rate(volume),
             });
 
             current_price = close;
@@ -512,6 +827,27 @@ impl AkshareProxy {
         Ok(prices)
     }
 
+    /// 以日线模拟数据的走势生成截至当前时刻、按周期对齐的分钟K线
+    fn get_mock_intraday_data(
+        &self,
+        stock_code: &str,
+        interval: IntradayInterval,
+        market: &Market,
+    ) -> Result<Vec<PriceData>, String> {
+        let mut prices = self.get_mock_stock_data(stock_code, MOCK_INTRADAY_BARS, market)?;
+
+        let step = interval.minutes() * 60;
+        let now = Utc::now().timestamp();
+        let latest = now - now.rem_euclid(step);
+        let count = prices.len() as i64;
+        for (i, price) in prices.iter_mut().enumerate() {
+            let timestamp = latest - (count - 1 - i as i64) * step;
+            price.date = DateTime::from_timestamp(timestamp, 0).unwrap_or_else(Utc::now);
+        }
+
+        Ok(prices)
+    }
+
     fn get_mock_fundamental_data(
         &self,
         stock_code: &str,
@@ -624,6 +960,12 @@ impl AkshareProxy {
                 ];
                 (indicators, "Unknown".to_string(), "General".to_string())
             }
+            // 加密货币没有财报与估值指标
+            Market::CRYPTO => (
+                Vec::new(),
+                "Cryptocurrency".to_string(),
+                "Digital Assets".to_string(),
+            ),
         };
 
         let mut valuation = std::collections::HashMap::new();
@@ -658,6 +1000,13 @@ impl AkshareProxy {
                 analyst_rating: "Buy".to_string(),
                 forecast_period: "12 months".to_string(),
             },
+            Market::CRYPTO => PerformanceForecasts {
+                revenue_growth_forecast: None,
+                earnings_growth_forecast: None,
+                target_price: None,
+                analyst_rating: "N/A".to_string(),
+                forecast_period: "N/A".to_string(),
+            },
             Market::UNKNOWN => PerformanceForecasts {
                 revenue_growth_forecast: Some(15.0 + (hash % 20) as f64),
                 earnings_growth_forecast: Some(12.0 + (hash % 15) as f64),
@@ -669,6 +1018,8 @@ impl AkshareProxy {
 
         let risk_assessment = RiskAssessment {
             beta: Some(1.0 + (hash % 50) as f64 / 100.0),
+            beta_source: Some(BetaSource::Upstream),
+            beta_index: None,
             debt_to_equity: Some(0.5 + (hash % 20) as f64 / 10.0),
             current_ratio: Some(1.5 + (hash % 10) as f64 / 10.0),
             quick_ratio: Some(1.2 + (hash % 8) as f64 / 10.0),
@@ -715,13 +1066,14 @@ impl AkshareProxy {
             Market::ASHARES => (vec!["新浪财经", "东方财富", "证券时报"], "A股"),
             Market::HONGKONG => (vec!["香港经济日报", "信报", "南华早报"], "港股"),
             Market::US => (vec!["Bloomberg", "Reuters", "Wall Street Journal"], "美股"),
+            Market::CRYPTO => (vec!["CoinDesk", "The Block", "Cointelegraph"], "加密货币"),
             Market::UNKNOWN => (vec!["Financial Times", "MarketWatch"], "股市"),
         };
 
         let mut news = Vec::new();
         for i in 0..20 {
             let date = Utc::now() - Duration::days((i % days.max(1) as u32) as i64);
-            let sentiment = ((hash + i as u32) % 200) as f64 / 100.0 - 1.0;
+            let sentiment = ((hash + i) % 200) as f64 / 100.0 - 1.0;
             let source = news_sources[i as usize % news_sources.len()];
 
             news.push(News {
@@ -741,38 +1093,140 @@ impl AkshareProxy {
             });
         }
 
-        let overall_sentiment = news.iter().map(|n| n.sentiment).sum::<f64>() / news.len() as f64;
+        let sentiment_analysis = SentimentAnalysis::from_news(&news, 0.75);
+
+        Ok((news, sentiment_analysis))
+    }
+}
+
+/// 模拟分钟数据的K线根数，相当于A股一个交易日的1分钟K线
+const MOCK_INTRADAY_BARS: i32 = 240;
+
+/// 填充接口路径模板中的 {code} 与 {days} 占位符
+fn render_endpoint(template: &str, code: &str, days: i32) -> String {
+    template
+        .replace("{code}", code)
+        .replace("{days}", &days.to_string())
+}
+
+/// 需要复权时在接口路径后追加 adjust 查询参数，不复权时保持原路径
+fn with_adjust_param(endpoint: String, adjust: PriceAdjustment) -> String {
+    if adjust == PriceAdjustment::None {
+        return endpoint;
+    }
+    let separator = if endpoint.contains('?') { '&' } else { '?' };
+    format!("{}{}adjust={}", endpoint, separator, adjust)
+}
 
-        let mut sentiment_by_type = std::collections::HashMap::new();
-        sentiment_by_type.insert("company_news".to_string(), overall_sentiment);
+/// 解析代理返回的日期或时间戳，依次尝试 `%Y-%m-%d`（按 UTC 零点，与日线的日期标签一致）、
+/// `%Y-%m-%d %H:%M:%S`、`%Y-%m-%d %H:%M`（均为交易所当地时间，按 `tz` 换算为 UTC）与 RFC3339
+fn parse_datetime(value: &str, tz: chrono_tz::Tz) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc());
+    }
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M"))
+        .ok()
+        .and_then(|dt| dt.and_local_timezone(tz).earliest())
+        .map(|dt| dt.with_timezone(&Utc))
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|dt| dt.with_timezone(&Utc))
+        })
+}
+
+/// 无法解析时记录警告并使用当前时间
+fn parse_timestamp(value: &str, tz: chrono_tz::Tz) -> DateTime<Utc> {
+    parse_datetime(value, tz).unwrap_or_else(|| {
+        log::warn!("Unrecognized date {:?}, using the current time", value);
+        Utc::now()
+    })
+}
+
+/// 解析代理返回的价格行并按日期升序排列，时间按 `market` 的交易所时区解析；
+/// 代理未提供的涨跌幅、成交额与换手率由相邻行推算
+pub(crate) fn parse_price_items(items: &[Value], market: &Market) -> Vec<PriceData> {
+    let tz = market.timezone();
+    // Fields the proxy may omit stay None until they are derived after sorting
+    let mut rows: Vec<_> = items
+        .iter()
+        .map(|item| {
+            let price = PriceData {
+                date: parse_timestamp(item["date"].as_str().unwrap_or(""), tz),
+                open: item["open"].as_f64().unwrap_or(0.0),
+                close: item["close"].as_f64().unwrap_or(0.0),
+                high: item["high"].as_f64().unwrap_or(0.0),
+                low: item["low"].as_f64().unwrap_or(0.0),
+                volume: item["volume"].as_i64().unwrap_or(0),
+                change_pct: 0.0,
+                turnover: 0.0,
+                turnover_rt: 0.0,
+            };
+            (
+                price,
+                item["change_pct"].as_f64(),
+                item["turnover"].as_f64(),
+                item["turnover_rate"].as_f64(),
+            )
+        })
+        .collect();
 
-        let mut news_distribution = std::collections::HashMap::new();
-        news_distribution.insert("company_news".to_string(), news.len() as i32);
+    // Sort by date ascending
+    rows.sort_by_key(|(price, ..)| price.date);
 
-        let sentiment_analysis = SentimentAnalysis {
-            overall_sentiment,
-            sentiment_trend: if overall_sentiment > 0.3 {
-                "非常积极".to_string()
-            } else if overall_sentiment > 0.1 {
-                "偏向积极".to_string()
-            } else if overall_sentiment > -0.1 {
-                "相对中性".to_string()
-            } else if overall_sentiment > -0.3 {
-                "偏向消极".to_string()
+    // Fill fields the proxy left out from the neighbouring rows
+    let mut prev_close = 0.0;
+    rows.into_iter()
+        .map(|(mut price, change_pct, turnover, turnover_rate)| {
+            price.change_pct = change_pct.unwrap_or(if prev_close > 0.0 {
+                (price.close - prev_close) / prev_close * 100.0
             } else {
-                "非常消极".to_string()
-            },
-            confidence_score: 0.75,
-            total_analyzed: news.len() as i32,
-            sentiment_by_type,
-            news_distribution,
-        };
+                0.0
+            });
+            price.turnover = turnover.unwrap_or(price.volume as f64 * price.close);
+            price.turnover_rt = turnover_rate.unwrap_or(estimated_turnover_rate(price.volume));
+            prev_close = price.close;
+            price
+        })
+        .collect()
+}
 
-        Ok((news, sentiment_analysis))
+/// 代理未提供换手率时按成交量估算（假定流通股本 1 亿股），单位与代理的换手率一致为百分比
+fn estimated_turnover_rate(volume: i64) -> f64 {
+    volume as f64 / 100_000_000.0 * 100.0
+}
+
+/// 解析盘前/盘后报价，缺失的涨跌幅按常规收盘价推算；两个时段都无报价时返回 None
+fn parse_extended_hours_quote(data: &Value) -> Option<ExtendedHoursQuote> {
+    let regular_close = data["regular_close"].as_f64().filter(|c| *c > 0.0)?;
+    let pre_market_price = data["pre_market_price"].as_f64();
+    let post_market_price = data["post_market_price"].as_f64();
+    if pre_market_price.is_none() && post_market_price.is_none() {
+        return None;
     }
+
+    let change_pct = |price: Option<f64>, field: &str| {
+        data[field]
+            .as_f64()
+            .or_else(|| price.map(|p| (p - regular_close) / regular_close * 100.0))
+    };
+
+    Some(ExtendedHoursQuote {
+        regular_close,
+        pre_market_price,
+        pre_market_change_pct: change_pct(pre_market_price, "pre_market_change_pct"),
+        post_market_price,
+        post_market_change_pct: change_pct(post_market_price, "post_market_change_pct"),
+        updated_at: data["updated_at"]
+            .as_str()
+            .and_then(|value| parse_datetime(value, Market::US.timezone()))
+            .unwrap_or_else(Utc::now),
+    })
 }
 
-// Mock data fetcher for development
+#[allow(dead_code)]
 pub struct MockDataFetcher;
 
 #[async_trait::async_trait]
@@ -789,6 +1243,16 @@ impl DataFetcher for MockDataFetcher {
             .get_mock_stock_data(stock_code, days, &market)
     }
 
+    async fn get_stock_data_adjusted(
+        &self,
+        stock_code: &str,
+        days: i32,
+        _adjust: PriceAdjustment,
+    ) -> Result<Vec<PriceData>, String> {
+        // Mock series have no corporate actions, so every adjustment gives the same prices
+        self.get_stock_data(stock_code, days).await
+    }
+
     async fn get_fundamental_data(&self, stock_code: &str) -> Result<FundamentalData, String> {
         let market = Market::from_stock_code(stock_code);
         AkshareProxy::new("http://localhost:5000".to_string(), 30)
@@ -854,11 +1318,268 @@ impl DataFetcher for MockDataFetcher {
             Market::ASHARES => format!("{}股票", stock_code),
             Market::HONGKONG => format!("{}控股", stock_code),
             Market::US => format!("{} Corp.", stock_code),
+            Market::CRYPTO => stock_code.to_string(),
             Market::UNKNOWN => format!("{}股票", stock_code),
         }
     }
 
-    fn clone(&self) -> Box<dyn DataFetcher> {
-        Box::new(MockDataFetcher)
+    async fn get_index_data(&self, index_code: &str, days: i32) -> Result<Vec<PriceData>, String> {
+        AkshareProxy::new("http://localhost:5000".to_string(), 30).get_mock_stock_data(
+            index_code,
+            days,
+            &Market::UNKNOWN,
+        )
+    }
+
+    async fn get_intraday_data(
+        &self,
+        stock_code: &str,
+        interval: &str,
+    ) -> Result<Vec<PriceData>, String> {
+        let market = Market::from_stock_code(stock_code);
+        AkshareProxy::new("http://localhost:5000".to_string(), 30).get_mock_intraday_data(
+            stock_code,
+            interval.parse()?,
+            &market,
+        )
+    }
+
+    async fn get_extended_hours_quote(
+        &self,
+        stock_code: &str,
+    ) -> Result<Option<ExtendedHoursQuote>, String> {
+        let market = Market::from_stock_code(stock_code);
+        if market != Market::US {
+            return Ok(None);
+        }
+
+        let prices = AkshareProxy::new("http://localhost:5000".to_string(), 30)
+            .get_mock_stock_data(stock_code, 1, &market)?;
+        let regular_close = prices.last().map(|p| p.close).unwrap_or(0.0);
+        Ok(parse_extended_hours_quote(&serde_json::json!({
+            "regular_close": regular_close,
+            "post_market_price": regular_close * 1.012,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rate_limiter_honors_configured_limit() {
+        let limiter = RateLimiter::new(5);
+        let started = Instant::now();
+        for _ in 0..10 {
+            limiter.acquire().await;
+        }
+        // The first 5 pass immediately, the next 5 wait for the window to roll over
+        let elapsed = started.elapsed();
+        assert!(elapsed >= StdDuration::from_millis(950), "{:?}", elapsed);
+        assert!(elapsed < StdDuration::from_secs(2), "{:?}", elapsed);
+
+        let unlimited = RateLimiter::new(0);
+        let started = Instant::now();
+        for _ in 0..100 {
+            unlimited.acquire().await;
+        }
+        assert!(started.elapsed() < StdDuration::from_millis(100));
+    }
+
+    /// Mock fetcher that records how many price requests are in flight at once
+    struct ConcurrencyProbe {
+        in_flight: std::sync::atomic::AtomicUsize,
+        peak: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl DataFetcher for ConcurrencyProbe {
+        async fn get_stock_data(
+            &self,
+            stock_code: &str,
+            days: i32,
+        ) -> Result<Vec<PriceData>, String> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(StdDuration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            MockDataFetcher.get_stock_data(stock_code, days).await
+        }
+
+        async fn get_fundamental_data(&self, stock_code: &str) -> Result<FundamentalData, String> {
+            MockDataFetcher.get_fundamental_data(stock_code).await
+        }
+
+        async fn get_news_data(
+            &self,
+            stock_code: &str,
+            days: i32,
+        ) -> Result<(Vec<News>, SentimentAnalysis), String> {
+            MockDataFetcher.get_news_data(stock_code, days).await
+        }
+
+        async fn get_stock_name(&self, stock_code: &str) -> String {
+            MockDataFetcher.get_stock_name(stock_code).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_individual_fetches_are_bounded() {
+        let probe = ConcurrencyProbe {
+            in_flight: Default::default(),
+            peak: Default::default(),
+        };
+        let codes: Vec<String> = (0..25).map(|i| format!("{:06}", i + 1)).collect();
+        let code_refs: Vec<&str> = codes.iter().map(String::as_str).collect();
+
+        let results = probe.get_stock_data_batch(&code_refs, 30).await;
+        assert_eq!(results.len(), 25);
+        assert!(results.values().all(|r| r.is_ok()));
+        assert_eq!(
+            probe.peak.load(Ordering::SeqCst),
+            MAX_CONCURRENT_PRICE_FETCHES
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_fetch_shares_one_fetcher() {
+        let probe = Arc::new(ConcurrencyProbe {
+            in_flight: Default::default(),
+            peak: Default::default(),
+        });
+        let fetcher: Arc<dyn DataFetcher> = probe.clone();
+
+        let (prices, _, _, name) = get_all_data_concurrent(&fetcher, "000001", 30, 7)
+            .await
+            .unwrap();
+        assert!(!prices.is_empty());
+        assert!(!name.is_empty());
+        // The spawned price task ran against the caller's instance, not a copy
+        assert_eq!(probe.peak.load(Ordering::SeqCst), 1);
+        assert_eq!(Arc::strong_count(&probe), 2);
+    }
+
+    #[test]
+    fn test_parse_datetime_formats() {
+        let shanghai = Market::ASHARES.timezone();
+        let day = parse_datetime("2024-03-05", shanghai).unwrap();
+        assert_eq!(day.to_rfc3339(), "2024-03-05T00:00:00+00:00");
+        // Naive times are exchange-local
+        let intraday = parse_datetime("2024-03-05 14:30:00", shanghai).unwrap();
+        assert_eq!(intraday.to_rfc3339(), "2024-03-05T06:30:00+00:00");
+        assert_eq!(parse_datetime("2024-03-05 14:30", shanghai), Some(intraday));
+        let new_york = parse_datetime("2024-07-01 09:30", Market::US.timezone()).unwrap();
+        assert_eq!(new_york.to_rfc3339(), "2024-07-01T13:30:00+00:00");
+        let offset = parse_datetime("2024-03-05T09:30:00+08:00", chrono_tz::UTC).unwrap();
+        assert_eq!(offset.to_rfc3339(), "2024-03-05T01:30:00+00:00");
+        assert!(parse_datetime("05/03/2024", shanghai).is_none());
+    }
+
+    #[test]
+    fn test_parse_price_items_prefers_upstream_fields() {
+        let items = serde_json::json!([
+            { "date": "2024-01-03", "open": 10.0, "close": 11.0, "high": 11.2, "low": 9.9,
+              "volume": 1000, "change_pct": 9.5, "turnover": 10950.0, "turnover_rate": 1.25 },
+            { "date": "2024-01-02", "open": 10.0, "close": 10.0, "high": 10.1, "low": 9.8,
+              "volume": 2000 },
+        ]);
+
+        let prices = parse_price_items(items.as_array().unwrap(), &Market::ASHARES);
+        // Rows without the fields fall back to computed values
+        assert_eq!(prices[0].change_pct, 0.0);
+        assert_eq!(prices[0].turnover, 20000.0);
+        assert_eq!(prices[0].turnover_rt, estimated_turnover_rate(2000));
+        assert!((prices[0].turnover_rt - 0.002).abs() < 1e-12);
+        // Upstream values are used as-is, even where they differ from the computation
+        assert_eq!(prices[1].change_pct, 9.5);
+        assert_eq!(prices[1].turnover, 10950.0);
+        assert_eq!(prices[1].turnover_rt, 1.25);
+    }
+
+    #[test]
+    fn test_intraday_rows_are_exchange_local_time() {
+        let items = serde_json::json!([
+            { "date": "2024-03-05 14:30", "open": 10.0, "close": 10.1, "high": 10.2, "low": 9.9,
+              "volume": 500 },
+        ]);
+
+        let prices = parse_price_items(items.as_array().unwrap(), &Market::ASHARES);
+        assert_eq!(
+            prices[0]
+                .date
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            "2024-03-05T06:30:00Z"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_proxy_errors_unless_mock_allowed() {
+        // Nothing listens on port 1, so every attempt fails to connect
+        let proxy = AkshareProxy::new("http://127.0.0.1:1".to_string(), 1).with_retries(1);
+        let err = proxy.get_stock_data("000001", 30).await.unwrap_err();
+        assert!(err.contains("000001"), "{}", err);
+        assert!(proxy.get_fundamental_data("000001").await.is_err());
+        assert!(proxy.get_news_data("000001", 7).await.is_err());
+
+        let proxy = proxy.with_mock_fallback(true);
+        assert!(!proxy.get_stock_data("000001", 30).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_price_range_is_validated_and_filtered() {
+        let today = Utc::now().date_naive();
+        let start = today - Duration::days(20);
+        let end = today - Duration::days(10);
+
+        let prices = MockDataFetcher
+            .get_stock_data_range("000001", start, end)
+            .await
+            .unwrap();
+        assert_eq!(prices.len(), 11);
+        assert_eq!(prices.first().unwrap().date.date_naive(), start);
+        assert_eq!(prices.last().unwrap().date.date_naive(), end);
+
+        assert!(validate_date_range(end, start).is_err());
+        assert!(validate_date_range(start, start).is_err());
+        assert!(validate_date_range(today + Duration::days(1), today + Duration::days(2)).is_err());
+        assert!(validate_date_range(end - Duration::days(MAX_PRICE_RANGE_DAYS + 1), end).is_err());
+        assert!(validate_date_range(end - Duration::days(MAX_PRICE_RANGE_DAYS), end).is_ok());
+    }
+
+    #[test]
+    fn test_adjust_param_is_only_added_when_requested() {
+        let endpoint = "api/stock/000001/price?days=30".to_string();
+        assert_eq!(
+            with_adjust_param(endpoint.clone(), PriceAdjustment::None),
+            endpoint
+        );
+        assert_eq!(
+            with_adjust_param(endpoint, PriceAdjustment::Qfq),
+            "api/stock/000001/price?days=30&adjust=qfq"
+        );
+        assert_eq!(
+            with_adjust_param("v2/us/AAPL".to_string(), PriceAdjustment::Hfq),
+            "v2/us/AAPL?adjust=hfq"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mock_intraday_bars_are_spaced_by_interval() {
+        let prices = MockDataFetcher
+            .get_intraday_data("000001", "15m")
+            .await
+            .unwrap();
+        assert_eq!(prices.len(), MOCK_INTRADAY_BARS as usize);
+        assert!(prices
+            .windows(2)
+            .all(|pair| pair[1].date - pair[0].date == Duration::minutes(15)));
+        assert!(prices.last().unwrap().date <= Utc::now());
+
+        let err = MockDataFetcher
+            .get_intraday_data("000001", "1d")
+            .await
+            .unwrap_err();
+        assert!(err.contains("1d"), "{}", err);
     }
 }
@@ -1,9 +1,15 @@
-use chrono::Utc;
-use sqlx::{postgres::PgPoolOptions, sqlite::SqlitePoolOptions, Pool, Postgres, Row, Sqlite};
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::postgres::{PgPoolOptions, PgRow};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow};
+use sqlx::{Pool, Postgres, Row, Sqlite};
+use std::str::FromStr;
 use uuid::Uuid;
 
 use crate::models::{
-    AnalysisReport, HistoryQuery, HistoryResponse, SavedAnalysis, SavedConfiguration,
+    AnalysisMetadata, AnalysisReport, AnalysisScores, DailyScore, HistoryQuery, HistoryResponse,
+    PaperAccount, PaperTrade, PriceAlert, ProviderUsage, SavedAnalysis, SavedConfiguration,
+    SavedSignal, SignalHistoryQuery, SignalHistoryResponse, StockActivity, TradingSignal,
+    TriggeredAlert, User, WatchlistEntry,
 };
 
 pub enum Database {
@@ -11,33 +17,68 @@ pub enum Database {
     Postgres(Pool<Postgres>),
 }
 
-impl Database {
-    pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
+/// 连接串对应的后端；SQLite 连接串已补全 sqlite: 前缀
+#[derive(Debug, PartialEq)]
+enum Backend {
+    Sqlite(String),
+    Postgres,
+}
+
+impl Backend {
+    fn from_url(database_url: &str) -> Result<Self, sqlx::Error> {
         if database_url.starts_with("postgres:") || database_url.starts_with("postgresql:") {
-            // Use PostgreSQL
-            let pool = PgPoolOptions::new()
-                .max_connections(5)
-                .connect(database_url)
-                .await?;
-            Ok(Database::Postgres(pool))
+            Ok(Backend::Postgres)
+        } else if database_url.starts_with("sqlite:") {
+            Ok(Backend::Sqlite(database_url.to_string()))
+        } else if database_url.contains("://") {
+            Err(sqlx::Error::Configuration(
+                format!(
+                    "unsupported database URL scheme (expected sqlite: or postgres://): {}",
+                    database_url
+                )
+                .into(),
+            ))
         } else {
-            // Use SQLite
-            let sqlite_url = if database_url.starts_with("sqlite:") {
-                database_url.to_string()
-            } else {
-                // Default to SQLite if no protocol specified
-                if database_url.contains(":") {
-                    database_url.to_string()
-                } else {
-                    format!("sqlite:{}", database_url)
-                }
-            };
+            // A bare path is treated as a SQLite file
+            Ok(Backend::Sqlite(format!("sqlite:{}", database_url)))
+        }
+    }
+}
 
-            let pool = SqlitePoolOptions::new()
-                .max_connections(5)
-                .connect(&sqlite_url)
-                .await?;
-            Ok(Database::Sqlite(pool))
+impl Database {
+    /// 使用默认连接池大小连接数据库
+    #[allow(dead_code)]
+    pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
+        Self::connect(database_url, 5).await
+    }
+
+    /// 按连接串的 scheme 选择 PostgreSQL 或 SQLite 连接池；SQLite 文件不存在时自动创建
+    pub async fn connect(database_url: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
+        let max_connections = max_connections.max(1);
+        match Backend::from_url(database_url)? {
+            Backend::Postgres => {
+                let pool = PgPoolOptions::new()
+                    .max_connections(max_connections)
+                    .connect(database_url)
+                    .await?;
+                Ok(Database::Postgres(pool))
+            }
+            Backend::Sqlite(sqlite_url) => {
+                let options = SqliteConnectOptions::from_str(&sqlite_url)?.create_if_missing(true);
+                let pool = SqlitePoolOptions::new()
+                    .max_connections(max_connections)
+                    .connect_with(options)
+                    .await?;
+                Ok(Database::Sqlite(pool))
+            }
+        }
+    }
+
+    /// 执行一次轻量查询，确认数据库连接可用
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        match self {
+            Database::Sqlite(pool) => sqlx::query("SELECT 1").execute(pool).await.map(|_| ()),
+            Database::Postgres(pool) => sqlx::query("SELECT 1").execute(pool).await.map(|_| ()),
         }
     }
 
@@ -46,8 +87,11 @@ impl Database {
         report: &AnalysisReport,
         ai_provider: Option<String>,
         ai_model: Option<String>,
+        ai_metadata: Option<&AnalysisMetadata>,
     ) -> Result<(), sqlx::Error> {
         let id = Uuid::new_v4();
+        let ai_tokens = ai_metadata.map(|m| m.tokens_used as i64);
+        let ai_latency_ms = ai_metadata.map(|m| m.processing_time_ms as i64);
 
         match self {
             Database::Sqlite(pool) => {
@@ -56,8 +100,8 @@ impl Database {
                     INSERT INTO saved_analyses (
                         id, stock_code, stock_name, analysis_date, price_info, technical, 
                         fundamental, sentiment, scores, recommendation, ai_analysis, data_quality,
-                        ai_provider, ai_model, created_at
-                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+                        ai_provider, ai_model, ai_tokens, ai_latency_ms, created_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
                     "#,
                 )
                 .bind(id.to_string())
@@ -74,6 +118,8 @@ impl Database {
                 .bind(serde_json::to_value(&report.data_quality).unwrap_or_default())
                 .bind(ai_provider)
                 .bind(ai_model)
+                .bind(ai_tokens)
+                .bind(ai_latency_ms)
                 .bind(Utc::now())
                 .execute(pool)
                 .await?;
@@ -84,8 +130,8 @@ impl Database {
                     INSERT INTO saved_analyses (
                         id, stock_code, stock_name, analysis_date, price_info, technical, 
                         fundamental, sentiment, scores, recommendation, ai_analysis, data_quality,
-                        ai_provider, ai_model, created_at
-                    ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+                        ai_provider, ai_model, ai_tokens, ai_latency_ms, created_at
+                    ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
                     "#,
                 )
                 .bind(id)
@@ -102,6 +148,8 @@ impl Database {
                 .bind(serde_json::to_value(&report.data_quality).unwrap_or_default())
                 .bind(ai_provider)
                 .bind(ai_model)
+                .bind(ai_tokens)
+                .bind(ai_latency_ms)
                 .bind(Utc::now())
                 .execute(pool)
                 .await?;
@@ -111,10 +159,53 @@ impl Database {
         Ok(())
     }
 
+    /// 导出用的历史查询：按 get_analysis_history 的单页上限分页读取全部匹配记录，
+    /// 仅在请求指定 limit 时截断
+    pub async fn export_analysis_history(
+        &self,
+        query: &HistoryQuery,
+    ) -> Result<HistoryResponse, sqlx::Error> {
+        const PAGE_SIZE: i64 = 100;
+        let offset = query.offset.unwrap_or(0).max(0);
+        let wanted = query.limit.map(|limit| limit.max(0));
+
+        let mut analyses = Vec::new();
+        let mut total = 0;
+        loop {
+            let fetched = analyses.len() as i64;
+            let page_size = wanted.map_or(PAGE_SIZE, |wanted| (wanted - fetched).min(PAGE_SIZE));
+            if page_size <= 0 {
+                break;
+            }
+            let page_query = HistoryQuery {
+                limit: Some(page_size),
+                offset: Some(offset + fetched),
+                ..query.clone()
+            };
+            let page = self.get_analysis_history(&page_query).await?;
+            total = page.total;
+            let page_len = page.analyses.len() as i64;
+            analyses.extend(page.analyses);
+            if page_len < page_size {
+                break;
+            }
+        }
+
+        Ok(HistoryResponse {
+            analyses,
+            total,
+            query: query.clone(),
+        })
+    }
+
     pub async fn get_analysis_history(
         &self,
         query: &HistoryQuery,
     ) -> Result<HistoryResponse, sqlx::Error> {
+        if let Some(terms) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            return self.search_analysis_history(query, terms).await;
+        }
+
         match self {
             Database::Sqlite(pool) => {
                 let limit = query.limit.unwrap_or(20).min(100);
@@ -204,6 +295,8 @@ impl Database {
                             .unwrap_or_default(),
                         ai_provider: row.get("ai_provider"),
                         ai_model: row.get("ai_model"),
+                        ai_tokens: row.get("ai_tokens"),
+                        ai_latency_ms: row.get("ai_latency_ms"),
                         created_at: row.get("created_at"),
                     };
                     analyses.push(analysis);
@@ -250,63 +343,29 @@ impl Database {
                         .get::<i64, _>("total")
                 };
 
-                // Get paginated data
-                let (data_query, binds) = if let Some(ref stock_code) = query.stock_code {
-                    if stock_code.is_empty() {
-                        (
-                            "SELECT * FROM saved_analyses ORDER BY created_at DESC LIMIT $1 OFFSET $2",
-                            vec![limit.to_string(), offset.to_string()]
-                        )
-                    } else {
-                        (
+                // Get paginated data; LIMIT and OFFSET are BIGINT parameters in Postgres
+                let rows = match query.stock_code.as_deref().filter(|code| !code.is_empty()) {
+                    Some(stock_code) => {
+                        sqlx::query(
                             "SELECT * FROM saved_analyses WHERE stock_code = $1 ORDER BY created_at DESC LIMIT $2 OFFSET $3",
-                            vec![stock_code.clone(), limit.to_string(), offset.to_string()]
                         )
+                        .bind(stock_code)
+                        .bind(limit)
+                        .bind(offset)
+                        .fetch_all(pool)
+                        .await?
+                    }
+                    None => {
+                        sqlx::query(
+                            "SELECT * FROM saved_analyses ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+                        )
+                        .bind(limit)
+                        .bind(offset)
+                        .fetch_all(pool)
+                        .await?
                     }
-                } else {
-                    (
-                        "SELECT * FROM saved_analyses ORDER BY created_at DESC LIMIT $1 OFFSET $2",
-                        vec![limit.to_string(), offset.to_string()],
-                    )
-                };
-
-                let query_builder = if binds.len() == 2 {
-                    sqlx::query(data_query).bind(&binds[0]).bind(&binds[1])
-                } else if binds.len() == 3 {
-                    sqlx::query(data_query)
-                        .bind(&binds[0])
-                        .bind(&binds[1])
-                        .bind(&binds[2])
-                } else {
-                    sqlx::query(data_query)
                 };
-
-                let rows = query_builder.fetch_all(pool).await?;
-                let mut analyses = Vec::new();
-
-                for row in rows {
-                    let analysis = SavedAnalysis {
-                        id: row.get("id"),
-                        stock_code: row.get("stock_code"),
-                        stock_name: row.get("stock_name"),
-                        analysis_date: row.get("analysis_date"),
-                        price_info: serde_json::from_value(row.get("price_info"))
-                            .unwrap_or_default(),
-                        technical: serde_json::from_value(row.get("technical")).unwrap_or_default(),
-                        fundamental: serde_json::from_value(row.get("fundamental"))
-                            .unwrap_or_default(),
-                        sentiment: serde_json::from_value(row.get("sentiment")).unwrap_or_default(),
-                        scores: serde_json::from_value(row.get("scores")).unwrap_or_default(),
-                        recommendation: row.get("recommendation"),
-                        ai_analysis: row.get("ai_analysis"),
-                        data_quality: serde_json::from_value(row.get("data_quality"))
-                            .unwrap_or_default(),
-                        ai_provider: row.get("ai_provider"),
-                        ai_model: row.get("ai_model"),
-                        created_at: row.get("created_at"),
-                    };
-                    analyses.push(analysis);
-                }
+                let analyses = rows.iter().map(postgres_saved_analysis).collect();
 
                 Ok(HistoryResponse {
                     analyses,
@@ -317,6 +376,127 @@ impl Database {
         }
     }
 
+    /// 在AI分析与投资建议中搜索关键词，按相关度排序，同样支持股票代码与日期筛选。
+    /// SQLite 使用 trigram 分词的 FTS5 表（关键词不足 3 个字符时退回 LIKE），PostgreSQL 使用 to_tsvector 并以 ILIKE 兜底
+    async fn search_analysis_history(
+        &self,
+        query: &HistoryQuery,
+        terms: &str,
+    ) -> Result<HistoryResponse, sqlx::Error> {
+        let limit = query.limit.unwrap_or(20).min(100);
+        let offset = query.offset.unwrap_or(0);
+        let stock_code = query.stock_code.as_deref().filter(|code| !code.is_empty());
+        let pattern = format!("%{}%", escape_like(terms));
+
+        let (analyses, total) = match self {
+            Database::Sqlite(pool) => {
+                // Trigram tokens need at least three characters; shorter terms fall back to
+                // LIKE, ranked by how much of the text the term occurrences cover
+                let (source, condition, rank, matcher) = if terms.chars().count() >= 3 {
+                    (
+                        "saved_analyses_fts JOIN saved_analyses a ON a.seq = saved_analyses_fts.rowid",
+                        "saved_analyses_fts MATCH ?1",
+                        "bm25(saved_analyses_fts)",
+                        format!("\"{}\"", terms.replace('"', "\"\"")),
+                    )
+                } else {
+                    (
+                        "saved_analyses a",
+                        "(a.ai_analysis LIKE ?1 ESCAPE '\\' OR a.recommendation LIKE ?1 ESCAPE '\\')",
+                        "LENGTH(REPLACE(COALESCE(a.ai_analysis, ''), ?7, '')) - LENGTH(COALESCE(a.ai_analysis, '')) \
+                         + LENGTH(REPLACE(a.recommendation, ?7, '')) - LENGTH(a.recommendation)",
+                        pattern,
+                    )
+                };
+                let filters = "(?2 IS NULL OR a.stock_code = ?2) \
+                     AND (?3 IS NULL OR a.analysis_date >= ?3) \
+                     AND (?4 IS NULL OR a.analysis_date <= ?4)";
+
+                let total: i64 = sqlx::query(&format!(
+                    "SELECT COUNT(*) AS total FROM {} WHERE {} AND {}",
+                    source, condition, filters
+                ))
+                .bind(&matcher)
+                .bind(stock_code)
+                .bind(query.start_date)
+                .bind(query.end_date)
+                .fetch_one(pool)
+                .await?
+                .get("total");
+
+                // ?7 is only referenced by the LIKE ranking; SQLite ignores unused arguments
+                let rows = sqlx::query(&format!(
+                    "SELECT a.* FROM {} WHERE {} AND {} ORDER BY {}, a.created_at DESC LIMIT ?5 OFFSET ?6",
+                    source, condition, filters, rank
+                ))
+                .bind(&matcher)
+                .bind(stock_code)
+                .bind(query.start_date)
+                .bind(query.end_date)
+                .bind(limit)
+                .bind(offset)
+                .bind(terms)
+                .fetch_all(pool)
+                .await?;
+
+                (rows.iter().map(sqlite_saved_analysis).collect(), total)
+            }
+            Database::Postgres(pool) => {
+                let filters = r#"
+                    (to_tsvector('simple', COALESCE(ai_analysis, '') || ' ' || recommendation)
+                        @@ plainto_tsquery('simple', $1)
+                     OR ai_analysis ILIKE $2 ESCAPE '\' OR recommendation ILIKE $2 ESCAPE '\')
+                    AND ($3::VARCHAR IS NULL OR stock_code = $3)
+                    AND ($4::TIMESTAMPTZ IS NULL OR analysis_date >= $4)
+                    AND ($5::TIMESTAMPTZ IS NULL OR analysis_date <= $5)
+                "#;
+
+                let total: i64 = sqlx::query(&format!(
+                    "SELECT COUNT(*) AS total FROM saved_analyses WHERE {}",
+                    filters
+                ))
+                .bind(terms)
+                .bind(&pattern)
+                .bind(stock_code)
+                .bind(query.start_date)
+                .bind(query.end_date)
+                .fetch_one(pool)
+                .await?
+                .get("total");
+
+                let rows = sqlx::query(&format!(
+                    r#"
+                    SELECT * FROM saved_analyses
+                    WHERE {}
+                    ORDER BY ts_rank(
+                        to_tsvector('simple', COALESCE(ai_analysis, '') || ' ' || recommendation),
+                        plainto_tsquery('simple', $1)
+                    ) DESC, created_at DESC
+                    LIMIT $6 OFFSET $7
+                    "#,
+                    filters
+                ))
+                .bind(terms)
+                .bind(&pattern)
+                .bind(stock_code)
+                .bind(query.start_date)
+                .bind(query.end_date)
+                .bind(limit)
+                .bind(offset)
+                .fetch_all(pool)
+                .await?;
+
+                (rows.iter().map(postgres_saved_analysis).collect(), total)
+            }
+        };
+
+        Ok(HistoryResponse {
+            analyses,
+            total,
+            query: query.clone(),
+        })
+    }
+
     pub async fn get_analysis_by_id(&self, id: Uuid) -> Result<Option<SavedAnalysis>, sqlx::Error> {
         match self {
             Database::Sqlite(pool) => {
@@ -347,6 +527,8 @@ impl Database {
                                 .unwrap_or_default(),
                             ai_provider: row.get("ai_provider"),
                             ai_model: row.get("ai_model"),
+                            ai_tokens: row.get("ai_tokens"),
+                            ai_latency_ms: row.get("ai_latency_ms"),
                             created_at: row.get("created_at"),
                         };
                         Ok(Some(analysis))
@@ -356,38 +538,417 @@ impl Database {
             }
             Database::Postgres(pool) => {
                 let query = "SELECT * FROM saved_analyses WHERE id = $1";
-                match sqlx::query(query).bind(id).fetch_optional(pool).await? {
-                    Some(row) => {
-                        let analysis = SavedAnalysis {
-                            id: row.get("id"),
-                            stock_code: row.get("stock_code"),
-                            stock_name: row.get("stock_name"),
-                            analysis_date: row.get("analysis_date"),
-                            price_info: serde_json::from_value(row.get("price_info"))
-                                .unwrap_or_default(),
-                            technical: serde_json::from_value(row.get("technical"))
-                                .unwrap_or_default(),
-                            fundamental: serde_json::from_value(row.get("fundamental"))
-                                .unwrap_or_default(),
-                            sentiment: serde_json::from_value(row.get("sentiment"))
-                                .unwrap_or_default(),
-                            scores: serde_json::from_value(row.get("scores")).unwrap_or_default(),
-                            recommendation: row.get("recommendation"),
-                            ai_analysis: row.get("ai_analysis"),
-                            data_quality: serde_json::from_value(row.get("data_quality"))
-                                .unwrap_or_default(),
-                            ai_provider: row.get("ai_provider"),
-                            ai_model: row.get("ai_model"),
-                            created_at: row.get("created_at"),
-                        };
-                        Ok(Some(analysis))
-                    }
-                    None => Ok(None),
-                }
+                let row = sqlx::query(query).bind(id).fetch_optional(pool).await?;
+                Ok(row.as_ref().map(postgres_saved_analysis))
+            }
+        }
+    }
+
+    /// 按提供商与模型汇总分析日期范围内的AI用量，费用由调用方按配置价格计算
+    pub async fn get_ai_usage(
+        &self,
+        start_date: Option<DateTime<Utc>>,
+        end_date: Option<DateTime<Utc>>,
+    ) -> Result<Vec<ProviderUsage>, sqlx::Error> {
+        match self {
+            Database::Sqlite(pool) => {
+                let rows = sqlx::query(
+                    r#"
+                    SELECT COALESCE(ai_provider, 'unknown') AS provider, ai_model AS model,
+                           COUNT(*) AS analyses, COUNT(ai_tokens) AS ai_analyses,
+                           CAST(COALESCE(SUM(ai_tokens), 0) AS INTEGER) AS total_tokens,
+                           CAST(AVG(ai_latency_ms) AS REAL) AS avg_latency_ms
+                    FROM saved_analyses
+                    WHERE (?1 IS NULL OR analysis_date >= ?1)
+                      AND (?2 IS NULL OR analysis_date <= ?2)
+                    GROUP BY provider, model
+                    ORDER BY total_tokens DESC, provider
+                    "#,
+                )
+                .bind(start_date)
+                .bind(end_date)
+                .fetch_all(pool)
+                .await?;
+
+                Ok(rows
+                    .iter()
+                    .map(|row| ProviderUsage {
+                        provider: row.get("provider"),
+                        model: row.get("model"),
+                        analyses: row.get("analyses"),
+                        ai_analyses: row.get("ai_analyses"),
+                        total_tokens: row.get("total_tokens"),
+                        avg_latency_ms: row.get("avg_latency_ms"),
+                        estimated_cost: None,
+                    })
+                    .collect())
+            }
+            Database::Postgres(pool) => {
+                let rows = sqlx::query(
+                    r#"
+                    SELECT COALESCE(ai_provider, 'unknown') AS provider, ai_model AS model,
+                           COUNT(*) AS analyses, COUNT(ai_tokens) AS ai_analyses,
+                           COALESCE(SUM(ai_tokens), 0)::BIGINT AS total_tokens,
+                           AVG(ai_latency_ms)::DOUBLE PRECISION AS avg_latency_ms
+                    FROM saved_analyses
+                    WHERE ($1::TIMESTAMPTZ IS NULL OR analysis_date >= $1)
+                      AND ($2::TIMESTAMPTZ IS NULL OR analysis_date <= $2)
+                    GROUP BY 1, 2
+                    ORDER BY total_tokens DESC, provider
+                    "#,
+                )
+                .bind(start_date)
+                .bind(end_date)
+                .fetch_all(pool)
+                .await?;
+
+                Ok(rows
+                    .iter()
+                    .map(|row| ProviderUsage {
+                        provider: row.get("provider"),
+                        model: row.get("model"),
+                        analyses: row.get("analyses"),
+                        ai_analyses: row.get("ai_analyses"),
+                        total_tokens: row.get("total_tokens"),
+                        avg_latency_ms: row.get("avg_latency_ms"),
+                        estimated_cost: None,
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    /// 按投资建议分组统计分析日期范围内的记录数，数量多的在前
+    pub async fn get_recommendation_counts(
+        &self,
+        start_date: Option<DateTime<Utc>>,
+        end_date: Option<DateTime<Utc>>,
+    ) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        let counts = match self {
+            Database::Sqlite(pool) => sqlx::query(
+                r#"
+                SELECT recommendation, COUNT(*) AS count
+                FROM saved_analyses
+                WHERE (?1 IS NULL OR analysis_date >= ?1)
+                  AND (?2 IS NULL OR analysis_date <= ?2)
+                GROUP BY recommendation
+                ORDER BY count DESC, recommendation
+                "#,
+            )
+            .bind(start_date)
+            .bind(end_date)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| (row.get("recommendation"), row.get("count")))
+            .collect(),
+            Database::Postgres(pool) => sqlx::query(
+                r#"
+                SELECT recommendation, COUNT(*) AS count
+                FROM saved_analyses
+                WHERE ($1::TIMESTAMPTZ IS NULL OR analysis_date >= $1)
+                  AND ($2::TIMESTAMPTZ IS NULL OR analysis_date <= $2)
+                GROUP BY recommendation
+                ORDER BY count DESC, recommendation
+                "#,
+            )
+            .bind(start_date)
+            .bind(end_date)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| (row.get("recommendation"), row.get("count")))
+            .collect(),
+        };
+
+        Ok(counts)
+    }
+
+    /// 按分析日期（UTC）统计每天的记录数与平均综合评分，日期升序
+    pub async fn get_daily_scores(
+        &self,
+        start_date: Option<DateTime<Utc>>,
+        end_date: Option<DateTime<Utc>>,
+    ) -> Result<Vec<DailyScore>, sqlx::Error> {
+        let rows: Vec<(String, i64, Option<f64>)> = match self {
+            Database::Sqlite(pool) => sqlx::query(
+                r#"
+                SELECT SUBSTR(analysis_date, 1, 10) AS day, COUNT(*) AS analyses,
+                       CAST(AVG(json_extract(scores, '$.comprehensive')) AS REAL) AS avg_comprehensive
+                FROM saved_analyses
+                WHERE (?1 IS NULL OR analysis_date >= ?1)
+                  AND (?2 IS NULL OR analysis_date <= ?2)
+                GROUP BY day
+                ORDER BY day
+                "#,
+            )
+            .bind(start_date)
+            .bind(end_date)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| {
+                (
+                    row.get("day"),
+                    row.get("analyses"),
+                    row.get("avg_comprehensive"),
+                )
+            })
+            .collect(),
+            Database::Postgres(pool) => sqlx::query(
+                r#"
+                SELECT TO_CHAR(analysis_date AT TIME ZONE 'UTC', 'YYYY-MM-DD') AS day,
+                       COUNT(*) AS analyses,
+                       AVG((scores->>'comprehensive')::DOUBLE PRECISION) AS avg_comprehensive
+                FROM saved_analyses
+                WHERE ($1::TIMESTAMPTZ IS NULL OR analysis_date >= $1)
+                  AND ($2::TIMESTAMPTZ IS NULL OR analysis_date <= $2)
+                GROUP BY day
+                ORDER BY day
+                "#,
+            )
+            .bind(start_date)
+            .bind(end_date)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| {
+                (
+                    row.get("day"),
+                    row.get("analyses"),
+                    row.get("avg_comprehensive"),
+                )
+            })
+            .collect(),
+        };
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(day, analyses, avg_comprehensive)| {
+                Some(DailyScore {
+                    date: NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()?,
+                    analyses,
+                    avg_comprehensive,
+                })
+            })
+            .collect())
+    }
+
+    /// 分析次数最多的股票，次数相同时最近分析的在前
+    pub async fn get_most_analyzed_stocks(
+        &self,
+        start_date: Option<DateTime<Utc>>,
+        end_date: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<Vec<StockActivity>, sqlx::Error> {
+        let stocks = match self {
+            Database::Sqlite(pool) => sqlx::query(
+                r#"
+                SELECT stock_code, MAX(stock_name) AS stock_name, COUNT(*) AS analyses,
+                       CAST(AVG(json_extract(scores, '$.comprehensive')) AS REAL) AS avg_comprehensive,
+                       MAX(analysis_date) AS last_analyzed
+                FROM saved_analyses
+                WHERE (?1 IS NULL OR analysis_date >= ?1)
+                  AND (?2 IS NULL OR analysis_date <= ?2)
+                GROUP BY stock_code
+                ORDER BY analyses DESC, last_analyzed DESC
+                LIMIT ?3
+                "#,
+            )
+            .bind(start_date)
+            .bind(end_date)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| StockActivity {
+                stock_code: row.get("stock_code"),
+                stock_name: row.get("stock_name"),
+                analyses: row.get("analyses"),
+                avg_comprehensive: row.get("avg_comprehensive"),
+                last_analyzed: row.get("last_analyzed"),
+            })
+            .collect(),
+            Database::Postgres(pool) => sqlx::query(
+                r#"
+                SELECT stock_code, MAX(stock_name) AS stock_name, COUNT(*) AS analyses,
+                       AVG((scores->>'comprehensive')::DOUBLE PRECISION) AS avg_comprehensive,
+                       MAX(analysis_date) AS last_analyzed
+                FROM saved_analyses
+                WHERE ($1::TIMESTAMPTZ IS NULL OR analysis_date >= $1)
+                  AND ($2::TIMESTAMPTZ IS NULL OR analysis_date <= $2)
+                GROUP BY stock_code
+                ORDER BY analyses DESC, last_analyzed DESC
+                LIMIT $3
+                "#,
+            )
+            .bind(start_date)
+            .bind(end_date)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| StockActivity {
+                stock_code: row.get("stock_code"),
+                stock_name: row.get("stock_name"),
+                analyses: row.get("analyses"),
+                avg_comprehensive: row.get("avg_comprehensive"),
+                last_analyzed: row.get("last_analyzed"),
+            })
+            .collect(),
+        };
+
+        Ok(stocks)
+    }
+
+    /// 按股票代码和分析日期范围获取保存的分析，按分析日期升序
+    pub async fn get_analyses_in_range(
+        &self,
+        stock_code: Option<&str>,
+        start_date: Option<DateTime<Utc>>,
+        end_date: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<Vec<SavedAnalysis>, sqlx::Error> {
+        match self {
+            Database::Sqlite(pool) => {
+                let rows = sqlx::query(
+                    r#"
+                    SELECT * FROM saved_analyses
+                    WHERE (?1 IS NULL OR stock_code = ?1)
+                      AND (?2 IS NULL OR analysis_date >= ?2)
+                      AND (?3 IS NULL OR analysis_date <= ?3)
+                    ORDER BY analysis_date ASC
+                    LIMIT ?4
+                    "#,
+                )
+                .bind(stock_code)
+                .bind(start_date)
+                .bind(end_date)
+                .bind(limit)
+                .fetch_all(pool)
+                .await?;
+
+                Ok(rows
+                    .iter()
+                    .map(|row| SavedAnalysis {
+                        id: row.get("id"),
+                        stock_code: row.get("stock_code"),
+                        stock_name: row.get("stock_name"),
+                        analysis_date: row.get("analysis_date"),
+                        price_info: row.get("price_info"),
+                        technical: row.get("technical"),
+                        fundamental: row.get("fundamental"),
+                        sentiment: row.get("sentiment"),
+                        scores: row.get("scores"),
+                        recommendation: row.get("recommendation"),
+                        ai_analysis: row.get("ai_analysis"),
+                        data_quality: row.get("data_quality"),
+                        ai_provider: row.get("ai_provider"),
+                        ai_model: row.get("ai_model"),
+                        ai_tokens: row.get("ai_tokens"),
+                        ai_latency_ms: row.get("ai_latency_ms"),
+                        created_at: row.get("created_at"),
+                    })
+                    .collect())
+            }
+            Database::Postgres(pool) => {
+                let rows = sqlx::query(
+                    r#"
+                    SELECT * FROM saved_analyses
+                    WHERE ($1::VARCHAR IS NULL OR stock_code = $1)
+                      AND ($2::TIMESTAMPTZ IS NULL OR analysis_date >= $2)
+                      AND ($3::TIMESTAMPTZ IS NULL OR analysis_date <= $3)
+                    ORDER BY analysis_date ASC
+                    LIMIT $4
+                    "#,
+                )
+                .bind(stock_code)
+                .bind(start_date)
+                .bind(end_date)
+                .bind(limit)
+                .fetch_all(pool)
+                .await?;
+
+                Ok(rows
+                    .iter()
+                    .map(|row| SavedAnalysis {
+                        id: row.get::<Uuid, _>("id").to_string(),
+                        stock_code: row.get("stock_code"),
+                        stock_name: row.get("stock_name"),
+                        analysis_date: row.get("analysis_date"),
+                        price_info: row.get("price_info"),
+                        technical: row.get("technical"),
+                        fundamental: row.get("fundamental"),
+                        sentiment: row.get("sentiment"),
+                        scores: row.get("scores"),
+                        recommendation: row.get("recommendation"),
+                        ai_analysis: row.get("ai_analysis"),
+                        data_quality: row.get("data_quality"),
+                        ai_provider: row.get("ai_provider"),
+                        ai_model: row.get("ai_model"),
+                        ai_tokens: row.get("ai_tokens"),
+                        ai_latency_ms: row.get("ai_latency_ms"),
+                        created_at: row.get("created_at"),
+                    })
+                    .collect())
             }
         }
     }
 
+    pub async fn update_analysis_scores(
+        &self,
+        id: &str,
+        scores: &AnalysisScores,
+    ) -> Result<bool, sqlx::Error> {
+        let scores = serde_json::to_value(scores).unwrap_or_default();
+
+        let result = match self {
+            Database::Sqlite(pool) => {
+                sqlx::query("UPDATE saved_analyses SET scores = ?1 WHERE id = ?2")
+                    .bind(scores)
+                    .bind(id)
+                    .execute(pool)
+                    .await?
+                    .rows_affected()
+            }
+            Database::Postgres(pool) => {
+                let Ok(id) = Uuid::parse_str(id) else {
+                    return Ok(false);
+                };
+                sqlx::query("UPDATE saved_analyses SET scores = $1 WHERE id = $2")
+                    .bind(scores)
+                    .bind(id)
+                    .execute(pool)
+                    .await?
+                    .rows_affected()
+            }
+        };
+
+        Ok(result > 0)
+    }
+
+    /// 删除分析日期早于 cutoff 的分析记录，返回删除的行数
+    pub async fn delete_analyses_before(&self, cutoff: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let deleted = match self {
+            Database::Sqlite(pool) => {
+                sqlx::query("DELETE FROM saved_analyses WHERE analysis_date < ?1")
+                    .bind(cutoff)
+                    .execute(pool)
+                    .await?
+                    .rows_affected()
+            }
+            Database::Postgres(pool) => {
+                sqlx::query("DELETE FROM saved_analyses WHERE analysis_date < $1")
+                    .bind(cutoff)
+                    .execute(pool)
+                    .await?
+                    .rows_affected()
+            }
+        };
+
+        Ok(deleted)
+    }
+
     pub async fn save_configuration(
         &self,
         config_type: &str,
@@ -462,27 +1023,1124 @@ impl Database {
         Ok(false)
     }
 
-    pub async fn create_tables(&self) -> Result<(), sqlx::Error> {
+    pub async fn save_signals(
+        &self,
+        stock_code: &str,
+        stock_name: &str,
+        signals: &[TradingSignal],
+    ) -> Result<(), sqlx::Error> {
         match self {
             Database::Sqlite(pool) => {
-                // Create tables for SQLite
-                sqlx::query(
-                    r#"
-                    CREATE TABLE IF NOT EXISTS saved_analyses (
-                        id TEXT PRIMARY KEY,
-                        stock_code TEXT NOT NULL,
+                for signal in signals {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO trading_signals (
+                            id, stock_code, stock_name, strategy_name, signal_type, strength,
+                            price, signal_time, reason, confidence, risk_level, expected_profit,
+                            stop_loss, take_profit, created_at
+                        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+                        "#,
+                    )
+                    .bind(Uuid::new_v4().to_string())
+                    .bind(stock_code)
+                    .bind(stock_name)
+                    .bind(&signal.strategy_name)
+                    .bind(&signal.signal_type)
+                    .bind(signal.strength)
+                    .bind(signal.price)
+                    .bind(signal.timestamp)
+                    .bind(&signal.reason)
+                    .bind(signal.confidence)
+                    .bind(&signal.risk_level)
+                    .bind(signal.expected_profit)
+                    .bind(signal.stop_loss)
+                    .bind(signal.take_profit)
+                    .bind(Utc::now())
+                    .execute(pool)
+                    .await?;
+                }
+            }
+            Database::Postgres(pool) => {
+                for signal in signals {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO trading_signals (
+                            id, stock_code, stock_name, strategy_name, signal_type, strength,
+                            price, signal_time, reason, confidence, risk_level, expected_profit,
+                            stop_loss, take_profit, created_at
+                        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+                        "#,
+                    )
+                    .bind(Uuid::new_v4())
+                    .bind(stock_code)
+                    .bind(stock_name)
+                    .bind(&signal.strategy_name)
+                    .bind(&signal.signal_type)
+                    .bind(signal.strength)
+                    .bind(signal.price)
+                    .bind(signal.timestamp)
+                    .bind(&signal.reason)
+                    .bind(signal.confidence)
+                    .bind(&signal.risk_level)
+                    .bind(signal.expected_profit)
+                    .bind(signal.stop_loss)
+                    .bind(signal.take_profit)
+                    .bind(Utc::now())
+                    .execute(pool)
+                    .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_signal_history(
+        &self,
+        stock_code: &str,
+        query: &SignalHistoryQuery,
+    ) -> Result<SignalHistoryResponse, sqlx::Error> {
+        let limit = query.limit.unwrap_or(50).clamp(1, 500);
+        let offset = query.offset.unwrap_or(0).max(0);
+
+        let (signals, total) = match self {
+            Database::Sqlite(pool) => {
+                let total = sqlx::query(
+                    "SELECT COUNT(*) as total FROM trading_signals WHERE stock_code = ?1",
+                )
+                .bind(stock_code)
+                .fetch_one(pool)
+                .await?
+                .get::<i64, _>("total");
+
+                let rows = sqlx::query(
+                    "SELECT * FROM trading_signals WHERE stock_code = ?1 ORDER BY signal_time DESC LIMIT ?2 OFFSET ?3",
+                )
+                .bind(stock_code)
+                .bind(limit)
+                .bind(offset)
+                .fetch_all(pool)
+                .await?;
+
+                let signals = rows
+                    .iter()
+                    .map(|row| SavedSignal {
+                        id: row.get("id"),
+                        stock_code: row.get("stock_code"),
+                        stock_name: row.get("stock_name"),
+                        signal: TradingSignal {
+                            strategy_name: row.get("strategy_name"),
+                            signal_type: row.get("signal_type"),
+                            strength: row.get("strength"),
+                            price: row.get("price"),
+                            timestamp: row.get("signal_time"),
+                            reason: row.get("reason"),
+                            confidence: row.get("confidence"),
+                            risk_level: row.get("risk_level"),
+                            expected_profit: row.get("expected_profit"),
+                            stop_loss: row.get("stop_loss"),
+                            take_profit: row.get("take_profit"),
+                            suggested_position_pct: 0.0,
+                        },
+                        created_at: row.get("created_at"),
+                    })
+                    .collect();
+
+                (signals, total)
+            }
+            Database::Postgres(pool) => {
+                let total = sqlx::query(
+                    "SELECT COUNT(*) as total FROM trading_signals WHERE stock_code = $1",
+                )
+                .bind(stock_code)
+                .fetch_one(pool)
+                .await?
+                .get::<i64, _>("total");
+
+                let rows = sqlx::query(
+                    "SELECT * FROM trading_signals WHERE stock_code = $1 ORDER BY signal_time DESC LIMIT $2 OFFSET $3",
+                )
+                .bind(stock_code)
+                .bind(limit)
+                .bind(offset)
+                .fetch_all(pool)
+                .await?;
+
+                let signals = rows
+                    .iter()
+                    .map(|row| SavedSignal {
+                        id: row.get::<Uuid, _>("id").to_string(),
+                        stock_code: row.get("stock_code"),
+                        stock_name: row.get("stock_name"),
+                        signal: TradingSignal {
+                            strategy_name: row.get("strategy_name"),
+                            signal_type: row.get("signal_type"),
+                            strength: row.get("strength"),
+                            price: row.get("price"),
+                            timestamp: row.get("signal_time"),
+                            reason: row.get("reason"),
+                            confidence: row.get("confidence"),
+                            risk_level: row.get("risk_level"),
+                            expected_profit: row.get("expected_profit"),
+                            stop_loss: row.get("stop_loss"),
+                            take_profit: row.get("take_profit"),
+                            suggested_position_pct: 0.0,
+                        },
+                        created_at: row.get("created_at"),
+                    })
+                    .collect();
+
+                (signals, total)
+            }
+        };
+
+        Ok(SignalHistoryResponse {
+            stock_code: stock_code.to_string(),
+            signals,
+            total,
+        })
+    }
+
+    pub async fn create_paper_account(&self, account: &PaperAccount) -> Result<(), sqlx::Error> {
+        let stocks = serde_json::to_value(&account.auto_execute_stocks).unwrap_or_default();
+        match self {
+            Database::Sqlite(pool) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO paper_accounts (
+                        id, name, initial_cash, position_fraction, auto_execute_stocks,
+                        created_at, updated_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                    "#,
+                )
+                .bind(&account.id)
+                .bind(&account.name)
+                .bind(account.initial_cash)
+                .bind(account.position_fraction)
+                .bind(stocks)
+                .bind(account.created_at)
+                .bind(account.updated_at)
+                .execute(pool)
+                .await?;
+            }
+            Database::Postgres(pool) => {
+                let id =
+                    Uuid::parse_str(&account.id).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                sqlx::query(
+                    r#"
+                    INSERT INTO paper_accounts (
+                        id, name, initial_cash, position_fraction, auto_execute_stocks,
+                        created_at, updated_at
+                    ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+                    "#,
+                )
+                .bind(id)
+                .bind(&account.name)
+                .bind(account.initial_cash)
+                .bind(account.position_fraction)
+                .bind(stocks)
+                .bind(account.created_at)
+                .bind(account.updated_at)
+                .execute(pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn list_paper_accounts(&self) -> Result<Vec<PaperAccount>, sqlx::Error> {
+        let accounts = match self {
+            Database::Sqlite(pool) => {
+                sqlx::query("SELECT * FROM paper_accounts ORDER BY created_at ASC")
+                    .fetch_all(pool)
+                    .await?
+                    .iter()
+                    .map(|row| PaperAccount {
+                        id: row.get("id"),
+                        name: row.get("name"),
+                        initial_cash: row.get("initial_cash"),
+                        position_fraction: row.get("position_fraction"),
+                        auto_execute_stocks: serde_json::from_value(row.get("auto_execute_stocks"))
+                            .unwrap_or_default(),
+                        created_at: row.get("created_at"),
+                        updated_at: row.get("updated_at"),
+                    })
+                    .collect()
+            }
+            Database::Postgres(pool) => {
+                sqlx::query("SELECT * FROM paper_accounts ORDER BY created_at ASC")
+                    .fetch_all(pool)
+                    .await?
+                    .iter()
+                    .map(|row| PaperAccount {
+                        id: row.get::<Uuid, _>("id").to_string(),
+                        name: row.get("name"),
+                        initial_cash: row.get("initial_cash"),
+                        position_fraction: row.get("position_fraction"),
+                        auto_execute_stocks: serde_json::from_value(row.get("auto_execute_stocks"))
+                            .unwrap_or_default(),
+                        created_at: row.get("created_at"),
+                        updated_at: row.get("updated_at"),
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(accounts)
+    }
+
+    pub async fn get_paper_account(&self, id: &str) -> Result<Option<PaperAccount>, sqlx::Error> {
+        Ok(self
+            .list_paper_accounts()
+            .await?
+            .into_iter()
+            .find(|account| account.id == id))
+    }
+
+    pub async fn update_paper_auto_execute(
+        &self,
+        id: &str,
+        stock_codes: &[String],
+    ) -> Result<bool, sqlx::Error> {
+        let stocks = serde_json::to_value(stock_codes).unwrap_or_default();
+        let result = match self {
+            Database::Sqlite(pool) => sqlx::query(
+                "UPDATE paper_accounts SET auto_execute_stocks = ?1, updated_at = ?2 WHERE id = ?3",
+            )
+            .bind(stocks)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(pool)
+            .await?
+            .rows_affected(),
+            Database::Postgres(pool) => {
+                let Ok(id) = Uuid::parse_str(id) else {
+                    return Ok(false);
+                };
+                sqlx::query(
+                    "UPDATE paper_accounts SET auto_execute_stocks = $1, updated_at = $2 WHERE id = $3",
+                )
+                .bind(stocks)
+                .bind(Utc::now())
+                .bind(id)
+                .execute(pool)
+                .await?
+                .rows_affected()
+            }
+        };
+
+        Ok(result > 0)
+    }
+
+    pub async fn save_paper_trade(&self, trade: &PaperTrade) -> Result<(), sqlx::Error> {
+        match self {
+            Database::Sqlite(pool) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO paper_trades (
+                        id, account_id, stock_code, stock_name, side, quantity, price, amount,
+                        signal, realized_pnl, executed_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                    "#,
+                )
+                .bind(&trade.id)
+                .bind(&trade.account_id)
+                .bind(&trade.stock_code)
+                .bind(&trade.stock_name)
+                .bind(&trade.side)
+                .bind(trade.quantity)
+                .bind(trade.price)
+                .bind(trade.amount)
+                .bind(&trade.signal)
+                .bind(trade.realized_pnl)
+                .bind(trade.executed_at)
+                .execute(pool)
+                .await?;
+            }
+            Database::Postgres(pool) => {
+                let id =
+                    Uuid::parse_str(&trade.id).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                let account_id = Uuid::parse_str(&trade.account_id)
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                sqlx::query(
+                    r#"
+                    INSERT INTO paper_trades (
+                        id, account_id, stock_code, stock_name, side, quantity, price, amount,
+                        signal, realized_pnl, executed_at
+                    ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                    "#,
+                )
+                .bind(id)
+                .bind(account_id)
+                .bind(&trade.stock_code)
+                .bind(&trade.stock_name)
+                .bind(&trade.side)
+                .bind(trade.quantity)
+                .bind(trade.price)
+                .bind(trade.amount)
+                .bind(&trade.signal)
+                .bind(trade.realized_pnl)
+                .bind(trade.executed_at)
+                .execute(pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 按成交时间升序返回账户的全部模拟成交
+    pub async fn get_paper_trades(&self, account_id: &str) -> Result<Vec<PaperTrade>, sqlx::Error> {
+        let trades = match self {
+            Database::Sqlite(pool) => sqlx::query(
+                "SELECT * FROM paper_trades WHERE account_id = ?1 ORDER BY executed_at ASC",
+            )
+            .bind(account_id)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| PaperTrade {
+                id: row.get("id"),
+                account_id: row.get("account_id"),
+                stock_code: row.get("stock_code"),
+                stock_name: row.get("stock_name"),
+                side: row.get("side"),
+                quantity: row.get("quantity"),
+                price: row.get("price"),
+                amount: row.get("amount"),
+                signal: row.get("signal"),
+                realized_pnl: row.get("realized_pnl"),
+                executed_at: row.get("executed_at"),
+            })
+            .collect(),
+            Database::Postgres(pool) => {
+                let Ok(account_id) = Uuid::parse_str(account_id) else {
+                    return Ok(Vec::new());
+                };
+                sqlx::query(
+                    "SELECT * FROM paper_trades WHERE account_id = $1 ORDER BY executed_at ASC",
+                )
+                .bind(account_id)
+                .fetch_all(pool)
+                .await?
+                .iter()
+                .map(|row| PaperTrade {
+                    id: row.get::<Uuid, _>("id").to_string(),
+                    account_id: row.get::<Uuid, _>("account_id").to_string(),
+                    stock_code: row.get("stock_code"),
+                    stock_name: row.get("stock_name"),
+                    side: row.get("side"),
+                    quantity: row.get("quantity"),
+                    price: row.get("price"),
+                    amount: row.get("amount"),
+                    signal: row.get("signal"),
+                    realized_pnl: row.get("realized_pnl"),
+                    executed_at: row.get("executed_at"),
+                })
+                .collect()
+            }
+        };
+
+        Ok(trades)
+    }
+
+    /// 新增用户，用户名或邮箱重复时返回唯一约束错误
+    pub async fn create_user(&self, user: &User) -> Result<(), sqlx::Error> {
+        match self {
+            Database::Sqlite(pool) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO users (
+                        id, username, email, password_hash, created_at, last_login,
+                        is_admin, api_usage, is_active
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                    "#,
+                )
+                .bind(&user.id)
+                .bind(&user.username)
+                .bind(&user.email)
+                .bind(&user.password_hash)
+                .bind(user.created_at)
+                .bind(user.last_login)
+                .bind(user.is_admin)
+                .bind(user.api_usage)
+                .bind(user.is_active)
+                .execute(pool)
+                .await?;
+            }
+            Database::Postgres(pool) => {
+                let id = Uuid::parse_str(&user.id).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                sqlx::query(
+                    r#"
+                    INSERT INTO users (
+                        id, username, email, password_hash, created_at, last_login,
+                        is_admin, api_usage, is_active
+                    ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                    "#,
+                )
+                .bind(id)
+                .bind(&user.username)
+                .bind(&user.email)
+                .bind(&user.password_hash)
+                .bind(user.created_at)
+                .bind(user.last_login)
+                .bind(user.is_admin)
+                .bind(user.api_usage)
+                .bind(user.is_active)
+                .execute(pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_user(&self, username: &str) -> Result<Option<User>, sqlx::Error> {
+        let user = match self {
+            Database::Sqlite(pool) => sqlx::query("SELECT * FROM users WHERE username = ?1")
+                .bind(username)
+                .fetch_optional(pool)
+                .await?
+                .map(|row| User {
+                    id: row.get("id"),
+                    username: row.get("username"),
+                    email: row.get("email"),
+                    password_hash: row.get("password_hash"),
+                    created_at: row.get("created_at"),
+                    last_login: row.get("last_login"),
+                    is_admin: row.get("is_admin"),
+                    api_usage: row.get("api_usage"),
+                    is_active: row.get("is_active"),
+                }),
+            Database::Postgres(pool) => sqlx::query("SELECT * FROM users WHERE username = $1")
+                .bind(username)
+                .fetch_optional(pool)
+                .await?
+                .map(|row| User {
+                    id: row.get::<Uuid, _>("id").to_string(),
+                    username: row.get("username"),
+                    email: row.get("email"),
+                    password_hash: row.get("password_hash"),
+                    created_at: row.get("created_at"),
+                    last_login: row.get("last_login"),
+                    is_admin: row.get("is_admin"),
+                    api_usage: row.get("api_usage"),
+                    is_active: row.get("is_active"),
+                }),
+        };
+
+        Ok(user)
+    }
+
+    /// 检查用户名与邮箱是否已被占用，返回 (用户名已存在, 邮箱已存在)
+    pub async fn find_user_conflicts(
+        &self,
+        username: &str,
+        email: &str,
+    ) -> Result<(bool, bool), sqlx::Error> {
+        let rows: Vec<(String, String)> = match self {
+            Database::Sqlite(pool) => {
+                sqlx::query_as(
+                    "SELECT username, email FROM users WHERE username = ?1 OR email = ?2",
+                )
+                .bind(username)
+                .bind(email)
+                .fetch_all(pool)
+                .await?
+            }
+            Database::Postgres(pool) => {
+                sqlx::query_as(
+                    "SELECT username, email FROM users WHERE username = $1 OR email = $2",
+                )
+                .bind(username)
+                .bind(email)
+                .fetch_all(pool)
+                .await?
+            }
+        };
+
+        Ok((
+            rows.iter().any(|(existing, _)| existing == username),
+            rows.iter().any(|(_, existing)| existing == email),
+        ))
+    }
+
+    /// 记录登录时间并累加调用次数
+    pub async fn record_login(&self, username: &str, at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        match self {
+            Database::Sqlite(pool) => {
+                sqlx::query(
+                    "UPDATE users SET last_login = ?1, api_usage = api_usage + 1 WHERE username = ?2",
+                )
+                .bind(at)
+                .bind(username)
+                .execute(pool)
+                .await?;
+            }
+            Database::Postgres(pool) => {
+                sqlx::query(
+                    "UPDATE users SET last_login = $1, api_usage = api_usage + 1 WHERE username = $2",
+                )
+                .bind(at)
+                .bind(username)
+                .execute(pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 添加自选股；同一用户已有该股票时只更新备注
+    pub async fn add_watchlist_entry(&self, entry: &WatchlistEntry) -> Result<(), sqlx::Error> {
+        match self {
+            Database::Sqlite(pool) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO watchlist (owner, stock_code, note, created_at)
+                    VALUES (?1, ?2, ?3, ?4)
+                    ON CONFLICT(owner, stock_code) DO UPDATE SET note = excluded.note
+                    "#,
+                )
+                .bind(&entry.owner)
+                .bind(&entry.stock_code)
+                .bind(&entry.note)
+                .bind(entry.created_at)
+                .execute(pool)
+                .await?;
+            }
+            Database::Postgres(pool) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO watchlist (owner, stock_code, note, created_at)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (owner, stock_code) DO UPDATE SET note = EXCLUDED.note
+                    "#,
+                )
+                .bind(&entry.owner)
+                .bind(&entry.stock_code)
+                .bind(&entry.note)
+                .bind(entry.created_at)
+                .execute(pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 按添加时间升序返回用户的自选股
+    pub async fn get_watchlist(&self, owner: &str) -> Result<Vec<WatchlistEntry>, sqlx::Error> {
+        let rows = match self {
+            Database::Sqlite(pool) => {
+                sqlx::query("SELECT * FROM watchlist WHERE owner = ?1 ORDER BY created_at ASC")
+                    .bind(owner)
+                    .fetch_all(pool)
+                    .await?
+                    .iter()
+                    .map(|row| WatchlistEntry {
+                        owner: row.get("owner"),
+                        stock_code: row.get("stock_code"),
+                        note: row.get("note"),
+                        created_at: row.get("created_at"),
+                    })
+                    .collect()
+            }
+            Database::Postgres(pool) => {
+                sqlx::query("SELECT * FROM watchlist WHERE owner = $1 ORDER BY created_at ASC")
+                    .bind(owner)
+                    .fetch_all(pool)
+                    .await?
+                    .iter()
+                    .map(|row| WatchlistEntry {
+                        owner: row.get("owner"),
+                        stock_code: row.get("stock_code"),
+                        note: row.get("note"),
+                        created_at: row.get("created_at"),
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(rows)
+    }
+
+    /// 所有用户自选股中出现过的股票代码（去重、升序），供定时分析使用
+    pub async fn get_all_watchlist_codes(&self) -> Result<Vec<String>, sqlx::Error> {
+        let codes: Vec<(String,)> = match self {
+            Database::Sqlite(pool) => {
+                sqlx::query_as("SELECT DISTINCT stock_code FROM watchlist ORDER BY stock_code")
+                    .fetch_all(pool)
+                    .await?
+            }
+            Database::Postgres(pool) => {
+                sqlx::query_as("SELECT DISTINCT stock_code FROM watchlist ORDER BY stock_code")
+                    .fetch_all(pool)
+                    .await?
+            }
+        };
+
+        Ok(codes.into_iter().map(|(code,)| code).collect())
+    }
+
+    /// 移除自选股，返回是否存在该条目
+    pub async fn remove_watchlist_entry(
+        &self,
+        owner: &str,
+        stock_code: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = match self {
+            Database::Sqlite(pool) => {
+                sqlx::query("DELETE FROM watchlist WHERE owner = ?1 AND stock_code = ?2")
+                    .bind(owner)
+                    .bind(stock_code)
+                    .execute(pool)
+                    .await?
+                    .rows_affected()
+            }
+            Database::Postgres(pool) => {
+                sqlx::query("DELETE FROM watchlist WHERE owner = $1 AND stock_code = $2")
+                    .bind(owner)
+                    .bind(stock_code)
+                    .execute(pool)
+                    .await?
+                    .rows_affected()
+            }
+        };
+
+        Ok(result > 0)
+    }
+
+    pub async fn create_price_alert(&self, alert: &PriceAlert) -> Result<(), sqlx::Error> {
+        match self {
+            Database::Sqlite(pool) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO alerts (
+                        id, owner, stock_code, alert_condition, webhook_url, condition_met,
+                        last_checked, created_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                    "#,
+                )
+                .bind(&alert.id)
+                .bind(&alert.owner)
+                .bind(&alert.stock_code)
+                .bind(alert.condition.to_string())
+                .bind(&alert.webhook_url)
+                .bind(alert.condition_met)
+                .bind(alert.last_checked)
+                .bind(alert.created_at)
+                .execute(pool)
+                .await?;
+            }
+            Database::Postgres(pool) => {
+                let id =
+                    Uuid::parse_str(&alert.id).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                sqlx::query(
+                    r#"
+                    INSERT INTO alerts (
+                        id, owner, stock_code, alert_condition, webhook_url, condition_met,
+                        last_checked, created_at
+                    ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                    "#,
+                )
+                .bind(id)
+                .bind(&alert.owner)
+                .bind(&alert.stock_code)
+                .bind(alert.condition.to_string())
+                .bind(&alert.webhook_url)
+                .bind(alert.condition_met)
+                .bind(alert.last_checked)
+                .bind(alert.created_at)
+                .execute(pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 全部价格提醒规则，按创建时间升序；条件无法解析的行会被跳过
+    pub async fn get_all_price_alerts(&self) -> Result<Vec<PriceAlert>, sqlx::Error> {
+        let alerts = match self {
+            Database::Sqlite(pool) => sqlx::query("SELECT * FROM alerts ORDER BY created_at ASC")
+                .fetch_all(pool)
+                .await?
+                .iter()
+                .filter_map(|row| {
+                    Some(PriceAlert {
+                        id: row.get("id"),
+                        owner: row.get("owner"),
+                        stock_code: row.get("stock_code"),
+                        condition: row.get::<String, _>("alert_condition").parse().ok()?,
+                        webhook_url: row.get("webhook_url"),
+                        condition_met: row.get("condition_met"),
+                        last_checked: row.get("last_checked"),
+                        created_at: row.get("created_at"),
+                    })
+                })
+                .collect(),
+            Database::Postgres(pool) => sqlx::query("SELECT * FROM alerts ORDER BY created_at ASC")
+                .fetch_all(pool)
+                .await?
+                .iter()
+                .filter_map(|row| {
+                    Some(PriceAlert {
+                        id: row.get::<Uuid, _>("id").to_string(),
+                        owner: row.get("owner"),
+                        stock_code: row.get("stock_code"),
+                        condition: row.get::<String, _>("alert_condition").parse().ok()?,
+                        webhook_url: row.get("webhook_url"),
+                        condition_met: row.get("condition_met"),
+                        last_checked: row.get("last_checked"),
+                        created_at: row.get("created_at"),
+                    })
+                })
+                .collect(),
+        };
+
+        Ok(alerts)
+    }
+
+    pub async fn get_price_alerts(&self, owner: &str) -> Result<Vec<PriceAlert>, sqlx::Error> {
+        Ok(self
+            .get_all_price_alerts()
+            .await?
+            .into_iter()
+            .filter(|alert| alert.owner == owner)
+            .collect())
+    }
+
+    /// 记录一次检查的结果
+    pub async fn update_price_alert_state(
+        &self,
+        id: &str,
+        condition_met: bool,
+        checked_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        match self {
+            Database::Sqlite(pool) => {
+                sqlx::query(
+                    "UPDATE alerts SET condition_met = ?1, last_checked = ?2 WHERE id = ?3",
+                )
+                .bind(condition_met)
+                .bind(checked_at)
+                .bind(id)
+                .execute(pool)
+                .await?;
+            }
+            Database::Postgres(pool) => {
+                let Ok(id) = Uuid::parse_str(id) else {
+                    return Ok(());
+                };
+                sqlx::query(
+                    "UPDATE alerts SET condition_met = $1, last_checked = $2 WHERE id = $3",
+                )
+                .bind(condition_met)
+                .bind(checked_at)
+                .bind(id)
+                .execute(pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 删除价格提醒规则，已触发的记录保留；返回是否存在该规则
+    pub async fn delete_price_alert(&self, owner: &str, id: &str) -> Result<bool, sqlx::Error> {
+        let result = match self {
+            Database::Sqlite(pool) => {
+                sqlx::query("DELETE FROM alerts WHERE owner = ?1 AND id = ?2")
+                    .bind(owner)
+                    .bind(id)
+                    .execute(pool)
+                    .await?
+                    .rows_affected()
+            }
+            Database::Postgres(pool) => {
+                let Ok(id) = Uuid::parse_str(id) else {
+                    return Ok(false);
+                };
+                sqlx::query("DELETE FROM alerts WHERE owner = $1 AND id = $2")
+                    .bind(owner)
+                    .bind(id)
+                    .execute(pool)
+                    .await?
+                    .rows_affected()
+            }
+        };
+
+        Ok(result > 0)
+    }
+
+    pub async fn save_triggered_alert(
+        &self,
+        triggered: &TriggeredAlert,
+    ) -> Result<(), sqlx::Error> {
+        match self {
+            Database::Sqlite(pool) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO triggered_alerts (
+                        id, alert_id, owner, stock_code, alert_condition, value, triggered_at
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                    "#,
+                )
+                .bind(&triggered.id)
+                .bind(&triggered.alert_id)
+                .bind(&triggered.owner)
+                .bind(&triggered.stock_code)
+                .bind(triggered.condition.to_string())
+                .bind(triggered.value)
+                .bind(triggered.triggered_at)
+                .execute(pool)
+                .await?;
+            }
+            Database::Postgres(pool) => {
+                let id =
+                    Uuid::parse_str(&triggered.id).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                let alert_id = Uuid::parse_str(&triggered.alert_id)
+                    .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                sqlx::query(
+                    r#"
+                    INSERT INTO triggered_alerts (
+                        id, alert_id, owner, stock_code, alert_condition, value, triggered_at
+                    ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+                    "#,
+                )
+                .bind(id)
+                .bind(alert_id)
+                .bind(&triggered.owner)
+                .bind(&triggered.stock_code)
+                .bind(triggered.condition.to_string())
+                .bind(triggered.value)
+                .bind(triggered.triggered_at)
+                .execute(pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 用户已触发的提醒，按触发时间倒序
+    pub async fn get_triggered_alerts(
+        &self,
+        owner: &str,
+        stock_code: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<TriggeredAlert>, sqlx::Error> {
+        let triggered = match self {
+            Database::Sqlite(pool) => sqlx::query(
+                r#"
+                SELECT * FROM triggered_alerts
+                WHERE owner = ?1 AND (?2 IS NULL OR stock_code = ?2)
+                ORDER BY triggered_at DESC
+                LIMIT ?3
+                "#,
+            )
+            .bind(owner)
+            .bind(stock_code)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .filter_map(|row| {
+                Some(TriggeredAlert {
+                    id: row.get("id"),
+                    alert_id: row.get("alert_id"),
+                    owner: row.get("owner"),
+                    stock_code: row.get("stock_code"),
+                    condition: row.get::<String, _>("alert_condition").parse().ok()?,
+                    value: row.get("value"),
+                    triggered_at: row.get("triggered_at"),
+                })
+            })
+            .collect(),
+            Database::Postgres(pool) => sqlx::query(
+                r#"
+                SELECT * FROM triggered_alerts
+                WHERE owner = $1 AND ($2::VARCHAR IS NULL OR stock_code = $2)
+                ORDER BY triggered_at DESC
+                LIMIT $3
+                "#,
+            )
+            .bind(owner)
+            .bind(stock_code)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .filter_map(|row| {
+                Some(TriggeredAlert {
+                    id: row.get::<Uuid, _>("id").to_string(),
+                    alert_id: row.get::<Uuid, _>("alert_id").to_string(),
+                    owner: row.get("owner"),
+                    stock_code: row.get("stock_code"),
+                    condition: row.get::<String, _>("alert_condition").parse().ok()?,
+                    value: row.get("value"),
+                    triggered_at: row.get("triggered_at"),
+                })
+            })
+            .collect(),
+        };
+
+        Ok(triggered)
+    }
+
+    pub async fn create_tables(&self) -> Result<(), sqlx::Error> {
+        match self {
+            Database::Sqlite(pool) => {
+                // Create tables for SQLite
+                sqlx::query(&sqlite_saved_analyses_table("saved_analyses"))
+                    .execute(pool)
+                    .await?;
+
+                // Databases created before AI usage was recorded lack these columns
+                let columns: Vec<String> = sqlx::query("PRAGMA table_info(saved_analyses)")
+                    .fetch_all(pool)
+                    .await?
+                    .iter()
+                    .map(|row| row.get("name"))
+                    .collect();
+                for column in ["ai_tokens", "ai_latency_ms"] {
+                    if !columns.iter().any(|c| c == column) {
+                        sqlx::query(&format!(
+                            "ALTER TABLE saved_analyses ADD COLUMN {} INTEGER",
+                            column
+                        ))
+                        .execute(pool)
+                        .await?;
+                    }
+                }
+
+                // Older tables are keyed only by the TEXT id, so the search index had to use the
+                // implicit rowid, which VACUUM may renumber. Rebuild them with the integer `seq`
+                // key and drop the old index; it is recreated and repopulated below
+                if !columns.iter().any(|c| c == "seq") {
+                    let mut tx = pool.begin().await?;
+                    for statement in [
+                        "DROP TRIGGER IF EXISTS saved_analyses_fts_insert",
+                        "DROP TRIGGER IF EXISTS saved_analyses_fts_delete",
+                        "DROP TRIGGER IF EXISTS saved_analyses_fts_update",
+                        "DROP TABLE IF EXISTS saved_analyses_fts",
+                    ] {
+                        sqlx::query(statement).execute(&mut *tx).await?;
+                    }
+                    sqlx::query(&sqlite_saved_analyses_table("saved_analyses_migrated"))
+                        .execute(&mut *tx)
+                        .await?;
+                    sqlx::query(&format!(
+                        "INSERT INTO saved_analyses_migrated ({0}) \
+                         SELECT {0} FROM saved_analyses ORDER BY created_at",
+                        SAVED_ANALYSES_COLUMNS
+                    ))
+                    .execute(&mut *tx)
+                    .await?;
+                    sqlx::query("DROP TABLE saved_analyses")
+                        .execute(&mut *tx)
+                        .await?;
+                    sqlx::query("ALTER TABLE saved_analyses_migrated RENAME TO saved_analyses")
+                        .execute(&mut *tx)
+                        .await?;
+                    tx.commit().await?;
+                }
+
+                sqlx::query(
+                    "CREATE INDEX IF NOT EXISTS idx_saved_analyses_analysis_date ON saved_analyses(analysis_date)",
+                )
+                .execute(pool)
+                .await?;
+
+                // Full-text index over the AI analysis and recommendation, kept in sync by triggers.
+                // The trigram tokenizer matches substrings, which suits unsegmented Chinese text
+                let fts_exists: bool = sqlx::query(
+                    "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'saved_analyses_fts')",
+                )
+                .fetch_one(pool)
+                .await?
+                .get(0);
+                if !fts_exists {
+                    sqlx::query(
+                        r#"
+                        CREATE VIRTUAL TABLE saved_analyses_fts USING fts5(
+                            ai_analysis, recommendation,
+                            content = 'saved_analyses', content_rowid = 'seq',
+                            tokenize = 'trigram'
+                        )
+                        "#,
+                    )
+                    .execute(pool)
+                    .await?;
+                    // Index analyses saved before the search table existed
+                    sqlx::query(
+                        "INSERT INTO saved_analyses_fts(saved_analyses_fts) VALUES ('rebuild')",
+                    )
+                    .execute(pool)
+                    .await?;
+                }
+
+                for trigger in [
+                    r#"
+                    CREATE TRIGGER IF NOT EXISTS saved_analyses_fts_insert AFTER INSERT ON saved_analyses BEGIN
+                        INSERT INTO saved_analyses_fts(rowid, ai_analysis, recommendation)
+                        VALUES (new.seq, new.ai_analysis, new.recommendation);
+                    END
+                    "#,
+                    r#"
+                    CREATE TRIGGER IF NOT EXISTS saved_analyses_fts_delete AFTER DELETE ON saved_analyses BEGIN
+                        INSERT INTO saved_analyses_fts(saved_analyses_fts, rowid, ai_analysis, recommendation)
+                        VALUES ('delete', old.seq, old.ai_analysis, old.recommendation);
+                    END
+                    "#,
+                    r#"
+                    CREATE TRIGGER IF NOT EXISTS saved_analyses_fts_update
+                    AFTER UPDATE OF ai_analysis, recommendation ON saved_analyses BEGIN
+                        INSERT INTO saved_analyses_fts(saved_analyses_fts, rowid, ai_analysis, recommendation)
+                        VALUES ('delete', old.seq, old.ai_analysis, old.recommendation);
+                        INSERT INTO saved_analyses_fts(rowid, ai_analysis, recommendation)
+                        VALUES (new.seq, new.ai_analysis, new.recommendation);
+                    END
+                    "#,
+                ] {
+                    sqlx::query(trigger).execute(pool).await?;
+                }
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS saved_configurations (
+                        id TEXT PRIMARY KEY,
+                        config_type TEXT NOT NULL,
+                        config_name TEXT NOT NULL,
+                        config_data TEXT NOT NULL,
+                        is_active INTEGER DEFAULT 0,
+                        created_at TEXT NOT NULL,
+                        updated_at TEXT NOT NULL
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS trading_signals (
+                        id TEXT PRIMARY KEY,
+                        stock_code TEXT NOT NULL,
                         stock_name TEXT NOT NULL,
-                        analysis_date TEXT NOT NULL,
-                        price_info TEXT NOT NULL,
-                        technical TEXT NOT NULL,
-                        fundamental TEXT NOT NULL,
-                        sentiment TEXT NOT NULL,
-                        scores TEXT NOT NULL,
-                        recommendation TEXT NOT NULL,
-                        ai_analysis TEXT,
-                        data_quality TEXT NOT NULL,
-                        ai_provider TEXT,
-                        ai_model TEXT,
+                        strategy_name TEXT NOT NULL,
+                        signal_type TEXT NOT NULL,
+                        strength REAL NOT NULL,
+                        price REAL NOT NULL,
+                        signal_time TEXT NOT NULL,
+                        reason TEXT NOT NULL,
+                        confidence REAL NOT NULL,
+                        risk_level TEXT NOT NULL,
+                        expected_profit REAL NOT NULL,
+                        stop_loss REAL NOT NULL,
+                        take_profit REAL NOT NULL,
                         created_at TEXT NOT NULL
                     )
                     "#,
@@ -490,14 +2148,20 @@ impl Database {
                 .execute(pool)
                 .await?;
 
+                sqlx::query(
+                    "CREATE INDEX IF NOT EXISTS idx_trading_signals_stock_time ON trading_signals(stock_code, signal_time)",
+                )
+                .execute(pool)
+                .await?;
+
                 sqlx::query(
                     r#"
-                    CREATE TABLE IF NOT EXISTS saved_configurations (
+                    CREATE TABLE IF NOT EXISTS paper_accounts (
                         id TEXT PRIMARY KEY,
-                        config_type TEXT NOT NULL,
-                        config_name TEXT NOT NULL,
-                        config_data TEXT NOT NULL,
-                        is_active INTEGER DEFAULT 0,
+                        name TEXT NOT NULL,
+                        initial_cash REAL NOT NULL,
+                        position_fraction REAL NOT NULL,
+                        auto_execute_stocks TEXT NOT NULL,
                         created_at TEXT NOT NULL,
                         updated_at TEXT NOT NULL
                     )
@@ -505,50 +2169,766 @@ impl Database {
                 )
                 .execute(pool)
                 .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS paper_trades (
+                        id TEXT PRIMARY KEY,
+                        account_id TEXT NOT NULL,
+                        stock_code TEXT NOT NULL,
+                        stock_name TEXT NOT NULL,
+                        side TEXT NOT NULL,
+                        quantity INTEGER NOT NULL,
+                        price REAL NOT NULL,
+                        amount REAL NOT NULL,
+                        signal TEXT NOT NULL,
+                        realized_pnl REAL NOT NULL,
+                        executed_at TEXT NOT NULL
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    "CREATE INDEX IF NOT EXISTS idx_paper_trades_account_time ON paper_trades(account_id, executed_at)",
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS users (
+                        id TEXT PRIMARY KEY,
+                        username TEXT NOT NULL UNIQUE,
+                        email TEXT NOT NULL UNIQUE,
+                        password_hash TEXT NOT NULL,
+                        created_at TEXT NOT NULL,
+                        last_login TEXT,
+                        is_admin BOOLEAN NOT NULL DEFAULT 0,
+                        api_usage INTEGER NOT NULL DEFAULT 0,
+                        is_active BOOLEAN NOT NULL DEFAULT 1
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS watchlist (
+                        owner TEXT NOT NULL,
+                        stock_code TEXT NOT NULL,
+                        note TEXT,
+                        created_at TEXT NOT NULL,
+                        PRIMARY KEY (owner, stock_code)
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS alerts (
+                        id TEXT PRIMARY KEY,
+                        owner TEXT NOT NULL,
+                        stock_code TEXT NOT NULL,
+                        alert_condition TEXT NOT NULL,
+                        webhook_url TEXT,
+                        condition_met BOOLEAN NOT NULL DEFAULT 0,
+                        last_checked TEXT,
+                        created_at TEXT NOT NULL
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS triggered_alerts (
+                        id TEXT PRIMARY KEY,
+                        alert_id TEXT NOT NULL,
+                        owner TEXT NOT NULL,
+                        stock_code TEXT NOT NULL,
+                        alert_condition TEXT NOT NULL,
+                        value REAL NOT NULL,
+                        triggered_at TEXT NOT NULL
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
             }
             Database::Postgres(pool) => {
-                // For PostgreSQL, tables should be created by init script
-                // But let's verify they exist and create them if needed
-                let table_exists = sqlx::query(
-                    "SELECT EXISTS (
-                        SELECT FROM information_schema.tables 
-                        WHERE table_schema = 'public' 
-                        AND table_name = 'saved_analyses'
-                    )",
+                // Mirrors database/init.sql; ids are always generated by the application,
+                // so the tables don't depend on the uuid-ossp extension
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS saved_analyses (
+                        id UUID PRIMARY KEY,
+                        stock_code VARCHAR(20) NOT NULL,
+                        stock_name VARCHAR(100) NOT NULL,
+                        analysis_date TIMESTAMP WITH TIME ZONE NOT NULL,
+                        price_info JSONB NOT NULL,
+                        technical JSONB NOT NULL,
+                        fundamental JSONB NOT NULL,
+                        sentiment JSONB NOT NULL,
+                        scores JSONB NOT NULL,
+                        recommendation VARCHAR(50) NOT NULL,
+                        ai_analysis TEXT,
+                        data_quality JSONB NOT NULL,
+                        ai_provider VARCHAR(50),
+                        ai_model VARCHAR(50),
+                        ai_tokens BIGINT,
+                        ai_latency_ms BIGINT,
+                        created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+                    )
+                    "#,
                 )
-                .fetch_one(pool)
+                .execute(pool)
                 .await?;
 
-                let exists: bool = table_exists.get("exists");
-                if !exists {
-                    log::warn!("saved_analyses table does not exist, attempting to create it");
-                    // Try to create the table
-                    sqlx::query(
-                        r#"
-                        CREATE TABLE saved_analyses (
-                            id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
-                            stock_code VARCHAR(20) NOT NULL,
-                            stock_name VARCHAR(100) NOT NULL,
-                            analysis_date TIMESTAMP WITH TIME ZONE NOT NULL,
-                            price_info JSONB NOT NULL,
-                            technical JSONB NOT NULL,
-                            fundamental JSONB NOT NULL,
-                            sentiment JSONB NOT NULL,
-                            scores JSONB NOT NULL,
-                            recommendation VARCHAR(50) NOT NULL,
-                            ai_analysis TEXT,
-                            data_quality JSONB NOT NULL,
-                            ai_provider VARCHAR(50),
-                            ai_model VARCHAR(50),
-                            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
-                        )
-                        "#,
+                sqlx::query(
+                    "ALTER TABLE saved_analyses ADD COLUMN IF NOT EXISTS ai_tokens BIGINT, ADD COLUMN IF NOT EXISTS ai_latency_ms BIGINT",
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    "CREATE INDEX IF NOT EXISTS idx_saved_analyses_analysis_date ON saved_analyses(analysis_date)",
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE INDEX IF NOT EXISTS idx_saved_analyses_search ON saved_analyses
+                    USING GIN (to_tsvector('simple', COALESCE(ai_analysis, '') || ' ' || recommendation))
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS saved_configurations (
+                        id UUID PRIMARY KEY,
+                        config_type VARCHAR(50) NOT NULL,
+                        config_name VARCHAR(100) NOT NULL,
+                        config_data JSONB NOT NULL,
+                        is_active BOOLEAN DEFAULT false,
+                        created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+                        updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
                     )
-                    .execute(pool)
-                    .await?;
-                }
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS trading_signals (
+                        id UUID PRIMARY KEY,
+                        stock_code VARCHAR(20) NOT NULL,
+                        stock_name VARCHAR(100) NOT NULL,
+                        strategy_name VARCHAR(100) NOT NULL,
+                        signal_type VARCHAR(20) NOT NULL,
+                        strength DOUBLE PRECISION NOT NULL,
+                        price DOUBLE PRECISION NOT NULL,
+                        signal_time TIMESTAMP WITH TIME ZONE NOT NULL,
+                        reason TEXT NOT NULL,
+                        confidence DOUBLE PRECISION NOT NULL,
+                        risk_level VARCHAR(20) NOT NULL,
+                        expected_profit DOUBLE PRECISION NOT NULL,
+                        stop_loss DOUBLE PRECISION NOT NULL,
+                        take_profit DOUBLE PRECISION NOT NULL,
+                        created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    "CREATE INDEX IF NOT EXISTS idx_trading_signals_stock_time ON trading_signals(stock_code, signal_time)",
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS paper_accounts (
+                        id UUID PRIMARY KEY,
+                        name VARCHAR(100) NOT NULL,
+                        initial_cash DOUBLE PRECISION NOT NULL,
+                        position_fraction DOUBLE PRECISION NOT NULL,
+                        auto_execute_stocks JSONB NOT NULL,
+                        created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                        updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS paper_trades (
+                        id UUID PRIMARY KEY,
+                        account_id UUID NOT NULL REFERENCES paper_accounts(id) ON DELETE CASCADE,
+                        stock_code VARCHAR(20) NOT NULL,
+                        stock_name VARCHAR(100) NOT NULL,
+                        side VARCHAR(10) NOT NULL,
+                        quantity BIGINT NOT NULL,
+                        price DOUBLE PRECISION NOT NULL,
+                        amount DOUBLE PRECISION NOT NULL,
+                        signal VARCHAR(20) NOT NULL,
+                        realized_pnl DOUBLE PRECISION NOT NULL,
+                        executed_at TIMESTAMP WITH TIME ZONE NOT NULL
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    "CREATE INDEX IF NOT EXISTS idx_paper_trades_account_time ON paper_trades(account_id, executed_at)",
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS users (
+                        id UUID PRIMARY KEY,
+                        username VARCHAR(50) NOT NULL UNIQUE,
+                        email VARCHAR(255) NOT NULL UNIQUE,
+                        password_hash VARCHAR(100) NOT NULL,
+                        created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                        last_login TIMESTAMP WITH TIME ZONE,
+                        is_admin BOOLEAN NOT NULL DEFAULT FALSE,
+                        api_usage BIGINT NOT NULL DEFAULT 0,
+                        is_active BOOLEAN NOT NULL DEFAULT TRUE
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS watchlist (
+                        owner VARCHAR(100) NOT NULL,
+                        stock_code VARCHAR(20) NOT NULL,
+                        note TEXT,
+                        created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                        PRIMARY KEY (owner, stock_code)
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS alerts (
+                        id UUID PRIMARY KEY,
+                        owner VARCHAR(100) NOT NULL,
+                        stock_code VARCHAR(20) NOT NULL,
+                        alert_condition VARCHAR(100) NOT NULL,
+                        webhook_url TEXT,
+                        condition_met BOOLEAN NOT NULL DEFAULT FALSE,
+                        last_checked TIMESTAMP WITH TIME ZONE,
+                        created_at TIMESTAMP WITH TIME ZONE NOT NULL
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS triggered_alerts (
+                        id UUID PRIMARY KEY,
+                        alert_id UUID NOT NULL,
+                        owner VARCHAR(100) NOT NULL,
+                        stock_code VARCHAR(20) NOT NULL,
+                        alert_condition VARCHAR(100) NOT NULL,
+                        value DOUBLE PRECISION NOT NULL,
+                        triggered_at TIMESTAMP WITH TIME ZONE NOT NULL
+                    )
+                    "#,
+                )
+                .execute(pool)
+                .await?;
             }
         }
         Ok(())
     }
 }
+
+/// 转义 LIKE 模式中的通配符，配合 ESCAPE '\' 使用
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// 分析历史的数据列（不含自增的 seq），迁移旧表时按此复制
+const SAVED_ANALYSES_COLUMNS: &str = "id, stock_code, stock_name, analysis_date, price_info, \
+    technical, fundamental, sentiment, scores, recommendation, ai_analysis, data_quality, \
+    ai_provider, ai_model, ai_tokens, ai_latency_ms, created_at";
+
+/// SQLite 分析历史表：seq 为稳定的整数主键（全文索引按它关联），id 保持唯一
+fn sqlite_saved_analyses_table(name: &str) -> String {
+    format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS {} (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            id TEXT NOT NULL UNIQUE,
+            stock_code TEXT NOT NULL,
+            stock_name TEXT NOT NULL,
+            analysis_date TEXT NOT NULL,
+            price_info TEXT NOT NULL,
+            technical TEXT NOT NULL,
+            fundamental TEXT NOT NULL,
+            sentiment TEXT NOT NULL,
+            scores TEXT NOT NULL,
+            recommendation TEXT NOT NULL,
+            ai_analysis TEXT,
+            data_quality TEXT NOT NULL,
+            ai_provider TEXT,
+            ai_model TEXT,
+            ai_tokens INTEGER,
+            ai_latency_ms INTEGER,
+            created_at TEXT NOT NULL
+        )
+        "#,
+        name
+    )
+}
+
+fn sqlite_saved_analysis(row: &SqliteRow) -> SavedAnalysis {
+    SavedAnalysis {
+        id: row.get("id"),
+        stock_code: row.get("stock_code"),
+        stock_name: row.get("stock_name"),
+        analysis_date: row.get("analysis_date"),
+        price_info: row.get("price_info"),
+        technical: row.get("technical"),
+        fundamental: row.get("fundamental"),
+        sentiment: row.get("sentiment"),
+        scores: row.get("scores"),
+        recommendation: row.get("recommendation"),
+        ai_analysis: row.get("ai_analysis"),
+        data_quality: row.get("data_quality"),
+        ai_provider: row.get("ai_provider"),
+        ai_model: row.get("ai_model"),
+        ai_tokens: row.get("ai_tokens"),
+        ai_latency_ms: row.get("ai_latency_ms"),
+        created_at: row.get("created_at"),
+    }
+}
+
+fn postgres_saved_analysis(row: &PgRow) -> SavedAnalysis {
+    SavedAnalysis {
+        id: row.get::<Uuid, _>("id").to_string(),
+        stock_code: row.get("stock_code"),
+        stock_name: row.get("stock_name"),
+        analysis_date: row.get("analysis_date"),
+        price_info: row.get("price_info"),
+        technical: row.get("technical"),
+        fundamental: row.get("fundamental"),
+        sentiment: row.get("sentiment"),
+        scores: row.get("scores"),
+        recommendation: row.get("recommendation"),
+        ai_analysis: row.get("ai_analysis"),
+        data_quality: row.get("data_quality"),
+        ai_provider: row.get("ai_provider"),
+        ai_model: row.get("ai_model"),
+        ai_tokens: row.get("ai_tokens"),
+        ai_latency_ms: row.get("ai_latency_ms"),
+        created_at: row.get("created_at"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal(strategy_name: &str, minutes_ago: i64) -> TradingSignal {
+        TradingSignal {
+            strategy_name: strategy_name.to_string(),
+            signal_type: "买入".to_string(),
+            strength: 70.0,
+            price: 10.5,
+            timestamp: Utc::now() - chrono::Duration::minutes(minutes_ago),
+            reason: "测试信号".to_string(),
+            confidence: 65.0,
+            risk_level: "中等".to_string(),
+            expected_profit: 0.1,
+            stop_loss: 9.8,
+            take_profit: 11.5,
+            suggested_position_pct: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_backend_detected_from_url_scheme() {
+        assert_eq!(
+            Backend::from_url("postgres://localhost:5432/stock_analyzer").unwrap(),
+            Backend::Postgres
+        );
+        assert_eq!(
+            Backend::from_url("postgresql://user@db/stocks").unwrap(),
+            Backend::Postgres
+        );
+        assert_eq!(
+            Backend::from_url("sqlite::memory:").unwrap(),
+            Backend::Sqlite("sqlite::memory:".to_string())
+        );
+        assert_eq!(
+            Backend::from_url("data/stock_analyzer.db").unwrap(),
+            Backend::Sqlite("sqlite:data/stock_analyzer.db".to_string())
+        );
+        assert!(Backend::from_url("mysql://localhost/stocks").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connect_creates_missing_sqlite_file() {
+        let path = std::env::temp_dir().join(format!("connect_{}.db", Uuid::new_v4()));
+        let database = Database::connect(&path.display().to_string(), 2)
+            .await
+            .unwrap();
+        database.create_tables().await.unwrap();
+        database.ping().await.unwrap();
+        assert!(path.exists());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_signal_history_roundtrip() {
+        let path = std::env::temp_dir().join(format!("signals_{}.db", Uuid::new_v4()));
+        let database = Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        database.create_tables().await.unwrap();
+
+        database
+            .save_signals(
+                "000001",
+                "平安银行",
+                &[signal("MACD", 10), signal("RSI", 5)],
+            )
+            .await
+            .unwrap();
+        database
+            .save_signals("600000", "浦发银行", &[signal("MACD", 1)])
+            .await
+            .unwrap();
+
+        let query = SignalHistoryQuery {
+            limit: Some(1),
+            offset: None,
+        };
+        let history = database.get_signal_history("000001", &query).await.unwrap();
+        assert_eq!(history.total, 2);
+        assert_eq!(history.signals.len(), 1);
+        assert_eq!(history.signals[0].signal.strategy_name, "RSI");
+        assert_eq!(history.signals[0].stock_name, "平安银行");
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_paper_trading_roundtrip() {
+        let path = std::env::temp_dir().join(format!("paper_{}.db", Uuid::new_v4()));
+        let database = Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        database.create_tables().await.unwrap();
+
+        let account = PaperAccount {
+            id: Uuid::new_v4().to_string(),
+            name: "信号跟踪".to_string(),
+            initial_cash: 100_000.0,
+            position_fraction: 0.2,
+            auto_execute_stocks: Vec::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        database.create_paper_account(&account).await.unwrap();
+        assert!(database
+            .update_paper_auto_execute(&account.id, &["000001".to_string()])
+            .await
+            .unwrap());
+        assert!(!database
+            .update_paper_auto_execute("missing", &[])
+            .await
+            .unwrap());
+
+        let trade = PaperTrade {
+            id: Uuid::new_v4().to_string(),
+            account_id: account.id.clone(),
+            stock_code: "000001".to_string(),
+            stock_name: "平安银行".to_string(),
+            side: "买入".to_string(),
+            quantity: 1000,
+            price: 10.5,
+            amount: 10_500.0,
+            signal: "强烈买入".to_string(),
+            realized_pnl: 0.0,
+            executed_at: Utc::now(),
+        };
+        database.save_paper_trade(&trade).await.unwrap();
+
+        let loaded = database
+            .get_paper_account(&account.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.auto_execute_stocks, vec!["000001"]);
+        let trades = database.get_paper_trades(&account.id).await.unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].quantity, 1000);
+        assert_eq!(trades[0].signal, "强烈买入");
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_watchlist_is_scoped_per_owner() {
+        let path = std::env::temp_dir().join(format!("watchlist_{}.db", Uuid::new_v4()));
+        let database = Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        database.create_tables().await.unwrap();
+
+        let entry =
+            |owner: &str, code: &str, note: Option<&str>, minutes_ago: i64| WatchlistEntry {
+                owner: owner.to_string(),
+                stock_code: code.to_string(),
+                note: note.map(str::to_string),
+                created_at: Utc::now() - chrono::Duration::minutes(minutes_ago),
+            };
+        database
+            .add_watchlist_entry(&entry("alice", "000001", None, 10))
+            .await
+            .unwrap();
+        database
+            .add_watchlist_entry(&entry("alice", "600036", Some("银行"), 5))
+            .await
+            .unwrap();
+        database
+            .add_watchlist_entry(&entry("", "000001", None, 1))
+            .await
+            .unwrap();
+        // Re-adding keeps the entry and updates its note
+        database
+            .add_watchlist_entry(&entry("alice", "000001", Some("观察"), 0))
+            .await
+            .unwrap();
+
+        let alice = database.get_watchlist("alice").await.unwrap();
+        assert_eq!(alice.len(), 2);
+        assert_eq!(alice[0].stock_code, "000001");
+        assert_eq!(alice[0].note.as_deref(), Some("观察"));
+        assert_eq!(database.get_watchlist("").await.unwrap().len(), 1);
+
+        assert!(database
+            .remove_watchlist_entry("alice", "600036")
+            .await
+            .unwrap());
+        assert!(!database
+            .remove_watchlist_entry("alice", "600036")
+            .await
+            .unwrap());
+        assert_eq!(database.get_watchlist("alice").await.unwrap().len(), 1);
+        assert_eq!(
+            database.get_all_watchlist_codes().await.unwrap(),
+            vec!["000001"]
+        );
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_history_export_is_not_capped_to_one_page() {
+        let database = Database::connect("sqlite::memory:", 1).await.unwrap();
+        database.create_tables().await.unwrap();
+        let Database::Sqlite(pool) = &database else {
+            unreachable!("an in-memory URL connects to SQLite");
+        };
+        for i in 0..130 {
+            sqlx::query(&format!(
+                "INSERT INTO saved_analyses ({}) VALUES (?1, '000001', '平安银行', \
+                 '2024-03-05T07:00:00Z', '{{}}', '{{}}', '{{}}', '{{}}', '{{}}', '持有', '', '{{}}', \
+                 NULL, NULL, NULL, NULL, ?2)",
+                SAVED_ANALYSES_COLUMNS
+            ))
+            .bind(i.to_string())
+            .bind(Utc::now() - chrono::Duration::minutes(i))
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+
+        let query = |limit, offset| HistoryQuery {
+            stock_code: Some("000001".to_string()),
+            start_date: None,
+            end_date: None,
+            limit,
+            offset,
+            q: None,
+        };
+        let page = database
+            .get_analysis_history(&query(None, None))
+            .await
+            .unwrap();
+        assert_eq!(page.analyses.len(), 20);
+
+        let export = database
+            .export_analysis_history(&query(None, None))
+            .await
+            .unwrap();
+        assert_eq!(export.total, 130);
+        assert_eq!(export.analyses.len(), 130);
+        assert_eq!(export.analyses[129].id, "129");
+
+        let export = database
+            .export_analysis_history(&query(Some(110), Some(5)))
+            .await
+            .unwrap();
+        assert_eq!(export.analyses.len(), 110);
+        assert_eq!(export.analyses[0].id, "5");
+    }
+
+    /// Needs a disposable Postgres database; skipped unless TEST_DATABASE_URL is set
+    #[tokio::test]
+    async fn test_postgres_history_roundtrip() {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            eprintln!("TEST_DATABASE_URL not set, skipping the Postgres history test");
+            return;
+        };
+        let database = Database::connect(&url, 1).await.unwrap();
+        database.create_tables().await.unwrap();
+        let Database::Postgres(pool) = &database else {
+            panic!("TEST_DATABASE_URL must point at Postgres");
+        };
+
+        let stock_code = format!("T{}", &Uuid::new_v4().simple().to_string()[..8]);
+        let mut ids = Vec::new();
+        for minutes_ago in 0..3 {
+            let id = Uuid::new_v4();
+            let created_at = Utc::now() - chrono::Duration::minutes(minutes_ago);
+            sqlx::query(&format!(
+                "INSERT INTO saved_analyses ({}) VALUES ($1, $2, '测试股票', $3, '{{}}', '{{}}', \
+                 '{{}}', '{{}}', '{{\"comprehensive\": 60.0}}', '持有', '', '{{}}', NULL, NULL, \
+                 NULL, NULL, $3)",
+                SAVED_ANALYSES_COLUMNS
+            ))
+            .bind(id)
+            .bind(&stock_code)
+            .bind(created_at)
+            .execute(pool)
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+
+        let query = HistoryQuery {
+            stock_code: Some(stock_code.clone()),
+            start_date: None,
+            end_date: None,
+            limit: Some(2),
+            offset: Some(1),
+            q: None,
+        };
+        let history = database.get_analysis_history(&query).await.unwrap();
+        assert_eq!(history.total, 3);
+        let returned: Vec<_> = history.analyses.iter().map(|a| a.id.clone()).collect();
+        assert_eq!(returned, vec![ids[1].to_string(), ids[2].to_string()]);
+        assert_eq!(history.analyses[0].scores["comprehensive"], 60.0);
+
+        let analysis = database.get_analysis_by_id(ids[0]).await.unwrap().unwrap();
+        assert_eq!(analysis.id, ids[0].to_string());
+        assert_eq!(analysis.stock_code, stock_code);
+
+        sqlx::query("DELETE FROM saved_analyses WHERE stock_code = $1")
+            .bind(&stock_code)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_history_search_survives_key_migration_and_vacuum() {
+        let path = std::env::temp_dir().join(format!("history_fts_{}.db", Uuid::new_v4()));
+        let database = Database::connect(&path.display().to_string(), 1)
+            .await
+            .unwrap();
+        let Database::Sqlite(pool) = &database else {
+            unreachable!("a file path connects to SQLite");
+        };
+
+        // The earlier layout: TEXT primary key, search index on the implicit rowid
+        sqlx::query(
+            "CREATE TABLE saved_analyses (id TEXT PRIMARY KEY, stock_code TEXT NOT NULL, \
+             stock_name TEXT NOT NULL, analysis_date TEXT NOT NULL, price_info TEXT NOT NULL, \
+             technical TEXT NOT NULL, fundamental TEXT NOT NULL, sentiment TEXT NOT NULL, \
+             scores TEXT NOT NULL, recommendation TEXT NOT NULL, ai_analysis TEXT, \
+             data_quality TEXT NOT NULL, ai_provider TEXT, ai_model TEXT, created_at TEXT NOT NULL)",
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "CREATE VIRTUAL TABLE saved_analyses_fts USING fts5(ai_analysis, recommendation, \
+             content = 'saved_analyses', content_rowid = 'rowid', tokenize = 'trigram')",
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        for (id, text) in [
+            ("a", "均线多头排列"),
+            ("b", "放量突破平台"),
+            ("c", "缩量回调"),
+        ] {
+            sqlx::query(
+                "INSERT INTO saved_analyses VALUES (?1, '000001', '平安银行', \
+                 '2024-03-05T07:00:00Z', '{}', '{}', '{}', '{}', '{}', '持有', ?2, '{}', \
+                 NULL, NULL, '2024-03-05T07:00:00Z')",
+            )
+            .bind(id)
+            .bind(text)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query("DELETE FROM saved_analyses WHERE id = 'a'")
+            .execute(pool)
+            .await
+            .unwrap();
+
+        database.create_tables().await.unwrap();
+        sqlx::query("VACUUM").execute(pool).await.unwrap();
+
+        let query = HistoryQuery {
+            stock_code: None,
+            start_date: None,
+            end_date: None,
+            limit: None,
+            offset: None,
+            q: Some("突破平台".to_string()),
+        };
+        let history = database.get_analysis_history(&query).await.unwrap();
+        assert_eq!(history.total, 1);
+        assert_eq!(history.analyses[0].id, "b");
+        assert_eq!(history.analyses[0].ai_tokens, None);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
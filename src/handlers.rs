@@ -1,55 +1,89 @@
-use actix_web::{web, Error, HttpResponse, Result};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage, HttpResponse, Result};
 use bytes::Bytes;
-use dashmap::DashMap;
-use std::collections::HashMap;
+use dashmap::{DashMap, DashSet};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
 use crate::ai_service::{get_ai_providers_info, AIService};
 use crate::analyzer::StockAnalyzer;
-use crate::auth::AuthService;
-use crate::cache::{CachedDataFetcherWrapper, DataCache};
+use crate::auth::{AuthService, RegisterError};
+use crate::cache::{CachedDataFetcher, CachedDataFetcherWrapper, DataCache};
 use crate::chip_monitor::ChipMonitor;
-use crate::currency::{CurrencyConverter, MarketTimeInfo};
+use crate::currency::{CurrencyConverter, MarketTimeInfo, DEFAULT_RATE_SOURCE};
 use crate::data_fetcher::{AkshareProxy, DataFetcher};
 use crate::database::Database;
 use crate::models::*;
+use crate::paper_trading::PaperBook;
+use crate::price_alerts::{resolve_webhook_url, PriceAlertMonitor};
+use crate::rate_limit::ClientRateLimiter;
+use crate::retention::HistoryRetention;
+use crate::scheduler::WatchlistScheduler;
 use crate::signal_alerts::SignalAlertSystem;
-use crate::trading_strategies::TradingStrategiesAnalyzer;
+use crate::trading_strategies::{TradingStrategiesAnalyzer, DEFAULT_ACCOUNT_RISK_PCT};
+use crate::tushare::TushareFetcher;
 use async_stream::stream;
+use futures::StreamExt;
+
+/// 批量任务的分析结果：成功的报告按股票代码保存，失败的代码单独记录
+#[derive(Default)]
+pub struct BatchTaskResults {
+    pub results: DashMap<String, AnalysisReport>,
+    pub failed: DashSet<String>,
+    /// 取消标记，批量任务在开始分析每只股票前检查
+    pub cancelled: Arc<AtomicBool>,
+}
 
 pub struct AppState {
     pub analyzer: Arc<StockAnalyzer>,
     pub task_status: Arc<DashMap<String, TaskStatus>>,
-    pub progress_tx: mpsc::UnboundedSender<ProgressUpdate>,
-    pub progress_rx: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<ProgressUpdate>>>,
+    pub batch_results: Arc<DashMap<String, Arc<BatchTaskResults>>>,
+    /// 进度广播通道，每个流式客户端通过 subscribe() 获取独立的接收端
+    pub progress_tx: broadcast::Sender<ProgressUpdate>,
     pub auth_service: Arc<tokio::sync::RwLock<AuthService>>,
     pub ai_service: Arc<tokio::sync::RwLock<AIService>>,
-    pub database: Arc<Database>,
+    /// 无状态模式（数据库不可用）下为 None
+    pub database: Option<Arc<Database>>,
     pub cache: Arc<DataCache>,
     pub currency_converter: Arc<CurrencyConverter>,
     pub chip_monitor: Arc<ChipMonitor>,
     pub trading_strategies_analyzer: Arc<TradingStrategiesAnalyzer>,
     pub signal_alert_system: Arc<tokio::sync::RwLock<SignalAlertSystem>>,
+    pub base_path: String,
+    pub persist_signals: bool,
+    /// 脱敏后的启动配置；未开放 /api/config/effective 时为 None
+    pub effective_config: Option<AppConfig>,
+    /// 实时价格推送的轮询间隔（秒）
+    pub price_stream_interval: u64,
+    pub paper_trading: PaperTradingConfig,
+    /// 串行化模拟账户的成交与自动执行列表修改，避免并发信号重复下单
+    pub paper_trading_lock: tokio::sync::Mutex<()>,
+    /// API 限流器，未启用限流时为 None
+    pub rate_limiter: Option<Arc<ClientRateLimiter>>,
+    /// 自选股定时分析
+    pub scheduler: Arc<WatchlistScheduler>,
 }
 
 impl AppState {
     pub async fn new(config: AppConfig) -> Result<Self, String> {
-        // Initialize database
-        let database = Arc::new(
-            Database::new(&config.database.url)
-                .await
-                .map_err(|e| format!("Failed to connect to database: {}", e))?,
-        );
-
-        // Create tables if migrations are enabled
-        if config.database.enable_migrations {
-            database
-                .create_tables()
-                .await
-                .map_err(|e| format!("Failed to create database tables: {}", e))?;
-        }
+        // Initialize database, optionally degrading to stateless mode
+        let database = match Self::connect_database(&config.database).await {
+            Ok(database) => Some(Arc::new(database)),
+            Err(e) if config.database.stateless_fallback => {
+                log::warn!(
+                    "{}; starting in stateless mode, history and configuration persistence disabled",
+                    e
+                );
+                None
+            }
+            Err(e) => return Err(e),
+        };
 
         // Initialize cache
         let cache_config = crate::cache::CacheConfig {
@@ -60,54 +94,92 @@ impl AppState {
             max_entries: config.cache.max_entries,
             cleanup_interval: config.cache.cleanup_interval,
             enable_stats: config.cache.enable_stats,
+            coalesce_requests: config.cache.coalesce_requests,
+            backend: config.cache.backend,
+            redis_url: config.cache.redis_url.clone(),
         };
 
-        let cache = Arc::new(DataCache::new(cache_config));
-
-        // Create data fetcher with caching if enabled
-        let data_fetcher: Box<dyn DataFetcher> = if config.cache.enabled {
-            let base_fetcher = AkshareProxy::new(
-                config.akshare.proxy_url.clone(),
-                config.akshare.timeout_seconds,
-            );
-            let cached_fetcher = CachedDataFetcherWrapper::new(base_fetcher, cache.clone());
-            Box::new(cached_fetcher)
-        } else {
-            Box::new(AkshareProxy::new(
-                config.akshare.proxy_url.clone(),
-                config.akshare.timeout_seconds,
-            ))
+        let cache = Arc::new(DataCache::from_config(cache_config).await?);
+        log::info!("Data cache backend: {}", cache.backend_name());
+
+        // Create data fetcher for the configured source, with caching if enabled
+        let data_fetcher = match config.data_source {
+            DataSourceKind::Akshare => {
+                let fetcher = AkshareProxy::new(
+                    config.akshare.proxy_url.clone(),
+                    config.akshare.timeout_seconds,
+                )
+                .with_price_batch_size(config.akshare.price_batch_size)
+                .with_endpoints(config.akshare.endpoints.clone())
+                .with_rate_limit(config.akshare.requests_per_second)
+                .with_retries(config.akshare.max_retries)
+                .with_mock_fallback(config.akshare.allow_mock_fallback);
+                Self::cached_fetcher(fetcher, &cache, config.cache.enabled)
+            }
+            DataSourceKind::Tushare => {
+                if config.tushare.token.is_empty() {
+                    return Err("使用 Tushare 数据源需要配置 TUSHARE_TOKEN".to_string());
+                }
+                let fetcher = TushareFetcher::from_config(&config.tushare);
+                Self::cached_fetcher(fetcher, &cache, config.cache.enabled)
+            }
         };
+        log::info!("Data source: {:?}", config.data_source);
 
-        let auth_service = Arc::new(tokio::sync::RwLock::new(AuthService::new(
-            config.auth.clone(),
-        )));
+        if config.auth.enabled && config.auth.admin_password.is_none() {
+            return Err("启用认证时需要配置 ADMIN_PASSWORD 作为初始管理员密码".to_string());
+        }
+        let secret_key = config.auth.secret_key.trim();
+        if config.auth.enabled && (secret_key.is_empty() || secret_key == DEFAULT_SECRET_KEY) {
+            return Err("启用认证时需要通过 AUTH_SECRET_KEY 配置自定义的签名密钥".to_string());
+        }
+        let auth_service = match &database {
+            Some(database) => {
+                AuthService::with_database(config.auth.clone(), database.clone()).await
+            }
+            None => AuthService::new(config.auth.clone()),
+        };
+        let auth_service = Arc::new(tokio::sync::RwLock::new(auth_service));
 
         // Initialize AI service with default config
         let ai_service = Arc::new(tokio::sync::RwLock::new(AIService::new(config.ai.clone())));
 
         // Try to load saved AI configuration from database
-        if let Ok(Some(saved_config)) = database.get_active_configuration("ai").await {
-            if let Ok(ai_config) =
-                serde_json::from_value::<crate::models::AIConfig>(saved_config.config_data)
-            {
-                let mut ai_service_writer = ai_service.write().await;
-                ai_service_writer.update_config(ai_config);
-                log::info!("Loaded saved AI configuration from database");
+        if let Some(database) = &database {
+            if let Ok(Some(saved_config)) = database.get_active_configuration("ai").await {
+                if let Ok(ai_config) =
+                    serde_json::from_value::<crate::models::AIConfig>(saved_config.config_data)
+                {
+                    let mut ai_service_writer = ai_service.write().await;
+                    ai_service_writer.update_config(ai_config);
+                    log::info!("Loaded saved AI configuration from database");
+                }
             }
         }
 
-        let analyzer = Arc::new(StockAnalyzer::with_database(
-            data_fetcher,
-            config.analysis.clone(),
-            ai_service.clone(),
-            database.clone(),
-        ));
+        let analyzer = Arc::new(match &database {
+            Some(database) => StockAnalyzer::with_database(
+                data_fetcher,
+                config.analysis.clone(),
+                ai_service.clone(),
+                database.clone(),
+            ),
+            None => StockAnalyzer::new(data_fetcher, config.analysis.clone(), ai_service.clone()),
+        });
 
-        let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+        let scheduler = Arc::new(WatchlistScheduler::new(config.schedule.clone()));
+        scheduler.start(analyzer.clone(), database.clone()).await;
+        Arc::new(PriceAlertMonitor::new(config.price_alerts.clone()))
+            .start(analyzer.clone(), database.clone());
+        HistoryRetention::new(config.database.retention_days).start(database.clone());
+
+        let (progress_tx, _) = broadcast::channel(config.server.progress_channel_capacity.max(1));
 
         // Initialize currency converter
-        let currency_converter = Arc::new(CurrencyConverter::new("USD".to_string(), 3600));
+        let currency_converter = Arc::new(
+            CurrencyConverter::new("USD".to_string(), 3600)
+                .with_rate_source(Some(DEFAULT_RATE_SOURCE.to_string())),
+        );
         
         // Initialize chip monitor
         let chip_monitor = Arc::new(ChipMonitor::new());
@@ -121,8 +193,8 @@ impl AppState {
         Ok(Self {
             analyzer,
             task_status: Arc::new(DashMap::new()),
+            batch_results: Arc::new(DashMap::new()),
             progress_tx,
-            progress_rx: Arc::new(tokio::sync::Mutex::new(progress_rx)),
             auth_service,
             ai_service,
             database,
@@ -131,7 +203,280 @@ impl AppState {
             chip_monitor,
             trading_strategies_analyzer,
             signal_alert_system,
+            base_path: config.server.normalized_base_path(),
+            persist_signals: config.database.persist_signals,
+            effective_config: config
+                .server
+                .expose_effective_config
+                .then(|| config.redacted()),
+            price_stream_interval: config.server.price_stream_interval_seconds,
+            paper_trading: config.paper_trading.clone(),
+            paper_trading_lock: tokio::sync::Mutex::new(()),
+            rate_limiter: config.rate_limit.enabled.then(|| {
+                Arc::new(ClientRateLimiter::per_minute(
+                    config.rate_limit.requests_per_minute,
+                ))
+            }),
+            scheduler,
+        })
+    }
+
+    async fn connect_database(config: &DatabaseConfig) -> Result<Database, String> {
+        let database = Database::connect(&config.url, config.max_connections)
+            .await
+            .map_err(|e| format!("Failed to connect to database: {}", e))?;
+
+        // Create tables if migrations are enabled
+        if config.enable_migrations {
+            database
+                .create_tables()
+                .await
+                .map_err(|e| format!("Failed to create database tables: {}", e))?;
+        }
+
+        Ok(database)
+    }
+
+    /// 缓存启用时为数据源套上缓存包装
+    fn cached_fetcher<T: CachedDataFetcher + 'static>(
+        fetcher: T,
+        cache: &Arc<DataCache>,
+        enabled: bool,
+    ) -> Arc<dyn DataFetcher> {
+        if enabled {
+            Arc::new(CachedDataFetcherWrapper::new(fetcher, cache.clone()))
+        } else {
+            Arc::new(fetcher)
+        }
+    }
+
+    /// 需要数据库的接口使用；无状态模式下返回 503
+    pub fn require_database(&self) -> Result<&Arc<Database>, Error> {
+        self.database.as_ref().ok_or_else(|| {
+            actix_web::error::InternalError::from_response(
+                "database unavailable",
+                HttpResponse::ServiceUnavailable().json(ApiResponse::<()>::error(
+                    "数据库不可用：服务运行在无状态模式，历史记录与配置持久化已停用".to_string(),
+                )),
+            )
+            .into()
+        })
+    }
+
+    /// 模拟交易接口使用；未启用时返回 404，无状态模式下返回 503
+    pub fn require_paper_trading(&self) -> Result<&Arc<Database>, Error> {
+        if !self.paper_trading.enabled {
+            return Err(actix_web::error::InternalError::from_response(
+                "paper trading disabled",
+                HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                    "模拟交易未启用，请设置 PAPER_TRADING_ENABLED=true".to_string(),
+                )),
+            )
+            .into());
+        }
+        self.require_database()
+    }
+
+    /// 认证启用时要求请求携带管理员 Bearer token；认证未启用时直接放行
+    pub async fn require_admin(&self, req: &actix_web::HttpRequest) -> Result<(), Error> {
+        let auth_service = self.auth_service.read().await;
+        if !auth_service.is_enabled() {
+            return Ok(());
+        }
+
+        let claims = bearer_token(req.headers()).map(|token| auth_service.verify_token(token));
+        let response = match claims {
+            Some(Ok(claims)) if claims.is_admin() => return Ok(()),
+            Some(Ok(_)) => HttpResponse::Forbidden()
+                .json(ApiResponse::<()>::error("需要管理员权限".to_string())),
+            Some(Err(e)) => HttpResponse::Unauthorized().json(ApiResponse::<()>::error(e)),
+            None => HttpResponse::Unauthorized()
+                .json(ApiResponse::<()>::error("缺少认证令牌".to_string())),
+        };
+        Err(actix_web::error::InternalError::from_response("admin required", response).into())
+    }
+
+    /// WebSocket 握手的认证：浏览器无法为 WebSocket 设置请求头，
+    /// 因此除 Authorization 头外也接受 `?token=` 查询参数；认证未启用时直接放行
+    async fn require_ws_auth(&self, req: &actix_web::HttpRequest) -> Result<(), Error> {
+        let auth_service = self.auth_service.read().await;
+        if !auth_service.is_enabled() {
+            return Ok(());
+        }
+
+        let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok();
+        let token = bearer_token(req.headers())
+            .map(str::to_string)
+            .or_else(|| query.and_then(|query| query.get("token").cloned()));
+        let error = match token.map(|token| auth_service.verify_token(&token)) {
+            Some(Ok(claims)) => match auth_service.get_user(claims.username()).await {
+                Some(user) if user.is_active => return Ok(()),
+                _ => "用户不存在或已停用".to_string(),
+            },
+            Some(Err(e)) => e,
+            None => "缺少认证令牌".to_string(),
+        };
+        let response = HttpResponse::Unauthorized().json(ApiResponse::<()>::error(error));
+        Err(actix_web::error::InternalError::from_response("unauthorized", response).into())
+    }
+}
+
+/// 从 Authorization 头中取出 Bearer token
+fn bearer_token(headers: &actix_web::http::header::HeaderMap) -> Option<&str> {
+    headers
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// 访问日志中的请求行，查询参数中的 token 替换为占位符
+pub fn log_request_line(req: &ServiceRequest) -> String {
+    let query = req
+        .query_string()
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some(("token", _)) => format!("token={}", REDACTED),
+            _ => pair.to_string(),
         })
+        .collect::<Vec<_>>()
+        .join("&");
+    let target = if query.is_empty() {
+        req.path().to_string()
+    } else {
+        format!("{}?{}", req.path(), query)
+    };
+    format!("{} {} {:?}", req.method(), target, req.version())
+}
+
+/// 认证中间件：认证启用时要求有效的 Bearer token，并将解析出的用户写入请求扩展
+/// （处理函数可通过 `web::ReqData<User>` 获取）；认证未启用时直接放行
+pub async fn require_auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(state) = req.app_data::<web::Data<AppState>>().cloned() {
+        let auth_service = state.auth_service.read().await;
+        if auth_service.is_enabled() {
+            let claims = bearer_token(req.headers()).map(|token| auth_service.verify_token(token));
+            let user = match claims {
+                Some(Ok(claims)) => auth_service
+                    .get_user(claims.username())
+                    .await
+                    .filter(|user| user.is_active)
+                    .ok_or_else(|| "用户不存在或已停用".to_string()),
+                Some(Err(e)) => Err(e),
+                None => Err("缺少认证令牌".to_string()),
+            };
+
+            match user {
+                Ok(user) => {
+                    req.extensions_mut().insert(user);
+                }
+                Err(e) => {
+                    let response = HttpResponse::Unauthorized().json(ApiResponse::<()>::error(e));
+                    let error =
+                        actix_web::error::InternalError::from_response("unauthorized", response);
+                    return Err(error.into());
+                }
+            }
+        }
+    }
+
+    next.call(req).await
+}
+
+/// 限流中间件：按客户端统计每分钟请求数，超出限额返回 429 并附带 Retry-After；
+/// 携带有效 token 的请求按 token 计数，其余按连接的 IP 计数
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if let Some(state) = req.app_data::<web::Data<AppState>>().cloned() {
+        if let Some(limiter) = &state.rate_limiter {
+            // Unverified tokens fall back to the IP so random tokens cannot open new buckets
+            let token = match bearer_token(req.headers()) {
+                Some(token) => {
+                    let auth_service = state.auth_service.read().await;
+                    auth_service
+                        .verify_token(token)
+                        .ok()
+                        .map(|_| format!("token:{}", token))
+                }
+                None => None,
+            };
+            let client = token.unwrap_or_else(|| {
+                let ip = req.peer_addr().map(|addr| addr.ip().to_string());
+                format!("ip:{}", ip.unwrap_or_default())
+            });
+
+            if let Err(retry_after) = limiter.check(&client) {
+                let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+                let response = HttpResponse::TooManyRequests()
+                    .insert_header((actix_web::http::header::RETRY_AFTER, seconds.to_string()))
+                    .json(ApiResponse::<()>::error(format!(
+                        "请求过于频繁，请 {} 秒后重试",
+                        seconds
+                    )));
+                let error =
+                    actix_web::error::InternalError::from_response("rate limited", response);
+                return Err(error.into());
+            }
+        }
+    }
+
+    next.call(req).await
+}
+
+/// 登录获取 Bearer token，认证未启用时返回错误
+pub async fn login(
+    data: web::Json<LoginRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let auth_service = state.auth_service.read().await;
+    match auth_service.login(data.into_inner()).await {
+        Ok(response) => Ok(HttpResponse::Ok().json(ApiResponse::success(response))),
+        Err(e) => Ok(HttpResponse::Unauthorized().json(ApiResponse::<AuthResponse>::error(e))),
+    }
+}
+
+/// 注册新用户并返回 token；用户名或邮箱已被注册时返回 409
+pub async fn register(
+    data: web::Json<RegisterRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let auth_service = state.auth_service.read().await;
+    let response = match auth_service.register(data.into_inner()).await {
+        Ok(response) => return Ok(HttpResponse::Created().json(ApiResponse::success(response))),
+        Err(RegisterError::Disabled(e)) => {
+            HttpResponse::Forbidden().json(ApiResponse::<()>::error(e))
+        }
+        Err(RegisterError::Conflict(e)) => {
+            HttpResponse::Conflict().json(ApiResponse::<()>::error(e))
+        }
+        Err(RegisterError::Invalid(e)) => {
+            HttpResponse::BadRequest().json(ApiResponse::<()>::error(e))
+        }
+        Err(RegisterError::Internal(e)) => {
+            log::error!("Failed to register user: {}", e);
+            HttpResponse::InternalServerError().json(ApiResponse::<()>::error(e))
+        }
+    };
+    Ok(response)
+}
+
+/// 当前请求的用户，认证未启用时返回 404
+pub async fn current_user(
+    user: Option<web::ReqData<User>>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    match user {
+        Some(user) => {
+            let response = state.auth_service.read().await.user_to_response(&user);
+            Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
+        }
+        None => Ok(HttpResponse::NotFound()
+            .json(ApiResponse::<UserResponse>::error("认证未启用".to_string()))),
     }
 }
 
@@ -143,7 +488,7 @@ pub async fn analyze_single(
 
     match state
         .analyzer
-        .analyze_single_stock(&request.stock_code, request.enable_ai.unwrap_or(true))
+        .analyze_single_stock(&request.stock_code, &AnalysisOptions::from(&request))
         .await
     {
         Ok(report) => Ok(HttpResponse::Ok().json(ApiResponse::success(report))),
@@ -158,8 +503,10 @@ pub async fn analyze_single_streaming(
     let request = data.into_inner();
     let stock_code = request.stock_code.clone();
     let stock_code_clone = stock_code.clone();
-    let enable_ai = request.enable_ai.unwrap_or(true);
+    let options = AnalysisOptions::from(&request);
     let progress_tx = state.progress_tx.clone();
+    // Subscribe before the analysis starts so no update for this request is missed
+    let mut progress_rx = progress_tx.subscribe();
 
     // Send initial progress update
     let _ = progress_tx.send(ProgressUpdate {
@@ -179,7 +526,7 @@ pub async fn analyze_single_streaming(
     let progress_tx_clone = progress_tx.clone();
 
     tokio::spawn(async move {
-        match analyzer.analyze_single_stock(&stock_code, enable_ai).await {
+        match analyzer.analyze_single_stock(&stock_code, &options).await {
             Ok(report) => {
                 // Send completion message with full report
                 let _ = progress_tx_clone.send(ProgressUpdate {
@@ -218,7 +565,6 @@ pub async fn analyze_single_streaming(
         .insert_header(("connection", "keep-alive"))
         .insert_header(("access-control-allow-origin", "*"))
         .streaming(stream! {
-            let mut progress_rx = state.progress_rx.lock().await;
             let mut last_message = None;
 
             // Send initial message
@@ -232,14 +578,27 @@ pub async fn analyze_single_streaming(
 
             loop {
                 tokio::select! {
-                    Some(progress_update) = progress_rx.recv() => {
+                    received = progress_rx.recv() => {
+                        let progress_update = match received {
+                            Ok(update) => update,
+                            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                                log::warn!(
+                                    "Streaming client for {} lagged, skipped {} progress updates",
+                                    stock_code_clone,
+                                    skipped
+                                );
+                                continue;
+                            }
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        };
+
                         // Only send messages for this specific stock
                         if progress_update.task_id == stock_code_clone {
-                            let message = if progress_update.analysis_report.is_some() {
+                            let message = if let Some(report) = &progress_update.analysis_report {
                                 // Send final result with actual analysis data
                                 serde_json::json!({
                                     "type": "final_result",
-                                    "data": progress_update.analysis_report.unwrap()
+                                    "data": report
                                 })
                             } else {
                                 // Send regular progress update
@@ -277,14 +636,26 @@ pub async fn analyze_batch(
     state: web::Data<AppState>,
 ) -> Result<HttpResponse> {
     let request = data.into_inner();
+    let task_id = start_batch_task(
+        &state,
+        &request.stock_codes,
+        AnalysisOptions::from(&request),
+    );
+    Ok(HttpResponse::Ok().json(ApiResponse::success(task_id)))
+}
+
+/// 登记批量分析任务并在后台执行，返回任务 ID；进度与结果通过批量任务接口查询
+fn start_batch_task(state: &AppState, stock_codes: &[String], options: AnalysisOptions) -> String {
     let task_id = Uuid::new_v4().to_string();
     let task_id_clone = task_id.clone();
+    // Each distinct code is analyzed once even if the caller listed it repeatedly
+    let stock_codes = Market::dedupe_codes(stock_codes);
 
     let task_status = TaskStatus {
         task_id: task_id.clone(),
         status: "运行中".to_string(),
         progress: 0.0,
-        total_stocks: request.stock_codes.len() as i32,
+        total_stocks: stock_codes.len() as i32,
         completed: 0,
         failed: 0,
         current_stock: None,
@@ -293,47 +664,93 @@ pub async fn analyze_batch(
     };
 
     state.task_status.insert(task_id.clone(), task_status);
+    let batch_results = Arc::new(BatchTaskResults::default());
+    state
+        .batch_results
+        .insert(task_id.clone(), batch_results.clone());
 
     let analyzer = state.analyzer.clone();
     let task_status = state.task_status.clone();
     let progress_tx = state.progress_tx.clone();
-    let stock_codes = request.stock_codes.clone();
-    let enable_ai = request.enable_ai.unwrap_or(true);
 
     tokio::spawn(async move {
         let total_stocks = stock_codes.len() as i32;
         let mut completed = 0;
         let mut failed = 0;
 
-        for (index, stock_code) in stock_codes.iter().enumerate() {
-            let progress = (index as f64 / total_stocks as f64) * 100.0;
+        // Fetch all price series up front so the per-stock analyses only fetch the rest
+        let mut prefetched = analyzer.prefetch_price_data(&stock_codes, &options).await;
+
+        // Analyze up to max_workers stocks at once; upstream calls stay paced by the
+        // data fetcher's rate limiter
+        let concurrency = analyzer.config().max_workers.max(1);
+        let analyses: Vec<_> = stock_codes
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(index, stock_code)| {
+                let price_data = prefetched.remove(&stock_code);
+                let analyzer = analyzer.clone();
+                let options = options.clone();
+                let task_status = task_status.clone();
+                let progress_tx = progress_tx.clone();
+                let task_id = task_id_clone.clone();
+                let cancelled = batch_results.cancelled.clone();
+                async move {
+                    if cancelled.load(Ordering::SeqCst) {
+                        return (stock_code, None);
+                    }
 
-            // Update current stock
-            if let Some(mut status) = task_status.get_mut(&task_id_clone) {
-                status.current_stock = Some(stock_code.clone());
-                status.progress = progress;
-                status.last_update = chrono::Utc::now();
-            }
+                    let progress = (index as f64 / total_stocks as f64) * 100.0;
 
-            // Send progress update
-            let _ = progress_tx.send(ProgressUpdate {
-                task_id: task_id_clone.clone(),
-                current: index as i32 + 1,
-                total: total_stocks,
-                percentage: progress,
-                status: "运行中".to_string(),
-                current_stock: Some(stock_code.clone()),
-                message: Some(format!("分析股票: {}", stock_code)),
-                timestamp: chrono::Utc::now(),
-                analysis_report: None,
-            });
+                    // Update current stock
+                    if let Some(mut status) = task_status.get_mut(&task_id) {
+                        status.current_stock = Some(stock_code.clone());
+                        status.last_update = chrono::Utc::now();
+                    }
+
+                    // Send progress update
+                    let _ = progress_tx.send(ProgressUpdate {
+                        task_id: task_id.clone(),
+                        current: index as i32 + 1,
+                        total: total_stocks,
+                        percentage: progress,
+                        status: "运行中".to_string(),
+                        current_stock: Some(stock_code.clone()),
+                        message: Some(format!("分析股票: {}", stock_code)),
+                        timestamp: chrono::Utc::now(),
+                        analysis_report: None,
+                    });
+
+                    let result = analyzer
+                        .analyze_with_price_data(&stock_code, &options, price_data)
+                        .await;
+                    (stock_code, Some(result))
+                }
+            })
+            .collect();
+        let mut results = futures::stream::iter(analyses).buffer_unordered(concurrency);
+
+        while let Some((stock_code, result)) = results.next().await {
+            // Skipped because the task was cancelled; dropping the stream stops the rest
+            let Some(result) = result else {
+                break;
+            };
 
-            match analyzer.analyze_single_stock(stock_code, enable_ai).await {
-                Ok(_) => {
+            match result {
+                Ok(report) => {
                     completed += 1;
+                    batch_results.results.insert(stock_code.clone(), report);
                 }
-                Err(_) => {
+                Err(e) => {
                     failed += 1;
+                    log::warn!(
+                        "Batch task {} failed to analyze {}: {}",
+                        task_id_clone,
+                        stock_code,
+                        e
+                    );
+                    batch_results.failed.insert(stock_code.clone());
                 }
             }
 
@@ -348,7 +765,7 @@ pub async fn analyze_batch(
             // Send completion update
             let _ = progress_tx.send(ProgressUpdate {
                 task_id: task_id_clone.clone(),
-                current: index as i32 + 1,
+                current: completed + failed,
                 total: total_stocks,
                 percentage: ((completed + failed) as f64 / total_stocks as f64) * 100.0,
                 status: "运行中".to_string(),
@@ -357,31 +774,50 @@ pub async fn analyze_batch(
                 timestamp: chrono::Utc::now(),
                 analysis_report: None,
             });
-
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         }
 
-        // Mark task as completed
+        let cancelled = batch_results.cancelled.load(Ordering::SeqCst);
+        let (final_status, message) = if cancelled {
+            log::info!(
+                "Batch task {} cancelled after {} of {} stocks",
+                task_id_clone,
+                completed + failed,
+                total_stocks
+            );
+            (
+                "已取消",
+                format!("批量分析已取消，已完成 {} 只", completed + failed),
+            )
+        } else {
+            ("已完成", "批量分析完成".to_string())
+        };
+
+        // Mark task as finished; a cancelled task keeps the progress it reached
+        let percentage = if cancelled {
+            ((completed + failed) as f64 / total_stocks as f64) * 100.0
+        } else {
+            100.0
+        };
         if let Some(mut status) = task_status.get_mut(&task_id_clone) {
-            status.status = "已完成".to_string();
-            status.progress = 100.0;
+            status.status = final_status.to_string();
+            status.progress = percentage;
             status.last_update = chrono::Utc::now();
         }
 
         let _ = progress_tx.send(ProgressUpdate {
             task_id: task_id_clone.clone(),
-            current: total_stocks,
+            current: completed + failed,
             total: total_stocks,
-            percentage: 100.0,
-            status: "已完成".to_string(),
+            percentage,
+            status: final_status.to_string(),
             current_stock: None,
-            message: Some("批量分析完成".to_string()),
+            message: Some(message),
             timestamp: chrono::Utc::now(),
             analysis_report: None,
         });
     });
 
-    Ok(HttpResponse::Ok().json(ApiResponse::success(task_id)))
+    task_id
 }
 
 pub async fn get_task_status(
@@ -398,20 +834,261 @@ pub async fn get_task_status(
     }
 }
 
+/// 取消运行中的批量任务：尚未开始的股票不再分析，正在分析的股票随任务一起中止；
+/// 已结束的任务直接返回成功
+pub async fn cancel_batch_task(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let task_id = path.into_inner();
+
+    let finished = match state.task_status.get(&task_id) {
+        Some(status) => status.status != "运行中",
+        None => {
+            let response = ApiResponse::<()>::error("任务不存在".to_string());
+            return Ok(HttpResponse::NotFound().json(response));
+        }
+    };
+    if finished {
+        let response =
+            ApiResponse::success(task_id).with_message("任务已结束，无需取消".to_string());
+        return Ok(HttpResponse::Ok().json(response));
+    }
+
+    if let Some(results) = state.batch_results.get(&task_id) {
+        results.cancelled.store(true, Ordering::SeqCst);
+    }
+    log::info!("Cancellation requested for batch task {}", task_id);
+
+    let response = ApiResponse::success(task_id).with_message("已请求取消任务".to_string());
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// 批量任务已收集的分析报告（按股票代码排序）与失败的股票代码，任务运行中也可查询已完成部分
+pub async fn get_batch_results(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let task_id = path.into_inner();
+
+    let (status, results) = match (
+        state.task_status.get(&task_id),
+        state.batch_results.get(&task_id),
+    ) {
+        (Some(status), Some(results)) => (status.status.clone(), results.clone()),
+        _ => {
+            let response = ApiResponse::<BatchResultsResponse>::error("任务不存在".to_string());
+            return Ok(HttpResponse::Ok().json(response));
+        }
+    };
+
+    let mut reports: Vec<AnalysisReport> = results
+        .results
+        .iter()
+        .map(|entry| entry.value().clone())
+        .collect();
+    reports.sort_by(|a, b| a.stock_code.cmp(&b.stock_code));
+    let mut failed_codes: Vec<String> = results.failed.iter().map(|code| code.clone()).collect();
+    failed_codes.sort();
+
+    let response = BatchResultsResponse {
+        task_id,
+        status,
+        reports,
+        failed_codes,
+    };
+    Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
+}
+
+/// WebSocket 心跳间隔，超过两个间隔未收到客户端消息则断开
+const WS_HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// 解析客户端订阅消息：支持 `{"task_id": "..."}` 或直接发送任务ID文本
+fn parse_ws_subscription(text: &str) -> Option<String> {
+    let text = text.trim();
+    let task_id = match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(serde_json::Value::Object(map)) => map.get("task_id")?.as_str()?.trim().to_string(),
+        Ok(serde_json::Value::String(task_id)) => task_id.trim().to_string(),
+        _ => text.to_string(),
+    };
+    (!task_id.is_empty()).then_some(task_id)
+}
+
+/// 进度推送 WebSocket：客户端首条消息指定 task_id，之后推送该任务的 ProgressUpdate JSON；
+/// 再次发送 task_id 可切换订阅
 pub async fn websocket_handler(
-    _req: actix_web::HttpRequest,
-    _stream: web::Payload,
-    _state: web::Data<AppState>,
+    req: actix_web::HttpRequest,
+    stream: web::Payload,
+    state: web::Data<AppState>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    // For now, return a simple response indicating WebSocket is not implemented
-    // The frontend should use the streaming endpoint instead
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "message": "WebSocket not implemented. Use /api/analyze/stream for streaming analysis."
-    })))
+    state.require_ws_auth(&req).await?;
+    let (response, mut session, mut msg_stream) = actix_ws::handle(&req, stream)?;
+    // Subscribe before the upgrade completes so updates sent right after are not missed
+    let mut progress_rx = state.progress_tx.subscribe();
+
+    actix_web::rt::spawn(async move {
+        let mut task_id: Option<String> = None;
+        let mut heartbeat = tokio::time::interval_at(
+            tokio::time::Instant::now() + WS_HEARTBEAT_INTERVAL,
+            WS_HEARTBEAT_INTERVAL,
+        );
+        let mut last_seen = std::time::Instant::now();
+
+        let close_reason = loop {
+            tokio::select! {
+                msg = msg_stream.recv() => {
+                    last_seen = std::time::Instant::now();
+                    match msg {
+                        Some(Ok(actix_ws::Message::Text(text))) => match parse_ws_subscription(&text) {
+                            Some(id) => {
+                                log::debug!("WebSocket client subscribed to task {}", id);
+                                task_id = Some(id);
+                            }
+                            None => {
+                                let error = serde_json::json!({
+                                    "type": "error",
+                                    "message": "请发送 {\"task_id\": \"...\"} 订阅任务进度"
+                                });
+                                if session.text(error.to_string()).await.is_err() {
+                                    return;
+                                }
+                            }
+                        },
+                        Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                            if session.pong(&bytes).await.is_err() {
+                                return;
+                            }
+                        }
+                        Some(Ok(actix_ws::Message::Close(reason))) => break reason,
+                        Some(Ok(_)) => {}
+                        Some(Err(e)) => {
+                            log::warn!("WebSocket protocol error: {}", e);
+                            break None;
+                        }
+                        None => return,
+                    }
+                }
+                received = progress_rx.recv() => {
+                    let update = match received {
+                        Ok(update) => update,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            log::warn!("WebSocket client lagged, skipped {} progress updates", skipped);
+                            continue;
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break None,
+                    };
+
+                    if task_id.as_deref() != Some(update.task_id.as_str()) {
+                        continue;
+                    }
+
+                    let payload = match serde_json::to_string(&update) {
+                        Ok(payload) => payload,
+                        Err(e) => {
+                            log::error!("Failed to serialize progress update: {}", e);
+                            continue;
+                        }
+                    };
+                    if session.text(payload).await.is_err() {
+                        return;
+                    }
+                }
+                _ = heartbeat.tick() => {
+                    if last_seen.elapsed() > WS_HEARTBEAT_INTERVAL * 2 {
+                        log::info!("WebSocket client heartbeat timed out, disconnecting");
+                        break None;
+                    }
+                    if session.ping(b"").await.is_err() {
+                        return;
+                    }
+                }
+            }
+        };
+
+        let _ = session.close(close_reason).await;
+    });
+
+    Ok(response)
+}
+
+/// 单项依赖检查的超时时间，避免探针被无响应的依赖拖住
+const HEALTH_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// 健康检查：默认只对数据库执行 SELECT 1，适合存活探针；deep=true 时额外检查 akshare 代理与 AI 服务商。
+/// 任一已启用的依赖检查失败时返回 503
+pub async fn health_check(
+    query: web::Query<HealthQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let deep = query.deep.unwrap_or(false);
+    let mut checks = BTreeMap::new();
+
+    let database = match &state.database {
+        Some(database) => {
+            check_dependency(async { database.ping().await.map_err(|e| e.to_string()) }).await
+        }
+        None => DependencyStatus::disabled(),
+    };
+    checks.insert("database".to_string(), database);
+
+    if deep {
+        // Probe with a copy of the AI config so the request doesn't hold the service lock
+        let ai_service = {
+            let ai_service = state.ai_service.read().await;
+            ai_service
+                .is_enabled()
+                .then(|| AIService::new(ai_service.get_config().clone()))
+        };
+        let ai_check = async {
+            match &ai_service {
+                Some(ai_service) => check_dependency(ai_service.check_connection()).await,
+                None => DependencyStatus::disabled(),
+            }
+        };
+        let (akshare, ai) = tokio::join!(
+            check_dependency(state.analyzer.data_fetcher().health_check()),
+            ai_check
+        );
+        checks.insert("akshare".to_string(), akshare);
+        checks.insert("ai".to_string(), ai);
+    }
+
+    let report = HealthReport::new(deep, checks);
+    if !report.is_healthy() {
+        let error = format!("依赖检查失败: {}", report.failed().join(", "));
+        return Ok(HttpResponse::ServiceUnavailable().json(ApiResponse {
+            success: false,
+            data: Some(report),
+            error: Some(error),
+            message: None,
+        }));
+    }
+
+    let message = if state.database.is_some() {
+        "服务运行正常"
+    } else {
+        "服务运行正常（无状态模式，数据库不可用）"
+    };
+    Ok(HttpResponse::Ok().json(ApiResponse::success(report).with_message(message.to_string())))
 }
 
-pub async fn health_check() -> Result<HttpResponse> {
-    Ok(HttpResponse::Ok().json(ApiResponse::success("服务运行正常".to_string())))
+async fn check_dependency<F>(check: F) -> DependencyStatus
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let started = std::time::Instant::now();
+    let result = tokio::time::timeout(HEALTH_CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(format!("检查超时（{}秒）", HEALTH_CHECK_TIMEOUT.as_secs())));
+    DependencyStatus {
+        status: if result.is_ok() {
+            DependencyState::Ok
+        } else {
+            DependencyState::Error
+        },
+        latency_ms: Some(started.elapsed().as_millis() as u64),
+        error: result.err(),
+    }
 }
 
 // Cache management endpoints
@@ -420,11 +1097,31 @@ pub async fn get_cache_stats(state: web::Data<AppState>) -> Result<HttpResponse>
     Ok(HttpResponse::Ok().json(ApiResponse::success(stats)))
 }
 
-pub async fn clear_cache(state: web::Data<AppState>) -> Result<HttpResponse> {
+/// 清空全部缓存；认证启用时需要管理员令牌
+pub async fn clear_cache(
+    req: actix_web::HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    state.require_admin(&req).await?;
     state.cache.clear().await;
     Ok(HttpResponse::Ok().json(ApiResponse::success("缓存已清空".to_string())))
 }
 
+/// 清除单只股票的缓存；认证启用时需要管理员令牌
+pub async fn clear_stock_cache(
+    req: actix_web::HttpRequest,
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    state.require_admin(&req).await?;
+    let stock_code = Market::normalize_code(&path.into_inner());
+    let removed = state.cache.clear_stock(&stock_code).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(format!(
+        "已清除 {} 的 {} 条缓存",
+        stock_code, removed
+    ))))
+}
+
 // Currency conversion endpoints
 pub async fn convert_currency(
     query: web::Query<CurrencyConversionQuery>,
@@ -491,21 +1188,31 @@ pub async fn get_supported_currencies(state: web::Data<AppState>) -> Result<Http
 }
 
 // Web handlers for templates
-pub async fn index() -> Result<HttpResponse> {
-    let html = include_str!("../templates/index.html");
-    Ok(HttpResponse::Ok()
+/// 渲染页面模板，将 __BASE_PATH__ 占位符替换为配置的路由前缀
+fn render_template(html: &str, base_path: &str) -> HttpResponse {
+    // The shared login snippet uses the base path placeholder too, so it goes in first
+    let html = html.replace("__AUTH_SCRIPT__", include_str!("../templates/auth.html"));
+    HttpResponse::Ok()
         .content_type("text/html; charset=utf-8")
-        .body(html))
+        .body(html.replace("__BASE_PATH__", base_path))
 }
 
-pub async fn batch() -> Result<HttpResponse> {
-    let html = include_str!("../templates/batch.html");
-    Ok(HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html))
+pub async fn index(state: web::Data<AppState>) -> Result<HttpResponse> {
+    Ok(render_template(
+        include_str!("../templates/index.html"),
+        &state.base_path,
+    ))
+}
+
+pub async fn batch(state: web::Data<AppState>) -> Result<HttpResponse> {
+    Ok(render_template(
+        include_str!("../templates/batch.html"),
+        &state.base_path,
+    ))
 }
 
 // Additional API endpoints
+/// 价格数据：默认返回近 days 日（缺省 30）；同时提供 start 与 end（YYYY-MM-DD）时返回该日期范围，此时不支持复权
 pub async fn get_stock_price(
     path: web::Path<String>,
     query: web::Query<HashMap<String, String>>,
@@ -516,21 +1223,243 @@ pub async fn get_stock_price(
         .get("days")
         .and_then(|d| d.parse::<i32>().ok())
         .unwrap_or(30);
-
-    match state
-        .analyzer
-        .data_fetcher()
-        .get_stock_data(&stock_code, days)
-        .await
+    let adjust = match query
+        .get("adjust")
+        .map(|a| a.parse::<PriceAdjustment>())
+        .transpose()
     {
-        Ok(data) => Ok(HttpResponse::Ok().json(ApiResponse::success(data))),
-        Err(error) => Ok(HttpResponse::Ok().json(ApiResponse::<Vec<PriceData>>::error(error))),
-    }
-}
+        Ok(adjust) => adjust.unwrap_or_default(),
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<Vec<PriceData>>::error(e)))
+        }
+    };
+    let range = match parse_price_range(query.get("start"), query.get("end")) {
+        Ok(Some(_)) if adjust != PriceAdjustment::None => {
+            let message = "按日期范围获取价格暂不支持复权".to_string();
+            return Ok(
+                HttpResponse::BadRequest().json(ApiResponse::<Vec<PriceData>>::error(message))
+            );
+        }
+        Ok(range) => range,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<Vec<PriceData>>::error(e)))
+        }
+    };
 
-pub async fn get_stock_fundamental(
-    path: web::Path<String>,
-    state: web::Data<AppState>,
+    let data_fetcher = state.analyzer.data_fetcher();
+    let result = match range {
+        Some((start, end)) => {
+            data_fetcher
+                .get_stock_data_range(&stock_code, start, end)
+                .await
+        }
+        None => {
+            data_fetcher
+                .get_stock_data_adjusted(&stock_code, days, adjust)
+                .await
+        }
+    };
+    match result {
+        Ok(data) => Ok(HttpResponse::Ok().json(ApiResponse::success(data))),
+        Err(error) => Ok(HttpResponse::Ok().json(ApiResponse::<Vec<PriceData>>::error(error))),
+    }
+}
+
+/// 解析价格接口的 start/end 参数：两者都缺省时为 None，只提供其一或日期无效时返回错误
+fn parse_price_range(
+    start: Option<&String>,
+    end: Option<&String>,
+) -> Result<Option<(chrono::NaiveDate, chrono::NaiveDate)>, String> {
+    let parse = |value: &str| {
+        chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+            .map_err(|_| format!("无效的日期: {}，应为 YYYY-MM-DD", value))
+    };
+    match (start, end) {
+        (None, None) => Ok(None),
+        (Some(start), Some(end)) => {
+            let (start, end) = (parse(start)?, parse(end)?);
+            crate::data_fetcher::validate_date_range(start, end)?;
+            Ok(Some((start, end)))
+        }
+        _ => Err("start 与 end 需要同时提供".to_string()),
+    }
+}
+
+/// 分钟K线（interval 缺省为 5m）及基于该序列计算的技术指标
+pub async fn get_intraday_data(
+    path: web::Path<String>,
+    query: web::Query<IntradayQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let stock_code = path.into_inner();
+    let interval = match query.interval.as_deref().unwrap_or("5m").parse() {
+        Ok(interval) => interval,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<IntradayAnalysis>::error(e)))
+        }
+    };
+
+    match state
+        .analyzer
+        .intraday_analysis(&stock_code, interval)
+        .await
+    {
+        Ok(intraday) => Ok(HttpResponse::Ok().json(ApiResponse::success(intraday))),
+        Err(error) => {
+            Ok(HttpResponse::InternalServerError()
+                .json(ApiResponse::<IntradayAnalysis>::error(error)))
+        }
+    }
+}
+
+/// 交易时段内按配置的间隔轮询数据源，以 SSE 推送有变化的最新价格，收盘后自动结束
+pub async fn stream_stock_price(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let stock_code = Market::normalize_code(&path.into_inner());
+    let market = Market::from_stock_code(&stock_code);
+    let interval = tokio::time::Duration::from_secs(state.price_stream_interval.max(1));
+    let data_fetcher = state.analyzer.data_fetcher().clone();
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("content-type", "text/event-stream"))
+        .insert_header(("cache-control", "no-cache"))
+        .insert_header(("connection", "keep-alive"))
+        .insert_header(("access-control-allow-origin", "*"))
+        .streaming(stream! {
+            let mut last_sent: Option<PriceData> = None;
+
+            loop {
+                if !market.is_market_open(chrono::Utc::now()) {
+                    yield Ok::<_, actix_web::Error>(Bytes::from(format!(
+                        "data: {}\n\n",
+                        serde_json::json!({
+                            "type": "market_closed",
+                            "message": format!("{}当前不在交易时段，价格推送结束", market)
+                        })
+                    )));
+                    break;
+                }
+
+                let message = match data_fetcher.get_stock_data_fresh(&stock_code, 1).await {
+                    Ok(data) => data
+                        .last()
+                        .filter(|latest| {
+                            last_sent.as_ref().is_none_or(|previous| {
+                                previous.date != latest.date
+                                    || previous.close != latest.close
+                                    || previous.volume != latest.volume
+                            })
+                        })
+                        .map(|latest| {
+                            last_sent = Some(latest.clone());
+                            serde_json::json!({ "type": "price", "data": latest })
+                        }),
+                    Err(error) => Some(serde_json::json!({ "type": "error", "message": error })),
+                };
+
+                if let Some(message) = message {
+                    yield Ok::<_, actix_web::Error>(Bytes::from(format!("data: {}\n\n", message)));
+                } else {
+                    // Unchanged price, keep the connection alive
+                    yield Ok::<_, actix_web::Error>(Bytes::from("data: {\"type\": \"keepalive\"}\n\n"));
+                }
+
+                tokio::time::sleep(interval).await;
+            }
+        }))
+}
+
+pub async fn get_risk_metrics(
+    path: web::Path<String>,
+    query: web::Query<RiskMetricsQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let stock_code = path.into_inner();
+
+    match state
+        .analyzer
+        .get_risk_metrics(&stock_code, query.days)
+        .await
+    {
+        Ok(metrics) => Ok(HttpResponse::Ok().json(ApiResponse::success(metrics))),
+        Err(error) => Ok(HttpResponse::BadRequest().json(ApiResponse::<RiskMetrics>::error(error))),
+    }
+}
+
+pub async fn get_stock_strategies(
+    path: web::Path<String>,
+    query: web::Query<StrategySignalsQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let stock_code = path.into_inner();
+    let account_risk_pct = query.account_risk_pct.unwrap_or(DEFAULT_ACCOUNT_RISK_PCT);
+
+    match state
+        .analyzer
+        .strategy_signals(
+            &stock_code,
+            query.days,
+            account_risk_pct,
+            &StrategyParams::default(),
+        )
+        .await
+    {
+        Ok(result) => Ok(HttpResponse::Ok().json(ApiResponse::success(result))),
+        Err(error) => {
+            Ok(HttpResponse::BadRequest().json(ApiResponse::<StrategySignals>::error(error)))
+        }
+    }
+}
+
+pub async fn analyze_stock_strategies(
+    path: web::Path<String>,
+    request: web::Json<StrategySignalsRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let stock_code = path.into_inner();
+    let request = request.into_inner();
+    let account_risk_pct = request.account_risk_pct.unwrap_or(DEFAULT_ACCOUNT_RISK_PCT);
+
+    match state
+        .analyzer
+        .strategy_signals(&stock_code, request.days, account_risk_pct, &request.params)
+        .await
+    {
+        Ok(result) => Ok(HttpResponse::Ok().json(ApiResponse::success(result))),
+        Err(error) => {
+            Ok(HttpResponse::BadRequest().json(ApiResponse::<StrategySignals>::error(error)))
+        }
+    }
+}
+
+pub async fn run_backtest(
+    request: web::Json<BacktestRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let request = request.into_inner();
+    let stock_code = request.stock_code.trim();
+    if stock_code.is_empty() {
+        let response = ApiResponse::<BacktestResult>::error("股票代码不能为空".to_string());
+        return Ok(HttpResponse::BadRequest().json(response));
+    }
+
+    match state
+        .analyzer
+        .backtest_strategy(stock_code, request.days, request.strategy)
+        .await
+    {
+        Ok(result) => Ok(HttpResponse::Ok().json(ApiResponse::success(result))),
+        Err(error) => {
+            Ok(HttpResponse::BadRequest().json(ApiResponse::<BacktestResult>::error(error)))
+        }
+    }
+}
+
+pub async fn get_stock_fundamental(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
 ) -> Result<HttpResponse> {
     let stock_code = path.into_inner();
 
@@ -587,24 +1516,32 @@ pub async fn get_stock_name(
 }
 
 // Configuration handlers
-pub async fn config() -> Result<HttpResponse> {
-    let html = include_str!("../templates/config.html");
-    Ok(HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html))
+pub async fn config(state: web::Data<AppState>) -> Result<HttpResponse> {
+    Ok(render_template(
+        include_str!("../templates/config.html"),
+        &state.base_path,
+    ))
 }
 
-pub async fn test_config() -> Result<HttpResponse> {
-    let html = include_str!("../templates/test_fix.html");
-    Ok(HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html))
+pub async fn test_config(state: web::Data<AppState>) -> Result<HttpResponse> {
+    Ok(render_template(
+        include_str!("../templates/test_fix.html"),
+        &state.base_path,
+    ))
 }
 
-pub async fn get_ai_config(state: web::Data<AppState>) -> Result<HttpResponse> {
+/// 当前AI配置（api_key 已脱敏）；认证启用时需要管理员令牌
+pub async fn get_ai_config(
+    req: actix_web::HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    state.require_admin(&req).await?;
     // Try to load configuration from database first
-    let config = if let Ok(Some(saved_config)) = state.database.get_active_configuration("ai").await
-    {
+    let saved_config = match &state.database {
+        Some(database) => database.get_active_configuration("ai").await.ok().flatten(),
+        None => None,
+    };
+    let config = if let Some(saved_config) = saved_config {
         if let Ok(ai_config) =
             serde_json::from_value::<crate::models::AIConfig>(saved_config.config_data)
         {
@@ -620,56 +1557,116 @@ pub async fn get_ai_config(state: web::Data<AppState>) -> Result<HttpResponse> {
         ai_service.get_config().clone()
     };
 
-    let response = serde_json::json!({
+    Ok(HttpResponse::Ok().json(ApiResponse::success(ai_config_response(&config))))
+}
+
+fn ai_config_response(config: &crate::models::AIConfig) -> serde_json::Value {
+    serde_json::json!({
         "provider": config.provider,
         "model": config.model,
         "enabled": config.enabled,
         "base_url": config.base_url,
-        "api_key": config.api_key, // Include API key from database
+        // Only whether a key is set is exposed; saving the placeholder keeps the stored key
+        "api_key": if config.api_key.is_empty() { "" } else { REDACTED },
         "is_configured": !config.api_key.is_empty(),
+        "custom_header_names": config
+            .custom_headers
+            .as_ref()
+            .map(|headers| headers.keys().cloned().collect::<Vec<_>>())
+            .unwrap_or_default(),
+        "token_prices": config.token_prices,
+        "fallback_providers": config
+            .fallback_providers
+            .iter()
+            .map(|fallback| fallback.redacted())
+            .collect::<Vec<_>>(),
+        "system_prompt": config.system_prompt,
+        "output_language": config.output_language,
+        "temperature": config.temperature,
+        "max_tokens": config.max_tokens,
         "supported_providers": get_ai_providers_info(),
-    });
-
-    Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
+    })
 }
 
+/// 更新AI配置，api_key 为空或为脱敏占位符时沿用已保存的密钥；认证启用时需要管理员令牌
 pub async fn update_ai_config(
+    req: actix_web::HttpRequest,
     data: web::Json<serde_json::Value>,
     state: web::Data<AppState>,
 ) -> Result<HttpResponse> {
+    state.require_admin(&req).await?;
     let mut ai_service = state.ai_service.write().await;
 
-    let update_config = crate::models::AIConfig {
+    let mut update_config = crate::models::AIConfig {
         provider: data["provider"].as_str().unwrap_or("openai").to_string(),
         api_key: data["api_key"].as_str().unwrap_or("").to_string(),
         base_url: data["base_url"].as_str().map(|s| s.to_string()),
         model: data["model"].as_str().map(|s| s.to_string()),
         enabled: data["enabled"].as_bool().unwrap_or(true),
         timeout_seconds: data["timeout_seconds"].as_u64().unwrap_or(30),
+        // Keep the configured headers unless the client sends a replacement
+        custom_headers: match data.get("custom_headers") {
+            Some(headers) => serde_json::from_value(headers.clone()).unwrap_or_default(),
+            None => ai_service.get_config().custom_headers.clone(),
+        },
+        token_prices: match data.get("token_prices") {
+            Some(prices) => serde_json::from_value(prices.clone()).unwrap_or_default(),
+            None => ai_service.get_config().token_prices.clone(),
+        },
+        fallback_providers: match data.get("fallback_providers") {
+            Some(fallbacks) => serde_json::from_value(fallbacks.clone()).unwrap_or_default(),
+            None => ai_service.get_config().fallback_providers.clone(),
+        },
+        system_prompt: match data.get("system_prompt") {
+            Some(prompt) => prompt.as_str().map(|s| s.to_string()),
+            None => ai_service.get_config().system_prompt.clone(),
+        },
+        output_language: match data.get("output_language") {
+            Some(language) => language.as_str().map(|s| s.to_string()),
+            None => ai_service.get_config().output_language.clone(),
+        },
+        temperature: match data.get("temperature") {
+            Some(temperature) => temperature.as_f64(),
+            None => ai_service.get_config().temperature,
+        },
+        max_tokens: match data.get("max_tokens") {
+            Some(max_tokens) => max_tokens.as_u64().map(|v| v as u32),
+            None => ai_service.get_config().max_tokens,
+        },
     };
-
-    // Update AI service configuration
-    ai_service.update_config(update_config.clone());
-
-    // Save configuration to database
-    let config_json = serde_json::to_value(update_config).unwrap_or_default();
-    match state
-        .database
-        .save_configuration("ai", "default", &config_json)
-        .await
-    {
-        Ok(id) => {
-            // Activate the newly saved configuration
-            if let Err(e) = state.database.activate_configuration(id).await {
-                log::warn!("Failed to activate AI configuration: {}", e);
+    // The config page posts back the masked key and header values it was given
+    update_config.restore_redacted(ai_service.get_config());
+
+    // Persist first and only swap the in-memory config once the write succeeded. The write
+    // lock is held across both steps so concurrent updates apply one at a time.
+    // In stateless mode the config only lives in memory.
+    if let Some(database) = &state.database {
+        let config_json = serde_json::to_value(&update_config).unwrap_or_default();
+        let id = match database
+            .save_configuration("ai", "default", &config_json)
+            .await
+        {
+            Ok(id) => id,
+            Err(e) => {
+                log::warn!("Failed to save AI configuration to database: {}", e);
+                let response: ApiResponse<serde_json::Value> =
+                    ApiResponse::error(format!("AI配置保存失败，未生效: {}", e));
+                return Ok(HttpResponse::InternalServerError().json(response));
             }
-        }
-        Err(e) => {
-            log::warn!("Failed to save AI configuration to database: {}", e);
+        };
+
+        // The row is stored as active already; activation only retires older entries
+        if let Err(e) = database.activate_configuration(id).await {
+            log::warn!("Failed to activate AI configuration: {}", e);
         }
     }
 
-    Ok(HttpResponse::Ok().json(ApiResponse::success("AI配置已更新")))
+    ai_service.update_config(update_config);
+    let applied = ai_service.get_config().clone();
+
+    Ok(HttpResponse::Ok().json(
+        ApiResponse::success(ai_config_response(&applied)).with_message("AI配置已更新".to_string()),
+    ))
 }
 
 pub async fn get_ai_providers() -> Result<HttpResponse> {
@@ -701,16 +1698,19 @@ pub async fn get_auth_config(state: web::Data<AppState>) -> Result<HttpResponse>
     Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
 }
 
+/// 更新认证配置（当前不会生效）；认证启用时需要管理员令牌
 pub async fn update_auth_config(
+    req: actix_web::HttpRequest,
     _data: web::Json<serde_json::Value>,
-    _state: web::Data<AppState>,
+    state: web::Data<AppState>,
 ) -> Result<HttpResponse> {
+    state.require_admin(&req).await?;
     // Note: In a real implementation, this would need proper error handling
     Ok(HttpResponse::Ok().json(ApiResponse::success("认证配置已更新")))
 }
 
 pub async fn get_system_config(_state: web::Data<AppState>) -> Result<HttpResponse> {
-    let config = load_config();
+    let config = crate::load_config();
 
     let response = serde_json::json!({
         "akshare_url": config.akshare.proxy_url,
@@ -723,10 +1723,32 @@ pub async fn get_system_config(_state: web::Data<AppState>) -> Result<HttpRespon
     Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
 }
 
+/// 返回服务实际生效的完整配置（配置文件、环境变量与默认值合并后），敏感信息已脱敏
+pub async fn get_effective_config(
+    req: actix_web::HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let Some(config) = &state.effective_config else {
+        return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+            "未开放生效配置查询，请设置 EXPOSE_EFFECTIVE_CONFIG=true".to_string(),
+        )));
+    };
+    state.require_admin(&req).await?;
+
+    // The AI config can be replaced at runtime from the database or the config page
+    let mut config = config.clone();
+    config.ai = state.ai_service.read().await.get_config().redacted();
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(config)))
+}
+
+/// 更新系统配置（需要重启生效）；认证启用时需要管理员令牌
 pub async fn update_system_config(
+    req: actix_web::HttpRequest,
     _data: web::Json<serde_json::Value>,
-    _state: web::Data<AppState>,
+    state: web::Data<AppState>,
 ) -> Result<HttpResponse> {
+    state.require_admin(&req).await?;
     // Note: System config changes would require restart in this implementation
     Ok(HttpResponse::Ok().json(ApiResponse::success("系统配置已更新（需要重启生效）")))
 }
@@ -760,7 +1782,8 @@ pub async fn get_analysis_history(
     query: web::Query<HistoryQuery>,
     state: web::Data<AppState>,
 ) -> Result<HttpResponse, Error> {
-    let history = state.database.get_analysis_history(&query).await;
+    let database = state.require_database()?;
+    let history = database.get_analysis_history(&query).await;
     match history {
         Ok(response) => Ok(HttpResponse::Ok().json(ApiResponse::success(response))),
         Err(e) => Ok(HttpResponse::InternalServerError().json(
@@ -769,11 +1792,528 @@ pub async fn get_analysis_history(
     }
 }
 
+/// 删除分析日期早于 before 的分析历史（RFC 3339 时间或 YYYY-MM-DD，按 UTC 零点），返回删除的记录数；
+/// 认证启用时需要管理员令牌
+pub async fn purge_analysis_history(
+    req: actix_web::HttpRequest,
+    query: web::Query<HistoryPurgeQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    state.require_admin(&req).await?;
+    let database = state.require_database()?;
+
+    let before = query.before.trim();
+    let cutoff = chrono::DateTime::parse_from_rfc3339(before)
+        .map(|date| date.with_timezone(&chrono::Utc))
+        .or_else(|_| {
+            chrono::NaiveDate::parse_from_str(before, "%Y-%m-%d")
+                .map(|date| date.and_time(chrono::NaiveTime::MIN).and_utc())
+        });
+    let Ok(cutoff) = cutoff else {
+        let message = format!(
+            "无效的 before 参数: {}，应为 RFC 3339 时间或 YYYY-MM-DD",
+            before
+        );
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(message)));
+    };
+
+    match database.delete_analyses_before(cutoff).await {
+        Ok(deleted) => {
+            log::info!("Removed {} analyses older than {}", deleted, cutoff);
+            let response = HistoryPurgeResponse {
+                deleted,
+                before: cutoff,
+            };
+            Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
+        }
+        Err(e) => {
+            let message = format!("Failed to delete analysis history: {}", e);
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(message)))
+        }
+    }
+}
+
+/// 导出分析历史，筛选条件与 /api/history 相同；format=csv（默认）以附件形式下载，format=json 返回与列表接口相同的结果
+pub async fn export_analysis_history(
+    query: web::Query<HistoryQuery>,
+    export: web::Query<HistoryExportQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let format = export
+        .format
+        .as_deref()
+        .unwrap_or("csv")
+        .to_ascii_lowercase();
+    if format != "csv" && format != "json" {
+        let message = format!("不支持的导出格式: {}（可选 csv、json）", format);
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(message)));
+    }
+
+    let database = state.require_database()?;
+    match database.export_analysis_history(&query).await {
+        Ok(history) if format == "json" => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(history)))
+        }
+        Ok(history) => {
+            let filename = format!(
+                "analysis_history_{}.csv",
+                chrono::Utc::now().format("%Y%m%d_%H%M%S")
+            );
+            Ok(HttpResponse::Ok()
+                .content_type("text/csv; charset=utf-8")
+                .insert_header((
+                    "content-disposition",
+                    format!("attachment; filename=\"{}\"", filename),
+                ))
+                .body(history.to_csv()))
+        }
+        Err(e) => Ok(
+            HttpResponse::InternalServerError().json(ApiResponse::<()>::error(format!(
+                "Failed to export analysis history: {}",
+                e
+            ))),
+        ),
+    }
+}
+
+/// 按提供商汇总日期范围内的AI用量（token、平均耗时与按配置价格估算的费用）
+pub async fn get_history_stats(
+    query: web::Query<HistoryStatsQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_database()?;
+    if let (Some(start), Some(end)) = (query.start_date, query.end_date) {
+        if start > end {
+            return Ok(
+                HttpResponse::BadRequest().json(ApiResponse::<HistoryStats>::error(
+                    "start_date 不能晚于 end_date".to_string(),
+                )),
+            );
+        }
+    }
+
+    match database
+        .get_ai_usage(query.start_date, query.end_date)
+        .await
+    {
+        Ok(usage) => {
+            let token_prices = state
+                .ai_service
+                .read()
+                .await
+                .get_config()
+                .token_prices
+                .clone();
+            let stats =
+                HistoryStats::from_usage(query.start_date, query.end_date, usage, &token_prices);
+            Ok(HttpResponse::Ok().json(ApiResponse::success(stats)))
+        }
+        Err(e) => Ok(
+            HttpResponse::InternalServerError().json(ApiResponse::<HistoryStats>::error(format!(
+                "Failed to get history stats: {}",
+                e
+            ))),
+        ),
+    }
+}
+
+/// 分析历史的使用统计：投资建议分布与多空归类、每日平均综合评分、最常分析的股票以及 AI 提供商用量
+pub async fn get_stats_summary(
+    query: web::Query<StatsSummaryQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_database()?;
+    let (start_date, end_date) = (query.start_date, query.end_date);
+    if let (Some(start), Some(end)) = (start_date, end_date) {
+        if start > end {
+            return Ok(
+                HttpResponse::BadRequest().json(ApiResponse::<StatsSummary>::error(
+                    "start_date 不能晚于 end_date".to_string(),
+                )),
+            );
+        }
+    }
+    let top = query.top.unwrap_or(10).clamp(1, 50);
+
+    let result = tokio::try_join!(
+        database.get_recommendation_counts(start_date, end_date),
+        database.get_daily_scores(start_date, end_date),
+        database.get_most_analyzed_stocks(start_date, end_date, top),
+        database.get_ai_usage(start_date, end_date),
+    );
+    match result {
+        Ok((recommendations, daily_scores, top_stocks, usage)) => {
+            let token_prices = state
+                .ai_service
+                .read()
+                .await
+                .get_config()
+                .token_prices
+                .clone();
+            let usage = HistoryStats::from_usage(start_date, end_date, usage, &token_prices);
+            let summary = StatsSummary::new(recommendations, daily_scores, top_stocks, usage);
+            Ok(HttpResponse::Ok().json(ApiResponse::success(summary)))
+        }
+        Err(e) => Ok(
+            HttpResponse::InternalServerError().json(ApiResponse::<StatsSummary>::error(format!(
+                "Failed to get stats summary: {}",
+                e
+            ))),
+        ),
+    }
+}
+
+/// 按当前权重重新计算历史分析的评分，默认只返回对比结果不覆盖原记录
+/// 认证启用时需要管理员令牌
+pub async fn rescore_history(
+    req: actix_web::HttpRequest,
+    data: web::Json<RescoreRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    state.require_admin(&req).await?;
+    let database = state.require_database()?;
+    let request = data.into_inner();
+    let stock_code = request.stock_code.as_deref().map(Market::normalize_code);
+    let limit = request.limit.unwrap_or(100).clamp(1, 500);
+
+    let analyses = match database
+        .get_analyses_in_range(
+            stock_code.as_deref(),
+            request.start_date,
+            request.end_date,
+            limit,
+        )
+        .await
+    {
+        Ok(analyses) => analyses,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(
+                ApiResponse::<RescoreResponse>::error(format!("Failed to load analyses: {}", e)),
+            ))
+        }
+    };
+
+    let mut results = Vec::new();
+    let mut skipped = 0;
+    for saved in &analyses {
+        match state
+            .analyzer
+            .rescore_saved_analysis(saved, request.recompute_subscores)
+            .await
+        {
+            Ok(result) => results.push(result),
+            Err(e) => {
+                log::warn!("Skipping analysis {} during rescore: {}", saved.id, e);
+                skipped += 1;
+            }
+        }
+    }
+
+    if request.commit {
+        for result in &results {
+            if let Err(e) = database
+                .update_analysis_scores(&result.id, &result.after)
+                .await
+            {
+                return Ok(HttpResponse::InternalServerError().json(
+                    ApiResponse::<RescoreResponse>::error(format!(
+                        "Failed to update scores for {}: {}",
+                        result.id, e
+                    )),
+                ));
+            }
+        }
+    }
+
+    let response = RescoreResponse {
+        weights: state.analyzer.config().weights.clone(),
+        committed: request.commit,
+        results,
+        skipped,
+    };
+    Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
+}
+
+/// 对比现行权重与候选权重下样本股票的综合评分和投资建议，不修改配置
+pub async fn simulate_weights(
+    data: web::Json<WeightSimulationRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let request = data.into_inner();
+    // Candidate weights are scaled like the configured ones so scores stay within 0-100
+    let weights = match request.weights.normalized() {
+        Ok(weights) => weights,
+        Err(e) => {
+            return Ok(
+                HttpResponse::BadRequest().json(ApiResponse::<WeightSimulationResponse>::error(e))
+            )
+        }
+    };
+
+    let stock_codes = Market::dedupe_codes(&request.stock_codes);
+    if stock_codes.is_empty() || stock_codes.len() > MAX_WEIGHT_SIMULATION_STOCKS {
+        return Ok(HttpResponse::BadRequest().json(
+            ApiResponse::<WeightSimulationResponse>::error(format!(
+                "股票数量须在 1 到 {} 之间",
+                MAX_WEIGHT_SIMULATION_STOCKS
+            )),
+        ));
+    }
+
+    let response = state
+        .analyzer
+        .simulate_weights(&stock_codes, &weights)
+        .await;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
+}
+
+/// 看板轮询用的精简评分，支持 ETag / If-None-Match 条件请求
+pub async fn get_scores(
+    req: actix_web::HttpRequest,
+    query: web::Query<ScoresQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let requested: Vec<String> = query
+        .codes
+        .split(',')
+        .map(|code| code.to_string())
+        .collect();
+    let stock_codes = Market::dedupe_codes(&requested);
+    if stock_codes.is_empty() || stock_codes.len() > MAX_SCORE_QUERY_STOCKS {
+        return Ok(
+            HttpResponse::BadRequest().json(ApiResponse::<ScoresResponse>::error(format!(
+                "股票数量须在 1 到 {} 之间",
+                MAX_SCORE_QUERY_STOCKS
+            ))),
+        );
+    }
+
+    let response = ApiResponse::success(state.analyzer.score_stocks(&stock_codes).await);
+    let body = serde_json::to_vec(&response).map_err(actix_web::error::ErrorInternalServerError)?;
+
+    // Scores are derived from cached upstream data, so an unchanged cache yields an identical body
+    let etag = {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        body.hash(&mut hasher);
+        format!("\"{:016x}\"", hasher.finish())
+    };
+
+    let not_modified = req
+        .headers()
+        .get(actix_web::http::header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == etag || tag == "*")
+        })
+        .unwrap_or(false);
+
+    if not_modified {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((actix_web::http::header::ETAG, etag))
+            .finish());
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header((actix_web::http::header::ETAG, etag))
+        .content_type("application/json")
+        .body(body))
+}
+
+/// 多只股票横向对比：评分、关键指标、估值、按综合评分排名与日收益率相关系数矩阵
+pub async fn compare_stocks(
+    request: web::Json<CompareRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let request = request.into_inner();
+    // Each distinct code is analyzed once; its entry lists every requested position
+    let groups = Market::group_codes(&request.stock_codes);
+    let stock_codes: Vec<String> = groups.iter().map(|(code, _)| code.clone()).collect();
+    if !(2..=MAX_COMPARE_STOCKS).contains(&stock_codes.len()) {
+        return Ok(
+            HttpResponse::BadRequest().json(ApiResponse::<ComparisonReport>::error(format!(
+                "对比的股票数量须在 2 到 {} 之间",
+                MAX_COMPARE_STOCKS
+            ))),
+        );
+    }
+
+    let options = AnalysisOptions {
+        enable_ai: request.enable_ai.unwrap_or(false),
+        days: request.days,
+        ..AnalysisOptions::default()
+    };
+    let mut report = state.analyzer.compare_stocks(&stock_codes, &options).await;
+    let positions: HashMap<String, Vec<usize>> = groups.into_iter().collect();
+    for entry in &mut report.stocks {
+        entry.requested_positions = positions
+            .get(&entry.stock_code)
+            .cloned()
+            .unwrap_or_default();
+    }
+    Ok(HttpResponse::Ok().json(ApiResponse::success(report)))
+}
+
+/// 组合分析：合并同一代码的多笔持仓后逐只分析一次，按市值汇总权重与加权评分
+pub async fn analyze_portfolio(
+    request: web::Json<PortfolioRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    use futures::stream;
+
+    let request = request.into_inner();
+    let holdings = PortfolioHolding::aggregate(&request.holdings);
+    if holdings.is_empty() || holdings.len() > MAX_PORTFOLIO_HOLDINGS {
+        return Ok(
+            HttpResponse::BadRequest().json(ApiResponse::<PortfolioReport>::error(format!(
+                "组合持仓数量须在 1 到 {} 之间",
+                MAX_PORTFOLIO_HOLDINGS
+            ))),
+        );
+    }
+    if let Some((holding, _)) = holdings
+        .iter()
+        .find(|(holding, _)| !(holding.quantity.is_finite() && holding.quantity > 0.0))
+    {
+        return Ok(
+            HttpResponse::BadRequest().json(ApiResponse::<PortfolioReport>::error(format!(
+                "持仓数量无效: {} {}",
+                holding.stock_code, holding.quantity
+            ))),
+        );
+    }
+
+    let base_currency = request
+        .base_currency
+        .as_deref()
+        .map(|c| c.trim().to_uppercase())
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| "CNY".to_string());
+    let options = AnalysisOptions {
+        enable_ai: false,
+        days: request.days,
+        ..AnalysisOptions::default()
+    };
+
+    let analyzer = &state.analyzer;
+    let converter = &state.currency_converter;
+    let results: Vec<_> = stream::iter(holdings)
+        .map(|(holding, requested_positions)| {
+            let options = &options;
+            let base_currency = &base_currency;
+            async move {
+                let result = async {
+                    let report = analyzer
+                        .analyze_single_stock(&holding.stock_code, options)
+                        .await?;
+                    let rate = converter
+                        .get_exchange_rate(report.market.get_currency(), base_currency)
+                        .await?;
+                    Ok::<_, String>((report, rate))
+                }
+                .await;
+                (holding, requested_positions, result)
+            }
+        })
+        .buffered(analyzer.config().max_workers.max(1))
+        .collect()
+        .await;
+
+    let mut positions = Vec::new();
+    let mut failed = std::collections::BTreeMap::new();
+    for (holding, requested_positions, result) in results {
+        match result {
+            Ok((report, rate)) => {
+                let current_price = report.price_info.current_price;
+                positions.push(PortfolioPosition {
+                    stock_code: holding.stock_code,
+                    stock_name: report.stock_name,
+                    market: report.market,
+                    quantity: holding.quantity,
+                    cost_price: holding.cost_price,
+                    current_price,
+                    market_value: holding.quantity * current_price * rate,
+                    weight: 0.0,
+                    profit_loss_pct: holding
+                        .cost_price
+                        .filter(|cost| *cost > 0.0)
+                        .map(|cost| (current_price - cost) / cost * 100.0),
+                    scores: report.scores,
+                    recommendation: report.recommendation,
+                    requested_positions,
+                });
+            }
+            Err(e) => {
+                failed.insert(holding.stock_code, e);
+            }
+        }
+    }
+
+    let total_market_value: f64 = positions.iter().map(|p| p.market_value).sum();
+    let mut weighted_score = 0.0;
+    if total_market_value > 0.0 {
+        for position in &mut positions {
+            position.weight = position.market_value / total_market_value * 100.0;
+            weighted_score += position.scores.comprehensive * position.weight / 100.0;
+        }
+    }
+
+    let report = PortfolioReport {
+        positions,
+        base_currency,
+        total_market_value,
+        weighted_score,
+        failed,
+        generated_at: chrono::Utc::now(),
+    };
+    Ok(HttpResponse::Ok().json(ApiResponse::success(report)))
+}
+
+/// 两只股票日收益率的相关系数、滚动相关系数与 beta
+pub async fn get_correlation(
+    query: web::Query<CorrelationQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let requested: Vec<String> = query
+        .codes
+        .split(',')
+        .map(|code| code.to_string())
+        .collect();
+    let stock_codes = Market::dedupe_codes(&requested);
+    let [stock_a, stock_b] = stock_codes.as_slice() else {
+        return Ok(
+            HttpResponse::BadRequest().json(ApiResponse::<CorrelationAnalysis>::error(
+                "需要提供两只不同的股票代码，例如 codes=AAPL,MSFT".to_string(),
+            )),
+        );
+    };
+
+    match state
+        .analyzer
+        .correlation_analysis(
+            stock_a,
+            stock_b,
+            query.days,
+            query.window.unwrap_or(DEFAULT_CORRELATION_WINDOW),
+        )
+        .await
+    {
+        Ok(analysis) => Ok(HttpResponse::Ok().json(ApiResponse::success(analysis))),
+        Err(error) => {
+            Ok(HttpResponse::BadRequest().json(ApiResponse::<CorrelationAnalysis>::error(error)))
+        }
+    }
+}
+
 pub async fn get_analysis_by_id(
     path: web::Path<uuid::Uuid>,
     state: web::Data<AppState>,
 ) -> Result<HttpResponse, Error> {
-    let analysis = state.database.get_analysis_by_id(*path).await;
+    let database = state.require_database()?;
+    let analysis = database.get_analysis_by_id(*path).await;
     match analysis {
         Ok(Some(analysis)) => Ok(HttpResponse::Ok().json(ApiResponse::success(analysis))),
         Ok(None) => Ok(
@@ -781,17 +2321,143 @@ pub async fn get_analysis_by_id(
                 "Analysis not found".to_string(),
             )),
         ),
-        Err(e) => Ok(HttpResponse::InternalServerError().json(
-            ApiResponse::<SavedAnalysis>::error(format!("Failed to get analysis: {}", e)),
-        )),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(
+            ApiResponse::<SavedAnalysis>::error(format!("Failed to get analysis: {}", e)),
+        )),
+    }
+}
+
+/// 导出单条分析记录：format=md（默认）为完整 Markdown 报告，format=json 为结构化报告，均以附件形式下载
+pub async fn export_analysis_report(
+    path: web::Path<uuid::Uuid>,
+    export: web::Query<HistoryExportQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let format = export
+        .format
+        .as_deref()
+        .unwrap_or("md")
+        .to_ascii_lowercase();
+    if format != "md" && format != "json" {
+        let message = format!("不支持的导出格式: {}（可选 md、json）", format);
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(message)));
+    }
+
+    let database = state.require_database()?;
+    let analysis = match database.get_analysis_by_id(*path).await {
+        Ok(Some(analysis)) => analysis,
+        Ok(None) => {
+            let response = ApiResponse::<()>::error("Analysis not found".to_string());
+            return Ok(HttpResponse::NotFound().json(response));
+        }
+        Err(e) => {
+            let response = ApiResponse::<()>::error(format!("Failed to get analysis: {}", e));
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+    };
+    let report = match analysis.to_report() {
+        Ok(report) => report,
+        Err(e) => {
+            let response = ApiResponse::<()>::error(format!("分析记录无法还原为报告: {}", e));
+            return Ok(HttpResponse::InternalServerError().json(response));
+        }
+    };
+
+    let (content_type, body) = if format == "json" {
+        let body = serde_json::to_string_pretty(&report)
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+        ("application/json; charset=utf-8", body)
+    } else {
+        ("text/markdown; charset=utf-8", report.to_markdown())
+    };
+    let filename = format!(
+        "{}_{}.{}",
+        report.stock_code,
+        report.analysis_date.format("%Y%m%d_%H%M%S"),
+        format
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((
+            "content-disposition",
+            format!("attachment; filename=\"{}\"", filename),
+        ))
+        .body(body))
+}
+
+pub async fn get_cached_analysis(
+    path: web::Path<String>,
+    query: web::Query<CachedAnalysisQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_database()?;
+    let stock_code = Market::normalize_code(&path.into_inner());
+    let max_age = query
+        .max_age
+        .unwrap_or(state.analyzer.config().cached_report_max_age_seconds);
+
+    let history_query = HistoryQuery {
+        stock_code: Some(stock_code.clone()),
+        start_date: None,
+        end_date: None,
+        limit: Some(1),
+        offset: Some(0),
+        q: None,
+    };
+
+    let latest = match database.get_analysis_history(&history_query).await {
+        Ok(history) => history.analyses.into_iter().next(),
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(
+                ApiResponse::<AnalysisReport>::error(format!("Failed to get analysis: {}", e)),
+            ))
+        }
+    };
+
+    let fresh = latest.filter(|analysis| {
+        (chrono::Utc::now() - analysis.analysis_date).num_seconds() <= max_age as i64
+    });
+
+    match fresh.map(|analysis| analysis.to_report()) {
+        Some(Ok(mut report)) => {
+            report.grade = state
+                .analyzer
+                .config()
+                .grade_for(report.scores.comprehensive);
+            Ok(HttpResponse::Ok().json(ApiResponse::success(report)))
+        }
+        Some(Err(e)) => {
+            log::warn!(
+                "Stored analysis for {} could not be restored: {}",
+                stock_code,
+                e
+            );
+            Ok(
+                HttpResponse::NotFound().json(ApiResponse::<AnalysisReport>::error(format!(
+                    "{} 的历史分析记录无法还原，请重新分析",
+                    stock_code
+                ))),
+            )
+        }
+        None => Ok(
+            HttpResponse::NotFound().json(ApiResponse::<AnalysisReport>::error(format!(
+                "{} 没有 {} 秒内的分析记录，请重新分析",
+                stock_code, max_age
+            ))),
+        ),
     }
 }
 
+/// 保存一份配置；认证启用时需要管理员令牌
 pub async fn save_configuration(
+    req: actix_web::HttpRequest,
     config: web::Json<serde_json::Value>,
     query: web::Query<serde_json::Value>,
     state: web::Data<AppState>,
 ) -> Result<HttpResponse, Error> {
+    state.require_admin(&req).await?;
+    let database = state.require_database()?;
     let config_type = query
         .get("type")
         .and_then(|v| v.as_str())
@@ -801,8 +2467,7 @@ pub async fn save_configuration(
         .and_then(|v| v.as_str())
         .unwrap_or("default");
 
-    match state
-        .database
+    match database
         .save_configuration(config_type, config_name, &config)
         .await
     {
@@ -816,13 +2481,17 @@ pub async fn save_configuration(
     }
 }
 
+/// 列出已保存的配置（可能包含密钥）；认证启用时需要管理员令牌
 pub async fn get_configurations(
+    req: actix_web::HttpRequest,
     query: web::Query<serde_json::Value>,
     state: web::Data<AppState>,
 ) -> Result<HttpResponse, Error> {
+    state.require_admin(&req).await?;
+    let database = state.require_database()?;
     let config_type = query.get("type").and_then(|v| v.as_str());
 
-    match state.database.list_configurations(config_type).await {
+    match database.list_configurations(config_type).await {
         Ok(configs) => Ok(HttpResponse::Ok().json(ApiResponse::success(configs))),
         Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<
             Vec<SavedConfiguration>,
@@ -833,11 +2502,15 @@ pub async fn get_configurations(
     }
 }
 
+/// 激活已保存的配置；认证启用时需要管理员令牌
 pub async fn activate_configuration(
+    req: actix_web::HttpRequest,
     path: web::Path<uuid::Uuid>,
     state: web::Data<AppState>,
 ) -> Result<HttpResponse, Error> {
-    match state.database.activate_configuration(*path).await {
+    state.require_admin(&req).await?;
+    let database = state.require_database()?;
+    match database.activate_configuration(*path).await {
         Ok(true) => Ok(HttpResponse::Ok().json(ApiResponse::success(true))),
         Ok(false) => Ok(HttpResponse::NotFound().json(ApiResponse::<bool>::error(
             "Configuration not found".to_string(),
@@ -851,11 +2524,15 @@ pub async fn activate_configuration(
     }
 }
 
+/// 删除已保存的配置；认证启用时需要管理员令牌
 pub async fn delete_configuration(
+    req: actix_web::HttpRequest,
     path: web::Path<uuid::Uuid>,
     state: web::Data<AppState>,
 ) -> Result<HttpResponse, Error> {
-    match state.database.delete_configuration(*path).await {
+    state.require_admin(&req).await?;
+    let database = state.require_database()?;
+    match database.delete_configuration(*path).await {
         Ok(true) => Ok(HttpResponse::Ok().json(ApiResponse::success(true))),
         Ok(false) => Ok(HttpResponse::NotFound().json(ApiResponse::<bool>::error(
             "Configuration not found".to_string(),
@@ -934,9 +2611,18 @@ pub async fn get_strategies_analysis(
 
 pub async fn generate_trading_signals(
     path: web::Path<String>,
+    query: web::Query<SignalGenerationQuery>,
     state: web::Data<AppState>,
 ) -> Result<HttpResponse, Error> {
     let stock_code = path.into_inner();
+    let account_risk_pct = query.account_risk_pct.unwrap_or(DEFAULT_ACCOUNT_RISK_PCT);
+    if !(account_risk_pct > 0.0 && account_risk_pct <= 100.0) {
+        let response = ApiResponse::<Vec<TradingSignal>>::error(format!(
+            "account_risk_pct 无效: {}，允许范围为 (0, 100]",
+            account_risk_pct
+        ));
+        return Ok(HttpResponse::BadRequest().json(response));
+    }
     
     // 获取价格数据和股票名称
     let price_data = match state
@@ -991,13 +2677,31 @@ pub async fn generate_trading_signals(
     };
     
     // 生成交易信号
-    let signals = state
-        .trading_strategies_analyzer
-        .generate_trading_signals(&trading_strategies, current_price);
+    let signals = state.trading_strategies_analyzer.generate_trading_signals(
+        &trading_strategies,
+        current_price,
+        account_risk_pct,
+    );
     
+    // 持久化信号，便于回溯信号的实际表现
+    if let Some(database) = state.database.as_ref().filter(|_| state.persist_signals) {
+        if !signals.is_empty() {
+            if let Err(e) = database
+                .save_signals(&stock_code, &stock_name, &signals)
+                .await
+            {
+                log::warn!(
+                    "Failed to persist trading signals for {}: {}",
+                    stock_code,
+                    e
+                );
+            }
+        }
+    }
+
     // 处理信号并生成提醒
     let mut signal_system = state.signal_alert_system.write().await;
-    let alerts = signal_system
+    signal_system
         .process_trading_signals(&stock_code, &stock_name, signals.clone(), current_price)
         .await;
     
@@ -1009,10 +2713,143 @@ pub async fn generate_trading_signals(
         &trading_strategies,
         &signals,
     );
+    drop(signal_system);
+
+    // 对开启自动执行的模拟账户按整体信号成交
+    if let Some(database) = state
+        .database
+        .as_ref()
+        .filter(|_| state.paper_trading.enabled)
+    {
+        execute_paper_trades(
+            database,
+            &state.paper_trading_lock,
+            &stock_code,
+            &stock_name,
+            &strategy_analysis.overall_signal,
+            current_price,
+        )
+        .await;
+    }
     
     Ok(HttpResponse::Ok().json(ApiResponse::success(strategy_analysis)))
 }
 
+/// 计算单只股票的交易信号（不持久化、不触发提醒与模拟成交），返回股票名称、最新价与信号
+async fn scan_stock_signals(
+    state: &AppState,
+    stock_code: &str,
+    account_risk_pct: f64,
+) -> Result<(String, f64, Vec<TradingSignal>), String> {
+    let fetcher = state.analyzer.data_fetcher();
+    let (price_data, stock_name) = tokio::join!(
+        fetcher.get_stock_data(stock_code, 60),
+        fetcher.get_stock_name(stock_code),
+    );
+    let price_data = price_data?;
+    let current_price = price_data
+        .last()
+        .map(|price| price.close)
+        .ok_or_else(|| "No price data available".to_string())?;
+
+    let strategies = state
+        .trading_strategies_analyzer
+        .analyze_all_strategies(stock_code, &price_data)
+        .await
+        .map_err(|e| format!("Failed to analyze strategies: {}", e))?;
+    let signals = state.trading_strategies_analyzer.generate_trading_signals(
+        &strategies,
+        current_price,
+        account_risk_pct,
+    );
+    Ok((stock_name, current_price, signals))
+}
+
+/// 批量扫描交易信号：重复的代码只扫描一次，结果列出其在请求中的全部位置
+pub async fn scan_trading_signals(
+    request: web::Json<SignalScanRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    use futures::stream;
+
+    let request = request.into_inner();
+    let account_risk_pct = request.account_risk_pct.unwrap_or(DEFAULT_ACCOUNT_RISK_PCT);
+    if !(account_risk_pct > 0.0 && account_risk_pct <= 100.0) {
+        return Ok(
+            HttpResponse::BadRequest().json(ApiResponse::<SignalScanReport>::error(format!(
+                "account_risk_pct 无效: {}，允许范围为 (0, 100]",
+                account_risk_pct
+            ))),
+        );
+    }
+
+    let groups = Market::group_codes(&request.stock_codes);
+    if groups.is_empty() || groups.len() > MAX_SIGNAL_SCAN_STOCKS {
+        return Ok(
+            HttpResponse::BadRequest().json(ApiResponse::<SignalScanReport>::error(format!(
+                "扫描的股票数量须在 1 到 {} 之间",
+                MAX_SIGNAL_SCAN_STOCKS
+            ))),
+        );
+    }
+
+    let state = &state;
+    let results: Vec<_> = stream::iter(groups)
+        .map(|(stock_code, requested_positions)| async move {
+            let result = scan_stock_signals(state, &stock_code, account_risk_pct).await;
+            (stock_code, requested_positions, result)
+        })
+        .buffered(state.analyzer.config().max_workers.max(1))
+        .collect()
+        .await;
+
+    let mut entries = Vec::new();
+    let mut failed = std::collections::BTreeMap::new();
+    for (stock_code, requested_positions, result) in results {
+        match result {
+            Ok((stock_name, current_price, signals)) => entries.push(SignalScanEntry {
+                stock_code,
+                stock_name,
+                current_price,
+                signals,
+                requested_positions,
+            }),
+            Err(e) => {
+                failed.insert(stock_code, e);
+            }
+        }
+    }
+
+    let report = SignalScanReport {
+        results: entries,
+        failed,
+        generated_at: chrono::Utc::now(),
+    };
+    Ok(HttpResponse::Ok().json(ApiResponse::success(report)))
+}
+
+pub async fn get_signal_history(
+    path: web::Path<String>,
+    query: web::Query<SignalHistoryQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_database()?;
+    let stock_code = path.into_inner();
+
+    match database
+        .get_signal_history(&stock_code, &query.into_inner())
+        .await
+    {
+        Ok(history) => Ok(HttpResponse::Ok().json(ApiResponse::success(history))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(
+            ApiResponse::<SignalHistoryResponse>::error(format!(
+                "Failed to get signal history: {}",
+                e
+            )),
+        )),
+    }
+}
+
 pub async fn get_active_alerts(state: web::Data<AppState>) -> Result<HttpResponse, Error> {
     let signal_system = state.signal_alert_system.read().await;
     let alerts = signal_system.get_active_alerts();
@@ -1055,112 +2892,472 @@ pub async fn get_signal_statistics(
     Ok(HttpResponse::Ok().json(ApiResponse::success(statistics)))
 }
 
-// Helper function to load config
-use crate::models::AppConfig;
-use std::fs;
+/// 以信号价对所有开启该股票自动执行的模拟账户成交，失败只记录日志，不影响信号接口
+async fn execute_paper_trades(
+    database: &Database,
+    lock: &tokio::sync::Mutex<()>,
+    stock_code: &str,
+    stock_name: &str,
+    overall_signal: &str,
+    price: f64,
+) {
+    let _guard = lock.lock().await;
+    let stock_code = Market::normalize_code(stock_code);
+
+    let accounts = match database.list_paper_accounts().await {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            log::warn!("Failed to load paper accounts: {}", e);
+            return;
+        }
+    };
+
+    for account in accounts
+        .into_iter()
+        .filter(|account| account.auto_execute_stocks.contains(&stock_code))
+    {
+        let trades = match database.get_paper_trades(&account.id).await {
+            Ok(trades) => trades,
+            Err(e) => {
+                log::warn!("Failed to load paper trades for {}: {}", account.id, e);
+                continue;
+            }
+        };
 
-fn load_config() -> AppConfig {
-    // Try to load from config file
-    if let Ok(config_str) = fs::read_to_string("config.json") {
-        if let Ok(config) = serde_json::from_str::<AppConfig>(&config_str) {
-            return config;
+        let book = PaperBook::replay(&account, &trades);
+        if let Some(trade) =
+            book.plan_trade(&account, &stock_code, stock_name, overall_signal, price)
+        {
+            match database.save_paper_trade(&trade).await {
+                Ok(()) => log::info!(
+                    "Paper account {} {} {} x{} @ {:.2}",
+                    account.name,
+                    trade.side,
+                    stock_code,
+                    trade.quantity,
+                    price
+                ),
+                Err(e) => log::warn!("Failed to save paper trade for {}: {}", account.id, e),
+            }
         }
     }
+}
 
-    // Try to load from environment or use defaults
-    AppConfig {
-        server: crate::models::ServerConfig {
-            host: std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
-            port: std::env::var("PORT")
-                .unwrap_or_else(|_| "8080".to_string())
-                .parse()
-                .unwrap_or(8080),
-            workers: std::env::var("WORKERS").ok().and_then(|w| w.parse().ok()),
-        },
-        analysis: crate::models::AnalysisConfig {
-            max_workers: std::env::var("MAX_WORKERS")
-                .unwrap_or_else(|_| "10".to_string())
-                .parse()
-                .unwrap_or(10),
-            timeout_seconds: std::env::var("TIMEOUT_SECONDS")
-                .unwrap_or_else(|_| "30".to_string())
-                .parse()
-                .unwrap_or(30),
-            weights: crate::models::AnalysisWeights {
-                technical: std::env::var("TECHNICAL_WEIGHT")
-                    .unwrap_or_else(|_| "0.5".to_string())
-                    .parse()
-                    .unwrap_or(0.5),
-                fundamental: std::env::var("FUNDAMENTAL_WEIGHT")
-                    .unwrap_or_else(|_| "0.3".to_string())
-                    .parse()
-                    .unwrap_or(0.3),
-                sentiment: std::env::var("SENTIMENT_WEIGHT")
-                    .unwrap_or_else(|_| "0.2".to_string())
-                    .parse()
-                    .unwrap_or(0.2),
-            },
-            parameters: crate::models::AnalysisParameters {
-                technical_period_days: std::env::var("TECHNICAL_PERIOD")
-                    .unwrap_or_else(|_| "60".to_string())
-                    .parse()
-                    .unwrap_or(60),
-                sentiment_period_days: std::env::var("SENTIMENT_PERIOD")
-                    .unwrap_or_else(|_| "30".to_string())
-                    .parse()
-                    .unwrap_or(30),
-            },
-        },
-        akshare: crate::models::AkshareConfig {
-            proxy_url: std::env::var("AKSERVICE_URL")
-                .unwrap_or_else(|_| "http://localhost:5000".to_string()),
-            timeout_seconds: std::env::var("AKSERVICE_TIMEOUT")
-                .unwrap_or_else(|_| "30".to_string())
-                .parse()
-                .unwrap_or(30),
-        },
-        ai: crate::models::AIConfig {
-            provider: std::env::var("AI_PROVIDER").unwrap_or_else(|_| "openai".to_string()),
-            api_key: std::env::var("AI_API_KEY").unwrap_or_else(|_| "".to_string()),
-            base_url: std::env::var("AI_BASE_URL").ok(),
-            model: std::env::var("AI_MODEL").ok(),
-            enabled: std::env::var("AI_ENABLED")
-                .unwrap_or_else(|_| "true".to_string())
-                .parse()
-                .unwrap_or(true),
-            timeout_seconds: std::env::var("AI_TIMEOUT")
-                .unwrap_or_else(|_| "30".to_string())
-                .parse()
-                .unwrap_or(30),
-        },
-        auth: crate::models::AuthConfig {
-            enabled: std::env::var("AUTH_ENABLED")
-                .unwrap_or_else(|_| "false".to_string())
-                .parse()
-                .unwrap_or(false),
-            secret_key: std::env::var("AUTH_SECRET_KEY")
-                .unwrap_or_else(|_| "your-secret-key-change-this".to_string()),
-            session_timeout: std::env::var("SESSION_TIMEOUT")
-                .unwrap_or_else(|_| "86400".to_string())
-                .parse()
-                .unwrap_or(86400),
-            bcrypt_cost: std::env::var("BCRYPT_COST")
-                .unwrap_or_else(|_| "12".to_string())
-                .parse()
-                .unwrap_or(12),
-        },
-        database: crate::models::DatabaseConfig {
-            url: std::env::var("DATABASE_URL")
-                .unwrap_or_else(|_| "sqlite:stock_analyzer.db".to_string()),
-            max_connections: std::env::var("DATABASE_MAX_CONNECTIONS")
-                .unwrap_or_else(|_| "5".to_string())
-                .parse()
-                .unwrap_or(5),
-            enable_migrations: std::env::var("DATABASE_ENABLE_MIGRATIONS")
-                .unwrap_or_else(|_| "true".to_string())
-                .parse()
-                .unwrap_or(true),
-        },
-        cache: crate::models::CacheConfig::default(),
+pub async fn create_paper_account(
+    request: web::Json<CreatePaperAccountRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_paper_trading()?;
+    let request = request.into_inner();
+
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Ok(
+            HttpResponse::BadRequest().json(ApiResponse::<PaperAccount>::error(
+                "账户名称不能为空".to_string(),
+            )),
+        );
+    }
+
+    let initial_cash = request
+        .initial_cash
+        .unwrap_or(state.paper_trading.default_initial_cash);
+    if !initial_cash.is_finite() || initial_cash <= 0.0 {
+        return Ok(
+            HttpResponse::BadRequest().json(ApiResponse::<PaperAccount>::error(
+                "初始资金必须大于0".to_string(),
+            )),
+        );
+    }
+
+    let position_fraction = request
+        .position_fraction
+        .unwrap_or(state.paper_trading.default_position_fraction);
+    if !(position_fraction > 0.0 && position_fraction <= 1.0) {
+        return Ok(
+            HttpResponse::BadRequest().json(ApiResponse::<PaperAccount>::error(
+                "仓位比例必须在 0 到 1 之间".to_string(),
+            )),
+        );
+    }
+
+    let now = chrono::Utc::now();
+    let account = PaperAccount {
+        id: Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        initial_cash,
+        position_fraction,
+        auto_execute_stocks: Vec::new(),
+        created_at: now,
+        updated_at: now,
+    };
+
+    match database.create_paper_account(&account).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(ApiResponse::success(account))),
+        Err(e) => Ok(
+            HttpResponse::InternalServerError().json(ApiResponse::<PaperAccount>::error(format!(
+                "创建模拟账户失败: {}",
+                e
+            ))),
+        ),
+    }
+}
+
+pub async fn list_paper_accounts(state: web::Data<AppState>) -> Result<HttpResponse, Error> {
+    let database = state.require_paper_trading()?;
+
+    match database.list_paper_accounts().await {
+        Ok(accounts) => Ok(HttpResponse::Ok().json(ApiResponse::success(accounts))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(
+            ApiResponse::<Vec<PaperAccount>>::error(format!("获取模拟账户失败: {}", e)),
+        )),
+    }
+}
+
+pub async fn update_paper_auto_execute(
+    path: web::Path<String>,
+    request: web::Json<PaperAutoExecuteRequest>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_paper_trading()?;
+    let account_id = path.into_inner();
+    let request = request.into_inner();
+    let _guard = state.paper_trading_lock.lock().await;
+
+    let mut account = match database.get_paper_account(&account_id).await {
+        Ok(Some(account)) => account,
+        Ok(None) => {
+            return Ok(
+                HttpResponse::NotFound().json(ApiResponse::<PaperAccount>::error(
+                    "模拟账户不存在".to_string(),
+                )),
+            )
+        }
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(
+                ApiResponse::<PaperAccount>::error(format!("获取模拟账户失败: {}", e)),
+            ))
+        }
+    };
+
+    let stock_codes = Market::dedupe_codes(&request.stock_codes);
+    if request.enabled {
+        for code in stock_codes {
+            if !account.auto_execute_stocks.contains(&code) {
+                account.auto_execute_stocks.push(code);
+            }
+        }
+    } else {
+        account
+            .auto_execute_stocks
+            .retain(|code| !stock_codes.contains(code));
+    }
+
+    match database
+        .update_paper_auto_execute(&account.id, &account.auto_execute_stocks)
+        .await
+    {
+        Ok(_) => {
+            account.updated_at = chrono::Utc::now();
+            Ok(HttpResponse::Ok().json(ApiResponse::success(account)))
+        }
+        Err(e) => Ok(
+            HttpResponse::InternalServerError().json(ApiResponse::<PaperAccount>::error(format!(
+                "更新自动执行设置失败: {}",
+                e
+            ))),
+        ),
+    }
+}
+
+pub async fn get_paper_ledger(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_paper_trading()?;
+    let account_id = path.into_inner();
+
+    let account =
+        match database.get_paper_account(&account_id).await {
+            Ok(Some(account)) => account,
+            Ok(None) => {
+                return Ok(
+                    HttpResponse::NotFound().json(ApiResponse::<PaperLedger>::error(
+                        "模拟账户不存在".to_string(),
+                    )),
+                )
+            }
+            Err(e) => {
+                return Ok(HttpResponse::InternalServerError().json(
+                    ApiResponse::<PaperLedger>::error(format!("获取模拟账户失败: {}", e)),
+                ))
+            }
+        };
+
+    let trades =
+        match database.get_paper_trades(&account.id).await {
+            Ok(trades) => trades,
+            Err(e) => {
+                return Ok(HttpResponse::InternalServerError().json(
+                    ApiResponse::<PaperLedger>::error(format!("获取模拟成交记录失败: {}", e)),
+                ))
+            }
+        };
+
+    // 持仓按最新收盘价估值，取价失败时退回最近成交价
+    let book = PaperBook::replay(&account, &trades);
+    let data_fetcher = state.analyzer.data_fetcher();
+    let last_prices: HashMap<String, f64> =
+        futures::future::join_all(book.holdings.keys().map(|code| async {
+            let price = data_fetcher
+                .get_stock_data(code, 5)
+                .await
+                .ok()
+                .and_then(|data| data.last().map(|p| p.close));
+            (code.clone(), price)
+        }))
+        .await
+        .into_iter()
+        .filter_map(|(code, price)| price.map(|price| (code, price)))
+        .collect();
+
+    let ledger = book.into_ledger(account, trades, &last_prices);
+    Ok(HttpResponse::Ok().json(ApiResponse::success(ledger)))
+}
+
+/// 自选股、价格提醒等用户数据的归属：认证启用时为当前用户 ID，未启用时为全局共享
+fn request_owner(user: &Option<web::ReqData<User>>) -> String {
+    user.as_ref()
+        .map(|user| user.id.clone())
+        .unwrap_or_default()
+}
+
+pub async fn add_watchlist_entry(
+    request: web::Json<AddWatchlistRequest>,
+    user: Option<web::ReqData<User>>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_database()?;
+    let request = request.into_inner();
+
+    let stock_code = Market::normalize_code(&request.stock_code);
+    if stock_code.is_empty() {
+        return Ok(
+            HttpResponse::BadRequest().json(ApiResponse::<WatchlistEntry>::error(
+                "股票代码不能为空".to_string(),
+            )),
+        );
+    }
+
+    let entry = WatchlistEntry {
+        owner: request_owner(&user),
+        stock_code,
+        note: request
+            .note
+            .map(|note| note.trim().to_string())
+            .filter(|note| !note.is_empty()),
+        created_at: chrono::Utc::now(),
+    };
+
+    match database.add_watchlist_entry(&entry).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(ApiResponse::success(entry))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(
+            ApiResponse::<WatchlistEntry>::error(format!("添加自选股失败: {}", e)),
+        )),
+    }
+}
+
+pub async fn get_watchlist(
+    user: Option<web::ReqData<User>>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_database()?;
+
+    match database.get_watchlist(&request_owner(&user)).await {
+        Ok(entries) => Ok(HttpResponse::Ok().json(ApiResponse::success(entries))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(
+            ApiResponse::<Vec<WatchlistEntry>>::error(format!("获取自选股失败: {}", e)),
+        )),
+    }
+}
+
+pub async fn remove_watchlist_entry(
+    path: web::Path<String>,
+    user: Option<web::ReqData<User>>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_database()?;
+    let stock_code = Market::normalize_code(&path.into_inner());
+
+    match database
+        .remove_watchlist_entry(&request_owner(&user), &stock_code)
+        .await
+    {
+        Ok(true) => {
+            Ok(HttpResponse::Ok()
+                .json(ApiResponse::success(format!("已移除自选股 {}", stock_code))))
+        }
+        Ok(false) => {
+            Ok(HttpResponse::NotFound()
+                .json(ApiResponse::<String>::error("自选股不存在".to_string())))
+        }
+        Err(e) => Ok(
+            HttpResponse::InternalServerError().json(ApiResponse::<String>::error(format!(
+                "移除自选股失败: {}",
+                e
+            ))),
+        ),
+    }
+}
+
+/// 自选股定时分析的启用状态、上次运行时间与结果、下次计划运行时间
+pub async fn get_schedule_status(state: web::Data<AppState>) -> Result<HttpResponse> {
+    let status = state.scheduler.status().await;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(status)))
+}
+
+/// 对全部自选股发起批量分析，返回的任务 ID 与 /api/batch/analyze 相同方式查询
+pub async fn analyze_watchlist(
+    request: Option<web::Json<WatchlistAnalyzeRequest>>,
+    user: Option<web::ReqData<User>>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_database()?;
+    let request = request.map(|r| r.into_inner()).unwrap_or_default();
+
+    let entries = match database.get_watchlist(&request_owner(&user)).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Ok(
+                HttpResponse::InternalServerError().json(ApiResponse::<String>::error(format!(
+                    "获取自选股失败: {}",
+                    e
+                ))),
+            )
+        }
+    };
+    if entries.is_empty() {
+        return Ok(HttpResponse::BadRequest()
+            .json(ApiResponse::<String>::error("自选股列表为空".to_string())));
+    }
+
+    let stock_codes: Vec<String> = entries.into_iter().map(|entry| entry.stock_code).collect();
+    let options = AnalysisOptions::from(&BatchAnalysisRequest {
+        stock_codes: Vec::new(),
+        enable_ai: request.enable_ai,
+        structured_only: request.structured_only,
+    });
+    let task_id = start_batch_task(&state, &stock_codes, options);
+    Ok(HttpResponse::Ok().json(ApiResponse::success(task_id)))
+}
+
+/// 创建价格提醒规则，条件形如 "price > 12.5"、"rsi < 30"、"comprehensive_score > 70"
+pub async fn create_price_alert(
+    request: web::Json<CreateAlertRequest>,
+    user: Option<web::ReqData<User>>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_database()?;
+    let request = request.into_inner();
+
+    let stock_code = Market::normalize_code(&request.stock_code);
+    if stock_code.is_empty() {
+        return Ok(
+            HttpResponse::BadRequest().json(ApiResponse::<PriceAlert>::error(
+                "股票代码不能为空".to_string(),
+            )),
+        );
+    }
+    let webhook_url = request
+        .webhook_url
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty());
+    if let Some(url) = &webhook_url {
+        if let Err(e) = resolve_webhook_url(url).await {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<PriceAlert>::error(e)));
+        }
+    }
+
+    let alert = PriceAlert {
+        id: Uuid::new_v4().to_string(),
+        owner: request_owner(&user),
+        stock_code,
+        condition: request.condition,
+        webhook_url,
+        condition_met: false,
+        last_checked: None,
+        created_at: chrono::Utc::now(),
+    };
+
+    match database.create_price_alert(&alert).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(ApiResponse::success(alert))),
+        Err(e) => Ok(
+            HttpResponse::InternalServerError().json(ApiResponse::<PriceAlert>::error(format!(
+                "创建价格提醒失败: {}",
+                e
+            ))),
+        ),
+    }
+}
+
+pub async fn get_price_alerts(
+    user: Option<web::ReqData<User>>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_database()?;
+
+    match database.get_price_alerts(&request_owner(&user)).await {
+        Ok(alerts) => Ok(HttpResponse::Ok().json(ApiResponse::success(alerts))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(
+            ApiResponse::<Vec<PriceAlert>>::error(format!("获取价格提醒失败: {}", e)),
+        )),
+    }
+}
+
+pub async fn delete_price_alert(
+    path: web::Path<String>,
+    user: Option<web::ReqData<User>>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_database()?;
+    let alert_id = path.into_inner();
+
+    match database
+        .delete_price_alert(&request_owner(&user), &alert_id)
+        .await
+    {
+        Ok(true) => {
+            Ok(HttpResponse::Ok()
+                .json(ApiResponse::success(format!("已删除价格提醒 {}", alert_id))))
+        }
+        Ok(false) => Ok(HttpResponse::NotFound()
+            .json(ApiResponse::<String>::error("价格提醒不存在".to_string()))),
+        Err(e) => Ok(
+            HttpResponse::InternalServerError().json(ApiResponse::<String>::error(format!(
+                "删除价格提醒失败: {}",
+                e
+            ))),
+        ),
+    }
+}
+
+/// 已触发的价格提醒，按触发时间倒序
+pub async fn get_triggered_alerts(
+    query: web::Query<TriggeredAlertQuery>,
+    user: Option<web::ReqData<User>>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let database = state.require_database()?;
+    let stock_code = query.stock_code.as_deref().map(Market::normalize_code);
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+
+    match database
+        .get_triggered_alerts(&request_owner(&user), stock_code.as_deref(), limit)
+        .await
+    {
+        Ok(triggered) => Ok(HttpResponse::Ok().json(ApiResponse::success(triggered))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(
+            ApiResponse::<Vec<TriggeredAlert>>::error(format!("获取已触发提醒失败: {}", e)),
+        )),
     }
 }
@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use crate::models::{PriceData, TechnicalAnalysis};
+
+/// 自定义技术指标接口
+///
+/// 实现该 trait 并注册到 `IndicatorRegistry` 后，指标结果会出现在
+/// `TechnicalAnalysis::custom_indicators` 中，无需修改核心的技术分析计算。
+pub trait Indicator: Send + Sync {
+    /// 指标名称，作为 custom_indicators 的键
+    fn name(&self) -> &str;
+
+    /// 基于按日期升序排列的价格数据计算指标值，数据不足时返回 None
+    fn compute(&self, price_data: &[PriceData]) -> Option<f64>;
+}
+
+/// 指标注册表
+#[derive(Default)]
+pub struct IndicatorRegistry {
+    indicators: Vec<Box<dyn Indicator>>,
+}
+
+impl IndicatorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 包含内置指标（ROC、CMF）的注册表
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(RateOfChange::new(12)));
+        registry.register(Box::new(ChaikinMoneyFlow::new(20)));
+        registry
+    }
+
+    /// 注册指标，同名指标会被替换
+    pub fn register(&mut self, indicator: Box<dyn Indicator>) {
+        self.indicators.retain(|i| i.name() != indicator.name());
+        self.indicators.push(indicator);
+    }
+
+    #[allow(dead_code)]
+    pub fn names(&self) -> Vec<String> {
+        self.indicators
+            .iter()
+            .map(|i| i.name().to_string())
+            .collect()
+    }
+
+    /// 计算所有已注册指标，跳过数据不足或结果无效的指标
+    pub fn compute_all(&self, price_data: &[PriceData]) -> HashMap<String, f64> {
+        self.indicators
+            .iter()
+            .filter_map(|indicator| {
+                indicator
+                    .compute(price_data)
+                    .filter(|value| value.is_finite())
+                    .map(|value| (indicator.name().to_string(), value))
+            })
+            .collect()
+    }
+}
+
+/// 收盘价相对 `period` 个交易日前的变化率（%）
+pub fn rate_of_change(prices: &[f64], period: usize) -> Option<f64> {
+    if prices.len() <= period {
+        return None;
+    }
+
+    let base = prices[prices.len() - 1 - period];
+    if base == 0.0 {
+        return None;
+    }
+
+    Some((prices[prices.len() - 1] - base) / base * 100.0)
+}
+
+/// 对数收益率的类夏普比率：日均对数收益 / 日对数收益标准差（未年化）
+pub fn log_return_sharpe(prices: &[f64]) -> Option<f64> {
+    if prices.len() < 3 || prices.iter().any(|p| *p <= 0.0) {
+        return None;
+    }
+
+    let returns: Vec<f64> = prices.windows(2).map(|w| (w[1] / w[0]).ln()).collect();
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let variance =
+        returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+    let std_dev = variance.sqrt();
+
+    if std_dev > 0.0 {
+        Some(mean / std_dev)
+    } else {
+        None
+    }
+}
+
+/// 最近 `period` 个日对数收益率的样本标准差（未年化，0.02 表示 2%）
+///
+/// 需要至少两个收益率；数据不足 `period` 时使用全部收益率
+pub fn log_return_volatility(prices: &[f64], period: usize) -> Option<f64> {
+    if prices.iter().any(|p| *p <= 0.0) {
+        return None;
+    }
+
+    let start = prices.len().saturating_sub(period + 1);
+    let returns: Vec<f64> = prices[start..]
+        .windows(2)
+        .map(|w| (w[1] / w[0]).ln())
+        .collect();
+    if returns.len() < 2 {
+        return None;
+    }
+
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let variance =
+        returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+    Some(variance.sqrt())
+}
+
+/// 最大回撤：窗口内从历史高点到其后低点的最大跌幅比例（0.25 表示 25%）
+pub fn max_drawdown(prices: &[f64]) -> Option<f64> {
+    let first = *prices.first()?;
+    let mut peak = first;
+    let mut max_drawdown = 0.0_f64;
+
+    for &price in prices {
+        peak = peak.max(price);
+        if peak > 0.0 {
+            max_drawdown = max_drawdown.max((peak - price) / peak);
+        }
+    }
+
+    Some(max_drawdown)
+}
+
+/// 将各技术指标映射到统一的 0-100 看涨程度（50 为中性），便于前端统一展示
+///
+/// - 振荡指标按均值回归解读，超卖端为看涨：
+///   RSI 30→100、50→50、70→0；随机指标 K 20→100、80→0；
+///   威廉指标 -80→100、-20→0；CCI -100→100、+100→0；布林带位置 0→100、1→0
+/// - MACD 柱按占现价比例经 tanh 压缩：50 + 50·tanh(柱/现价×100)，柱为现价 1% 时约 88
+/// - 均线偏离：50 + 50·tanh(现价相对 MA20 的偏离 / 5%)
+///
+/// 超出区间的值截断到 0 或 100；缺少现价或 MA20 时相应指标不输出
+pub fn normalize_indicators(
+    technical: &TechnicalAnalysis,
+    current_price: f64,
+) -> HashMap<String, f64> {
+    let mut normalized = HashMap::new();
+    normalized.insert("rsi".to_string(), linear_scale(technical.rsi, 30.0, 70.0));
+    normalized.insert(
+        "stochastic_k".to_string(),
+        linear_scale(technical.stochastic_k, 20.0, 80.0),
+    );
+    normalized.insert(
+        "williams_r".to_string(),
+        linear_scale(technical.williams_r, -80.0, -20.0),
+    );
+    normalized.insert(
+        "cci".to_string(),
+        linear_scale(technical.cci, -100.0, 100.0),
+    );
+    normalized.insert(
+        "bb_position".to_string(),
+        linear_scale(technical.bb_position, 0.0, 1.0),
+    );
+
+    if current_price > 0.0 {
+        normalized.insert(
+            "macd_histogram".to_string(),
+            tanh_scale(technical.macd_histogram / current_price * 100.0),
+        );
+        if technical.ma20 > 0.0 {
+            normalized.insert(
+                "ma20_deviation".to_string(),
+                tanh_scale((current_price / technical.ma20 - 1.0) / 0.05),
+            );
+        }
+    }
+
+    normalized.retain(|_, value| value.is_finite());
+    normalized
+}
+
+/// 线性映射：`bullish_at` 对应 100，`bearish_at` 对应 0，超出部分截断
+fn linear_scale(value: f64, bullish_at: f64, bearish_at: f64) -> f64 {
+    ((value - bearish_at) / (bullish_at - bearish_at) * 100.0).clamp(0.0, 100.0)
+}
+
+/// 无界值的对称压缩：0 对应 50，正值趋向 100
+fn tanh_scale(value: f64) -> f64 {
+    50.0 + 50.0 * value.tanh()
+}
+
+/// 变动率指标 (Rate of Change)
+pub struct RateOfChange {
+    period: usize,
+    name: String,
+}
+
+impl RateOfChange {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            name: format!("roc_{}", period),
+        }
+    }
+}
+
+impl Indicator for RateOfChange {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compute(&self, price_data: &[PriceData]) -> Option<f64> {
+        let prices: Vec<f64> = price_data.iter().map(|p| p.close).collect();
+        rate_of_change(&prices, self.period)
+    }
+}
+
+/// 蔡金资金流量指标 (Chaikin Money Flow)，取值范围 -1 到 1
+pub struct ChaikinMoneyFlow {
+    period: usize,
+    name: String,
+}
+
+impl ChaikinMoneyFlow {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            name: format!("cmf_{}", period),
+        }
+    }
+}
+
+impl Indicator for ChaikinMoneyFlow {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compute(&self, price_data: &[PriceData]) -> Option<f64> {
+        if self.period == 0 || price_data.len() < self.period {
+            return None;
+        }
+
+        let window = &price_data[price_data.len() - self.period..];
+        let mut money_flow_volume = 0.0;
+        let mut total_volume = 0.0;
+
+        for candle in window {
+            let range = candle.high - candle.low;
+            let multiplier = if range > 0.0 {
+                ((candle.close - candle.low) - (candle.high - candle.close)) / range
+            } else {
+                0.0
+            };
+            money_flow_volume += multiplier * candle.volume as f64;
+            total_volume += candle.volume as f64;
+        }
+
+        if total_volume > 0.0 {
+            Some(money_flow_volume / total_volume)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn candle(close: f64, high: f64, low: f64, volume: i64) -> PriceData {
+        PriceData {
+            date: Utc::now(),
+            open: close,
+            close,
+            high,
+            low,
+            volume,
+            change_pct: 0.0,
+            turnover: 0.0,
+            turnover_rt: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_rate_of_change() {
+        let prices = vec![10.0, 10.5, 11.0, 12.0];
+        assert!((rate_of_change(&prices, 3).unwrap() - 20.0).abs() < 1e-9);
+        assert!(rate_of_change(&prices, 4).is_none());
+    }
+
+    #[test]
+    fn test_log_return_sharpe_prefers_steady_trend() {
+        let trend = |noise: f64| -> Vec<f64> {
+            (0..30)
+                .map(|i| {
+                    10.0 * 1.01f64.powi(i) * (1.0 + noise * if i % 2 == 0 { 1.0 } else { -1.0 })
+                })
+                .collect()
+        };
+
+        let steady = log_return_sharpe(&trend(0.001)).unwrap();
+        let choppy = log_return_sharpe(&trend(0.05)).unwrap();
+        assert!(steady > choppy);
+        assert!(log_return_sharpe(&[10.0, 11.0]).is_none());
+    }
+
+    #[test]
+    fn test_normalize_indicators_known_mappings() {
+        let technical = TechnicalAnalysis {
+            rsi: 30.0,
+            stochastic_k: 80.0,
+            williams_r: -50.0,
+            cci: 250.0,
+            bb_position: 0.25,
+            macd_histogram: 0.0,
+            ma20: 10.0,
+            ..Default::default()
+        };
+
+        let normalized = normalize_indicators(&technical, 10.0);
+        assert_eq!(normalized["rsi"], 100.0);
+        assert_eq!(normalized["stochastic_k"], 0.0);
+        assert_eq!(normalized["williams_r"], 50.0);
+        assert_eq!(normalized["cci"], 0.0);
+        assert_eq!(normalized["bb_position"], 75.0);
+        assert_eq!(normalized["macd_histogram"], 50.0);
+        assert_eq!(normalized["ma20_deviation"], 50.0);
+
+        let rsi_70 = TechnicalAnalysis {
+            rsi: 70.0,
+            macd_histogram: 0.1,
+            ..Default::default()
+        };
+        let normalized = normalize_indicators(&rsi_70, 10.0);
+        assert_eq!(normalized["rsi"], 0.0);
+        assert!((normalized["macd_histogram"] - 88.079).abs() < 1e-3);
+        assert!(!normalized.contains_key("ma20_deviation"));
+        assert!(normalized.values().all(|v| (0.0..=100.0).contains(v)));
+    }
+
+    #[test]
+    fn test_log_return_volatility_uses_recent_returns() {
+        // Alternating +10% / -10% moves have a daily log-return deviation of about 0.1
+        let choppy: Vec<f64> = (0..21)
+            .map(|i| if i % 2 == 0 { 100.0 } else { 110.0 })
+            .collect();
+        let volatility = log_return_volatility(&choppy, 20).unwrap();
+        assert!((volatility - 0.0977).abs() < 1e-3);
+
+        // A constant-growth series has no dispersion, and only the last window counts
+        let mut prices = choppy.clone();
+        prices.extend((1..=20).map(|i| 100.0 * 1.01f64.powi(i)));
+        assert!(log_return_volatility(&prices, 20).unwrap() < 1e-9);
+        assert!(log_return_volatility(&[10.0, 11.0], 20).is_none());
+        assert!(log_return_volatility(&[10.0, 0.0, 11.0], 20).is_none());
+    }
+
+    #[test]
+    fn test_max_drawdown() {
+        let prices = vec![10.0, 12.0, 9.0, 11.0, 15.0, 12.0];
+        assert!((max_drawdown(&prices).unwrap() - 0.25).abs() < 1e-9);
+        assert_eq!(max_drawdown(&[10.0, 11.0, 12.0]), Some(0.0));
+        assert!(max_drawdown(&[]).is_none());
+    }
+
+    #[test]
+    fn test_chaikin_money_flow_closes_at_high() {
+        let data: Vec<PriceData> = (0..5).map(|_| candle(11.0, 11.0, 10.0, 1000)).collect();
+        let cmf = ChaikinMoneyFlow::new(5).compute(&data).unwrap();
+        assert!((cmf - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_registry_replaces_same_name() {
+        let mut registry = IndicatorRegistry::with_builtins();
+        registry.register(Box::new(RateOfChange::new(12)));
+        assert_eq!(registry.names(), vec!["cmf_20", "roc_12"]);
+
+        let data: Vec<PriceData> = (0..30)
+            .map(|i| candle(10.0 + i as f64, 11.0 + i as f64, 9.0 + i as f64, 1000))
+            .collect();
+        let values = registry.compute_all(&data);
+        assert!(values.contains_key("roc_12"));
+        assert!(values.contains_key("cmf_20"));
+    }
+}
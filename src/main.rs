@@ -1,5 +1,5 @@
 use actix_cors::Cors;
-use actix_web::{web, App, HttpServer};
+use actix_web::{middleware, web, App, HttpServer};
 use env_logger::Env;
 use log::info;
 
@@ -12,9 +12,16 @@ mod currency;
 mod data_fetcher;
 mod database;
 mod handlers;
+mod indicators;
 mod models;
+mod paper_trading;
+mod price_alerts;
+mod rate_limit;
+mod retention;
+mod scheduler;
 mod signal_alerts;
 mod trading_strategies;
+mod tushare;
 
 use crate::handlers::AppState;
 use crate::models::AppConfig;
@@ -24,7 +31,11 @@ async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(Env::default().default_filter_or("info"));
 
     // Load configuration
-    let config = load_config();
+    let mut config = load_config();
+    if let Err(e) = config.normalize_weights() {
+        log::error!("{e}");
+        return Err(std::io::Error::other(e));
+    }
 
     info!(
         "Starting Rust Stock Analyzer on {}:{}",
@@ -32,7 +43,12 @@ async fn main() -> std::io::Result<()> {
     );
     info!("Akshare proxy URL: {}", config.akshare.proxy_url);
     info!("Max workers: {}", config.analysis.max_workers);
-    info!("Database URL: {}", config.database.url);
+    info!(
+        "Database URL: {}",
+        models::redact_url_credentials(&config.database.url)
+    );
+
+    models::Market::set_overrides(config.market_overrides.clone());
 
     let app_state = match AppState::new(config.clone()).await {
         Ok(state) => web::Data::new(state),
@@ -42,6 +58,11 @@ async fn main() -> std::io::Result<()> {
         }
     };
 
+    let base_path = app_state.base_path.clone();
+    if !base_path.is_empty() {
+        info!("Routes mounted under base path: {}", base_path);
+    }
+
     HttpServer::new(move || {
         let cors = Cors::default()
             .allowed_origin_fn(|_origin, _req_head| true)
@@ -52,93 +73,264 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .app_data(app_state.clone())
             .wrap(cors)
-            .wrap(actix_web::middleware::Logger::default())
+            // Same as the default format, except that the /ws `token` parameter is masked
+            .wrap(
+                middleware::Logger::new(
+                    r#"%a "%{request_line}xi" %s %b "%{Referer}i" "%{User-Agent}i" %T"#,
+                )
+                .custom_request_replace("request_line", handlers::log_request_line),
+            )
             .service(
-                web::scope("/api")
-                    .route("/analyze", web::post().to(handlers::analyze_single))
-                    .route(
-                        "/analyze/stream",
-                        web::post().to(handlers::analyze_single_streaming),
-                    )
-                    .route("/batch/analyze", web::post().to(handlers::analyze_batch))
-                    .route(
-                        "/batch/status/{task_id}",
-                        web::get().to(handlers::get_task_status),
-                    )
-                    .route(
-                        "/stock/{stock_code}/price",
-                        web::get().to(handlers::get_stock_price),
-                    )
-                    .route(
-                        "/stock/{stock_code}/fundamental",
-                        web::get().to(handlers::get_stock_fundamental),
-                    )
-                    .route(
-                        "/stock/{stock_code}/news",
-                        web::get().to(handlers::get_stock_news),
-                    )
-                    .route(
-                        "/stock/{stock_code}/name",
-                        web::get().to(handlers::get_stock_name),
-                    )
-                    .route("/health", web::get().to(handlers::health_check))
-                    .route("/cache/stats", web::get().to(handlers::get_cache_stats))
-                    .route("/cache/clear", web::post().to(handlers::clear_cache))
-                    .route(
-                        "/currency/convert",
-                        web::get().to(handlers::convert_currency),
-                    )
-                    .route(
-                        "/currency/exchange-rate",
-                        web::get().to(handlers::get_exchange_rate),
+                web::scope(&base_path)
+                    // Public API routes are registered ahead of the authenticated /api scope
+                    .route("/api/health", web::get().to(handlers::health_check))
+                    // Login and registration skip auth but stay rate limited against brute force
+                    .service(
+                        web::resource("/api/auth/login")
+                            .wrap(middleware::from_fn(handlers::rate_limit))
+                            .route(web::post().to(handlers::login)),
                     )
-                    .route(
-                        "/currency/supported",
-                        web::get().to(handlers::get_supported_currencies),
+                    .service(
+                        web::resource("/api/auth/register")
+                            .wrap(middleware::from_fn(handlers::rate_limit))
+                            .route(web::post().to(handlers::register)),
                     )
-                    .route("/market/time", web::get().to(handlers::get_market_time))
                     .service(
-                        web::scope("/config")
-                            .route("/ai", web::get().to(handlers::get_ai_config))
-                            .route("/ai", web::post().to(handlers::update_ai_config))
-                            .route("/ai/providers", web::get().to(handlers::get_ai_providers))
-                            .route("/ai/test", web::post().to(handlers::test_ai_connection))
-                            .route("/auth", web::get().to(handlers::get_auth_config))
-                            .route("/auth", web::post().to(handlers::update_auth_config))
-                            .route("/system", web::get().to(handlers::get_system_config))
-                            .route("/system", web::post().to(handlers::update_system_config))
+                        web::scope("/api")
+                            .wrap(middleware::from_fn(handlers::require_auth))
+                            .wrap(middleware::from_fn(handlers::rate_limit))
+                            .route("/auth/me", web::get().to(handlers::current_user))
+                            .route("/analyze", web::post().to(handlers::analyze_single))
+                            .route(
+                                "/analyze/stream",
+                                web::post().to(handlers::analyze_single_streaming),
+                            )
+                            .route(
+                                "/analyze/cached/{stock_code}",
+                                web::get().to(handlers::get_cached_analysis),
+                            )
+                            .route("/batch/analyze", web::post().to(handlers::analyze_batch))
+                            .route(
+                                "/batch/status/{task_id}",
+                                web::get().to(handlers::get_task_status),
+                            )
+                            .route(
+                                "/batch/cancel/{task_id}",
+                                web::post().to(handlers::cancel_batch_task),
+                            )
+                            .route(
+                                "/batch/results/{task_id}",
+                                web::get().to(handlers::get_batch_results),
+                            )
+                            .route(
+                                "/stock/{stock_code}/price",
+                                web::get().to(handlers::get_stock_price),
+                            )
+                            .route(
+                                "/stock/{stock_code}/price/stream",
+                                web::get().to(handlers::stream_stock_price),
+                            )
+                            .route(
+                                "/stock/{stock_code}/intraday",
+                                web::get().to(handlers::get_intraday_data),
+                            )
+                            .route(
+                                "/stock/{stock_code}/fundamental",
+                                web::get().to(handlers::get_stock_fundamental),
+                            )
+                            .route(
+                                "/stock/{stock_code}/news",
+                                web::get().to(handlers::get_stock_news),
+                            )
+                            .route(
+                                "/stock/{stock_code}/name",
+                                web::get().to(handlers::get_stock_name),
+                            )
+                            .route(
+                                "/stock/{stock_code}/risk-metrics",
+                                web::get().to(handlers::get_risk_metrics),
+                            )
+                            .route(
+                                "/stock/{stock_code}/strategies",
+                                web::get().to(handlers::get_stock_strategies),
+                            )
+                            .route(
+                                "/stock/{stock_code}/strategies",
+                                web::post().to(handlers::analyze_stock_strategies),
+                            )
+                            .route(
+                                "/stock/{stock_code}/signals/history",
+                                web::get().to(handlers::get_signal_history),
+                            )
+                            .route("/scores", web::get().to(handlers::get_scores))
+                            .route("/compare", web::post().to(handlers::compare_stocks))
+                            .route("/portfolio", web::post().to(handlers::analyze_portfolio))
+                            .route("/correlation", web::get().to(handlers::get_correlation))
+                            .route("/cache/stats", web::get().to(handlers::get_cache_stats))
+                            .route("/cache/clear", web::post().to(handlers::clear_cache))
+                            .route(
+                                "/cache/clear/{stock_code}",
+                                web::post().to(handlers::clear_stock_cache),
+                            )
+                            .route(
+                                "/currency/convert",
+                                web::get().to(handlers::convert_currency),
+                            )
+                            .route(
+                                "/currency/exchange-rate",
+                                web::get().to(handlers::get_exchange_rate),
+                            )
+                            .route(
+                                "/currency/supported",
+                                web::get().to(handlers::get_supported_currencies),
+                            )
+                            .route("/market/time", web::get().to(handlers::get_market_time))
+                            .service(
+                                web::scope("/config")
+                                    .route("/ai", web::get().to(handlers::get_ai_config))
+                                    .route("/ai", web::post().to(handlers::update_ai_config))
+                                    .route(
+                                        "/ai/providers",
+                                        web::get().to(handlers::get_ai_providers),
+                                    )
+                                    .route("/ai/test", web::post().to(handlers::test_ai_connection))
+                                    .route("/auth", web::get().to(handlers::get_auth_config))
+                                    .route("/auth", web::post().to(handlers::update_auth_config))
+                                    .route("/system", web::get().to(handlers::get_system_config))
+                                    .route(
+                                        "/effective",
+                                        web::get().to(handlers::get_effective_config),
+                                    )
+                                    .route(
+                                        "/system",
+                                        web::post().to(handlers::update_system_config),
+                                    )
+                                    .route(
+                                        "/datasource/test",
+                                        web::post().to(handlers::test_datasource),
+                                    ),
+                            )
+                            .route("/history", web::get().to(handlers::get_analysis_history))
+                            .route(
+                                "/history",
+                                web::delete().to(handlers::purge_analysis_history),
+                            )
+                            .route("/history/stats", web::get().to(handlers::get_history_stats))
+                            .route("/stats/summary", web::get().to(handlers::get_stats_summary))
+                            .route(
+                                "/history/export",
+                                web::get().to(handlers::export_analysis_history),
+                            )
+                            .route(
+                                "/history/rescore",
+                                web::post().to(handlers::rescore_history),
+                            )
+                            .route(
+                                "/weights/simulate",
+                                web::post().to(handlers::simulate_weights),
+                            )
+                            .route("/history/{id}", web::get().to(handlers::get_analysis_by_id))
+                            .route(
+                                "/history/{id}/export",
+                                web::get().to(handlers::export_analysis_report),
+                            )
                             .route(
                                 "/datasource/test",
                                 web::post().to(handlers::test_datasource),
-                            ),
-                    )
-                    .route("/history", web::get().to(handlers::get_analysis_history))
-                    .route("/history/{id}", web::get().to(handlers::get_analysis_by_id))
-                    .route("/datasource/test", web::post().to(handlers::test_datasource))
-                    // 筹码监控和策略分析端点
-                    .route("/chip/analysis/{stock_code}", web::get().to(handlers::get_chip_analysis))
-                    .route("/strategies/analysis/{stock_code}", web::get().to(handlers::get_strategies_analysis))
-                    .route("/signals/generate/{stock_code}", web::post().to(handlers::generate_trading_signals))
-                    .route("/alerts", web::get().to(handlers::get_active_alerts))
-                    .route("/alerts/{stock_code}", web::get().to(handlers::get_stock_alerts))
-                    .route("/alerts/{alert_id}/cancel", web::post().to(handlers::cancel_alert))
-                    .route("/alerts/statistics/{stock_code}", web::get().to(handlers::get_signal_statistics))
-                    .service(
-                        web::scope("/configurations")
-                            .route("", web::post().to(handlers::save_configuration))
-                            .route("", web::get().to(handlers::get_configurations))
+                            )
+                            // 筹码监控和策略分析端点
+                            .route(
+                                "/chip/analysis/{stock_code}",
+                                web::get().to(handlers::get_chip_analysis),
+                            )
+                            .route(
+                                "/strategies/analysis/{stock_code}",
+                                web::get().to(handlers::get_strategies_analysis),
+                            )
+                            .route(
+                                "/signals/generate/{stock_code}",
+                                web::post().to(handlers::generate_trading_signals),
+                            )
+                            .route(
+                                "/signals/scan",
+                                web::post().to(handlers::scan_trading_signals),
+                            )
+                            .route("/backtest", web::post().to(handlers::run_backtest))
+                            .route("/alerts", web::get().to(handlers::get_active_alerts))
+                            // 价格提醒规则；固定路径需在 /alerts/{stock_code} 之前注册
+                            .route("/alerts", web::post().to(handlers::create_price_alert))
+                            .route(
+                                "/alerts/triggered",
+                                web::get().to(handlers::get_triggered_alerts),
+                            )
+                            .route("/alerts/rules", web::get().to(handlers::get_price_alerts))
+                            .route(
+                                "/alerts/rules/{alert_id}",
+                                web::delete().to(handlers::delete_price_alert),
+                            )
+                            .route(
+                                "/alerts/{stock_code}",
+                                web::get().to(handlers::get_stock_alerts),
+                            )
+                            .route(
+                                "/alerts/{alert_id}/cancel",
+                                web::post().to(handlers::cancel_alert),
+                            )
+                            .route(
+                                "/alerts/statistics/{stock_code}",
+                                web::get().to(handlers::get_signal_statistics),
+                            )
+                            .service(
+                                web::scope("/paper/accounts")
+                                    .route("", web::post().to(handlers::create_paper_account))
+                                    .route("", web::get().to(handlers::list_paper_accounts))
+                                    .route(
+                                        "/{id}/auto-execute",
+                                        web::post().to(handlers::update_paper_auto_execute),
+                                    )
+                                    .route(
+                                        "/{id}/ledger",
+                                        web::get().to(handlers::get_paper_ledger),
+                                    ),
+                            )
+                            .service(
+                                web::scope("/watchlist")
+                                    .route("", web::post().to(handlers::add_watchlist_entry))
+                                    .route("", web::get().to(handlers::get_watchlist))
+                                    .route(
+                                        "/analyze",
+                                        web::post().to(handlers::analyze_watchlist),
+                                    )
+                                    .route(
+                                        "/{stock_code}",
+                                        web::delete().to(handlers::remove_watchlist_entry),
+                                    ),
+                            )
                             .route(
-                                "/{id}/activate",
-                                web::post().to(handlers::activate_configuration),
+                                "/schedule/status",
+                                web::get().to(handlers::get_schedule_status),
                             )
-                            .route("/{id}", web::delete().to(handlers::delete_configuration)),
-                    ),
+                            .service(
+                                web::scope("/configurations")
+                                    .route("", web::post().to(handlers::save_configuration))
+                                    .route("", web::get().to(handlers::get_configurations))
+                                    .route(
+                                        "/{id}/activate",
+                                        web::post().to(handlers::activate_configuration),
+                                    )
+                                    .route(
+                                        "/{id}",
+                                        web::delete().to(handlers::delete_configuration),
+                                    ),
+                            ),
+                    )
+                    .route("/ws", web::get().to(handlers::websocket_handler))
+                    .route("", web::get().to(handlers::index))
+                    .route("/", web::get().to(handlers::index))
+                    .route("/batch", web::get().to(handlers::batch))
+                    .route("/config", web::get().to(handlers::config))
+                    .route("/test-config", web::get().to(handlers::test_config)),
             )
-            .route("/ws", web::get().to(handlers::websocket_handler))
-            .route("/", web::get().to(handlers::index))
-            .route("/batch", web::get().to(handlers::batch))
-            .route("/config", web::get().to(handlers::config))
-            .route("/test-config", web::get().to(handlers::test_config))
     })
     .bind((config.server.host.as_str(), config.server.port))?
     .workers(config.server.workers.unwrap_or(4))
@@ -146,6 +338,7 @@ async fn main() -> std::io::Result<()> {
     .await
 }
 
+// Shared by startup and the system config endpoint, so every setting is read in one place
 fn load_config() -> AppConfig {
     use std::fs;
 
@@ -157,6 +350,17 @@ fn load_config() -> AppConfig {
     }
 
     // Try to load from environment or use defaults
+    let parameters = models::AnalysisParameters {
+        technical_period_days: std::env::var("TECHNICAL_PERIOD")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .unwrap_or(60),
+        sentiment_period_days: std::env::var("SENTIMENT_PERIOD")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .unwrap_or(30),
+    };
+
     AppConfig {
         server: models::ServerConfig {
             host: std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
@@ -165,6 +369,19 @@ fn load_config() -> AppConfig {
                 .parse()
                 .unwrap_or(8080),
             workers: std::env::var("WORKERS").ok().and_then(|w| w.parse().ok()),
+            base_path: std::env::var("BASE_PATH").unwrap_or_default(),
+            progress_channel_capacity: std::env::var("PROGRESS_CHANNEL_CAPACITY")
+                .unwrap_or_else(|_| "256".to_string())
+                .parse()
+                .unwrap_or(256),
+            expose_effective_config: std::env::var("EXPOSE_EFFECTIVE_CONFIG")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+            price_stream_interval_seconds: std::env::var("PRICE_STREAM_INTERVAL")
+                .unwrap_or_else(|_| "15".to_string())
+                .parse()
+                .unwrap_or(15),
         },
         analysis: models::AnalysisConfig {
             max_workers: std::env::var("MAX_WORKERS")
@@ -189,15 +406,78 @@ fn load_config() -> AppConfig {
                     .parse()
                     .unwrap_or(0.2),
             },
-            parameters: models::AnalysisParameters {
-                technical_period_days: std::env::var("TECHNICAL_PERIOD")
-                    .unwrap_or_else(|_| "60".to_string())
+            parameters: parameters.clone(),
+            anomaly_alert: models::AnomalyAlertConfig {
+                enabled: std::env::var("ANOMALY_ALERT_ENABLED")
+                    .unwrap_or_else(|_| "false".to_string())
+                    .parse()
+                    .unwrap_or(false),
+                score_delta_threshold: std::env::var("ANOMALY_SCORE_DELTA")
+                    .unwrap_or_else(|_| "20".to_string())
+                    .parse()
+                    .unwrap_or(20.0),
+                webhook_url: std::env::var("ANOMALY_WEBHOOK_URL").ok(),
+            },
+            scoring_mode: std::env::var("SCORING_MODE")
+                .unwrap_or_else(|_| "indicator".to_string())
+                .parse()
+                .unwrap_or_default(),
+            cached_report_max_age_seconds: std::env::var("CACHED_REPORT_MAX_AGE")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .unwrap_or(3600),
+            risk_free_rate: std::env::var("RISK_FREE_RATE")
+                .unwrap_or_else(|_| "0.02".to_string())
+                .parse()
+                .unwrap_or(0.02),
+            market_parameters: std::env::var("MARKET_PERIODS")
+                .map(|v| models::AnalysisParameters::parse_market_overrides(&v, &parameters))
+                .unwrap_or_default(),
+            compare_to_index: std::env::var("COMPARE_TO_INDEX")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .unwrap_or(true),
+            local_beta: std::env::var("LOCAL_BETA")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .unwrap_or(true),
+            data_fetch_timeout_seconds: std::env::var("DATA_FETCH_TIMEOUT")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+            ai_phase_timeout_seconds: std::env::var("AI_PHASE_TIMEOUT")
+                .unwrap_or_else(|_| "120".to_string())
+                .parse()
+                .unwrap_or(120),
+            max_news: std::env::var("MAX_NEWS")
+                .unwrap_or_else(|_| "50".to_string())
+                .parse()
+                .unwrap_or(50),
+            summary_mode: std::env::var("SUMMARY_MODE")
+                .unwrap_or_else(|_| "deterministic".to_string())
+                .parse()
+                .unwrap_or_default(),
+            grade_thresholds: std::env::var("GRADE_THRESHOLDS")
+                .ok()
+                .and_then(|v| models::GradeThreshold::parse_curve(&v))
+                .unwrap_or_else(models::GradeThreshold::default_curve),
+            liquidity_gate: models::LiquidityGateConfig {
+                enabled: std::env::var("LIQUIDITY_GATE_ENABLED")
+                    .unwrap_or_else(|_| "true".to_string())
+                    .parse()
+                    .unwrap_or(true),
+                lookback_days: std::env::var("LIQUIDITY_LOOKBACK_DAYS")
+                    .unwrap_or_else(|_| "20".to_string())
+                    .parse()
+                    .unwrap_or(20),
+                min_avg_volume: std::env::var("MIN_AVG_VOLUME")
+                    .unwrap_or_else(|_| "100000".to_string())
                     .parse()
-                    .unwrap_or(60),
-                sentiment_period_days: std::env::var("SENTIMENT_PERIOD")
-                    .unwrap_or_else(|_| "30".to_string())
+                    .unwrap_or(100_000.0),
+                min_avg_turnover: std::env::var("MIN_AVG_TURNOVER")
+                    .unwrap_or_else(|_| "1000000".to_string())
                     .parse()
-                    .unwrap_or(30),
+                    .unwrap_or(1_000_000.0),
             },
         },
         akshare: models::AkshareConfig {
@@ -207,6 +487,40 @@ fn load_config() -> AppConfig {
                 .unwrap_or_else(|_| "30".to_string())
                 .parse()
                 .unwrap_or(30),
+            price_batch_size: std::env::var("AKSERVICE_PRICE_BATCH_SIZE")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .unwrap_or(0),
+            endpoints: models::AkshareEndpoints::default(),
+            requests_per_second: std::env::var("AKSERVICE_REQUESTS_PER_SECOND")
+                .unwrap_or_else(|_| "10".to_string())
+                .parse()
+                .unwrap_or(10),
+            max_retries: std::env::var("AKSERVICE_MAX_RETRIES")
+                .unwrap_or_else(|_| "2".to_string())
+                .parse()
+                .unwrap_or(2),
+            allow_mock_fallback: std::env::var("AKSERVICE_ALLOW_MOCK_FALLBACK")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+        },
+        data_source: std::env::var("DATA_SOURCE")
+            .unwrap_or_else(|_| "akshare".to_string())
+            .parse()
+            .unwrap_or_default(),
+        tushare: models::TushareConfig {
+            token: std::env::var("TUSHARE_TOKEN").unwrap_or_default(),
+            api_url: std::env::var("TUSHARE_API_URL")
+                .unwrap_or_else(|_| "http://api.tushare.pro".to_string()),
+            timeout_seconds: std::env::var("TUSHARE_TIMEOUT")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            requests_per_second: std::env::var("TUSHARE_REQUESTS_PER_SECOND")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .unwrap_or(3),
         },
         ai: models::AIConfig {
             provider: std::env::var("AI_PROVIDER").unwrap_or_else(|_| "openai".to_string()),
@@ -221,6 +535,23 @@ fn load_config() -> AppConfig {
                 .unwrap_or_else(|_| "30".to_string())
                 .parse()
                 .unwrap_or(30),
+            custom_headers: std::env::var("AI_CUSTOM_HEADERS")
+                .ok()
+                .and_then(|v| models::AIConfig::parse_custom_headers(&v)),
+            token_prices: std::env::var("AI_TOKEN_PRICES")
+                .map(|v| models::AIConfig::parse_token_prices(&v))
+                .unwrap_or_default(),
+            fallback_providers: std::env::var("AI_FALLBACK_PROVIDERS")
+                .map(|v| models::AIConfig::parse_fallback_providers(&v))
+                .unwrap_or_default(),
+            system_prompt: std::env::var("AI_SYSTEM_PROMPT").ok(),
+            output_language: std::env::var("AI_OUTPUT_LANGUAGE").ok(),
+            temperature: std::env::var("AI_TEMPERATURE")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            max_tokens: std::env::var("AI_MAX_TOKENS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
         },
         auth: models::AuthConfig {
             enabled: std::env::var("AUTH_ENABLED")
@@ -228,7 +559,7 @@ fn load_config() -> AppConfig {
                 .parse()
                 .unwrap_or(false),
             secret_key: std::env::var("AUTH_SECRET_KEY")
-                .unwrap_or_else(|_| "your-secret-key-change-this".to_string()),
+                .unwrap_or_else(|_| models::DEFAULT_SECRET_KEY.to_string()),
             session_timeout: std::env::var("SESSION_TIMEOUT")
                 .unwrap_or_else(|_| "86400".to_string())
                 .parse()
@@ -237,6 +568,13 @@ fn load_config() -> AppConfig {
                 .unwrap_or_else(|_| "12".to_string())
                 .parse()
                 .unwrap_or(12),
+            admin_password: std::env::var("ADMIN_PASSWORD")
+                .ok()
+                .filter(|password| !password.is_empty()),
+            allow_registration: std::env::var("ALLOW_REGISTRATION")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
         },
         database: models::DatabaseConfig {
             url: std::env::var("DATABASE_URL")
@@ -249,6 +587,18 @@ fn load_config() -> AppConfig {
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()
                 .unwrap_or(true),
+            persist_signals: std::env::var("DATABASE_PERSIST_SIGNALS")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .unwrap_or(true),
+            stateless_fallback: std::env::var("DATABASE_STATELESS_FALLBACK")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+            retention_days: std::env::var("DATABASE_RETENTION_DAYS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .unwrap_or(0),
         },
         cache: models::CacheConfig {
             enabled: std::env::var("CACHE_ENABLED")
@@ -283,6 +633,66 @@ fn load_config() -> AppConfig {
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()
                 .unwrap_or(true),
+            coalesce_requests: std::env::var("CACHE_COALESCE_REQUESTS")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .unwrap_or(true),
+            backend: std::env::var("CACHE_BACKEND")
+                .unwrap_or_else(|_| "memory".to_string())
+                .parse()
+                .unwrap_or_default(),
+            redis_url: std::env::var("REDIS_URL").ok(),
+        },
+        paper_trading: models::PaperTradingConfig {
+            enabled: std::env::var("PAPER_TRADING_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+            default_initial_cash: std::env::var("PAPER_INITIAL_CASH")
+                .unwrap_or_else(|_| "1000000".to_string())
+                .parse()
+                .unwrap_or(1_000_000.0),
+            default_position_fraction: std::env::var("PAPER_POSITION_FRACTION")
+                .unwrap_or_else(|_| "0.2".to_string())
+                .parse()
+                .unwrap_or(0.2),
+        },
+        rate_limit: models::RateLimitConfig {
+            enabled: std::env::var("RATE_LIMIT_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+            requests_per_minute: std::env::var("RATE_LIMIT_REQUESTS_PER_MINUTE")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+        },
+        schedule: models::ScheduleConfig {
+            enabled: std::env::var("SCHEDULE_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+            interval_seconds: std::env::var("SCHEDULE_INTERVAL_SECONDS")
+                .unwrap_or_else(|_| "86400".to_string())
+                .parse()
+                .unwrap_or(86400),
+            enable_ai: std::env::var("SCHEDULE_ENABLE_AI")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+        },
+        price_alerts: models::PriceAlertConfig {
+            enabled: std::env::var("PRICE_ALERTS_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+            check_interval_seconds: std::env::var("PRICE_ALERT_CHECK_INTERVAL_SECONDS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()
+                .unwrap_or(300),
         },
+        market_overrides: std::env::var("MARKET_OVERRIDES")
+            .map(|v| models::Market::parse_overrides(&v))
+            .unwrap_or_default(),
     }
 }
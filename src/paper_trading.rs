@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::models::{PaperAccount, PaperLedger, PaperPosition, PaperTrade};
+
+/// 触发模拟买入的整体信号
+pub const STRONG_BUY: &str = "强烈买入";
+/// 触发模拟卖出的整体信号
+pub const STRONG_SELL: &str = "强烈卖出";
+
+/// 单只股票的持仓
+#[derive(Debug, Clone, PartialEq)]
+pub struct Holding {
+    pub stock_name: String,
+    pub quantity: i64,
+    pub cost_basis: f64,
+    pub last_trade_price: f64,
+}
+
+impl Holding {
+    pub fn avg_cost(&self) -> f64 {
+        if self.quantity > 0 {
+            self.cost_basis / self.quantity as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// 按时间顺序重放成交记录得到的账户状态
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaperBook {
+    pub cash: f64,
+    pub holdings: HashMap<String, Holding>,
+    pub realized_pnl: f64,
+}
+
+impl PaperBook {
+    pub fn replay(account: &PaperAccount, trades: &[PaperTrade]) -> Self {
+        let mut book = Self {
+            cash: account.initial_cash,
+            holdings: HashMap::new(),
+            realized_pnl: 0.0,
+        };
+
+        for trade in trades {
+            match trade.side.as_str() {
+                "买入" => {
+                    book.cash -= trade.amount;
+                    let holding = book
+                        .holdings
+                        .entry(trade.stock_code.clone())
+                        .or_insert_with(|| Holding {
+                            stock_name: trade.stock_name.clone(),
+                            quantity: 0,
+                            cost_basis: 0.0,
+                            last_trade_price: trade.price,
+                        });
+                    holding.quantity += trade.quantity;
+                    holding.cost_basis += trade.amount;
+                    holding.last_trade_price = trade.price;
+                }
+                "卖出" => {
+                    book.cash += trade.amount;
+                    book.realized_pnl += trade.realized_pnl;
+                    if let Some(holding) = book.holdings.get_mut(&trade.stock_code) {
+                        let sold_cost = holding.avg_cost() * trade.quantity as f64;
+                        holding.quantity -= trade.quantity;
+                        holding.cost_basis -= sold_cost;
+                        holding.last_trade_price = trade.price;
+                        if holding.quantity <= 0 {
+                            book.holdings.remove(&trade.stock_code);
+                        }
+                    }
+                }
+                other => log::warn!("Ignoring paper trade {} with side {}", trade.id, other),
+            }
+        }
+
+        book
+    }
+
+    /// 根据整体信号决定是否成交：强烈买入时空仓则按仓位比例以信号价买入整数股，
+    /// 强烈卖出时清仓；其余信号、已持仓时的重复买入与空仓卖出均不成交
+    pub fn plan_trade(
+        &self,
+        account: &PaperAccount,
+        stock_code: &str,
+        stock_name: &str,
+        overall_signal: &str,
+        price: f64,
+    ) -> Option<PaperTrade> {
+        if !price.is_finite() || price <= 0.0 {
+            return None;
+        }
+
+        let (side, quantity, realized_pnl) = match overall_signal {
+            STRONG_BUY if !self.holdings.contains_key(stock_code) => {
+                let budget = self.cash.max(0.0) * account.position_fraction;
+                let quantity = (budget / price).floor() as i64;
+                ("买入", quantity, 0.0)
+            }
+            STRONG_SELL => {
+                let holding = self.holdings.get(stock_code)?;
+                let realized = (price - holding.avg_cost()) * holding.quantity as f64;
+                ("卖出", holding.quantity, realized)
+            }
+            _ => return None,
+        };
+
+        if quantity <= 0 {
+            return None;
+        }
+
+        Some(PaperTrade {
+            id: Uuid::new_v4().to_string(),
+            account_id: account.id.clone(),
+            stock_code: stock_code.to_string(),
+            stock_name: stock_name.to_string(),
+            side: side.to_string(),
+            quantity,
+            price,
+            amount: price * quantity as f64,
+            signal: overall_signal.to_string(),
+            realized_pnl,
+            executed_at: Utc::now(),
+        })
+    }
+
+    /// 以最新价对持仓估值，缺少最新价时使用最近成交价
+    pub fn into_ledger(
+        self,
+        account: PaperAccount,
+        trades: Vec<PaperTrade>,
+        last_prices: &HashMap<String, f64>,
+    ) -> PaperLedger {
+        let mut positions: Vec<PaperPosition> = self
+            .holdings
+            .into_iter()
+            .map(|(stock_code, holding)| {
+                let last_price = last_prices
+                    .get(&stock_code)
+                    .copied()
+                    .unwrap_or(holding.last_trade_price);
+                let market_value = last_price * holding.quantity as f64;
+                let unrealized_pnl = market_value - holding.cost_basis;
+                PaperPosition {
+                    avg_cost: holding.avg_cost(),
+                    stock_code,
+                    stock_name: holding.stock_name,
+                    quantity: holding.quantity,
+                    cost_basis: holding.cost_basis,
+                    last_price,
+                    market_value,
+                    unrealized_pnl,
+                    unrealized_pnl_pct: if holding.cost_basis > 0.0 {
+                        unrealized_pnl / holding.cost_basis * 100.0
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect();
+        positions.sort_by(|a, b| a.stock_code.cmp(&b.stock_code));
+
+        let market_value = positions.iter().fold(0.0, |sum, p| sum + p.market_value);
+        let unrealized_pnl = positions.iter().fold(0.0, |sum, p| sum + p.unrealized_pnl);
+        let total_value = self.cash + market_value;
+        let total_return_pct = if account.initial_cash > 0.0 {
+            (total_value / account.initial_cash - 1.0) * 100.0
+        } else {
+            0.0
+        };
+
+        PaperLedger {
+            account,
+            cash: self.cash,
+            positions,
+            trades,
+            realized_pnl: self.realized_pnl,
+            unrealized_pnl,
+            total_value,
+            total_return_pct,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account() -> PaperAccount {
+        PaperAccount {
+            id: "acc".to_string(),
+            name: "测试账户".to_string(),
+            initial_cash: 100_000.0,
+            position_fraction: 0.5,
+            auto_execute_stocks: vec!["000001".to_string()],
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_signals_drive_trades_and_pnl() {
+        let account = account();
+        let mut trades = Vec::new();
+
+        let book = PaperBook::replay(&account, &trades);
+        assert!(book
+            .plan_trade(&account, "000001", "平安银行", "买入", 10.0)
+            .is_none());
+        assert!(book
+            .plan_trade(&account, "000001", "平安银行", STRONG_SELL, 10.0)
+            .is_none());
+
+        let buy = book
+            .plan_trade(&account, "000001", "平安银行", STRONG_BUY, 12.0)
+            .unwrap();
+        assert_eq!(buy.side, "买入");
+        assert_eq!(buy.quantity, 4166);
+        trades.push(buy);
+
+        // 已持仓时不重复买入
+        let book = PaperBook::replay(&account, &trades);
+        assert!(book
+            .plan_trade(&account, "000001", "平安银行", STRONG_BUY, 11.0)
+            .is_none());
+
+        let sell = book
+            .plan_trade(&account, "000001", "平安银行", STRONG_SELL, 15.0)
+            .unwrap();
+        assert_eq!(sell.quantity, 4166);
+        assert!((sell.realized_pnl - 4166.0 * 3.0).abs() < 1e-6);
+        trades.push(sell);
+
+        let ledger = PaperBook::replay(&account, &trades).into_ledger(
+            account.clone(),
+            trades,
+            &HashMap::new(),
+        );
+        assert!(ledger.positions.is_empty());
+        assert!((ledger.cash - (100_000.0 + 4166.0 * 3.0)).abs() < 1e-6);
+        assert!((ledger.total_return_pct - 12.498).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_ledger_marks_positions_to_last_price() {
+        let account = account();
+        let book = PaperBook::replay(&account, &[]);
+        let buy = book
+            .plan_trade(&account, "000001", "平安银行", STRONG_BUY, 10.0)
+            .unwrap();
+        let trades = vec![buy];
+
+        let last_prices = HashMap::from([("000001".to_string(), 11.0)]);
+        let ledger =
+            PaperBook::replay(&account, &trades).into_ledger(account.clone(), trades, &last_prices);
+        let position = &ledger.positions[0];
+        assert_eq!(position.quantity, 5000);
+        assert!((position.unrealized_pnl - 5000.0).abs() < 1e-6);
+        assert!((position.unrealized_pnl_pct - 10.0).abs() < 1e-6);
+        assert!((ledger.total_value - 105_000.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,327 @@
+use chrono::Utc;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::time::MissedTickBehavior;
+
+use crate::analyzer::StockAnalyzer;
+use crate::database::Database;
+use crate::models::{AlertSnapshot, PriceAlert, PriceAlertConfig, TriggeredAlert};
+
+/// 价格提醒后台检查：按固定间隔为每条规则获取行情快照并评估条件，
+/// 条件由不成立变为成立时记录触发并推送 webhook
+pub struct PriceAlertMonitor {
+    config: PriceAlertConfig,
+}
+
+impl PriceAlertMonitor {
+    pub fn new(config: PriceAlertConfig) -> Self {
+        Self { config }
+    }
+
+    /// 启动后台循环；未启用或没有数据库时不运行
+    pub fn start(self: &Arc<Self>, analyzer: Arc<StockAnalyzer>, database: Option<Arc<Database>>) {
+        if !self.config.enabled {
+            return;
+        }
+        let Some(database) = database else {
+            log::warn!("Price alerts need a database; alert monitor not started");
+            return;
+        };
+
+        let interval = std::time::Duration::from_secs(self.config.check_interval_seconds.max(1));
+        log::info!("Checking price alerts every {} seconds", interval.as_secs());
+
+        let monitor = self.clone();
+        tokio::spawn(async move {
+            let mut timer =
+                tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                timer.tick().await;
+                monitor.check_once(&analyzer, &database).await;
+            }
+        });
+    }
+
+    /// 检查全部规则一次，返回本次触发的提醒
+    pub async fn check_once(
+        &self,
+        analyzer: &StockAnalyzer,
+        database: &Database,
+    ) -> Vec<TriggeredAlert> {
+        let alerts = match database.get_all_price_alerts().await {
+            Ok(alerts) => alerts,
+            Err(e) => {
+                log::warn!("Could not load price alerts: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let mut by_stock: HashMap<&str, Vec<&PriceAlert>> = HashMap::new();
+        for alert in &alerts {
+            by_stock.entry(&alert.stock_code).or_default().push(alert);
+        }
+
+        // 同一股票的多条规则共用一次快照
+        let concurrency = analyzer.config().max_workers.max(1);
+        // Owned codes keep the stream future `Send` for the spawned monitor loop
+        let stock_codes: Vec<String> = by_stock.keys().map(|code| code.to_string()).collect();
+        let snapshots: Vec<(String, Result<AlertSnapshot, String>)> =
+            futures::stream::iter(stock_codes)
+                .map(|stock_code| async move {
+                    let snapshot = analyzer.alert_snapshot(&stock_code).await;
+                    (stock_code, snapshot)
+                })
+                .buffer_unordered(concurrency)
+                .collect()
+                .await;
+
+        let mut triggered = Vec::new();
+        for (stock_code, snapshot) in snapshots {
+            let snapshot = match snapshot {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    log::warn!("Price alert check for {} failed: {}", stock_code, e);
+                    continue;
+                }
+            };
+
+            let checked_at = Utc::now();
+            for alert in &by_stock[stock_code.as_str()] {
+                let value = alert.condition.evaluate(&snapshot);
+                if let Err(e) = database
+                    .update_price_alert_state(&alert.id, value.is_some(), checked_at)
+                    .await
+                {
+                    log::warn!("Failed to update price alert {}: {}", alert.id, e);
+                    continue;
+                }
+
+                let Some(value) = value.filter(|_| !alert.condition_met) else {
+                    continue;
+                };
+                let record = TriggeredAlert {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    alert_id: alert.id.clone(),
+                    owner: alert.owner.clone(),
+                    stock_code: alert.stock_code.clone(),
+                    condition: alert.condition,
+                    value,
+                    triggered_at: checked_at,
+                };
+                if let Err(e) = database.save_triggered_alert(&record).await {
+                    log::warn!("Failed to record triggered price alert {}: {}", alert.id, e);
+                }
+                log::info!(
+                    "Price alert {} triggered: {} {} (value {})",
+                    alert.id,
+                    alert.stock_code,
+                    alert.condition,
+                    value
+                );
+                if let Some(url) = &alert.webhook_url {
+                    notify_webhook(url.clone(), &record);
+                }
+                triggered.push(record);
+            }
+        }
+
+        triggered
+    }
+}
+
+/// 校验 webhook 地址并解析主机：必须是 http(s) 地址，且解析出的全部地址都是公网地址；
+/// 回环、私有、链路本地（含 169.254.169.254 元数据服务）与未指定地址一律拒绝，避免借提醒访问内网
+pub async fn resolve_webhook_url(url: &str) -> Result<(reqwest::Url, SocketAddr), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("webhook_url 格式不正确: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("webhook_url 必须是 http(s) 地址".to_string());
+    }
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| "webhook_url 缺少主机名".to_string())?;
+    // IPv6 literals keep their brackets in the URL but not in the lookup
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = parsed.port_or_known_default().unwrap_or(80);
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("无法解析 webhook 主机 {}: {}", host, e))?
+        .collect();
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        return Err(format!("webhook_url 不能指向本机或内网地址: {}", addr.ip()));
+    }
+    let addr = addrs
+        .first()
+        .copied()
+        .ok_or_else(|| format!("无法解析 webhook 主机 {}", host))?;
+    Ok((parsed, addr))
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            // 100.64.0.0/10 is carrier-grade NAT space, private in practice
+            let shared = a == 100 && (b & 0xc0) == 64;
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || shared)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                // fc00::/7 is unique-local, fe80::/10 is link-local
+                let unique_local = (first & 0xfe00) == 0xfc00;
+                let link_local = (first & 0xffc0) == 0xfe80;
+                !(ip.is_loopback() || ip.is_unspecified() || unique_local || link_local)
+            }
+        },
+    }
+}
+
+fn notify_webhook(url: String, triggered: &TriggeredAlert) {
+    let payload = serde_json::json!({
+        "event": "price_alert",
+        "alert": triggered,
+    });
+
+    tokio::spawn(async move {
+        // Resolve again at send time and pin the checked address, so a DNS change
+        // after the rule was created cannot point the request at an internal host
+        let (url, addr) = match resolve_webhook_url(&url).await {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                log::warn!("Skipping price alert webhook: {}", e);
+                return;
+            }
+        };
+        let mut builder = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(std::time::Duration::from_secs(10));
+        if let Some(host) = url.host_str() {
+            builder = builder.resolve(host, addr);
+        }
+        let client = match builder.build() {
+            Ok(client) => client,
+            Err(e) => {
+                log::warn!("Failed to build price alert webhook client: {}", e);
+                return;
+            }
+        };
+
+        let result = client.post(url).json(&payload).send().await;
+        match result {
+            Ok(response) if !response.status().is_success() => {
+                log::warn!("Price alert webhook returned status {}", response.status());
+            }
+            Err(e) => log::warn!("Failed to send price alert webhook: {}", e),
+            _ => {}
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_webhook_rejects_internal_addresses() {
+        for url in [
+            "http://127.0.0.1/hook",
+            "http://localhost:8080/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.1.2.3/hook",
+            "http://192.168.0.10/hook",
+            "http://0.0.0.0/hook",
+            "http://[::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+            "ftp://example.com/hook",
+        ] {
+            assert!(resolve_webhook_url(url).await.is_err(), "{}", url);
+        }
+
+        let (url, addr) = resolve_webhook_url("https://93.184.216.34/hook")
+            .await
+            .unwrap();
+        assert_eq!(url.path(), "/hook");
+        assert_eq!(addr, "93.184.216.34:443".parse().unwrap());
+    }
+    use crate::ai_service::AIService;
+    use crate::data_fetcher::MockDataFetcher;
+    use crate::models::{AIConfig, AnalysisConfig};
+    use tokio::sync::RwLock;
+
+    #[tokio::test]
+    async fn test_alert_fires_once_while_condition_holds() {
+        let path = std::env::temp_dir().join(format!("price_alerts_{}.db", uuid::Uuid::new_v4()));
+        let database = Arc::new(
+            Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+                .await
+                .unwrap(),
+        );
+        database.create_tables().await.unwrap();
+
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+
+        let alert = |condition: String| PriceAlert {
+            id: uuid::Uuid::new_v4().to_string(),
+            owner: "alice".to_string(),
+            stock_code: "000001".to_string(),
+            condition: condition.parse().unwrap(),
+            webhook_url: None,
+            condition_met: false,
+            last_checked: None,
+            created_at: Utc::now(),
+        };
+        // Mock prices are random, so use thresholds every snapshot falls on the same side of
+        let met = alert("price > 0".to_string());
+        let unmet = alert("price < 0".to_string());
+        database.create_price_alert(&met).await.unwrap();
+        database.create_price_alert(&unmet).await.unwrap();
+
+        let monitor = PriceAlertMonitor::new(PriceAlertConfig::default());
+        let fired = monitor.check_once(&analyzer, &database).await;
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].alert_id, met.id);
+        assert!(fired[0].value > 0.0);
+
+        // 条件持续成立时不重复触发
+        assert!(monitor.check_once(&analyzer, &database).await.is_empty());
+
+        let stored = database.get_price_alerts("alice").await.unwrap();
+        assert!(stored.iter().all(|alert| alert.last_checked.is_some()));
+        assert!(
+            stored
+                .iter()
+                .find(|alert| alert.id == met.id)
+                .unwrap()
+                .condition_met
+        );
+
+        let history = database
+            .get_triggered_alerts("alice", Some("000001"), 50)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert!(database
+            .get_triggered_alerts("bob", None, 50)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
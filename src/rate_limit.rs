@@ -0,0 +1,95 @@
+use dashmap::DashMap;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// 超过该客户端数量时，在记录请求前清理窗口内已无请求的客户端
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// HTTP API 的按客户端限流器：每个客户端在滑动窗口内最多 `max_requests` 次请求
+pub struct ClientRateLimiter {
+    max_requests: usize,
+    window: Duration,
+    clients: DashMap<String, VecDeque<Instant>>,
+}
+
+impl ClientRateLimiter {
+    pub fn per_minute(requests_per_minute: u32) -> Self {
+        Self::new(requests_per_minute as usize, Duration::from_secs(60))
+    }
+
+    pub fn new(max_requests: usize, window: Duration) -> Self {
+        Self {
+            max_requests: max_requests.max(1),
+            window,
+            clients: DashMap::new(),
+        }
+    }
+
+    /// 记录客户端的一次请求；超出限额时不记录，并返回需要等待的时长
+    pub fn check(&self, client: &str) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        if self.clients.len() > PRUNE_THRESHOLD {
+            self.prune(now);
+        }
+
+        let mut requests = self.clients.entry(client.to_string()).or_default();
+        while requests
+            .front()
+            .is_some_and(|&time| now.duration_since(time) >= self.window)
+        {
+            requests.pop_front();
+        }
+
+        if requests.len() >= self.max_requests {
+            let oldest = requests[0];
+            return Err(self.window.saturating_sub(now.duration_since(oldest)));
+        }
+
+        requests.push_back(now);
+        Ok(())
+    }
+
+    fn prune(&self, now: Instant) {
+        self.clients.retain(|_, requests| {
+            requests
+                .back()
+                .is_some_and(|&time| now.duration_since(time) < self.window)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sliding_window_per_client() {
+        let limiter = ClientRateLimiter::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(limiter.check_at("a", start).is_ok());
+        assert!(limiter
+            .check_at("a", start + Duration::from_secs(20))
+            .is_ok());
+        let retry_after = limiter
+            .check_at("a", start + Duration::from_secs(30))
+            .unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(30));
+
+        // Other clients have their own window
+        assert!(limiter
+            .check_at("b", start + Duration::from_secs(30))
+            .is_ok());
+
+        // The first request leaves the window after 60 seconds
+        assert!(limiter
+            .check_at("a", start + Duration::from_secs(60))
+            .is_ok());
+        assert!(limiter
+            .check_at("a", start + Duration::from_secs(61))
+            .is_err());
+    }
+}
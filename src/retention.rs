@@ -0,0 +1,131 @@
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Arc;
+use tokio::time::MissedTickBehavior;
+
+use crate::database::Database;
+
+/// 两次清理之间的间隔
+const CLEANUP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// 分析历史保留策略：后台每天删除分析日期早于保留天数的记录
+pub struct HistoryRetention {
+    retention_days: u32,
+}
+
+impl HistoryRetention {
+    pub fn new(retention_days: u32) -> Self {
+        Self { retention_days }
+    }
+
+    /// 早于该时间的记录会被删除；保留天数为 0 时不清理
+    pub fn cutoff(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        (self.retention_days > 0).then(|| now - Duration::days(self.retention_days as i64))
+    }
+
+    /// 启动后台清理，启动时立即执行一次；未设置保留天数或没有数据库时不运行
+    pub fn start(self, database: Option<Arc<Database>>) {
+        if self.retention_days == 0 {
+            return;
+        }
+        let Some(database) = database else {
+            log::warn!("History retention needs a database; cleanup not started");
+            return;
+        };
+
+        log::info!("Keeping analysis history for {} days", self.retention_days);
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(CLEANUP_INTERVAL);
+            timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                timer.tick().await;
+                if let Err(e) = self.run_once(&database).await {
+                    log::warn!("Analysis history cleanup failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// 清理一次，返回删除的记录数
+    pub async fn run_once(&self, database: &Database) -> Result<u64, sqlx::Error> {
+        let Some(cutoff) = self.cutoff(Utc::now()) else {
+            return Ok(0);
+        };
+        let deleted = database.delete_analyses_before(cutoff).await?;
+        if deleted > 0 {
+            log::info!(
+                "Removed {} analyses older than {}",
+                deleted,
+                cutoff.format("%Y-%m-%d %H:%M:%S")
+            );
+        }
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_service::AIService;
+    use crate::analyzer::StockAnalyzer;
+    use crate::data_fetcher::MockDataFetcher;
+    use crate::models::{AIConfig, AnalysisConfig, AnalysisOptions, HistoryQuery};
+    use tokio::sync::RwLock;
+
+    #[tokio::test]
+    async fn test_run_once_removes_only_expired_analyses() {
+        let path = std::env::temp_dir().join(format!("retention_{}.db", uuid::Uuid::new_v4()));
+        let database = Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        database.create_tables().await.unwrap();
+
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::new(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+        );
+        let options = AnalysisOptions {
+            structured_only: true,
+            ..Default::default()
+        };
+        let mut report = analyzer
+            .analyze_single_stock("000001", &options)
+            .await
+            .unwrap();
+        for days_ago in [1, 10, 40] {
+            report.analysis_date = Utc::now() - Duration::days(days_ago);
+            database
+                .save_analysis(&report, None, None, None)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            HistoryRetention::new(0).run_once(&database).await.unwrap(),
+            0
+        );
+        assert_eq!(
+            HistoryRetention::new(30).run_once(&database).await.unwrap(),
+            1
+        );
+        assert_eq!(
+            HistoryRetention::new(5).run_once(&database).await.unwrap(),
+            1
+        );
+
+        let query = HistoryQuery {
+            stock_code: None,
+            start_date: None,
+            end_date: None,
+            limit: Some(10),
+            offset: Some(0),
+            q: None,
+        };
+        let history = database.get_analysis_history(&query).await.unwrap();
+        assert_eq!(history.analyses.len(), 1);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
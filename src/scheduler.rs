@@ -0,0 +1,222 @@
+use chrono::{DateTime, Duration, Utc};
+use futures::StreamExt;
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::MissedTickBehavior;
+
+use crate::analyzer::StockAnalyzer;
+use crate::database::Database;
+use crate::models::{AnalysisOptions, ScheduleConfig};
+
+/// 定时分析的运行状态
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleStatus {
+    /// 配置已启用且数据库可用时为 true
+    pub enabled: bool,
+    pub interval_seconds: u64,
+    pub enable_ai: bool,
+    /// 当前是否正在分析
+    pub running: bool,
+    pub last_run_started: Option<DateTime<Utc>>,
+    pub last_run_finished: Option<DateTime<Utc>>,
+    /// 上次运行成功分析与失败的股票数
+    pub last_run_analyzed: usize,
+    pub last_run_failed: usize,
+    /// 下次计划运行时间，未启用或正在运行时为 None
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+/// 自选股定时分析：后台按固定间隔分析所有用户自选股中的股票，报告由分析器写入分析历史
+pub struct WatchlistScheduler {
+    config: ScheduleConfig,
+    status: RwLock<ScheduleStatus>,
+}
+
+impl WatchlistScheduler {
+    pub fn new(config: ScheduleConfig) -> Self {
+        let status = ScheduleStatus {
+            enabled: false,
+            interval_seconds: config.interval_seconds,
+            enable_ai: config.enable_ai,
+            running: false,
+            last_run_started: None,
+            last_run_finished: None,
+            last_run_analyzed: 0,
+            last_run_failed: 0,
+            next_run: None,
+        };
+        Self {
+            config,
+            status: RwLock::new(status),
+        }
+    }
+
+    /// 启动后台循环，首次运行在一个间隔之后；未启用或没有数据库时不运行
+    pub async fn start(
+        self: &Arc<Self>,
+        analyzer: Arc<StockAnalyzer>,
+        database: Option<Arc<Database>>,
+    ) {
+        if !self.config.enabled {
+            return;
+        }
+        let Some(database) = database else {
+            log::warn!("Scheduled watchlist analysis needs a database; scheduler not started");
+            return;
+        };
+
+        let interval = std::time::Duration::from_secs(self.config.interval_seconds.max(1));
+        {
+            let mut status = self.status.write().await;
+            status.enabled = true;
+            status.next_run = Some(Utc::now() + self.period());
+        }
+        log::info!(
+            "Scheduled watchlist analysis every {} seconds",
+            interval.as_secs()
+        );
+
+        let scheduler = self.clone();
+        tokio::spawn(async move {
+            let mut timer =
+                tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                timer.tick().await;
+                scheduler.run_once(&analyzer, &database).await;
+            }
+        });
+    }
+
+    pub async fn status(&self) -> ScheduleStatus {
+        self.status.read().await.clone()
+    }
+
+    fn period(&self) -> Duration {
+        Duration::seconds(self.config.interval_seconds.max(1) as i64)
+    }
+
+    async fn run_once(&self, analyzer: &StockAnalyzer, database: &Database) {
+        let started = Utc::now();
+        {
+            let mut status = self.status.write().await;
+            status.running = true;
+            status.last_run_started = Some(started);
+            status.next_run = None;
+        }
+
+        let stock_codes = match database.get_all_watchlist_codes().await {
+            Ok(codes) => codes,
+            Err(e) => {
+                log::warn!("Scheduled analysis could not load the watchlist: {}", e);
+                Vec::new()
+            }
+        };
+
+        let options = AnalysisOptions {
+            enable_ai: self.config.enable_ai,
+            ..AnalysisOptions::default()
+        };
+        let concurrency = analyzer.config().max_workers.max(1);
+        // Owned codes keep the stream future `Send` for the spawned scheduler loop
+        let outcomes: Vec<bool> = futures::stream::iter(stock_codes)
+            .map(|stock_code| {
+                let options = &options;
+                async move {
+                    match analyzer.analyze_single_stock(&stock_code, options).await {
+                        Ok(_) => true,
+                        Err(e) => {
+                            log::warn!("Scheduled analysis of {} failed: {}", stock_code, e);
+                            false
+                        }
+                    }
+                }
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+
+        let analyzed = outcomes.iter().filter(|ok| **ok).count();
+        let failed = outcomes.len() - analyzed;
+        let finished = Utc::now();
+        log::info!(
+            "Scheduled watchlist analysis finished: {} analyzed, {} failed",
+            analyzed,
+            failed
+        );
+
+        let mut status = self.status.write().await;
+        status.running = false;
+        status.last_run_finished = Some(finished);
+        status.last_run_analyzed = analyzed;
+        status.last_run_failed = failed;
+        // Ticks keep their fixed cadence; an overrunning run triggers the next one right away
+        status.next_run = Some((started + self.period()).max(finished));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_service::AIService;
+    use crate::data_fetcher::MockDataFetcher;
+    use crate::models::{AIConfig, AnalysisConfig, HistoryQuery, WatchlistEntry};
+
+    #[tokio::test]
+    async fn test_run_once_analyzes_every_watchlist_stock() {
+        let path = std::env::temp_dir().join(format!("schedule_{}.db", uuid::Uuid::new_v4()));
+        let database = Arc::new(
+            Database::new(&format!("sqlite:{}?mode=rwc", path.display()))
+                .await
+                .unwrap(),
+        );
+        database.create_tables().await.unwrap();
+        for (owner, code) in [("alice", "000001"), ("bob", "000001"), ("bob", "600036")] {
+            database
+                .add_watchlist_entry(&WatchlistEntry {
+                    owner: owner.to_string(),
+                    stock_code: code.to_string(),
+                    note: None,
+                    created_at: Utc::now(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let ai_service = Arc::new(RwLock::new(AIService::new(AIConfig::default())));
+        let analyzer = StockAnalyzer::with_database(
+            Arc::new(MockDataFetcher),
+            AnalysisConfig::default(),
+            ai_service,
+            database.clone(),
+        );
+        let scheduler = WatchlistScheduler::new(ScheduleConfig {
+            enabled: true,
+            interval_seconds: 3600,
+            enable_ai: false,
+        });
+
+        scheduler.run_once(&analyzer, &database).await;
+
+        let status = scheduler.status().await;
+        assert!(!status.running);
+        assert_eq!((status.last_run_analyzed, status.last_run_failed), (2, 0));
+        let started = status.last_run_started.unwrap();
+        assert_eq!(status.next_run, Some(started + Duration::seconds(3600)));
+
+        let query = HistoryQuery {
+            stock_code: None,
+            start_date: None,
+            end_date: None,
+            limit: Some(10),
+            offset: Some(0),
+            q: None,
+        };
+        let history = database.get_analysis_history(&query).await.unwrap();
+        assert_eq!(history.analyses.len(), 2);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
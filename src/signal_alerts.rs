@@ -112,7 +112,7 @@ impl SignalAlertSystem {
 
     /// 添加信号到历史记录
     fn add_to_signal_history(&mut self, stock_code: &str, signal: TradingSignal) {
-        let history = self.signal_history.entry(stock_code.to_string()).or_insert_with(VecDeque::new);
+        let history = self.signal_history.entry(stock_code.to_string()).or_default();
         history.push_back(signal);
         
         // 限制历史记录数量
@@ -149,6 +149,7 @@ impl SignalAlertSystem {
     }
 
     /// 获取信号历史记录
+    #[allow(dead_code)]
     pub fn get_signal_history(&self, stock_code: &str, limit: Option<usize>) -> Vec<&TradingSignal> {
         let limit = limit.unwrap_or(10);
         if let Some(history) = self.signal_history.get(stock_code) {
@@ -160,7 +161,7 @@ impl SignalAlertSystem {
 
     /// 取消提醒
     pub fn cancel_alert(&mut self, alert_id: &str) -> Result<(), String> {
-        if let Some(mut alert) = self.active_alerts.get_mut(alert_id) {
+        if let Some(alert) = self.active_alerts.get_mut(alert_id) {
             alert.is_active = false;
             Ok(())
         } else {
@@ -169,8 +170,9 @@ impl SignalAlertSystem {
     }
 
     /// 更新提醒状态
+    #[allow(dead_code)]
     pub fn update_alert_status(&mut self, alert_id: &str, is_active: bool) -> Result<(), String> {
-        if let Some(mut alert) = self.active_alerts.get_mut(alert_id) {
+        if let Some(alert) = self.active_alerts.get_mut(alert_id) {
             alert.is_active = is_active;
             Ok(())
         } else {
@@ -179,8 +181,9 @@ impl SignalAlertSystem {
     }
 
     /// 标记通知已发送
+    #[allow(dead_code)]
     pub fn mark_notification_sent(&mut self, alert_id: &str) -> Result<(), String> {
-        if let Some(mut alert) = self.active_alerts.get_mut(alert_id) {
+        if let Some(alert) = self.active_alerts.get_mut(alert_id) {
             alert.notification_sent = true;
             Ok(())
         } else {
@@ -189,6 +192,7 @@ impl SignalAlertSystem {
     }
 
     /// 获取待发送的通知
+    #[allow(dead_code)]
     pub fn get_pending_notifications(&self) -> Vec<&SignalAlert> {
         self.active_alerts.values()
             .filter(|alert| alert.is_active && !alert.notification_sent && self.enable_notifications)
@@ -291,7 +295,7 @@ impl SignalAlertSystem {
     pub fn generate_strategy_analysis_report(
         &self,
         stock_code: &str,
-        stock_name: &str,
+        _stock_name: &str,
         chip_analysis: &ChipAnalysis,
         trading_strategies: &TradingStrategies,
         signals: &[TradingSignal],
@@ -368,7 +372,8 @@ impl SignalAlertSystem {
     /// 市场情绪分析
     fn analyze_market_sentiment(&self, chip_analysis: &ChipAnalysis, trading_strategies: &TradingStrategies) -> String {
         let net_inflow = chip_analysis.capital_flow.net_inflow;
-        let rsi = trading_strategies.rsi.current_rsi;
+        // RSI 未能计算时按中性处理
+        let rsi = trading_strategies.rsi.as_ref().map_or(50.0, |rsi| rsi.current_rsi);
         
         match (net_inflow, rsi) {
             (inflow, r) if inflow > 1000000.0 && r > 70.0 => "乐观但谨慎".to_string(),
@@ -387,19 +392,20 @@ impl SignalAlertSystem {
                 format!("建议分批建仓，目标仓位{}，设置止损位", position)
             },
             s if s.contains("买入") => {
-                format!("建议少量建仓，目标仓位30%-50%，严格止损")
+                "建议少量建仓，目标仓位30%-50%，严格止损".to_string()
             },
             s if s.contains("强烈卖出") => {
-                format!("建议立即减仓或清仓，锁定利润")
+                "建议立即减仓或清仓，锁定利润".to_string()
             },
             s if s.contains("卖出") => {
-                format!("建议逐步减仓，降低仓位至30%以下")
+                "建议逐步减仓，降低仓位至30%以下".to_string()
             },
             _ => "建议保持现有仓位，密切关注市场变化".to_string(),
         }
     }
 
     /// 设置配置参数
+    #[allow(dead_code)]
     pub fn set_config(&mut self, config: AlertConfig) {
         self.alert_timeout_hours = config.alert_timeout_hours;
         self.max_history_size = config.max_history_size;
@@ -408,6 +414,7 @@ impl SignalAlertSystem {
     }
 
     /// 获取系统状态
+    #[allow(dead_code)]
     pub fn get_system_status(&self) -> SystemStatus {
         SystemStatus {
             active_alerts_count: self.active_alerts.values().filter(|a| a.is_active).count(),
@@ -443,6 +450,7 @@ pub struct AlertConfig {
 
 /// 系统状态
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(dead_code)]
 pub struct SystemStatus {
     pub active_alerts_count: usize,        // 活跃提醒数量
     pub total_signals_processed: usize,     // 总处理信号数
@@ -480,6 +488,7 @@ mod tests {
             expected_profit: 0.5,
             stop_loss: 9.5,
             take_profit: 10.8,
+            suggested_position_pct: 0.0,
         }
     }
 
@@ -551,6 +560,7 @@ mod tests {
                 expected_profit: 0.5,
                 stop_loss: 9.5,
                 take_profit: 10.8,
+                suggested_position_pct: 0.0,
             },
             TradingSignal {
                 strategy_name: "RSI策略".to_string(),
@@ -564,6 +574,7 @@ mod tests {
                 expected_profit: 0.4,
                 stop_loss: 9.5,
                 take_profit: 10.5,
+                suggested_position_pct: 0.0,
             },
         ];
         
@@ -1,9 +1,17 @@
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use chrono::Utc;
 
 use crate::models::*;
 
+/// 未指定时单笔交易承担的账户风险比例（%）
+pub const DEFAULT_ACCOUNT_RISK_PCT: f64 = 1.0;
+/// 单个信号建议仓位的上限（%）
+pub const MAX_POSITION_PCT: f64 = 25.0;
+
+/// 一目均衡表周期：转换线、基准线（同时为迟行线与先行带的位移）、先行带B
+const ICHIMOKU_TENKAN_PERIOD: usize = 9;
+const ICHIMOKU_KIJUN_PERIOD: usize = 26;
+const ICHIMOKU_SENKOU_B_PERIOD: usize = 52;
+
 /// 交易策略分析器
 pub struct TradingStrategiesAnalyzer {
     // 策略配置
@@ -15,6 +23,8 @@ pub struct TradingStrategiesAnalyzer {
     pub ma_long_period: i32,         // 长期均线周期
     pub bb_period: i32,             // 布林带周期
     pub bb_std_dev: f64,            // 布林带标准差倍数
+    pub allow_partial_strategies: bool, // 单个策略失败时是否返回其余策略结果
+    pub divergence_lookback: usize, // 背离检测比较价格与指标趋势的窗口长度
 }
 
 impl TradingStrategiesAnalyzer {
@@ -29,25 +39,89 @@ impl TradingStrategiesAnalyzer {
             ma_long_period: 20,
             bb_period: 20,
             bb_std_dev: 2.0,
+            allow_partial_strategies: true,
+            divergence_lookback: 5,
+        }
+    }
+
+    /// 按请求参数覆盖默认策略配置，未提供的字段沿用 `new()` 的默认值
+    pub fn with_params(params: &StrategyParams) -> Result<Self, String> {
+        let defaults = Self::new();
+        let analyzer = Self {
+            rsi_overbought: params.rsi_overbought.unwrap_or(defaults.rsi_overbought),
+            rsi_oversold: params.rsi_oversold.unwrap_or(defaults.rsi_oversold),
+            macd_fast_period: params.macd_fast_period.unwrap_or(defaults.macd_fast_period),
+            macd_slow_period: params.macd_slow_period.unwrap_or(defaults.macd_slow_period),
+            ma_short_period: params.ma_short_period.unwrap_or(defaults.ma_short_period),
+            ma_long_period: params.ma_long_period.unwrap_or(defaults.ma_long_period),
+            bb_period: params.bb_period.unwrap_or(defaults.bb_period),
+            bb_std_dev: params.bb_std_dev.unwrap_or(defaults.bb_std_dev),
+            ..defaults
+        };
+
+        for (name, period) in [
+            ("macd_fast_period", analyzer.macd_fast_period),
+            ("macd_slow_period", analyzer.macd_slow_period),
+            ("ma_short_period", analyzer.ma_short_period),
+            ("ma_long_period", analyzer.ma_long_period),
+            ("bb_period", analyzer.bb_period),
+        ] {
+            if period <= 0 {
+                return Err(format!("{} 必须大于 0: {}", name, period));
+            }
+        }
+        if analyzer.macd_fast_period >= analyzer.macd_slow_period {
+            return Err(format!(
+                "macd_fast_period ({}) 必须小于 macd_slow_period ({})",
+                analyzer.macd_fast_period, analyzer.macd_slow_period
+            ));
+        }
+        if analyzer.ma_short_period >= analyzer.ma_long_period {
+            return Err(format!(
+                "ma_short_period ({}) 必须小于 ma_long_period ({})",
+                analyzer.ma_short_period, analyzer.ma_long_period
+            ));
+        }
+        if !(analyzer.rsi_oversold > 0.0
+            && analyzer.rsi_oversold < analyzer.rsi_overbought
+            && analyzer.rsi_overbought < 100.0)
+        {
+            return Err(format!(
+                "RSI 阈值无效: 需满足 0 < rsi_oversold ({}) < rsi_overbought ({}) < 100",
+                analyzer.rsi_oversold, analyzer.rsi_overbought
+            ));
         }
+        if !(analyzer.bb_std_dev.is_finite() && analyzer.bb_std_dev > 0.0) {
+            return Err(format!("bb_std_dev 必须大于 0: {}", analyzer.bb_std_dev));
+        }
+
+        Ok(analyzer)
     }
 
     /// 分析所有交易策略
     pub async fn analyze_all_strategies(
         &self,
-        stock_code: &str,
+        _stock_code: &str,
         price_data: &[PriceData],
     ) -> Result<TradingStrategies, Box<dyn std::error::Error>> {
         if price_data.is_empty() {
             return Err("No price data available for strategy analysis".into());
         }
 
-        let macd_strategy = self.analyze_macd_strategy(price_data).await?;
-        let rsi_strategy = self.analyze_rsi_strategy(price_data).await?;
-        let ma_strategy = self.analyze_moving_average_strategy(price_data).await?;
-        let bb_strategy = self.analyze_bollinger_bands_strategy(price_data).await?;
-        let kline_strategy = self.analyze_kline_patterns_strategy(price_data).await?;
-        let volume_strategy = self.analyze_volume_analysis_strategy(price_data).await?;
+        // 各策略独立计算，历史较短时仍返回可计算的部分
+        let mut skipped = Vec::new();
+        let macd_strategy = self.partial("macd", self.analyze_macd_strategy(price_data).await, &mut skipped)?;
+        let rsi_strategy = self.partial("rsi", self.analyze_rsi_strategy(price_data).await, &mut skipped)?;
+        let ma_strategy = self.partial("moving_average", self.analyze_moving_average_strategy(price_data).await, &mut skipped)?;
+        let bb_strategy = self.partial("bollinger_bands", self.analyze_bollinger_bands_strategy(price_data).await, &mut skipped)?;
+        let kline_strategy = self.partial("kline_patterns", self.analyze_kline_patterns_strategy(price_data).await, &mut skipped)?;
+        let volume_strategy = self.partial("volume_analysis", self.analyze_volume_analysis_strategy(price_data).await, &mut skipped)?;
+        let ichimoku_strategy = self.partial("ichimoku", self.analyze_ichimoku_strategy(price_data).await, &mut skipped)?;
+
+        if skipped.len() == 7 {
+            let reasons: Vec<String> = skipped.iter().map(|s| s.reason.clone()).collect();
+            return Err(format!("No strategy could be computed: {}", reasons.join("; ")).into());
+        }
 
         Ok(TradingStrategies {
             macd: macd_strategy,
@@ -56,9 +130,31 @@ impl TradingStrategiesAnalyzer {
             bollinger_bands: bb_strategy,
             kline_patterns: kline_strategy,
             volume_analysis: volume_strategy,
+            ichimoku: ichimoku_strategy,
+            skipped_strategies: skipped,
         })
     }
 
+    /// 允许部分失败时记录跳过原因并返回 None，否则原样返回错误
+    fn partial<T>(
+        &self,
+        strategy: &str,
+        result: Result<T, Box<dyn std::error::Error>>,
+        skipped: &mut Vec<SkippedStrategy>,
+    ) -> Result<Option<T>, Box<dyn std::error::Error>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if self.allow_partial_strategies => {
+                skipped.push(SkippedStrategy {
+                    strategy: strategy.to_string(),
+                    reason: e.to_string(),
+                });
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// MACD策略分析
     pub async fn analyze_macd_strategy(
         &self,
@@ -164,7 +260,15 @@ impl TradingStrategiesAnalyzer {
 
         let bandwidth = self.calculate_bandwidth(*current_upper, *current_lower, *current_middle);
         let signal_type = self.generate_bb_signal(*current_price, *current_upper, *current_lower);
-        let squeeze = bandwidth < 0.1; // 简化的挤压检测
+
+        // 挤压以该股票自身近期带宽为参照，而非固定阈值
+        let bandwidth_series: Vec<f64> = upper_band
+            .iter()
+            .zip(&middle_band)
+            .zip(&lower_band)
+            .map(|((upper, middle), lower)| self.calculate_bandwidth(*upper, *lower, *middle))
+            .collect();
+        let squeeze = self.detect_bb_squeeze(&bandwidth_series);
 
         Ok(BollingerBandsStrategy {
             period: self.bb_period,
@@ -230,86 +334,319 @@ impl TradingStrategiesAnalyzer {
         })
     }
 
-    /// 生成交易信号
+    /// 一目均衡表策略分析，至少需要 52 根K线以计算先行带B
+    pub async fn analyze_ichimoku_strategy(
+        &self,
+        price_data: &[PriceData],
+    ) -> Result<IchimokuStrategy, Box<dyn std::error::Error>> {
+        if price_data.len() < ICHIMOKU_SENKOU_B_PERIOD {
+            return Err("Insufficient data for Ichimoku analysis".into());
+        }
+
+        let highs: Vec<f64> = price_data.iter().map(|p| p.high).collect();
+        let lows: Vec<f64> = price_data.iter().map(|p| p.low).collect();
+        let last = price_data.len() - 1;
+        let current_price = price_data[last].close;
+
+        let tenkan_sen = self.midpoint(&highs, &lows, last, ICHIMOKU_TENKAN_PERIOD);
+        let kijun_sen = self.midpoint(&highs, &lows, last, ICHIMOKU_KIJUN_PERIOD);
+
+        // 当前K线对应的云层由 26 根K线前的先行带前移而来
+        let cloud_at = if last >= ICHIMOKU_SENKOU_B_PERIOD - 1 + ICHIMOKU_KIJUN_PERIOD {
+            last - ICHIMOKU_KIJUN_PERIOD
+        } else {
+            last
+        };
+        let senkou_span_a = (self.midpoint(&highs, &lows, cloud_at, ICHIMOKU_TENKAN_PERIOD)
+            + self.midpoint(&highs, &lows, cloud_at, ICHIMOKU_KIJUN_PERIOD))
+            / 2.0;
+        let senkou_span_b = self.midpoint(&highs, &lows, cloud_at, ICHIMOKU_SENKOU_B_PERIOD);
+
+        let cloud_top = senkou_span_a.max(senkou_span_b);
+        let cloud_bottom = senkou_span_a.min(senkou_span_b);
+        let cloud_position = if current_price > cloud_top {
+            "云上"
+        } else if current_price < cloud_bottom {
+            "云下"
+        } else {
+            "云中"
+        };
+
+        let prev_tenkan = self.midpoint(&highs, &lows, last - 1, ICHIMOKU_TENKAN_PERIOD);
+        let prev_kijun = self.midpoint(&highs, &lows, last - 1, ICHIMOKU_KIJUN_PERIOD);
+        let tk_cross = if prev_tenkan <= prev_kijun && tenkan_sen > kijun_sen {
+            "金叉"
+        } else if prev_tenkan >= prev_kijun && tenkan_sen < kijun_sen {
+            "死叉"
+        } else {
+            "无"
+        };
+
+        let signal_type = self.generate_ichimoku_signal(cloud_position, tenkan_sen, kijun_sen);
+
+        Ok(IchimokuStrategy {
+            tenkan_sen,
+            kijun_sen,
+            senkou_span_a,
+            senkou_span_b,
+            chikou_span: current_price,
+            cloud_position: cloud_position.to_string(),
+            tk_cross: tk_cross.to_string(),
+            signal_type,
+        })
+    }
+
+    /// 生成交易信号，`account_risk_pct` 为单笔交易愿意承受的账户亏损比例（%），用于计算建议仓位
     pub fn generate_trading_signals(
         &self,
         strategies: &TradingStrategies,
         current_price: f64,
+        account_risk_pct: f64,
     ) -> Vec<TradingSignal> {
         let mut signals = Vec::new();
 
         // MACD信号
-        if strategies.macd.signal_type != "持有" {
+        if let Some(macd) = strategies.macd.as_ref().filter(|s| s.signal_type != "持有") {
             signals.push(TradingSignal {
                 strategy_name: "MACD策略".to_string(),
-                signal_type: strategies.macd.signal_type.clone(),
-                strength: self.calculate_signal_strength(&strategies.macd.signal_type),
+                signal_type: macd.signal_type.clone(),
+                strength: self.calculate_signal_strength(&macd.signal_type),
                 price: current_price,
                 timestamp: Utc::now(),
-                reason: format!("MACD信号: {}线与信号线交叉", strategies.macd.signal_type),
-                confidence: self.calculate_macd_confidence(&strategies.macd),
-                risk_level: self.calculate_risk_level(&strategies.macd.signal_type),
-                expected_profit: self.calculate_expected_profit(&strategies.macd.signal_type, current_price),
-                stop_loss: self.calculate_stop_loss(&strategies.macd.signal_type, current_price),
-                take_profit: self.calculate_take_profit(&strategies.macd.signal_type, current_price),
+                reason: format!("MACD信号: {}线与信号线交叉", macd.signal_type),
+                confidence: self.calculate_macd_confidence(macd),
+                risk_level: self.calculate_risk_level(&macd.signal_type),
+                expected_profit: self.calculate_expected_profit(&macd.signal_type, current_price),
+                stop_loss: self.calculate_stop_loss(&macd.signal_type, current_price),
+                take_profit: self.calculate_take_profit(&macd.signal_type, current_price),
+                suggested_position_pct: self.calculate_position_pct(
+                    &macd.signal_type,
+                    current_price,
+                    account_risk_pct,
+                ),
             });
         }
 
         // RSI信号
-        if strategies.rsi.signal_type != "持有" {
+        if let Some(rsi) = strategies.rsi.as_ref().filter(|s| s.signal_type != "持有") {
             signals.push(TradingSignal {
                 strategy_name: "RSI策略".to_string(),
-                signal_type: strategies.rsi.signal_type.clone(),
-                strength: self.calculate_signal_strength(&strategies.rsi.signal_type),
+                signal_type: rsi.signal_type.clone(),
+                strength: self.calculate_signal_strength(&rsi.signal_type),
                 price: current_price,
                 timestamp: Utc::now(),
-                reason: format!("RSI超买超卖信号: {:.1}", strategies.rsi.current_rsi),
-                confidence: self.calculate_rsi_confidence(&strategies.rsi),
-                risk_level: self.calculate_risk_level(&strategies.rsi.signal_type),
-                expected_profit: self.calculate_expected_profit(&strategies.rsi.signal_type, current_price),
-                stop_loss: self.calculate_stop_loss(&strategies.rsi.signal_type, current_price),
-                take_profit: self.calculate_take_profit(&strategies.rsi.signal_type, current_price),
+                reason: format!("RSI超买超卖信号: {:.1}", rsi.current_rsi),
+                confidence: self.calculate_rsi_confidence(rsi),
+                risk_level: self.calculate_risk_level(&rsi.signal_type),
+                expected_profit: self.calculate_expected_profit(&rsi.signal_type, current_price),
+                stop_loss: self.calculate_stop_loss(&rsi.signal_type, current_price),
+                take_profit: self.calculate_take_profit(&rsi.signal_type, current_price),
+                suggested_position_pct: self.calculate_position_pct(
+                    &rsi.signal_type,
+                    current_price,
+                    account_risk_pct,
+                ),
             });
         }
 
         // 移动平均线信号
-        if strategies.moving_average.signal_type != "持有" {
+        if let Some(moving_average) = strategies.moving_average.as_ref().filter(|s| s.signal_type != "持有") {
             signals.push(TradingSignal {
                 strategy_name: "均线策略".to_string(),
-                signal_type: strategies.moving_average.signal_type.clone(),
-                strength: self.calculate_signal_strength(&strategies.moving_average.signal_type),
+                signal_type: moving_average.signal_type.clone(),
+                strength: self.calculate_signal_strength(&moving_average.signal_type),
                 price: current_price,
                 timestamp: Utc::now(),
                 reason: format!("均线交叉信号: {}日均线与{}日均线", 
-                    strategies.moving_average.short_period, strategies.moving_average.long_period),
-                confidence: self.calculate_ma_confidence(&strategies.moving_average),
-                risk_level: self.calculate_risk_level(&strategies.moving_average.signal_type),
-                expected_profit: self.calculate_expected_profit(&strategies.moving_average.signal_type, current_price),
-                stop_loss: self.calculate_stop_loss(&strategies.moving_average.signal_type, current_price),
-                take_profit: self.calculate_take_profit(&strategies.moving_average.signal_type, current_price),
+                    moving_average.short_period, moving_average.long_period),
+                confidence: self.calculate_ma_confidence(moving_average),
+                risk_level: self.calculate_risk_level(&moving_average.signal_type),
+                expected_profit: self.calculate_expected_profit(&moving_average.signal_type, current_price),
+                stop_loss: self.calculate_stop_loss(&moving_average.signal_type, current_price),
+                take_profit: self.calculate_take_profit(&moving_average.signal_type, current_price),
+                suggested_position_pct: self.calculate_position_pct(
+                    &moving_average.signal_type,
+                    current_price,
+                    account_risk_pct,
+                ),
             });
         }
 
         // 布林带信号
-        if strategies.bollinger_bands.signal_type != "持有" {
+        if let Some(bollinger_bands) = strategies.bollinger_bands.as_ref().filter(|s| s.signal_type != "持有") {
             signals.push(TradingSignal {
                 strategy_name: "布林带策略".to_string(),
-                signal_type: strategies.bollinger_bands.signal_type.clone(),
-                strength: self.calculate_signal_strength(&strategies.bollinger_bands.signal_type),
+                signal_type: bollinger_bands.signal_type.clone(),
+                strength: self.calculate_signal_strength(&bollinger_bands.signal_type),
                 price: current_price,
                 timestamp: Utc::now(),
                 reason: "布林带突破信号".to_string(),
-                confidence: self.calculate_bb_confidence(&strategies.bollinger_bands),
-                risk_level: self.calculate_risk_level(&strategies.bollinger_bands.signal_type),
-                expected_profit: self.calculate_expected_profit(&strategies.bollinger_bands.signal_type, current_price),
-                stop_loss: self.calculate_stop_loss(&strategies.bollinger_bands.signal_type, current_price),
-                take_profit: self.calculate_take_profit(&strategies.bollinger_bands.signal_type, current_price),
+                confidence: self.calculate_bb_confidence(bollinger_bands),
+                risk_level: self.calculate_risk_level(&bollinger_bands.signal_type),
+                expected_profit: self.calculate_expected_profit(&bollinger_bands.signal_type, current_price),
+                stop_loss: self.calculate_stop_loss(&bollinger_bands.signal_type, current_price),
+                take_profit: self.calculate_take_profit(&bollinger_bands.signal_type, current_price),
+                suggested_position_pct: self.calculate_position_pct(
+                    &bollinger_bands.signal_type,
+                    current_price,
+                    account_risk_pct,
+                ),
             });
         }
 
+        // 一目均衡表信号
+        if let Some(ichimoku) = strategies.ichimoku.as_ref().filter(|s| s.signal_type != "持有") {
+            signals.push(TradingSignal {
+                strategy_name: "一目均衡表策略".to_string(),
+                signal_type: ichimoku.signal_type.clone(),
+                strength: self.calculate_signal_strength(&ichimoku.signal_type),
+                price: current_price,
+                timestamp: Utc::now(),
+                reason: format!("价格位于{}，转换线{}基准线（{}）",
+                    ichimoku.cloud_position,
+                    if ichimoku.tenkan_sen > ichimoku.kijun_sen { "高于" } else { "低于" },
+                    match ichimoku.tk_cross.as_str() {
+                        "无" => "未交叉",
+                        cross => cross,
+                    }),
+                confidence: self.calculate_ichimoku_confidence(ichimoku),
+                risk_level: self.calculate_risk_level(&ichimoku.signal_type),
+                expected_profit: self.calculate_expected_profit(&ichimoku.signal_type, current_price),
+                stop_loss: self.calculate_stop_loss(&ichimoku.signal_type, current_price),
+                take_profit: self.calculate_take_profit(&ichimoku.signal_type, current_price),
+                suggested_position_pct: self.calculate_position_pct(
+                    &ichimoku.signal_type,
+                    current_price,
+                    account_risk_pct,
+                ),
+            });
+        }
+
+        signals
+    }
+
+    /// 逐根K线回测单个策略：仅做多、满仓进出。空仓时出现买入信号按当日收盘价开仓，
+    /// 持仓时出现卖出信号按当日收盘价平仓，期末仍持仓则按最后收盘价平仓
+    pub fn backtest(&self, price_data: &[PriceData], strategy: StrategyKind) -> BacktestResult {
+        let prices: Vec<f64> = price_data.iter().map(|p| p.close).collect();
+        let signals = self.signal_series(&prices, strategy);
+
+        let mut trades = Vec::new();
+        let mut entry: Option<usize> = None;
+        let mut equity = 1.0;
+        let mut equity_curve = Vec::with_capacity(prices.len());
+
+        for i in 0..prices.len() {
+            if let Some(open) = entry {
+                if prices[i - 1] > 0.0 {
+                    equity *= prices[i] / prices[i - 1];
+                }
+
+                let at_end = i == prices.len() - 1;
+                if signals[i] == "卖出" || at_end {
+                    let entry_price = price_data[open].close;
+                    let exit_price = price_data[i].close;
+                    trades.push(BacktestTrade {
+                        entry_date: price_data[open].date,
+                        entry_price,
+                        exit_date: price_data[i].date,
+                        exit_price,
+                        return_rate: if entry_price > 0.0 {
+                            exit_price / entry_price - 1.0
+                        } else {
+                            0.0
+                        },
+                        holding_bars: i - open,
+                        closed_at_end: at_end && signals[i] != "卖出",
+                    });
+                    entry = None;
+                }
+            } else if signals[i] == "买入" && i + 1 < prices.len() {
+                entry = Some(i);
+            }
+            equity_curve.push(equity);
+        }
+
+        let wins = trades.iter().filter(|t| t.return_rate > 0.0).count();
+        let benchmark_return = match (prices.first(), prices.last()) {
+            (Some(&first), Some(&last)) if first > 0.0 => last / first - 1.0,
+            _ => 0.0,
+        };
+
+        BacktestResult {
+            strategy,
+            start_date: price_data.first().map(|p| p.date),
+            end_date: price_data.last().map(|p| p.date),
+            bars: price_data.len(),
+            total_return: equity - 1.0,
+            benchmark_return,
+            win_rate: (!trades.is_empty()).then(|| wins as f64 / trades.len() as f64),
+            max_drawdown: crate::indicators::max_drawdown(&equity_curve).unwrap_or(0.0),
+            trade_count: trades.len(),
+            sharpe_ratio: self.equity_sharpe_ratio(&equity_curve),
+            trades,
+        }
+    }
+
+    /// 每根K线的策略信号，与该K线及之前数据上运行对应 analyze_* 得到的信号一致；数据不足时为“持有”
+    fn signal_series(&self, prices: &[f64], strategy: StrategyKind) -> Vec<String> {
+        let mut signals = vec!["持有".to_string(); prices.len()];
+
+        match strategy {
+            StrategyKind::Macd => {
+                let (macd_line, signal_line, histogram) = self.calculate_macd(prices);
+                for i in (self.macd_slow_period.max(1) as usize - 1)..prices.len() {
+                    signals[i] =
+                        self.generate_macd_signal(macd_line[i], signal_line[i], histogram[i]);
+                }
+            }
+            StrategyKind::Rsi => {
+                // RSI 值 k 由 prices[k..k + 14] 计算，对应第 k + 14 根K线的分析
+                for (k, rsi) in self.calculate_rsi(prices, 14).iter().enumerate() {
+                    signals[k + 14] = self.generate_rsi_signal(*rsi);
+                }
+            }
+            StrategyKind::MovingAverage => {
+                let short_period = self.ma_short_period as usize;
+                let long_period = self.ma_long_period as usize;
+                let short_ma = self.calculate_sma(prices, self.ma_short_period);
+                let long_ma = self.calculate_sma(prices, self.ma_long_period);
+                for i in long_period.max(short_period)..prices.len() {
+                    let (signal, _, _) = self.generate_ma_signal(
+                        &short_ma[..=i + 1 - short_period],
+                        &long_ma[..=i + 1 - long_period],
+                    );
+                    signals[i] = signal;
+                }
+            }
+            StrategyKind::BollingerBands => {
+                let period = self.bb_period as usize;
+                let (upper, _, lower) =
+                    self.calculate_bollinger_bands(prices, self.bb_period, self.bb_std_dev);
+                for i in period..prices.len() {
+                    signals[i] =
+                        self.generate_bb_signal(prices[i], upper[i - period], lower[i - period]);
+                }
+            }
+        }
+
         signals
     }
 
+    /// 按日净值收益计算的年化夏普比率（无风险利率按 0）
+    fn equity_sharpe_ratio(&self, equity_curve: &[f64]) -> Option<f64> {
+        let returns: Vec<f64> = equity_curve.windows(2).map(|w| w[1] / w[0] - 1.0).collect();
+        if returns.len() < 2 {
+            return None;
+        }
+
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        let variance =
+            returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+        let std_dev = variance.sqrt();
+        (std_dev > 0.0).then(|| mean / std_dev * 252f64.sqrt())
+    }
+
     // MACD计算函数
     fn calculate_macd(&self, prices: &[f64]) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
         let ema_fast = self.calculate_ema(prices, self.macd_fast_period);
@@ -364,7 +701,7 @@ impl TradingStrategiesAnalyzer {
     fn calculate_sma(&self, prices: &[f64], period: i32) -> Vec<f64> {
         let mut sma_values = Vec::new();
         
-        for i in period as usize..prices.len() {
+        for i in period as usize..=prices.len() {
             let window = &prices[i - period as usize..i];
             let sum: f64 = window.iter().sum();
             sma_values.push(sum / period as f64);
@@ -416,9 +753,10 @@ impl TradingStrategiesAnalyzer {
 
     // MACD信号生成
     fn generate_macd_signal(&self, macd: f64, signal: f64, histogram: f64) -> String {
-        if macd > signal && histogram > 0.0 {
+        // 仅在零轴同侧确认的交叉才视为有效信号
+        if macd > signal && histogram > 0.0 && macd > 0.0 {
             "买入".to_string()
-        } else if macd < signal && histogram < 0.0 {
+        } else if macd < signal && histogram < 0.0 && macd < 0.0 {
             "卖出".to_string()
         } else {
             "持有".to_string()
@@ -461,6 +799,23 @@ impl TradingStrategiesAnalyzer {
         (signal_type, golden_cross, death_cross)
     }
 
+    // 一目均衡表信号生成：云上且转换线高于基准线为买入，云下且转换线低于基准线为卖出
+    fn generate_ichimoku_signal(&self, cloud_position: &str, tenkan: f64, kijun: f64) -> String {
+        match cloud_position {
+            "云上" if tenkan > kijun => "买入".to_string(),
+            "云下" if tenkan < kijun => "卖出".to_string(),
+            _ => "持有".to_string(),
+        }
+    }
+
+    // 截至 end（含）的 period 根K线最高价与最低价的中值
+    fn midpoint(&self, highs: &[f64], lows: &[f64], end: usize, period: usize) -> f64 {
+        let start = (end + 1).saturating_sub(period);
+        let highest = highs[start..=end].iter().cloned().fold(f64::MIN, f64::max);
+        let lowest = lows[start..=end].iter().cloned().fold(f64::MAX, f64::min);
+        (highest + lowest) / 2.0
+    }
+
     // 布林带信号生成
     fn generate_bb_signal(&self, price: f64, upper: f64, lower: f64) -> String {
         if price >= upper {
@@ -477,20 +832,42 @@ impl TradingStrategiesAnalyzer {
         let mut patterns = Vec::new();
         
         if price_data.len() >= 3 {
-            // 检测锤子线
-            if self.is_hammer_pattern(&price_data[price_data.len() - 1]) {
+            let last = &price_data[price_data.len() - 1];
+            let prior_uptrend = self.is_prior_uptrend(price_data);
+
+            // 检测锤子线（出现在下跌后）
+            if !prior_uptrend && self.is_hammer_pattern(last) {
                 patterns.push("锤子线".to_string());
             }
             
-            // 检测吊颈线
-            if self.is_hanging_man_pattern(&price_data[price_data.len() - 1]) {
+            // 检测吊颈线（出现在上涨后）
+            if prior_uptrend && self.is_hanging_man_pattern(last) {
                 patterns.push("吊颈线".to_string());
             }
             
             // 检测启明星
-            if price_data.len() >= 3 && self.is_morning_star_pattern(&price_data[price_data.len() - 3..]) {
+            if self.is_morning_star_pattern(&price_data[price_data.len() - 3..]) {
                 patterns.push("启明星".to_string());
             }
+
+            // 检测黄昏星
+            if self.is_evening_star_pattern(&price_data[price_data.len() - 3..]) {
+                patterns.push("黄昏星".to_string());
+            }
+
+            // 检测吞没形态
+            let previous = &price_data[price_data.len() - 2];
+            if self.is_bullish_engulfing_pattern(previous, last) {
+                patterns.push("看涨吞没".to_string());
+            }
+            if self.is_bearish_engulfing_pattern(previous, last) {
+                patterns.push("看跌吞没".to_string());
+            }
+
+            // 检测十字星
+            if self.is_doji_pattern(last) {
+                patterns.push("十字星".to_string());
+            }
         }
         
         patterns
@@ -600,11 +977,11 @@ impl TradingStrategiesAnalyzer {
     fn calculate_accumulation_distribution(&self, price_data: &[PriceData]) -> f64 {
         let mut ad_line = 0.0;
         
-        for i in 1..price_data.len() {
-            let close = price_data[i].close;
-            let low = price_data[i].low;
-            let high = price_data[i].high;
-            let volume = price_data[i].volume as f64;
+        for candle in price_data.iter().skip(1) {
+            let close = candle.close;
+            let low = candle.low;
+            let high = candle.high;
+            let volume = candle.volume as f64;
             
             let clv = if high != low {
                 ((close - low) - (high - close)) / (high - low)
@@ -667,6 +1044,27 @@ impl TradingStrategiesAnalyzer {
         }
     }
 
+    // 建议仓位（%）：账户风险比例 / 止损距离比例，上限 MAX_POSITION_PCT
+    fn calculate_position_pct(
+        &self,
+        signal_type: &str,
+        current_price: f64,
+        account_risk_pct: f64,
+    ) -> f64 {
+        if current_price <= 0.0 || !account_risk_pct.is_finite() || account_risk_pct <= 0.0 {
+            return 0.0;
+        }
+
+        let stop_distance =
+            (current_price - self.calculate_stop_loss(signal_type, current_price)).abs();
+        if stop_distance <= 0.0 {
+            return 0.0;
+        }
+
+        let stop_distance_pct = stop_distance / current_price * 100.0;
+        (account_risk_pct / stop_distance_pct * 100.0).min(MAX_POSITION_PCT)
+    }
+
     // 线性趋势计算
     fn calculate_linear_trend(&self, values: &[f64]) -> f64 {
         if values.len() < 2 {
@@ -695,7 +1093,7 @@ impl TradingStrategiesAnalyzer {
         let lower_shadow = candle.open.min(candle.close) - candle.low;
         let upper_shadow = candle.high - candle.open.max(candle.close);
         
-        lower_shadow > 2.0 * body && upper_shadow < 0.1 * body
+        lower_shadow > 2.0 * body && upper_shadow < body
     }
 
     fn is_hanging_man_pattern(&self, candle: &PriceData) -> bool {
@@ -718,6 +1116,53 @@ impl TradingStrategiesAnalyzer {
         third.close > first.open
     }
 
+    fn is_evening_star_pattern(&self, candles: &[PriceData]) -> bool {
+        if candles.len() < 3 {
+            return false;
+        }
+
+        let first = &candles[0];
+        let second = &candles[1];
+        let third = &candles[2];
+
+        // 第一根是阳线，第二根是小实体，第三根是阴线且收于第一根开盘价之下
+        first.close > first.open
+            && (second.close - second.open).abs() < (first.close - first.open).abs() * 0.5
+            && third.close < third.open
+            && third.close < first.open
+    }
+
+    // 前一根为阴线，当前阳线实体完全覆盖前一根实体
+    fn is_bullish_engulfing_pattern(&self, previous: &PriceData, current: &PriceData) -> bool {
+        previous.close < previous.open
+            && current.close > current.open
+            && current.open <= previous.close
+            && current.close >= previous.open
+            && current.close - current.open > previous.open - previous.close
+    }
+
+    // 前一根为阳线，当前阴线实体完全覆盖前一根实体
+    fn is_bearish_engulfing_pattern(&self, previous: &PriceData, current: &PriceData) -> bool {
+        previous.close > previous.open
+            && current.close < current.open
+            && current.open >= previous.close
+            && current.close <= previous.open
+            && current.open - current.close > previous.close - previous.open
+    }
+
+    // 实体不超过全天振幅的 10%
+    fn is_doji_pattern(&self, candle: &PriceData) -> bool {
+        let range = candle.high - candle.low;
+        range > 0.0 && (candle.close - candle.open).abs() <= range * 0.1
+    }
+
+    // 最新K线之前的短期走势：前一根收盘高于 5 根之前的收盘视为上涨
+    fn is_prior_uptrend(&self, price_data: &[PriceData]) -> bool {
+        let previous = price_data.len() - 2;
+        let start = previous.saturating_sub(4);
+        price_data[previous].close > price_data[start].close
+    }
+
     fn is_head_and_shoulders_pattern(&self, price_data: &[PriceData]) -> bool {
         // 简化的头肩顶检测
         if price_data.len() < 5 {
@@ -800,11 +1245,21 @@ impl TradingStrategiesAnalyzer {
     }
 
     // K线信号生成
+    // 按看涨/看跌形态数量给出方向，十字星等中性形态不改变方向
     fn generate_kline_signal(&self, patterns: &[String], reversal_patterns: &[String]) -> String {
-        if !reversal_patterns.is_empty() {
-            "反转信号".to_string()
-        } else if !patterns.is_empty() {
-            "形态信号".to_string()
+        let mut bias = 0;
+        for pattern in patterns.iter().chain(reversal_patterns) {
+            match pattern.as_str() {
+                "锤子线" | "启明星" | "看涨吞没" | "头肩底" => bias += 1,
+                "吊颈线" | "黄昏星" | "看跌吞没" | "头肩顶" => bias -= 1,
+                _ => {}
+            }
+        }
+
+        if bias > 0 {
+            "买入".to_string()
+        } else if bias < 0 {
+            "卖出".to_string()
         } else {
             "持有".to_string()
         }
@@ -814,9 +1269,7 @@ impl TradingStrategiesAnalyzer {
     fn generate_volume_signal(&self, volume_ratio: f64, volume_trend: &str, mfi: f64) -> String {
         if volume_ratio > 2.0 && volume_trend == "放量" {
             "买入".to_string()
-        } else if volume_ratio > 2.0 && volume_trend == "缩量" {
-            "卖出".to_string()
-        } else if mfi > 80.0 {
+        } else if (volume_ratio > 2.0 && volume_trend == "缩量") || mfi > 80.0 {
             "卖出".to_string()
         } else if mfi < 20.0 {
             "买入".to_string()
@@ -834,7 +1287,9 @@ impl TradingStrategiesAnalyzer {
         let mut reliability = 0.0;
         for pattern in patterns {
             match pattern.as_str() {
-                "锤子线" | "启明星" => reliability += 70.0,
+                "看涨吞没" | "看跌吞没" => reliability += 75.0,
+                "锤子线" | "启明星" | "黄昏星" => reliability += 70.0,
+                "十字星" => reliability += 45.0,
                 "头肩顶" | "头肩底" => reliability += 80.0,
                 "旗形" | "三角形" => reliability += 60.0,
                 _ => reliability += 50.0,
@@ -846,26 +1301,28 @@ impl TradingStrategiesAnalyzer {
 
     // MACD背离检测
     fn detect_macd_divergence(&self, prices: &[f64], macd_line: &[f64]) -> bool {
-        if prices.len() < 5 || macd_line.len() < 5 {
-            return false;
-        }
-        
-        let price_trend = self.calculate_linear_trend(&prices[prices.len() - 5..]);
-        let macd_trend = self.calculate_linear_trend(&macd_line[macd_line.len() - 5..]);
-        
-        price_trend > 0.0 && macd_trend < 0.0 || price_trend < 0.0 && macd_trend > 0.0
+        self.detect_divergence(prices, macd_line)
     }
 
     // RSI背离检测
     fn detect_rsi_divergence(&self, prices: &[f64], rsi_values: &[f64]) -> bool {
-        if prices.len() < 5 || rsi_values.len() < 5 {
+        self.detect_divergence(prices, rsi_values)
+    }
+
+    // 最近 divergence_lookback 个点内价格与指标趋势方向相反即为背离：
+    // 价格上涨而指标下降为顶背离，价格下跌而指标上升为底背离
+    fn detect_divergence(&self, prices: &[f64], indicator: &[f64]) -> bool {
+        let lookback = self.divergence_lookback.max(2);
+        if prices.len() < lookback || indicator.len() < lookback {
             return false;
         }
         
-        let price_trend = self.calculate_linear_trend(&prices[prices.len() - 5..]);
-        let rsi_trend = self.calculate_linear_trend(&rsi_values[rsi_values.len() - 5..]);
+        let price_trend = self.calculate_linear_trend(&prices[prices.len() - lookback..]);
+        let indicator_trend = self.calculate_linear_trend(&indicator[indicator.len() - lookback..]);
         
-        price_trend > 0.0 && rsi_trend < 0.0 || price_trend < 0.0 && rsi_trend > 0.0
+        let bearish = price_trend > 0.0 && indicator_trend < 0.0;
+        let bullish = price_trend < 0.0 && indicator_trend > 0.0;
+        bearish || bullish
     }
 
     // 置信度计算函数
@@ -893,6 +1350,17 @@ impl TradingStrategiesAnalyzer {
         (base_confidence + cross_bonus + spread_bonus).min(100.0)
     }
 
+    fn calculate_ichimoku_confidence(&self, ichimoku: &IchimokuStrategy) -> f64 {
+        let base_confidence: f64 = 65.0;
+        // 信号当根出现同向的转换线/基准线交叉时更可信
+        let cross_bonus = match (ichimoku.signal_type.as_str(), ichimoku.tk_cross.as_str()) {
+            ("买入", "金叉") | ("卖出", "死叉") => 20.0,
+            _ => 0.0,
+        };
+
+        (base_confidence + cross_bonus).min(100.0)
+    }
+
     fn calculate_bb_confidence(&self, bb: &BollingerBandsStrategy) -> f64 {
         let base_confidence = 65.0;
         let squeeze_bonus = if bb.squeeze { 20.0 } else { 0.0 };
@@ -943,6 +1411,40 @@ mod tests {
         assert_eq!(analyzer.macd_slow_period, 26);
     }
 
+    #[test]
+    fn test_divergence_detection() {
+        let analyzer = TradingStrategiesAnalyzer::new();
+        let rising: Vec<f64> = (0..20).map(|i| 10.0 + i as f64 * 0.1).collect();
+        let falling: Vec<f64> = (0..20).map(|i| 70.0 - i as f64).collect();
+
+        // 顶背离：价格创新高而指标走弱
+        assert!(analyzer.detect_rsi_divergence(&rising, &falling));
+        assert!(analyzer.detect_macd_divergence(&rising, &falling));
+        // 底背离：价格走低而指标回升
+        assert!(analyzer.detect_rsi_divergence(&falling, &rising));
+        // 同向运动不是背离
+        assert!(!analyzer.detect_rsi_divergence(&rising, &rising));
+        assert!(!analyzer.detect_macd_divergence(&falling, &falling));
+        // 数据不足一个窗口时不判断
+        assert!(!analyzer.detect_rsi_divergence(&rising[..4], &falling[..4]));
+    }
+
+    #[test]
+    fn test_divergence_lookback_is_configurable() {
+        // 指标整体上升，只有最后几个点回落
+        let prices: Vec<f64> = (0..20).map(|i| 10.0 + i as f64 * 0.1).collect();
+        let mut indicator: Vec<f64> = (0..20).map(|i| 40.0 + i as f64).collect();
+        for (offset, value) in indicator.iter_mut().skip(15).enumerate() {
+            *value = 55.0 - offset as f64 * 0.5;
+        }
+
+        let mut analyzer = TradingStrategiesAnalyzer::new();
+        assert_eq!(analyzer.divergence_lookback, 5);
+        assert!(analyzer.detect_rsi_divergence(&prices, &indicator));
+        analyzer.divergence_lookback = 14;
+        assert!(!analyzer.detect_rsi_divergence(&prices, &indicator));
+    }
+
     #[test]
     fn test_sma_calculation() {
         let analyzer = TradingStrategiesAnalyzer::new();
@@ -1019,4 +1521,347 @@ mod tests {
         
         assert!(analyzer.is_hammer_pattern(&hammer));
     }
-}
\ No newline at end of file
+
+    fn candle(open: f64, close: f64, high: f64, low: f64) -> PriceData {
+        PriceData {
+            date: Utc::now(),
+            open,
+            close,
+            high,
+            low,
+            volume: 100000,
+            change_pct: 0.0,
+            turnover: 0.0,
+            turnover_rt: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_engulfing_pattern_detection() {
+        let analyzer = TradingStrategiesAnalyzer::new();
+        let down = candle(10.5, 10.0, 10.6, 9.9);
+        let up = candle(10.0, 10.5, 10.6, 9.9);
+
+        let bullish = candle(9.9, 10.7, 10.8, 9.8);
+        assert!(analyzer.is_bullish_engulfing_pattern(&down, &bullish));
+        assert!(!analyzer.is_bearish_engulfing_pattern(&down, &bullish));
+        // 同色或未完全覆盖都不算
+        assert!(!analyzer.is_bullish_engulfing_pattern(&up, &bullish));
+        let partial = candle(10.1, 10.7, 10.8, 10.0);
+        assert!(!analyzer.is_bullish_engulfing_pattern(&down, &partial));
+
+        let bearish = candle(10.6, 9.8, 10.7, 9.7);
+        assert!(analyzer.is_bearish_engulfing_pattern(&up, &bearish));
+        assert!(!analyzer.is_bullish_engulfing_pattern(&up, &bearish));
+
+        let patterns = analyzer.detect_kline_patterns(&[up.clone(), up.clone(), bearish]);
+        assert!(patterns.contains(&"看跌吞没".to_string()));
+        assert_eq!(analyzer.generate_kline_signal(&patterns, &[]), "卖出");
+        assert_eq!(analyzer.calculate_pattern_reliability(&["看涨吞没".to_string()]), 75.0);
+    }
+
+    #[test]
+    fn test_doji_pattern_detection() {
+        let analyzer = TradingStrategiesAnalyzer::new();
+        assert!(analyzer.is_doji_pattern(&candle(10.0, 10.02, 10.5, 9.5)));
+        assert!(!analyzer.is_doji_pattern(&candle(10.0, 10.3, 10.5, 9.5)));
+        // 一字线没有振幅，不视为十字星
+        assert!(!analyzer.is_doji_pattern(&candle(10.0, 10.0, 10.0, 10.0)));
+
+        let data = [
+            candle(10.0, 10.1, 10.2, 9.9),
+            candle(10.1, 10.2, 10.3, 10.0),
+            candle(10.2, 10.21, 10.6, 9.8),
+        ];
+        let patterns = analyzer.detect_kline_patterns(&data);
+        assert_eq!(patterns, vec!["十字星".to_string()]);
+        assert_eq!(analyzer.generate_kline_signal(&patterns, &[]), "持有");
+    }
+
+    #[test]
+    fn test_star_pattern_detection() {
+        let analyzer = TradingStrategiesAnalyzer::new();
+        let evening = [
+            candle(10.0, 11.0, 11.1, 9.9),
+            candle(11.1, 11.2, 11.3, 11.0),
+            candle(11.0, 9.8, 11.1, 9.7),
+        ];
+        assert!(analyzer.is_evening_star_pattern(&evening));
+        assert!(!analyzer.is_morning_star_pattern(&evening));
+        let patterns = analyzer.detect_kline_patterns(&evening);
+        assert!(patterns.contains(&"黄昏星".to_string()));
+        assert_eq!(analyzer.generate_kline_signal(&patterns, &[]), "卖出");
+
+        let morning = [
+            candle(11.0, 10.0, 11.1, 9.9),
+            candle(9.9, 9.8, 10.0, 9.7),
+            candle(10.0, 11.2, 11.3, 9.9),
+        ];
+        assert!(analyzer.is_morning_star_pattern(&morning));
+        assert!(!analyzer.is_evening_star_pattern(&morning));
+        let patterns = analyzer.detect_kline_patterns(&morning);
+        assert_eq!(analyzer.generate_kline_signal(&patterns, &[]), "买入");
+    }
+
+    #[test]
+    fn test_hammer_and_hanging_man_depend_on_prior_trend() {
+        let analyzer = TradingStrategiesAnalyzer::new();
+        let hammer = candle(10.0, 10.2, 10.3, 9.0);
+
+        let after_decline = [
+            candle(11.0, 10.8, 11.1, 10.7),
+            candle(10.8, 10.4, 10.9, 10.3),
+            hammer.clone(),
+        ];
+        let patterns = analyzer.detect_kline_patterns(&after_decline);
+        assert!(patterns.contains(&"锤子线".to_string()));
+        assert!(!patterns.contains(&"吊颈线".to_string()));
+
+        let after_rally = [candle(9.0, 9.2, 9.3, 8.9), candle(9.2, 9.8, 9.9, 9.1), hammer];
+        let patterns = analyzer.detect_kline_patterns(&after_rally);
+        assert!(patterns.contains(&"吊颈线".to_string()));
+        assert!(!patterns.contains(&"锤子线".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_short_history_returns_partial_strategies() {
+        let price_data: Vec<PriceData> = (0..20)
+            .map(|i| {
+                let close = 10.0 + (i % 5) as f64 * 0.2;
+                PriceData {
+                    date: Utc::now() - chrono::Duration::days(20 - i),
+                    open: close - 0.1,
+                    close,
+                    high: close + 0.2,
+                    low: close - 0.2,
+                    volume: 100000 + i * 1000,
+                    change_pct: 0.0,
+                    turnover: 0.0,
+                    turnover_rt: 0.0,
+                }
+            })
+            .collect();
+
+        let mut analyzer = TradingStrategiesAnalyzer::new();
+        let strategies = analyzer
+            .analyze_all_strategies("000001", &price_data)
+            .await
+            .unwrap();
+        assert!(strategies.macd.is_none());
+        assert!(strategies.rsi.is_some());
+        assert!(strategies.moving_average.is_some());
+        assert!(strategies.volume_analysis.is_some());
+        let skipped: Vec<&str> = strategies
+            .skipped_strategies
+            .iter()
+            .map(|s| s.strategy.as_str())
+            .collect();
+        assert_eq!(skipped, vec!["macd", "ichimoku"]);
+
+        // 所有策略都无法计算时仍然返回错误
+        assert!(analyzer
+            .analyze_all_strategies("000001", &create_test_price_data())
+            .await
+            .is_err());
+
+        analyzer.allow_partial_strategies = false;
+        assert!(analyzer
+            .analyze_all_strategies("000001", &price_data)
+            .await
+            .is_err());
+    }
+
+    fn series(closes: &[f64]) -> Vec<PriceData> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| PriceData {
+                date: Utc::now() - chrono::Duration::days((closes.len() - i) as i64),
+                open: close,
+                close,
+                high: close * 1.01,
+                low: close * 0.99,
+                volume: 100000,
+                change_pct: 0.0,
+                turnover: 0.0,
+                turnover_rt: 0.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_with_params_overrides_and_validates() {
+        let analyzer = TradingStrategiesAnalyzer::with_params(&StrategyParams {
+            rsi_overbought: Some(80.0),
+            rsi_oversold: Some(20.0),
+            ma_short_period: Some(10),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(analyzer.rsi_overbought, 80.0);
+        assert_eq!(analyzer.rsi_oversold, 20.0);
+        assert_eq!(analyzer.ma_short_period, 10);
+        // 未提供的字段保持默认值
+        assert_eq!(analyzer.ma_long_period, 20);
+        assert_eq!(analyzer.macd_fast_period, 12);
+        assert_eq!(analyzer.bb_std_dev, 2.0);
+
+        for params in [
+            StrategyParams {
+                bb_period: Some(0),
+                ..Default::default()
+            },
+            StrategyParams {
+                macd_fast_period: Some(26),
+                ..Default::default()
+            },
+            StrategyParams {
+                ma_short_period: Some(30),
+                ..Default::default()
+            },
+            StrategyParams {
+                rsi_oversold: Some(75.0),
+                ..Default::default()
+            },
+            StrategyParams {
+                bb_std_dev: Some(-1.0),
+                ..Default::default()
+            },
+        ] {
+            assert!(TradingStrategiesAnalyzer::with_params(&params).is_err());
+        }
+    }
+
+    #[test]
+    fn test_position_size_from_account_risk() {
+        let analyzer = TradingStrategiesAnalyzer::new();
+        // 止损距离 5%，承担 1% 账户风险时建议 20% 仓位
+        assert!((analyzer.calculate_position_pct("买入", 10.0, 1.0) - 20.0).abs() < 1e-9);
+        assert!((analyzer.calculate_position_pct("卖出", 10.0, 0.5) - 10.0).abs() < 1e-9);
+        assert_eq!(
+            analyzer.calculate_position_pct("买入", 10.0, 3.0),
+            MAX_POSITION_PCT
+        );
+        // 持有信号没有止损距离，无效风险比例不给仓位
+        assert_eq!(analyzer.calculate_position_pct("持有", 10.0, 1.0), 0.0);
+        assert_eq!(analyzer.calculate_position_pct("买入", 10.0, 0.0), 0.0);
+        assert_eq!(analyzer.calculate_position_pct("买入", 10.0, f64::NAN), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_bollinger_squeeze_is_relative_to_recent_bandwidth() {
+        let analyzer = TradingStrategiesAnalyzer::new();
+        let swing = |i: usize, amplitude: f64| {
+            if i.is_multiple_of(2) {
+                10.0 + amplitude
+            } else {
+                10.0 - amplitude
+            }
+        };
+
+        // 持续的窄幅波动：带宽绝对值小，但相对自身历史并未收窄
+        let steady: Vec<f64> = (0..70).map(|i| swing(i, 0.1)).collect();
+        let bb = analyzer.analyze_bollinger_bands_strategy(&series(&steady)).await.unwrap();
+        assert!(bb.bandwidth < 0.1);
+        assert!(!bb.squeeze);
+
+        // 大幅波动后转入窄幅整理：带宽低于近 20 期均值的 80%
+        let contracting: Vec<f64> = (0..70)
+            .map(|i| swing(i, if i < 40 { 1.0 } else { 0.1 }))
+            .collect();
+        let bb = analyzer.analyze_bollinger_bands_strategy(&series(&contracting)).await.unwrap();
+        assert!(bb.squeeze);
+    }
+
+    #[tokio::test]
+    async fn test_ichimoku_cloud_and_tk_signals() {
+        let analyzer = TradingStrategiesAnalyzer::new();
+        let rising: Vec<f64> = (0..80).map(|i| 10.0 + i as f64 * 0.1).collect();
+        let ichimoku = analyzer.analyze_ichimoku_strategy(&series(&rising)).await.unwrap();
+        assert_eq!(ichimoku.cloud_position, "云上");
+        assert!(ichimoku.tenkan_sen > ichimoku.kijun_sen);
+        assert!(ichimoku.senkou_span_a > ichimoku.senkou_span_b);
+        assert_eq!(ichimoku.signal_type, "买入");
+
+        let falling: Vec<f64> = rising.iter().rev().cloned().collect();
+        let ichimoku = analyzer.analyze_ichimoku_strategy(&series(&falling)).await.unwrap();
+        assert_eq!(ichimoku.cloud_position, "云下");
+        assert_eq!(ichimoku.signal_type, "卖出");
+
+        // V 形反转：低点移出转换线窗口的那根K线上，转换线上穿基准线
+        let mut rebound: Vec<f64> = (0..60).map(|i| 20.0 - i as f64 * 0.1).collect();
+        rebound.extend((1..=9).map(|k| 14.1 + k as f64 * 0.3));
+        let ichimoku = analyzer.analyze_ichimoku_strategy(&series(&rebound)).await.unwrap();
+        assert_eq!(ichimoku.tk_cross, "金叉");
+
+        // 52 根以上即可计算（云层不前移），更短时报错
+        assert!(analyzer.analyze_ichimoku_strategy(&series(&rising[..52])).await.is_ok());
+        assert!(analyzer.analyze_ichimoku_strategy(&series(&rising[..51])).await.is_err());
+
+        let strategies = analyzer.analyze_all_strategies("000001", &series(&rising)).await.unwrap();
+        let signals =
+            analyzer.generate_trading_signals(&strategies, 17.9, DEFAULT_ACCOUNT_RISK_PCT);
+        assert!(signals
+            .iter()
+            .any(|s| s.strategy_name == "一目均衡表策略" && s.signal_type == "买入"));
+    }
+
+    #[test]
+    fn test_backtest_moving_average_round_trip() {
+        let analyzer = TradingStrategiesAnalyzer::new();
+        // 下跌、上涨、再下跌：上涨途中金叉买入，回落后死叉卖出
+        let closes: Vec<f64> = (0..90)
+            .map(|i| match i {
+                0..=29 => 20.0 - i as f64 * 0.2,
+                30..=59 => 14.0 + (i - 30) as f64 * 0.4,
+                _ => 26.0 - (i - 60) as f64 * 0.4,
+            })
+            .collect();
+        let result = analyzer.backtest(&series(&closes), StrategyKind::MovingAverage);
+
+        assert_eq!(result.bars, 90);
+        assert_eq!(result.trade_count, 1);
+        let trade = &result.trades[0];
+        assert!(trade.exit_price > trade.entry_price);
+        assert!(!trade.closed_at_end);
+        assert_eq!(result.win_rate, Some(1.0));
+        assert!((result.total_return - trade.return_rate).abs() < 1e-9);
+        assert!(result.total_return > result.benchmark_return);
+        assert!(result.max_drawdown > 0.0 && result.max_drawdown < 0.2);
+        assert!(result.sharpe_ratio.unwrap() > 0.0);
+
+        // 没有信号时不交易
+        let flat = analyzer.backtest(&series(&[10.0; 40]), StrategyKind::MovingAverage);
+        assert_eq!(flat.trade_count, 0);
+        assert_eq!(flat.win_rate, None);
+        assert_eq!(flat.total_return, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_backtest_signals_match_live_strategies() {
+        let analyzer = TradingStrategiesAnalyzer::new();
+        let closes: Vec<f64> = (0..80)
+            .map(|i| 10.0 + (i as f64 * 0.35).sin() * 2.0 + i as f64 * 0.02)
+            .collect();
+
+        // 每个前缀的最后一根信号应与对该前缀调用 analyze_* 的结果一致
+        for end in [40, 55, 63, 80] {
+            let data = series(&closes[..end]);
+            let last = |kind| analyzer.signal_series(&closes[..end], kind).pop().unwrap();
+            let macd = analyzer.analyze_macd_strategy(&data).await.unwrap();
+            let rsi = analyzer.analyze_rsi_strategy(&data).await.unwrap();
+            let ma = analyzer
+                .analyze_moving_average_strategy(&data)
+                .await
+                .unwrap();
+            let bb = analyzer
+                .analyze_bollinger_bands_strategy(&data)
+                .await
+                .unwrap();
+            assert_eq!(last(StrategyKind::Macd), macd.signal_type);
+            assert_eq!(last(StrategyKind::Rsi), rsi.signal_type);
+            assert_eq!(last(StrategyKind::MovingAverage), ma.signal_type);
+            assert_eq!(last(StrategyKind::BollingerBands), bb.signal_type);
+        }
+    }
+}
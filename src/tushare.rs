@@ -0,0 +1,550 @@
+use chrono::{Duration, NaiveDate, Utc};
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::cache::CachedDataFetcher;
+use crate::data_fetcher::{parse_price_items, validate_date_range, DataFetcher, RateLimiter};
+use crate::models::*;
+
+type Row = Map<String, Value>;
+
+/// Tushare Pro 数据源：以 token 调用 api.tushare.pro 的 POST 接口，
+/// 将按列返回的 fields/items 转换为 PriceData 与 FundamentalData
+pub struct TushareFetcher {
+    client: Client,
+    api_url: String,
+    token: String,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl TushareFetcher {
+    pub fn new(token: String, timeout_secs: u64) -> Self {
+        let client = Client::builder()
+            .timeout(std::time::Duration::from_secs(timeout_secs))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            client,
+            api_url: TushareConfig::default().api_url,
+            token,
+            rate_limiter: Arc::new(RateLimiter::new(3)),
+        }
+    }
+
+    pub fn from_config(config: &TushareConfig) -> Self {
+        Self::new(config.token.clone(), config.timeout_seconds)
+            .with_api_url(config.api_url.clone())
+            .with_rate_limit(config.requests_per_second)
+    }
+
+    pub fn with_api_url(mut self, api_url: String) -> Self {
+        self.api_url = api_url;
+        self
+    }
+
+    /// 每秒最多发出 `requests_per_second` 个请求，0 表示不限制
+    pub fn with_rate_limit(mut self, requests_per_second: usize) -> Self {
+        self.rate_limiter = Arc::new(RateLimiter::new(requests_per_second));
+        self
+    }
+
+    /// 调用一个 Tushare 接口，返回以字段名为键的行
+    async fn query(&self, api_name: &str, params: Value, fields: &str) -> Result<Vec<Row>, String> {
+        let _permit = self.rate_limiter.acquire().await;
+
+        let body = json!({
+            "api_name": api_name,
+            "token": self.token,
+            "params": params,
+            "fields": fields,
+        });
+        let response = self
+            .client
+            .post(&self.api_url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Tushare 请求失败: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("Tushare HTTP {}", status));
+        }
+
+        let data = response
+            .json::<Value>()
+            .await
+            .map_err(|e| format!("Tushare 响应解析失败: {}", e))?;
+        parse_response(api_name, &data)
+    }
+
+    async fn fetch_prices(
+        &self,
+        stock_code: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<PriceData>, String> {
+        let stock_code = Market::normalize_code(stock_code);
+        let (ts_code, api) = daily_api(&stock_code)?;
+        let params = json!({
+            "ts_code": ts_code,
+            "start_date": start.format("%Y%m%d").to_string(),
+            "end_date": end.format("%Y%m%d").to_string(),
+        });
+        let rows = self.query(api.name, params, PRICE_FIELDS).await?;
+        let market = Market::from_stock_code(&stock_code);
+        Ok(parse_price_items(&price_items(&rows, &api), &market))
+    }
+
+    /// A 股估值、财务指标与行业信息；财务指标接口需要较高积分，单个接口失败时只记录警告
+    async fn fetch_ashare_fundamentals(&self, ts_code: &str) -> Result<FundamentalData, String> {
+        let today = Utc::now().date_naive();
+        let (basic, valuation, indicators) = tokio::join!(
+            self.query(
+                "stock_basic",
+                json!({ "ts_code": ts_code }),
+                "ts_code,name,industry,market"
+            ),
+            self.query(
+                "daily_basic",
+                json!({
+                    "ts_code": ts_code,
+                    "start_date": (today - Duration::days(14)).format("%Y%m%d").to_string(),
+                    "end_date": today.format("%Y%m%d").to_string(),
+                }),
+                "trade_date,pe_ttm,pb,dv_ttm"
+            ),
+            self.query(
+                "fina_indicator",
+                json!({ "ts_code": ts_code }),
+                "end_date,roe,netprofit_margin,or_yoy,current_ratio,quick_ratio,debt_to_assets"
+            ),
+        );
+        if let (Err(e), Err(_), Err(_)) = (&basic, &valuation, &indicators) {
+            return Err(e.clone());
+        }
+
+        let latest = |result: Result<Vec<Row>, String>, date_field: &str, api_name: &str| {
+            result
+                .map_err(|e| log::warn!("Tushare {} failed for {}: {}", api_name, ts_code, e))
+                .ok()
+                .and_then(|rows| latest_row(rows, date_field))
+                .unwrap_or_default()
+        };
+        let basic = latest(basic, "ts_code", "stock_basic");
+        let valuation = latest(valuation, "trade_date", "daily_basic");
+        let indicators = latest(indicators, "end_date", "fina_indicator");
+        Ok(ashare_fundamentals(&basic, &valuation, &indicators))
+    }
+}
+
+impl Clone for TushareFetcher {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            api_url: self.api_url.clone(),
+            token: self.token.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CachedDataFetcher for TushareFetcher {}
+
+#[async_trait::async_trait]
+impl DataFetcher for TushareFetcher {
+    async fn get_stock_data(&self, stock_code: &str, days: i32) -> Result<Vec<PriceData>, String> {
+        let end = Utc::now().date_naive();
+        let start = end - Duration::days(days.max(1) as i64);
+        self.fetch_prices(stock_code, start, end).await
+    }
+
+    async fn get_stock_data_range(
+        &self,
+        stock_code: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<PriceData>, String> {
+        validate_date_range(start, end)?;
+        self.fetch_prices(stock_code, start, end).await
+    }
+
+    async fn get_fundamental_data(&self, stock_code: &str) -> Result<FundamentalData, String> {
+        let stock_code = Market::normalize_code(stock_code);
+        match Market::from_stock_code(&stock_code) {
+            Market::ASHARES => {
+                let (ts_code, _) = daily_api(&stock_code)?;
+                self.fetch_ashare_fundamentals(&ts_code).await
+            }
+            // Tushare has no comparable per-stock financials outside A-shares
+            _ => Ok(ashare_fundamentals(&Row::new(), &Row::new(), &Row::new())),
+        }
+    }
+
+    async fn get_news_data(
+        &self,
+        _stock_code: &str,
+        _days: i32,
+    ) -> Result<(Vec<News>, SentimentAnalysis), String> {
+        // Tushare news feeds are market-wide and need separate permissions, so sentiment
+        // is reported as empty with zero confidence instead of guessed
+        Ok((Vec::new(), SentimentAnalysis::from_news(&[], 0.0)))
+    }
+
+    async fn get_stock_name(&self, stock_code: &str) -> String {
+        let stock_code = Market::normalize_code(stock_code);
+        let lookup = match Market::from_stock_code(&stock_code) {
+            Market::ASHARES => daily_api(&stock_code)
+                .ok()
+                .map(|(ts_code, _)| ("stock_basic", ts_code)),
+            Market::HONGKONG => Some(("hk_basic", format!("{}.HK", stock_code))),
+            Market::US => Some(("us_basic", stock_code.clone())),
+            _ => None,
+        };
+
+        let name = match lookup {
+            Some((api_name, ts_code)) => self
+                .query(api_name, json!({ "ts_code": ts_code }), "ts_code,name")
+                .await
+                .ok()
+                .and_then(|rows| rows.into_iter().next())
+                .and_then(|row| row.get("name").and_then(Value::as_str).map(String::from))
+                .filter(|name| !name.is_empty()),
+            None => None,
+        };
+        name.unwrap_or_else(|| format!("{}股票", stock_code))
+    }
+
+    async fn get_index_data(&self, index_code: &str, days: i32) -> Result<Vec<PriceData>, String> {
+        let end = Utc::now().date_naive();
+        let start = end - Duration::days(days.max(1) as i64);
+        let (ts_code, api) = index_api(index_code);
+        let params = json!({
+            "ts_code": ts_code,
+            "start_date": start.format("%Y%m%d").to_string(),
+            "end_date": end.format("%Y%m%d").to_string(),
+        });
+        let rows = self.query(api.name, params, PRICE_FIELDS).await?;
+        let market = Market::from_stock_code(index_code);
+        let prices = parse_price_items(&price_items(&rows, &api), &market);
+        if prices.is_empty() {
+            return Err(format!("指数 {} 没有返回价格数据", index_code));
+        }
+        Ok(prices)
+    }
+
+    async fn health_check(&self) -> Result<(), String> {
+        let today = Utc::now().format("%Y%m%d").to_string();
+        self.query(
+            "trade_cal",
+            json!({ "exchange": "SSE", "start_date": today, "end_date": today }),
+            "cal_date",
+        )
+        .await
+        .map(|_| ())
+    }
+}
+
+const PRICE_FIELDS: &str = "trade_date,open,high,low,close,pct_chg,pct_change,vol,amount";
+
+/// 日线接口名及其成交量、成交额换算到股、元的倍数
+struct DailyApi {
+    name: &'static str,
+    volume_unit: f64,
+    amount_unit: f64,
+}
+
+/// A 股与国内指数的成交量以手、成交额以千元计
+const ASHARE_DAILY: DailyApi = DailyApi {
+    name: "daily",
+    volume_unit: 100.0,
+    amount_unit: 1000.0,
+};
+const INDEX_DAILY: DailyApi = DailyApi {
+    name: "index_daily",
+    volume_unit: 100.0,
+    amount_unit: 1000.0,
+};
+const HK_DAILY: DailyApi = DailyApi {
+    name: "hk_daily",
+    volume_unit: 1.0,
+    amount_unit: 1.0,
+};
+const US_DAILY: DailyApi = DailyApi {
+    name: "us_daily",
+    volume_unit: 1.0,
+    amount_unit: 1.0,
+};
+const GLOBAL_INDEX_DAILY: DailyApi = DailyApi {
+    name: "index_global",
+    volume_unit: 1.0,
+    amount_unit: 1.0,
+};
+
+/// 规范化后的股票代码对应的 ts_code 与日线接口
+fn daily_api(stock_code: &str) -> Result<(String, DailyApi), String> {
+    match Market::from_stock_code(stock_code) {
+        // Normalized codes only keep a suffix when it names a different exchange
+        Market::ASHARES if stock_code.contains('.') => Ok((stock_code.to_string(), ASHARE_DAILY)),
+        Market::ASHARES => {
+            let exchange = Market::ashare_exchange(stock_code);
+            Ok((format!("{}.{}", stock_code, exchange), ASHARE_DAILY))
+        }
+        Market::HONGKONG => Ok((format!("{}.HK", stock_code), HK_DAILY)),
+        Market::US => Ok((stock_code.to_string(), US_DAILY)),
+        market => Err(format!(
+            "Tushare 数据源不支持{}代码: {}",
+            market, stock_code
+        )),
+    }
+}
+
+/// "sh000001" 形式的国内指数走 index_daily，HSI、SPX 等境外指数走 index_global
+fn index_api(index_code: &str) -> (String, DailyApi) {
+    let lower = index_code.to_lowercase();
+    for (prefix, exchange) in [("sh", "SH"), ("sz", "SZ")] {
+        if let Some(code) = lower.strip_prefix(prefix) {
+            if !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()) {
+                return (format!("{}.{}", code, exchange), INDEX_DAILY);
+            }
+        }
+    }
+    (index_code.to_uppercase(), GLOBAL_INDEX_DAILY)
+}
+
+/// 检查返回码，并把按列返回的 data.fields 与 data.items 展开为以字段名为键的行
+fn parse_response(api_name: &str, data: &Value) -> Result<Vec<Row>, String> {
+    let code = data["code"].as_i64().unwrap_or(-1);
+    if code != 0 {
+        return Err(format!(
+            "Tushare 接口 {} 返回错误 {}: {}",
+            api_name,
+            code,
+            data["msg"].as_str().unwrap_or("")
+        ));
+    }
+
+    let fields: Vec<&str> = data["data"]["fields"]
+        .as_array()
+        .map(|fields| fields.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let rows: Vec<Row> = data["data"]["items"]
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_array)
+                .map(|item| {
+                    fields
+                        .iter()
+                        .map(|field| field.to_string())
+                        .zip(item.iter().cloned())
+                        .collect::<Row>()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(rows)
+}
+
+/// 按日期字段（YYYYMMDD，可按字符串比较）取最新的一行
+fn latest_row(rows: Vec<Row>, date_field: &str) -> Option<Row> {
+    rows.into_iter().max_by(|a, b| {
+        let date = |row: &Row| {
+            row.get(date_field)
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string()
+        };
+        date(a).cmp(&date(b))
+    })
+}
+
+/// 转换为 akshare 代理的价格行格式，复用同一套解析与缺失字段推算
+fn price_items(rows: &[Row], api: &DailyApi) -> Vec<Value> {
+    rows.iter()
+        .map(|row| {
+            let number = |field: &str| row.get(field).and_then(Value::as_f64);
+            let date = row
+                .get("trade_date")
+                .and_then(Value::as_str)
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y%m%d").ok())
+                .map(|d| d.to_string())
+                .unwrap_or_default();
+            json!({
+                "date": date,
+                "open": number("open"),
+                "close": number("close"),
+                "high": number("high"),
+                "low": number("low"),
+                "volume": number("vol").map(|v| (v * api.volume_unit).round() as i64),
+                "change_pct": number("pct_chg").or_else(|| number("pct_change")),
+                "turnover": number("amount").map(|a| a * api.amount_unit),
+            })
+        })
+        .collect()
+}
+
+/// 由 stock_basic、daily_basic 与 fina_indicator 的最新一行组装基本面数据，缺失的指标不填充
+fn ashare_fundamentals(basic: &Row, valuation: &Row, indicators: &Row) -> FundamentalData {
+    let number = |row: &Row, field: &str| row.get(field).and_then(Value::as_f64);
+    let text = |row: &Row, field: &str| {
+        row.get(field)
+            .and_then(Value::as_str)
+            .filter(|s| !s.is_empty())
+            .unwrap_or("未知")
+            .to_string()
+    };
+
+    let mut financial_indicators = Vec::new();
+    for (row, field, name, unit) in [
+        (indicators, "netprofit_margin", "净利润率", "%"),
+        (indicators, "roe", "净资产收益率", "%"),
+        (indicators, "or_yoy", "营收增长率", "%"),
+        (valuation, "pe_ttm", "市盈率", "倍"),
+        (valuation, "pb", "市净率", "倍"),
+        (valuation, "dv_ttm", "股息率", "%"),
+    ] {
+        if let Some(value) = number(row, field) {
+            financial_indicators.push(FinancialIndicator {
+                name: name.to_string(),
+                value,
+                unit: unit.to_string(),
+            });
+        }
+    }
+
+    let mut valuation_map = HashMap::new();
+    for (field, key) in [("pe_ttm", "pe_ratio"), ("pb", "pb_ratio")] {
+        if let Some(value) = number(valuation, field) {
+            valuation_map.insert(key.to_string(), value);
+        }
+    }
+
+    // Tushare reports debt/assets in percent; convert to debt/equity
+    let debt_to_equity = number(indicators, "debt_to_assets")
+        .filter(|ratio| *ratio < 100.0)
+        .map(|ratio| ratio / (100.0 - ratio));
+
+    FundamentalData {
+        financial_indicators,
+        valuation: valuation_map,
+        industry: text(basic, "industry"),
+        sector: text(basic, "market"),
+        performance_forecasts: PerformanceForecasts {
+            revenue_growth_forecast: None,
+            earnings_growth_forecast: None,
+            target_price: None,
+            analyst_rating: "未评级".to_string(),
+            forecast_period: "12个月".to_string(),
+        },
+        risk_assessment: RiskAssessment {
+            beta: None,
+            beta_source: None,
+            beta_index: None,
+            debt_to_equity,
+            current_ratio: number(indicators, "current_ratio"),
+            quick_ratio: number(indicators, "quick_ratio"),
+            interest_coverage: None,
+            risk_level: "中等".to_string(),
+        },
+        financial_health: FinancialHealth {
+            profitability_score: 50.0,
+            liquidity_score: 50.0,
+            solvency_score: 50.0,
+            efficiency_score: 50.0,
+            overall_health_score: 50.0,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_response_maps_to_price_data() {
+        let data = json!({
+            "code": 0,
+            "msg": "",
+            "data": {
+                "fields": ["ts_code", "trade_date", "open", "high", "low", "close", "pct_chg", "vol", "amount"],
+                "items": [
+                    ["000001.SZ", "20240103", 9.5, 9.6, 9.3, 9.4, -1.05, 1200.0, 1128.5],
+                    ["000001.SZ", "20240102", 9.4, 9.6, 9.35, 9.5, 1.06, 1000.0, 950.0]
+                ],
+                "has_more": false
+            }
+        });
+
+        let rows = parse_response("daily", &data).unwrap();
+        assert_eq!(rows[0]["trade_date"], "20240103");
+        let prices = parse_price_items(&price_items(&rows, &ASHARE_DAILY), &Market::ASHARES);
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices[0].date.to_rfc3339(), "2024-01-02T00:00:00+00:00");
+        assert_eq!(prices[1].close, 9.4);
+        assert_eq!(prices[1].change_pct, -1.05);
+        // Lots and thousands of yuan are converted to shares and yuan
+        assert_eq!(prices[1].volume, 120_000);
+        assert_eq!(prices[1].turnover, 1_128_500.0);
+
+        let error = json!({ "code": 40203, "msg": "抱歉，您没有访问该接口的权限", "data": null });
+        let err = parse_response("fina_indicator", &error).unwrap_err();
+        assert!(
+            err.contains("40203") && err.contains("fina_indicator"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_codes_map_to_tushare_apis() {
+        let ts_code = |code: &str| daily_api(code).map(|(ts_code, api)| (ts_code, api.name));
+        assert_eq!(ts_code("600519"), Ok(("600519.SH".to_string(), "daily")));
+        assert_eq!(ts_code("300750"), Ok(("300750.SZ".to_string(), "daily")));
+        assert_eq!(ts_code("830799"), Ok(("830799.BJ".to_string(), "daily")));
+        assert_eq!(ts_code("000001"), Ok(("000001.SZ".to_string(), "daily")));
+        assert_eq!(ts_code("000001.SH"), Ok(("000001.SH".to_string(), "daily")));
+        assert_eq!(ts_code("00700"), Ok(("00700.HK".to_string(), "hk_daily")));
+        assert_eq!(ts_code("AAPL"), Ok(("AAPL".to_string(), "us_daily")));
+        assert!(ts_code("BTC-USD").is_err());
+
+        assert_eq!(index_api("sh000001").0, "000001.SH");
+        assert_eq!(index_api("HSI").1.name, "index_global");
+    }
+
+    #[test]
+    fn test_fundamentals_use_latest_rows() {
+        let row = |value: Value| value.as_object().unwrap().clone();
+        let indicators = latest_row(
+            vec![
+                row(json!({ "end_date": "20240930", "roe": 8.2, "debt_to_assets": 75.0 })),
+                row(json!({ "end_date": "20231231", "roe": 10.1 })),
+            ],
+            "end_date",
+        )
+        .unwrap();
+        let valuation = row(json!({ "trade_date": "20241015", "pe_ttm": 4.8, "pb": 0.52 }));
+        let basic = row(json!({ "ts_code": "000001.SZ", "industry": "银行", "market": "主板" }));
+
+        let fundamental = ashare_fundamentals(&basic, &valuation, &indicators);
+        assert_eq!(fundamental.industry, "银行");
+        assert_eq!(fundamental.valuation.get("pe_ratio"), Some(&4.8));
+        let roe = fundamental
+            .financial_indicators
+            .iter()
+            .find(|indicator| indicator.name == "净资产收益率")
+            .unwrap();
+        assert_eq!(roe.value, 8.2);
+        assert_eq!(fundamental.risk_assessment.debt_to_equity, Some(3.0));
+        // Fields Tushare did not return are left out rather than defaulted
+        assert!(fundamental
+            .financial_indicators
+            .iter()
+            .all(|indicator| indicator.name != "股息率"));
+    }
+}